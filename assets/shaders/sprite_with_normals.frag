@@ -24,20 +24,87 @@ struct Rect_Light {
     float _pad2;
 };
 
-#define MAX_POINT_LIGHTS 4
-#define MAX_RECT_LIGHTS 4
+// size: 48 B
+struct Spot_Light {
+    vec4 color_and_intensity;
+    vec2 position;
+    float radius;
+    float attenuation;
+    vec2 direction;
+    // Cosines of the half cone angles where the light ends and where it starts fading.
+    float cos_outer;
+    float cos_inner;
+};
+
+// size: 64 B
+struct Cookie_Light {
+    vec4 color_and_intensity;
+    vec2 position;
+    float radius;
+    float attenuation;
+    // The cookie's uv rect inside the cookie atlas (x, y, width, height)
+    vec4 cookie_rect;
+    // (cos, sin) of the cookie's rotation
+    vec2 rotation;
+    float _pad1;
+    float _pad2;
+};
+
+// @Volatile: these must match the constants in inle_gfx's light_grid.rs
+#define MAX_POINT_LIGHTS 256
+#define MAX_RECT_LIGHTS 64
+#define MAX_SPOT_LIGHTS 64
+#define MAX_COOKIE_LIGHTS 16
+#define LIGHT_GRID_WIDTH 16
+#define LIGHT_GRID_HEIGHT 9
+#define MAX_LIGHT_INDICES 4096
+// @Volatile: these must match the constants in inle_gfx's shadow_maps.rs
+#define MAX_SHADOW_MAPS 16
+#define SHADOW_MAP_RESOLUTION 128
 
 layout (std140) uniform LightsBlock {
 	Ambient_Light ambient_light;
 	Point_Light point_lights[MAX_POINT_LIGHTS];
 	Rect_Light rect_lights[MAX_RECT_LIGHTS];
+	Spot_Light spot_lights[MAX_SPOT_LIGHTS];
+	Cookie_Light cookie_lights[MAX_COOKIE_LIGHTS];
+};
+
+// The visible area is split into a grid of tiles, each listing the lights that reach it.
+layout (std140) uniform LightGridBlock {
+	// xy: world position of the grid's top-left corner, zw: 1 / tile size
+	vec4 grid_origin_and_inv_tile_size;
+	// Each tile is packed as (first light index, n_point | n_rect << 8 | n_spot << 16 | n_cookie << 24),
+	// 2 tiles per uvec4.
+	uvec4 grid_tiles[LIGHT_GRID_WIDTH * LIGHT_GRID_HEIGHT / 2];
+	// 16-bit indices into the light arrays, packed in pairs.
+	// Each tile lists its point lights first, then its rect, spot and cookie lights.
+	uvec4 light_indices[MAX_LIGHT_INDICES / 8];
+};
+
+// Point lights with occluders in range have a 1D shadow map, storing for each direction around the
+// light the distance of the nearest occluder (divided by the light radius).
+layout (std140) uniform ShadowsBlock {
+	// For each point light, 1 + the index of its shadow map, or 0 if it has none. 8 bits each.
+	uvec4 point_light_shadow_maps[MAX_POINT_LIGHTS / 16];
+	vec4 shadow_maps[MAX_SHADOW_MAPS * SHADOW_MAP_RESOLUTION / 4];
 };
 
+// How many shadow map directions are averaged on each side of the sampled one, to soften the
+// shadow edges.
+#define SHADOW_PENUMBRA_TAPS 2
+#define SHADOW_BIAS 0.01
+
 #define DIFFUSE_BIAS 0.2
 #define MAX_ENCODED_ANGLE 65535
 #define PI 3.14159265359
 
 uniform sampler2D tex;
+uniform sampler2D cookies;
+// The size of the biggest occluder drawn with this material, or 0 if it doesn't cast shadows.
+// An occluder's fragments are behind its own edges facing the light, so they're only shadowed by
+// the occluders farther than that from them.
+uniform float occluder_extent;
 uniform sampler2D normals;
 uniform float shininess;
 uniform vec3 specular_color;
@@ -67,6 +134,85 @@ vec2 point_to_rect_vector(vec2 point_pos, vec2 rect_pos_min, vec2 rect_pos_max)
     return -vec2(dist_x * sign(pos_relative_to_rect.x - rect_half_width), dist_y * sign(pos_relative_to_rect.y - rect_half_height));
 }
 
+uvec2 get_grid_tile(vec2 pos) {
+    vec2 tile_pos = (pos - grid_origin_and_inv_tile_size.xy) * grid_origin_and_inv_tile_size.zw;
+    ivec2 tile = clamp(ivec2(floor(tile_pos)), ivec2(0), ivec2(LIGHT_GRID_WIDTH - 1, LIGHT_GRID_HEIGHT - 1));
+    int idx = tile.y * LIGHT_GRID_WIDTH + tile.x;
+    uvec4 tiles = grid_tiles[idx / 2];
+    return (idx % 2 == 0) ? tiles.xy : tiles.zw;
+}
+
+uint get_light_index(uint i) {
+    uint pair = light_indices[i / 8u][(i / 2u) % 4u];
+    return (pair >> (16u * (i % 2u))) & 0xFFFFu;
+}
+
+// @Volatile: must match inle_gfx's light::falloff
+float light_falloff(float dist, float radius, float attenuation) {
+    if (radius <= 0.0) {
+        return 0.0;
+    }
+    return pow(max(0.0, 1.0 - dist / radius), 1.0 + attenuation);
+}
+
+uint get_shadow_map(uint point_light_idx) {
+    uint packed = point_light_shadow_maps[point_light_idx / 16u][(point_light_idx / 4u) % 4u];
+    return (packed >> (8u * (point_light_idx % 4u))) & 0xFFu;
+}
+
+float get_shadow_depth(uint map, int bin) {
+    // Note: bin is never less than -SHADOW_MAP_RESOLUTION, and % is undefined for negative operands.
+    uint wrapped_bin = uint(bin + SHADOW_MAP_RESOLUTION) % uint(SHADOW_MAP_RESOLUTION);
+    uint i = map * uint(SHADOW_MAP_RESOLUTION) + wrapped_bin;
+    return shadow_maps[i / 4u][i % 4u];
+}
+
+// Returns how much of the point light reaches the fragment (1: fully lit, 0: fully in shadow).
+// The neighbouring directions of the shadow map are averaged to get a penumbra.
+float point_light_shadow(uint point_light_idx, vec2 light_to_frag, float radius) {
+    uint map = get_shadow_map(point_light_idx);
+    if (map == 0u) {
+        return 1.0;
+    }
+    map -= 1u;
+
+    float dist = (length(light_to_frag) - occluder_extent) / radius - SHADOW_BIAS;
+    float bin = (atan(light_to_frag.y, light_to_frag.x) + PI) / (2.0 * PI) * float(SHADOW_MAP_RESOLUTION) - 0.5;
+    float first_bin = floor(bin);
+    float t = bin - first_bin;
+    float lit = 0.0;
+    for (int i = -SHADOW_PENUMBRA_TAPS; i <= SHADOW_PENUMBRA_TAPS + 1; ++i) {
+        // The outermost taps are weighted by how near the sampled direction is to them.
+        float weight = (i == -SHADOW_PENUMBRA_TAPS) ? 1.0 - t : (i == SHADOW_PENUMBRA_TAPS + 1) ? t : 1.0;
+        lit += weight * step(dist, get_shadow_depth(map, int(first_bin) + i));
+    }
+    return lit / float(2 * SHADOW_PENUMBRA_TAPS + 1);
+}
+
+float spot_cone_factor(Spot_Light light, vec2 light_to_frag) {
+    float len = length(light_to_frag);
+    if (len == 0.0) {
+        return 1.0;
+    }
+    float cos_a = dot(light_to_frag / len, light.direction);
+    float t = clamp((cos_a - light.cos_outer) / max(light.cos_inner - light.cos_outer, 1e-4), 0.0, 1.0);
+    return t * t * (3.0 - 2.0 * t);
+}
+
+vec3 sample_cookie(Cookie_Light light, vec2 light_to_frag) {
+    // Rotate the fragment into the cookie's space, where the cookie covers [-radius, radius].
+    vec2 local = vec2(
+        light.rotation.x * light_to_frag.x + light.rotation.y * light_to_frag.y,
+        -light.rotation.y * light_to_frag.x + light.rotation.x * light_to_frag.y
+    );
+    vec2 uv = local / light.radius * 0.5 + 0.5;
+    if (any(lessThan(uv, vec2(0.0))) || any(greaterThan(uv, vec2(1.0)))) {
+        return vec3(0.0);
+    }
+    vec4 cookie = texture(cookies, light.cookie_rect.xy + uv * light.cookie_rect.zw);
+    return cookie.rgb * cookie.a;
+}
+
 void main() {
     vec4 pixel = texture(tex, tex_coord);
 
@@ -92,8 +238,16 @@ void main() {
 
     vec3 view_dir = vec3(0.0, 0.0, -1.0);
 
-    for (int i = 0; i < MAX_POINT_LIGHTS; ++i) {
-        Point_Light light = point_lights[i];
+    uvec2 tile = get_grid_tile(world_pos);
+    uint first_light = tile.x;
+    uint n_point_lights = tile.y & 0xFFu;
+    uint n_rect_lights = (tile.y >> 8) & 0xFFu;
+    uint n_spot_lights = (tile.y >> 16) & 0xFFu;
+    uint n_cookie_lights = tile.y >> 24;
+
+    for (uint i = 0u; i < n_point_lights; ++i) {
+        uint light_idx = get_light_index(first_light + i);
+        Point_Light light = point_lights[light_idx];
         vec2 frag_to_light = light.position - world_pos;
         vec3 light_dir = normalize(vec3(frag_to_light, 0.0));
         float diff = max(dot(normal, light_dir), 0.0);
@@ -106,13 +260,14 @@ void main() {
         vec3 result = light.color_and_intensity.w * (diffuse + specular);
 
         float dist = length(frag_to_light);
-        float atten = pow(max(0.0, mix(1.0, 0.0, dist / light.radius)), 1.0 + light.attenuation);
+        float atten = light_falloff(dist, light.radius, light.attenuation);
+        atten *= point_light_shadow(light_idx, -frag_to_light, light.radius);
 
         color += result * atten;
     }
 
-    for (int i = 0; i < MAX_RECT_LIGHTS; ++i) {
-        Rect_Light light = rect_lights[i];
+    for (uint i = 0u; i < n_rect_lights; ++i) {
+        Rect_Light light = rect_lights[get_light_index(first_light + n_point_lights + i)];
         vec2 frag_to_light = point_to_rect_vector(world_pos, light.pos_min, light.pos_max);
         vec3 light_dir = normalize(vec3(frag_to_light, 0.0));
         float dist = length(frag_to_light);
@@ -128,7 +283,48 @@ void main() {
 
         vec3 result = light.color_and_intensity.w * (diffuse + specular);
 
-        float atten = pow(max(0.0, mix(1.0, 0.0, dist / light.radius)), 1.0 + light.attenuation);
+        float atten = light_falloff(dist, light.radius, light.attenuation);
+
+        color += result * atten;
+    }
+
+    uint first_spot_light = first_light + n_point_lights + n_rect_lights;
+    for (uint i = 0u; i < n_spot_lights; ++i) {
+        Spot_Light light = spot_lights[get_light_index(first_spot_light + i)];
+        vec2 frag_to_light = light.position - world_pos;
+        vec3 light_dir = normalize(vec3(frag_to_light, 0.0));
+        float diff = max(dot(normal, light_dir), 0.0);
+        vec3 diffuse = (DIFFUSE_BIAS + diff) * light.color_and_intensity.rgb;
+
+        vec3 half_dir = normalize(light_dir + view_dir);
+        float spec = pow(max(dot(half_dir, normal), 0.0), max(1.0, shininess));
+        vec3 specular = specular_color * spec * light.color_and_intensity.rgb;
+
+        vec3 result = light.color_and_intensity.w * (diffuse + specular);
+
+        float dist = length(frag_to_light);
+        float atten = light_falloff(dist, light.radius, light.attenuation) * spot_cone_factor(light, -frag_to_light);
+
+        color += result * atten;
+    }
+
+    uint first_cookie_light = first_spot_light + n_spot_lights;
+    for (uint i = 0u; i < n_cookie_lights; ++i) {
+        Cookie_Light light = cookie_lights[get_light_index(first_cookie_light + i)];
+        vec2 frag_to_light = light.position - world_pos;
+        vec3 light_color = light.color_and_intensity.rgb * sample_cookie(light, -frag_to_light);
+        vec3 light_dir = normalize(vec3(frag_to_light, 0.0));
+        float diff = max(dot(normal, light_dir), 0.0);
+        vec3 diffuse = (DIFFUSE_BIAS + diff) * light_color;
+
+        vec3 half_dir = normalize(light_dir + view_dir);
+        float spec = pow(max(dot(half_dir, normal), 0.0), max(1.0, shininess));
+        vec3 specular = specular_color * spec * light_color;
+
+        vec3 result = light.color_and_intensity.w * (diffuse + specular);
+
+        float dist = length(frag_to_light);
+        float atten = light_falloff(dist, light.radius, light.attenuation);
 
         color += result * atten;
     }
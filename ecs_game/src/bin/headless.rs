@@ -0,0 +1,51 @@
+// Plays back a replay without any window, rendering or audio and writes the state of the
+// simulation at each frame to a summary file, so replays can be checked on machines with no GPU.
+// Usage: ecs_headless <replay_file> <n_frames> <summary_file>
+
+use std::process::exit;
+
+#[cfg(debug_assertions)]
+fn main() {
+    use std::path::PathBuf;
+
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 4 {
+        eprintln!("Usage: {} <replay_file> <n_frames> <summary_file>", args[0]);
+        exit(1);
+    }
+
+    let replay_file = PathBuf::from(&args[1]);
+    let n_frames = args[2].parse::<u64>().unwrap_or_else(|_| {
+        eprintln!("Invalid frame count {}", args[2]);
+        exit(1);
+    });
+    let summary_file = PathBuf::from(&args[3]);
+
+    let summaries = ecs_game::headless::run_replay(&replay_file, n_frames).unwrap_or_else(|err| {
+        eprintln!("Failed to run replay {:?}: {}", replay_file, err);
+        exit(1);
+    });
+
+    let written = std::fs::File::create(&summary_file).and_then(|file| {
+        let mut writer = std::io::BufWriter::new(file);
+        ecs_game::headless::write_summary(&summaries, &mut writer)
+    });
+    if let Err(err) = written {
+        eprintln!("Failed to write summary to {:?}: {}", summary_file, err);
+        exit(1);
+    }
+
+    println!(
+        "Simulated {} frames: summary written to {:?}",
+        summaries.len(),
+        summary_file
+    );
+}
+
+#[cfg(not(debug_assertions))]
+fn main() {
+    eprintln!(
+        "ecs_headless only works in debug builds, since replays are not available otherwise."
+    );
+    exit(1);
+}
@@ -16,14 +16,16 @@ pub enum Console_Cmd {
     Set_Cfg_Var { name: String, value: Cfg_Value },
     Toggle_Cfg_Var { name: String },
     Trace_Fn { fn_name: String },
+    Save_Level { path: String },
+    Load_Level { path: String },
 }
 
 // @Improve @Convenience: this is ugly! We must manually synch this list with
 // the parse_cmd below *and* the enum declaration above!
 // We can @WaitForStable until we can do a const match on the enum, but maybe
 // there is a better way.
-pub const ALL_CMD_STRINGS: [&str; 9] = [
-    "quit", "cam", "var", "toggle", "fps", "trace", "log", "hud", "zoom",
+pub const ALL_CMD_STRINGS: [&str; 11] = [
+    "quit", "cam", "var", "toggle", "fps", "trace", "log", "hud", "zoom", "save", "load",
 ];
 
 // Parses and executes 'cmdline'. May return a string to output to the console.
@@ -90,6 +92,12 @@ fn parse_cmd(cmdline: &str) -> Result<Console_Cmd, Console_Error> {
             ["hud"] => Ok(Console_Cmd::Toggle_Cfg_Var {
                 name: String::from("engine/debug/overlay/display"),
             }),
+            ["save", path] => Ok(Console_Cmd::Save_Level {
+                path: (*path).to_string(),
+            }),
+            ["load", path] => Ok(Console_Cmd::Load_Level {
+                path: (*path).to_string(),
+            }),
             _ => Err(Console_Error::new(format!("Unknown command: {}", cmdline))),
         }
     }
@@ -152,6 +160,24 @@ fn execute_command(
             inle_app::app::set_traced_fn(&mut engine_state.debug_systems, fn_name);
             None
         }
+        Console_Cmd::Save_Level { path } => gs
+            .save_level_snapshot(std::path::Path::new(&path))
+            .err()
+            .map(|err| {
+                (
+                    format!("Failed to save level to {}: {}", path, err),
+                    colors::RED,
+                )
+            }),
+        Console_Cmd::Load_Level { path } => gs
+            .load_level_snapshot(std::path::Path::new(&path))
+            .err()
+            .map(|err| {
+                (
+                    format!("Failed to load level from {}: {}", path, err),
+                    colors::RED,
+                )
+            }),
     }
 }
 
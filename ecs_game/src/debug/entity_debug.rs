@@ -1,5 +1,7 @@
 use inle_common::fixed_string::Fixed_String_64;
 use inle_math::vector::Vec2f;
+use inle_serialize::{Binary_Serializable, Byte_Stream};
+use std::io;
 
 #[derive(Copy, Clone, Default)]
 pub struct C_Debug_Data {
@@ -7,3 +9,29 @@ pub struct C_Debug_Data {
     pub prev_positions: [Vec2f; 10],
     pub n_prev_positions_filled: u8,
 }
+
+impl Binary_Serializable for C_Debug_Data {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        String::from(&self.entity_name).serialize(output)?;
+        for pos in &self.prev_positions {
+            pos.serialize(output)?;
+        }
+        output.write_u8(self.n_prev_positions_filled)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        let entity_name = String::deserialize(input)?;
+        if entity_name.len() > 64 {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        let mut res = Self {
+            entity_name: Fixed_String_64::from(entity_name.as_str()),
+            ..Default::default()
+        };
+        for pos in &mut res.prev_positions {
+            *pos = Vec2f::deserialize(input)?;
+        }
+        res.n_prev_positions_filled = input.read_u8()?;
+        Ok(res)
+    }
+}
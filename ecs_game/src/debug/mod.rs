@@ -1,3 +1,5 @@
 pub mod console_executor;
 pub mod entity_debug;
+pub mod replay;
+pub mod rewind;
 pub mod systems;
@@ -0,0 +1,70 @@
+use crate::game_state::Game_State;
+use crate::gameplay_system::Gameplay_System;
+use inle_common::hash::{fnv1a_64, fnv1a_64_update, FNV1A_64_OFFSET_BASIS};
+use inle_common::stringid::String_Id;
+use inle_core::rand::Default_Rng;
+use inle_replay::replay_data::Replay_State_Checksum;
+use inle_serialize::{Binary_Serializable, Byte_Stream};
+
+/// Records a checksum of the game state if we're recording a replay, or compares it with the
+/// recorded one if we're playing a replay back.
+pub fn update_state_checksum(game_state: &mut Game_State) {
+    trace!("replay::update_state_checksum");
+
+    let cur_frame = game_state.engine_state.cur_frame;
+    let gameplay_system = &game_state.gameplay_system;
+    let engine_state = &mut game_state.engine_state;
+    let rng = &engine_state.rng;
+
+    if let Some(rip) = engine_state.replay_input_provider.as_mut() {
+        rip.check_state_checksum(cur_frame, || calc_state_hashes(gameplay_system, rng));
+    } else {
+        let recording_system = &mut engine_state.debug_systems.replay_recording_system;
+        if recording_system.should_record_state_checksum(cur_frame) {
+            recording_system.record_state_checksum(Replay_State_Checksum {
+                frame_number: cur_frame,
+                hashes: calc_state_hashes(gameplay_system, rng),
+            });
+        }
+    }
+}
+
+/// Hashes each serializable component storage of all active levels (which includes the
+/// entities' transforms and velocities), plus the RNG state.
+fn calc_state_hashes(
+    gameplay_system: &Gameplay_System,
+    rng: &Default_Rng,
+) -> Vec<(String_Id, u64)> {
+    let mut hashes = vec![];
+
+    match gameplay_system.hash_active_levels() {
+        Ok(levels) => {
+            for level in levels {
+                combine_hash(&mut hashes, sid!("entities"), level.n_entities as u64);
+                for (name, hash) in level.storage_hashes {
+                    combine_hash(&mut hashes, name, hash);
+                }
+            }
+        }
+        Err(err) => lerr!("Failed to hash the active levels: {}", err),
+    }
+
+    let mut rng_state = Byte_Stream::new();
+    match rng.serialize(&mut rng_state) {
+        Ok(()) => hashes.push((sid!("rng"), fnv1a_64(rng_state.as_ref()))),
+        Err(err) => lerr!("Failed to hash the rng state: {}", err),
+    }
+
+    hashes
+}
+
+/// Folds `hash` into the entry named `name`, so all levels contribute to the same entries.
+fn combine_hash(hashes: &mut Vec<(String_Id, u64)>, name: String_Id, hash: u64) {
+    match hashes.iter_mut().find(|(n, _)| *n == name) {
+        Some((_, combined)) => *combined = fnv1a_64_update(*combined, &hash.to_le_bytes()),
+        None => hashes.push((
+            name,
+            fnv1a_64_update(FNV1A_64_OFFSET_BASIS, &hash.to_le_bytes()),
+        )),
+    }
+}
@@ -0,0 +1,94 @@
+use crate::game_state::Game_State;
+use inle_serialize::{Binary_Serializable, Byte_Stream};
+use std::io;
+use std::time::Duration;
+
+/// Saves the current game state into the rewind history, associated to the current Debug_Log frame.
+pub fn record_frame(game_state: &mut Game_State) {
+    trace!("rewind::record_frame");
+
+    if !game_state
+        .engine_state
+        .debug_systems
+        .rewind_history
+        .is_enabled()
+    {
+        return;
+    }
+
+    let mut output = Byte_Stream::new();
+    if let Err(err) = serialize_game_state(game_state, &mut output) {
+        lerr!("Failed to record rewind frame: {}", err);
+        return;
+    }
+
+    let debug_systems = &mut game_state.engine_state.debug_systems;
+    let frame = debug_systems.log.cur_frame;
+    debug_systems
+        .rewind_history
+        .push_frame(frame, output.as_ref());
+}
+
+/// Pauses the game and rewinds it to `frame` when a frame gets selected from the debug UI,
+/// or resumes it (from the rewound state) when the selection is cleared.
+pub fn select_frame(game_state: &mut Game_State, frame: Option<u64>) {
+    game_state.engine_state.time.paused = frame.is_some();
+    game_state
+        .engine_state
+        .debug_systems
+        .debug_ui
+        .frame_scroller
+        .manually_selected = frame.is_some();
+
+    if let Some(frame) = frame {
+        rewind_to_frame(game_state, frame);
+    }
+}
+
+/// Restores the game state recorded at `frame`, if it's still in the rewind history.
+/// Returns true if the state was restored.
+pub fn rewind_to_frame(game_state: &mut Game_State, frame: u64) -> bool {
+    trace!("rewind::rewind_to_frame");
+
+    let data = match game_state
+        .engine_state
+        .debug_systems
+        .rewind_history
+        .get_frame(frame)
+    {
+        Some(data) => data,
+        None => {
+            lwarn!("Cannot rewind to frame {}: it's not in the history.", frame);
+            return false;
+        }
+    };
+
+    let mut input = Byte_Stream::new_from_vec(data);
+    match restore_game_state(game_state, &mut input) {
+        Ok(()) => {
+            lverbose!("Rewound to frame {}", frame);
+            true
+        }
+        Err(err) => {
+            lerr!("Failed to rewind to frame {}: {}", frame, err);
+            false
+        }
+    }
+}
+
+fn serialize_game_state(game_state: &Game_State, output: &mut Byte_Stream) -> io::Result<()> {
+    game_state.engine_state.rng.serialize(output)?;
+    output.write_u64(game_state.accumulated_update_time.as_nanos() as u64)?;
+    game_state.gameplay_system.serialize_active_levels(output)
+}
+
+fn restore_game_state(game_state: &mut Game_State, input: &mut Byte_Stream) -> io::Result<()> {
+    let rng = Binary_Serializable::deserialize(input)?;
+    let accumulated_update_time = Duration::from_nanos(input.read_u64()?);
+    game_state.gameplay_system.restore_active_levels(input)?;
+
+    game_state.engine_state.rng = rng;
+    game_state.accumulated_update_time = accumulated_update_time;
+
+    Ok(())
+}
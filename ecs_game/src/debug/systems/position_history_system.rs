@@ -2,7 +2,9 @@ use inle_cfg::Cfg_Var;
 use inle_ecs::components::base::C_Spatial2D;
 use inle_ecs::ecs_world::Ecs_World;
 use inle_math::vector::Vec2f;
+use inle_serialize::{Binary_Serializable, Byte_Stream};
 use std::collections::VecDeque;
+use std::io;
 use std::time::Duration;
 
 #[derive(Clone, Debug)]
@@ -25,6 +27,30 @@ impl C_Position_History {
     }
 }
 
+impl Binary_Serializable for C_Position_History {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.sampling_interval.serialize(output)?;
+        output.write_f32(self.min_delta_pos)?;
+        output.write_u32(self.positions.len() as u32)?;
+        for pos in &self.positions {
+            pos.serialize(output)?;
+        }
+        self.time_since_latest_record.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        let sampling_interval = Duration::deserialize(input)?;
+        let min_delta_pos = input.read_f32()?;
+        let positions = Vec::<Vec2f>::deserialize(input)?.into();
+        Ok(Self {
+            sampling_interval,
+            min_delta_pos,
+            positions,
+            time_since_latest_record: Duration::deserialize(input)?,
+        })
+    }
+}
+
 pub struct Position_History_System {
     hist_size: Cfg_Var<u32>,
 }
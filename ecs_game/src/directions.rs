@@ -1,7 +1,7 @@
 use std::ops::*;
 
 #[repr(u8)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Square_Direction {
     Up = 0,
     Right = 1,
@@ -9,6 +9,17 @@ pub enum Square_Direction {
     Left = 3,
 }
 
+impl Square_Direction {
+    pub const fn opposite(self) -> Self {
+        match self {
+            Self::Up => Self::Down,
+            Self::Right => Self::Left,
+            Self::Down => Self::Up,
+            Self::Left => Self::Right,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Default, PartialEq, Eq)]
 pub struct Square_Directions(u8);
 
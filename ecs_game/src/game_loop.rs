@@ -1,9 +1,12 @@
 use super::{Game_Resources, Game_State};
+use crate::levels::Level;
 use crate::states::state::Game_State_Args;
 use inle_alloc::temp::*;
 use inle_app::{app, render_system};
 use inle_common::colors;
 use inle_core::time;
+use inle_ecs::components::base::C_Spatial2D;
+use inle_events::evt_register::Event_Register;
 use inle_gfx::render_window::Render_Window_Handle;
 use inle_math::transform::Transform2D;
 use inle_physics::physics;
@@ -15,7 +18,6 @@ use {
     inle_common::paint_props::Paint_Properties,
     inle_common::stringid::String_Id,
     inle_debug::painter::Debug_Painter,
-    inle_ecs::components::base::C_Spatial2D,
     inle_ecs::ecs_world::Ecs_World,
     inle_gfx::render_window,
     inle_input::input_state::Input_State,
@@ -184,8 +186,13 @@ where
             || scroller.cur_second != prev_selected_second
             || was_manually_selected != scroller.manually_selected
         {
-            game_state.engine_state.time.paused = scroller.manually_selected;
+            let selected_frame = if scroller.manually_selected {
+                Some(scroller.get_real_selected_frame())
+            } else {
+                None
+            };
             debug_systems.trace_overlay_update_t = 0.;
+            crate::debug::rewind::select_frame(game_state, selected_frame);
         }
     }
 
@@ -370,9 +377,17 @@ where
                     do_update_physics = true;
                 }
 
+                game_state.engine_state.systems.physics_settings.substeps = game_state
+                    .cvars
+                    .physics_substeps
+                    .read(&game_state.engine_state.config)
+                    .max(1);
+
                 while game_state.accumulated_update_time >= update_dt {
                     let update_start = std::time::Instant::now();
 
+                    store_prev_transforms(&mut game_state.gameplay_system);
+
                     game_state.gameplay_system.update(
                         &update_dt,
                         &mut game_state.engine_state,
@@ -411,6 +426,13 @@ where
                 }
 
                 game_state.n_updates_last_frame = n_updates;
+                if update_dt > Duration::default() {
+                    game_state.tick_interpolation = (game_state
+                        .accumulated_update_time
+                        .as_secs_f32()
+                        / update_dt.as_secs_f32())
+                    .min(1.);
+                }
                 game_state
                     .engine_state
                     .input_state
@@ -433,6 +455,14 @@ where
         }
     }
 
+    #[cfg(debug_assertions)]
+    {
+        if !game_state.engine_state.time.paused {
+            crate::debug::rewind::record_frame(game_state);
+        }
+        crate::debug::replay::update_state_checksum(game_state);
+    }
+
     // Update audio
     {
         trace!("audio_system_update");
@@ -460,6 +490,7 @@ where
     #[cfg(debug_assertions)]
     {
         game_state.engine_state.config.update();
+        game_resources.prefabs.update();
         game_state.fps_debug.tick(&real_dt);
     }
 
@@ -551,6 +582,7 @@ fn update_graphics<'a, 's, 'r>(
         let gameplay_system = &mut game_state.gameplay_system;
         let batches = &mut game_state.level_batches;
         let frame_alloc = &mut game_state.engine_state.frame_alloc;
+        let tick_interpolation = game_state.tick_interpolation;
         #[cfg(debug_assertions)]
         let painters = &mut game_state.engine_state.debug_systems.painters;
         gameplay_system.levels.foreach_active_level(|level| {
@@ -559,6 +591,7 @@ fn update_graphics<'a, 's, 'r>(
                 ecs_world: &level.world,
                 frame_alloc,
                 render_cfg,
+                tick_interpolation,
                 window,
                 camera: &level.get_camera_transform(),
                 gres,
@@ -686,19 +719,18 @@ fn update_debug_graphics<'a, 's, 'r>(
         let profile_graph = debug_ui.get_graph(sid!("fn_profile"));
         let cur_selected = profile_graph.get_selected_point();
         if cur_selected != prev_selected {
-            game_state.engine_state.time.paused = cur_selected.is_some();
-            debug_ui.frame_scroller.manually_selected = cur_selected.is_some();
-            if let Some(sel) = cur_selected {
-                let profile_graph = debug_ui.get_graph(sid!("fn_profile"));
+            let selected_frame = cur_selected.map(|sel| {
                 // @Robustness @Refactoring: this should be a u64
                 let real_frame: u32 = profile_graph
                     .data
                     .get_point_metadata(sel.index, sid!("real_frame"))
                     .expect("Failed to get point frame metadata!");
-                debug_ui
-                    .frame_scroller
-                    .set_real_selected_frame(real_frame as u64);
+                real_frame as u64
+            });
+            if let Some(frame) = selected_frame {
+                debug_ui.frame_scroller.set_real_selected_frame(frame);
             }
+            crate::debug::rewind::select_frame(game_state, selected_frame);
         }
     }
 
@@ -719,6 +751,21 @@ fn update_debug_graphics<'a, 's, 'r>(
     }
 }
 
+/// Saves the current transform of all entities, so the rendering can interpolate between it
+/// and the one we're about to simulate.
+fn store_prev_transforms(gameplay_system: &mut crate::gameplay_system::Gameplay_System) {
+    trace!("store_prev_transforms");
+
+    gameplay_system.levels.foreach_active_level(|level| {
+        foreach_entity!(&level.world,
+            read: ;
+            write: C_Spatial2D;
+            |_e, (), (spatial,): (&mut C_Spatial2D,)| {
+            spatial.prev_transform = Some(spatial.transform);
+        });
+    });
+}
+
 fn update_physics(
     game_state: &mut Game_State,
     update_dt: Duration,
@@ -741,38 +788,64 @@ fn update_physics(
             .entry(level.id)
             .or_insert_with(physics::Collision_System_Debug_Data::default);
 
+        update_level_physics(
+            level,
+            update_dt,
+            phys_settings,
+            evt_register,
+            frame_alloc,
+            #[cfg(debug_assertions)]
+            coll_debug,
+        );
+    });
+}
+
+/// Runs all the physics substeps of a tick on `level`, then raises its collision events.
+pub(crate) fn update_level_physics(
+    level: &mut Level,
+    update_dt: Duration,
+    phys_settings: &physics::Physics_Settings,
+    evt_register: &mut Event_Register,
+    frame_alloc: &mut Temp_Allocator,
+    #[cfg(debug_assertions)] coll_debug: &mut physics::Collision_System_Debug_Data,
+) {
+    let substep_dt = update_dt / phys_settings.substeps;
+
+    for _ in 0..phys_settings.substeps {
         physics::update_collisions(
+            &substep_dt,
             &mut level.world,
             &level.chunks,
             &mut level.phys_world,
             phys_settings,
-            evt_register,
             frame_alloc,
             #[cfg(debug_assertions)]
             coll_debug,
         );
 
-        {
-            let mut moved = excl_temp_array(frame_alloc);
-            crate::movement_system::update(
-                &update_dt,
-                &mut level.world,
-                &level.phys_world,
-                &mut moved,
-            );
+        let mut moved = excl_temp_array(frame_alloc);
+        crate::movement_system::update(
+            &substep_dt,
+            &mut level.world,
+            &level.phys_world,
+            &mut moved,
+        );
 
-            let moved = unsafe { moved.into_read_only() };
-            for mov in &moved {
-                level.chunks.update_collider(
-                    mov.handle,
-                    mov.prev_pos,
-                    mov.new_pos,
-                    mov.extent,
-                    frame_alloc,
-                );
-            }
+        let moved = unsafe { moved.into_read_only() };
+        for mov in &moved {
+            level.chunks.update_collider(
+                mov.handle,
+                mov.prev_pos,
+                mov.new_pos,
+                mov.extent,
+                frame_alloc,
+            );
         }
-    });
+    }
+
+    // NOTE: we do this after the last substep, so each contact only raises its events once
+    // per tick.
+    physics::raise_collision_events(&mut level.phys_world, evt_register);
 }
 
 #[cfg(debug_assertions)]
@@ -1197,9 +1270,12 @@ fn update_physics_debug_overlay(
     debug_overlay.clear();
     debug_overlay
         .add_line(&format!(
-            "[phys] n_inter_tests: {}, n_chunks: {}",
+            "[phys] n_inter_tests: {}, n_sweep_tests: {}, n_chunks: {}, n_islands: {}, n_sleeping: {}",
             collision_data.n_intersection_tests,
+            collision_data.n_sweep_tests,
             chunks.n_chunks(),
+            collision_data.n_islands,
+            collision_data.n_sleeping_bodies,
         ))
         .with_color(colors::rgba(0, 173, 90, 220));
 }
@@ -1237,7 +1313,7 @@ fn debug_draw_colliders(
         write: ;
     |_e, (collider_comp, _spatial): (&C_Collider, &C_Spatial2D), ()| {
         for collider in phys_world.get_all_colliders(collider_comp.phys_body_handle) {
-            // Note: since our collision detector doesn't handle rotation, draw the colliders with rot = 0
+            // Note: Rect and Circle colliders ignore rotation, so draw them with rot = 0
             // @Incomplete: scale?
             let mut transform = Transform2D::from_pos_rot_scale(collider.position, rad(0.), v2!(1., 1.));
 
@@ -1250,7 +1326,11 @@ fn debug_draw_colliders(
                 5,
                 colors::BLACK);
 
-            let mut cld_color = colors::rgba(255, 255, 0, 100);
+            let mut cld_color = if phys_world.is_sleeping(collider.entity) {
+                colors::rgba(120, 120, 120, 100)
+            } else {
+                colors::rgba(255, 255, 0, 100)
+            };
 
             let colliding_with = phys_world.get_collisions(collider.handle);
             if !colliding_with.is_empty() {
@@ -1288,6 +1368,33 @@ fn debug_draw_colliders(
                         cld_color,
                     );
                 }
+                Collision_Shape::Oriented_Rect { .. } | Collision_Shape::Polygon { .. } => {
+                    let vertices = collider.world_polygon().unwrap();
+                    for (i, &from) in vertices.iter().enumerate() {
+                        debug_painter.add_line(
+                            Line {
+                                from,
+                                to: vertices[(i + 1) % vertices.len()],
+                                thickness: 1.,
+                            },
+                            cld_color,
+                        );
+                    }
+                }
+                Collision_Shape::Capsule { .. } => {
+                    let (from, to, radius) = collider.world_rounded().unwrap();
+                    for &center in &[from, to] {
+                        debug_painter.add_circle(Circle { center, radius }, cld_color);
+                    }
+                    debug_painter.add_line(
+                        Line {
+                            from,
+                            to,
+                            thickness: 2. * radius,
+                        },
+                        cld_color,
+                    );
+                }
                 _ => {}
             }
 
@@ -1304,6 +1411,31 @@ fn debug_draw_colliders(
                 5, colors::ORANGE);
         }
     });
+
+    for joint in phys_world.get_all_joints() {
+        use inle_physics::joints::Joint_Kind;
+
+        let (anchor_a, anchor_b) = match phys_world.get_joint_anchors(joint) {
+            Some(anchors) => anchors,
+            None => continue,
+        };
+        let color = match joint.kind {
+            Joint_Kind::Distance { .. } => colors::AQUA,
+            Joint_Kind::Revolute => colors::FUCHSIA,
+            Joint_Kind::Spring { .. } => colors::GREEN,
+        };
+        debug_painter.add_line(
+            Line {
+                from: anchor_a,
+                to: anchor_b,
+                thickness: 1.,
+            },
+            color,
+        );
+        for &center in &[anchor_a, anchor_b] {
+            debug_painter.add_circle(Circle { center, radius: 2. }, color);
+        }
+    }
 }
 
 #[cfg(debug_assertions)]
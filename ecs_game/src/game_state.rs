@@ -24,6 +24,8 @@ pub struct Game_State<'a> {
     pub execution_time: Duration,
 
     pub accumulated_update_time: Duration,
+    /// How far we are between the latest simulated tick and the next one, in [0, 1].
+    pub tick_interpolation: f32,
 
     pub sleep_granularity: Option<Duration>,
 
@@ -50,6 +52,8 @@ pub struct CVars {
     pub gameplay_update_tick_ms: Cfg_Var<f32>,
     pub gameplay_max_time_budget_ms: Cfg_Var<f32>,
 
+    pub physics_substeps: Cfg_Var<u32>,
+
     pub vsync: Cfg_Var<bool>,
 
     pub clear_color: Cfg_Var<u32>,
@@ -110,6 +114,7 @@ pub struct Game_Resources<'a> {
     pub audio: inle_resources::audio::Audio_Resources<'a>,
     // Note: this is not inside gfx because we need to borrow from both at the same time
     pub shader_cache: inle_resources::gfx::Shader_Cache<'a>,
+    pub prefabs: inle_ecs::prefab::Prefab_Registry,
 }
 
 #[repr(C)]
@@ -230,6 +235,7 @@ fn create_game_state<'a>(
         if let Some(in_replay_file) = &appcfg.in_replay_file {
             linfo!("Loading replay file {:?}", in_replay_file);
             if let Some(replay_data) = app::try_create_replay_data(in_replay_file) {
+                check_replay_compatibility(&replay_data.header, &engine_state.config);
                 app::set_replay_data(&mut engine_state, replay_data);
             }
         }
@@ -245,9 +251,17 @@ fn create_game_state<'a>(
         &mut game_resources.shader_cache,
     )?;
 
+    game_resources
+        .prefabs
+        .load_from_dir(&inle_core::env::asset_dir_path(
+            &engine_state.env,
+            "prefabs",
+        ));
+
     #[cfg(debug_assertions)]
     {
         app::start_config_watch(&engine_state.env, &mut engine_state.config)?;
+        app::start_prefab_watch(&engine_state.env, &mut game_resources.prefabs)?;
 
         let ui_scale = Cfg_Var::<f32>::new("engine/debug/ui/ui_scale", &engine_state.config);
         let font_name = Cfg_Var::<String>::new("engine/debug/ui/font", &engine_state.config);
@@ -280,7 +294,8 @@ fn create_game_state<'a>(
     {
         let record_replay_data = debug_cvars.record_replay.read(&engine_state.config);
         if record_replay_data && engine_state.app_config.in_replay_file.is_none() {
-            app::start_recording(&mut engine_state).unwrap_or_else(|err| {
+            let level_id = sid!(gameplay_system::TEST_LEVEL);
+            app::start_recording(&mut engine_state, level_id).unwrap_or_else(|err| {
                 lerr!("Failed to start recording input: {}", err);
             });
         }
@@ -294,6 +309,7 @@ fn create_game_state<'a>(
             window,
             engine_state,
             accumulated_update_time: Duration::default(),
+            tick_interpolation: 0.,
             sleep_granularity: None,
             level_batches: HashMap::new(),
             execution_time: Duration::default(),
@@ -318,6 +334,7 @@ fn create_game_state<'a>(
 fn create_cvars(cfg: &inle_cfg::Config) -> CVars {
     let gameplay_update_tick_ms = Cfg_Var::new("engine/gameplay/update_tick_ms", cfg);
     let gameplay_max_time_budget_ms = Cfg_Var::new("engine/gameplay/max_time_budget_ms", cfg);
+    let physics_substeps = Cfg_Var::new("engine/physics/substeps", cfg);
     let clear_color = Cfg_Var::new("engine/rendering/clear_color", cfg);
     let vsync = Cfg_Var::new("engine/window/vsync", cfg);
     let enable_shaders = Cfg_Var::new("engine/rendering/enable_shaders", cfg);
@@ -329,6 +346,7 @@ fn create_cvars(cfg: &inle_cfg::Config) -> CVars {
     CVars {
         gameplay_update_tick_ms,
         gameplay_max_time_budget_ms,
+        physics_substeps,
         vsync,
         clear_color,
         enable_shaders,
@@ -394,14 +412,46 @@ fn create_debug_cvars(cfg: &inle_cfg::Config) -> Debug_CVars {
     }
 }
 
+/// Warns if the replay was recorded in conditions different from the current ones,
+/// as it will likely desync.
+#[cfg(debug_assertions)]
+fn check_replay_compatibility(
+    header: &inle_replay::replay_data::Replay_Header,
+    cfg: &inle_cfg::Config,
+) {
+    if let Some(level_id) = header.level_id {
+        let cur_level_id = sid!(gameplay_system::TEST_LEVEL);
+        if level_id != cur_level_id {
+            lwarn!(
+                "Replay was recorded on level {} but level {} will be loaded.",
+                level_id,
+                cur_level_id
+            );
+        }
+    }
+
+    if let Some(config_hash) = header.config_hash {
+        let cur_config_hash = cfg.content_hash();
+        if config_hash != cur_config_hash {
+            lwarn!(
+                "Replay was recorded with a different config (hash {:016x}, current is {:016x}): it may desync.",
+                config_hash,
+                cur_config_hash
+            );
+        }
+    }
+}
+
 fn create_game_resources<'a>() -> Box<Game_Resources<'a>> {
     let gfx = inle_resources::gfx::Gfx_Resources::new();
     let audio = inle_resources::audio::Audio_Resources::new();
     let shader_cache = inle_resources::gfx::Shader_Cache::new();
+    let prefabs = inle_ecs::prefab::Prefab_Registry::default();
     Box::new(Game_Resources {
         gfx,
         audio,
         shader_cache,
+        prefabs,
     })
 }
 
@@ -5,6 +5,7 @@ use super::systems::camera_system;
 use super::systems::controllable_system::{self, C_Controllable};
 use crate::systems::ai;
 use crate::systems::ground_detection_system;
+use crate::systems::tilemap_system;
 //use super::systems::dumb_movement_system;
 use super::systems::gravity_system;
 //use super::systems::ground_collision_calculation_system::Ground_Collision_Calculation_System;
@@ -23,6 +24,7 @@ use inle_core::env::Env_Info;
 use inle_core::{rand, time};
 use inle_ecs::components::base::C_Spatial2D;
 use inle_ecs::ecs_world::{Ecs_World, Entity};
+use inle_ecs::snapshot::Component_Serialization_Registry;
 use inle_events::evt_register::Event_Register;
 use inle_gfx::components::{C_Animated_Sprite, C_Camera2D, C_Renderable};
 use inle_gfx::particles::Particle_Manager;
@@ -36,15 +38,29 @@ use inle_math::transform::Transform2D;
 use inle_math::vector::Vec2f;
 use inle_physics::collider;
 use inle_resources::gfx::{tex_path, Gfx_Resources};
+use inle_serialize::Byte_Stream;
 use std::collections::HashMap;
+use std::io;
+use std::path::Path;
 use std::sync::{Arc, Mutex, MutexGuard};
 use std::time::Duration;
 
+// @Temporary: the only level we load for now.
+pub const TEST_LEVEL: &str = "test";
+
 #[derive(Default, Copy, Clone)]
 pub struct Gameplay_System_Config {
     pub n_entities_to_spawn: usize,
 }
 
+#[cfg(debug_assertions)]
+#[derive(Debug, PartialEq)]
+pub struct Level_Hashes {
+    pub level_id: String_Id,
+    pub n_entities: usize,
+    pub storage_hashes: Vec<(String_Id, u64)>,
+}
+
 pub struct Gameplay_System {
     pub levels: Levels,
 
@@ -53,6 +69,8 @@ pub struct Gameplay_System {
 
     ai_system: ai::Ai_System,
 
+    comp_serialization_registry: Component_Serialization_Registry,
+
     //ground_collision_calc_system: Ground_Collision_Calculation_System,
     //pub pixel_collision_system: Pixel_Collision_System,
     //pub cursor_entity: Option<Entity>,
@@ -80,6 +98,7 @@ impl Gameplay_System {
             input_cfg: Input_Config::default(),
             cfg: Gameplay_System_Config::default(),
             ai_system: ai::Ai_System::default(),
+            comp_serialization_registry: crate::levels::new_comp_serialization_registry(),
             //ground_collision_calc_system: Ground_Collision_Calculation_System::new(),
             //pixel_collision_system: Pixel_Collision_System::default(),
             //cursor_entity: None,
@@ -113,7 +132,7 @@ impl Gameplay_System {
         level_batches: &mut HashMap<String_Id, Batches>,
         cvars: &crate::game_state::CVars,
     ) {
-        let level_id = sid!("test");
+        let level_id = sid!(TEST_LEVEL);
         let mut level =
             load_system::level_load_sync(level_id, engine_state, game_res, self.cfg, cvars);
 
@@ -229,7 +248,7 @@ impl Gameplay_System {
         engine_state: &mut Engine_State,
         level_batches: &mut HashMap<String_Id, Batches>,
     ) {
-        let level_id = sid!("test");
+        let level_id = sid!(TEST_LEVEL);
 
         if let Some(idx) = self
             .levels
@@ -252,6 +271,95 @@ impl Gameplay_System {
         engine_state.systems.particle_mgrs.remove(&level_id);
     }
 
+    /// Saves a snapshot of the first active level to `path`.
+    pub fn save_level_snapshot(&self, path: &Path) -> io::Result<()> {
+        let level = self
+            .levels
+            .first_active_level()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no active level"))?;
+
+        let mut output = Byte_Stream::new();
+        level.serialize_snapshot(&self.comp_serialization_registry, &mut output)?;
+        std::fs::write(path, output.as_ref())?;
+
+        linfo!("Saved snapshot of level {} to {}", level.id, path.display());
+
+        Ok(())
+    }
+
+    /// Replaces the first active level with the snapshot saved in `path`.
+    pub fn load_level_snapshot(&mut self, path: &Path) -> io::Result<()> {
+        let mut input = Byte_Stream::new_from_vec(std::fs::read(path)?);
+        let mut level = self
+            .levels
+            .first_active_level()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no active level"))?;
+
+        level.restore_snapshot(&self.comp_serialization_registry, &mut input)?;
+
+        linfo!("Loaded snapshot of level {} from {}", level.id, path.display());
+
+        Ok(())
+    }
+
+    /// Writes the snapshots of all active levels, in order.
+    #[cfg(debug_assertions)]
+    pub fn serialize_active_levels(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        output.write_u32(self.levels.active_levels.len() as u32)?;
+        for &idx in &self.levels.active_levels {
+            self.levels.loaded_levels[idx]
+                .lock()
+                .unwrap()
+                .serialize_snapshot(&self.comp_serialization_registry, output)?;
+        }
+        Ok(())
+    }
+
+    /// Restores the snapshots written by serialize_active_levels().
+    /// The active levels must be the same as when the snapshots were taken.
+    #[cfg(debug_assertions)]
+    pub fn restore_active_levels(&mut self, input: &mut Byte_Stream) -> io::Result<()> {
+        let n_levels = input.read_u32()? as usize;
+        if n_levels != self.levels.active_levels.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "snapshot has {} levels, but {} are active",
+                    n_levels,
+                    self.levels.active_levels.len()
+                ),
+            ));
+        }
+
+        for &idx in &self.levels.active_levels {
+            self.levels.loaded_levels[idx]
+                .lock()
+                .unwrap()
+                .restore_snapshot(&self.comp_serialization_registry, input)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the number of entities and the hash of each serializable component storage
+    /// of all active levels, in order.
+    #[cfg(debug_assertions)]
+    pub fn hash_active_levels(&self) -> io::Result<Vec<Level_Hashes>> {
+        self.levels
+            .active_levels
+            .iter()
+            .map(|&idx| {
+                let level = self.levels.loaded_levels[idx].lock().unwrap();
+                Ok(Level_Hashes {
+                    level_id: level.id,
+                    n_entities: level.world.entities().len(),
+                    storage_hashes: self
+                        .comp_serialization_registry
+                        .hash_storages(&level.world)?,
+                })
+            })
+            .collect()
+    }
+
     pub fn update(
         &mut self,
         dt: &Duration,
@@ -306,9 +414,10 @@ impl Gameplay_System {
 
             //ground_collision_calc_system.update(world, &mut level.phys_world, &mut level.chunks);
 
-            gravity_system::update(&dt, world, cfg);
+            gravity_system::update(&dt, world, &level.phys_world, cfg);
 
             gfx::multi_sprite_animation_system::update(&dt, world, frame_alloc);
+            tilemap_system::update(&mut level.world, &mut level.phys_world, &mut level.chunks);
             level.chunks.update(&mut level.world, &level.phys_world);
 
             // @Temporary DEBUG (this only works if we only have 1 test level)
@@ -324,8 +433,11 @@ impl Gameplay_System {
         trace!("gameplay_system::late_update");
 
         self.levels.foreach_active_level(|level| {
+            inle_app::hierarchy_system::update(&mut level.world);
+
             level.world.notify_destroyed(evt_register);
             level.world.destroy_pending();
+            level.world.advance_tick();
         });
     }
 
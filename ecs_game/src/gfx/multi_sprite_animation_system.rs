@@ -1,6 +1,8 @@
 use inle_alloc::temp::*;
 use inle_ecs::ecs_world::Ecs_World;
 use inle_gfx::components::C_Multi_Renderable;
+use inle_serialize::{Binary_Serializable, Byte_Stream};
+use std::io;
 use std::time::Duration;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -33,6 +35,82 @@ pub struct C_Multi_Renderable_Animation {
     pub anim_t: [f32; C_Multi_Renderable::MAX_RENDERABLES],
 }
 
+const TRACK_NONE: u8 = 0x0;
+const TRACK_SINUSOIDAL: u8 = 0x1;
+const TRACK_ABS_SINUSOIDAL: u8 = 0x2;
+
+impl Binary_Serializable for Animation_Track {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        match *self {
+            Animation_Track::None => output.write_u8(TRACK_NONE),
+            Animation_Track::Sinusoidal {
+                freq_hz,
+                amplitude,
+                phase,
+                exp,
+            } => {
+                output.write_u8(TRACK_SINUSOIDAL)?;
+                output.write_f32(freq_hz)?;
+                output.write_f32(amplitude)?;
+                output.write_f32(phase)?;
+                exp.serialize(output)
+            }
+            Animation_Track::Abs_Sinusoidal {
+                freq_hz,
+                amplitude,
+                phase,
+                exp,
+            } => {
+                output.write_u8(TRACK_ABS_SINUSOIDAL)?;
+                output.write_f32(freq_hz)?;
+                output.write_f32(amplitude)?;
+                output.write_f32(phase)?;
+                output.write_f32(exp)
+            }
+        }
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        match input.read_u8()? {
+            TRACK_NONE => Ok(Animation_Track::None),
+            TRACK_SINUSOIDAL => Ok(Animation_Track::Sinusoidal {
+                freq_hz: input.read_f32()?,
+                amplitude: input.read_f32()?,
+                phase: input.read_f32()?,
+                exp: i32::deserialize(input)?,
+            }),
+            TRACK_ABS_SINUSOIDAL => Ok(Animation_Track::Abs_Sinusoidal {
+                freq_hz: input.read_f32()?,
+                amplitude: input.read_f32()?,
+                phase: input.read_f32()?,
+                exp: input.read_f32()?,
+            }),
+            _ => Err(io::ErrorKind::InvalidData.into()),
+        }
+    }
+}
+
+impl Binary_Serializable for C_Multi_Renderable_Animation {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        for i in 0..C_Multi_Renderable::MAX_RENDERABLES {
+            self.anim_tracks_x[i].serialize(output)?;
+            self.anim_tracks_y[i].serialize(output)?;
+            output.write_f32(self.anim_t[i])?;
+        }
+        Ok(())
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        let mut res = Self::default();
+        for i in 0..C_Multi_Renderable::MAX_RENDERABLES {
+            res.anim_tracks_x[i] = Animation_Track::deserialize(input)?;
+            res.anim_tracks_y[i] = Animation_Track::deserialize(input)?;
+            res.anim_t[i] = input.read_f32()?;
+        }
+        Ok(res)
+    }
+}
+
 pub fn update(dt: &Duration, world: &mut Ecs_World, frame_alloc: &mut Temp_Allocator) {
     foreach_entity!(world,
         read: ;
@@ -0,0 +1,153 @@
+use crate::game_loop;
+use crate::game_state::internal_game_init;
+use crate::gameplay_system::Level_Hashes;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
+
+// Runs the game with the null window, gfx and audio backends, so it can be used on machines
+// with no display or GPU. Each frame simulates exactly one gameplay tick, so the same replay
+// always yields the same results.
+
+#[derive(Debug, PartialEq)]
+pub struct Frame_Summary {
+    pub frame: u64,
+    pub levels: Vec<Level_Hashes>,
+}
+
+/// Plays back `replay_file` for `n_frames` frames (or until the game quits) and returns the
+/// state of the active levels at the end of each frame.
+/// Fails if the playback diverges from the state checksums stored in the replay.
+pub fn run_replay(
+    replay_file: &Path,
+    n_frames: u64,
+) -> Result<Vec<Frame_Summary>, Box<dyn std::error::Error>> {
+    let mut loggers = unsafe { inle_diagnostics::log::create_loggers() };
+    inle_diagnostics::log::add_default_logger(&mut loggers);
+
+    let args = [
+        String::from("ecs_headless"),
+        String::from("--replay"),
+        replay_file.to_string_lossy().into_owned(),
+    ];
+    let (mut game_state, mut game_resources) = internal_game_init(&args, loggers)?;
+
+    if game_state.engine_state.replay_input_provider.is_none() {
+        return Err(format!("failed to load replay file {:?}", replay_file).into());
+    }
+
+    let ms_per_frame = game_state
+        .cvars
+        .gameplay_update_tick_ms
+        .read(&game_state.engine_state.config);
+    game_state.engine_state.time.fixed_real_dt =
+        Some(Duration::from_micros((ms_per_frame * 1000.0) as u64));
+
+    let mut summaries = Vec::with_capacity(n_frames as usize);
+    let mut desync = None;
+    for _ in 0..n_frames {
+        if game_state.engine_state.should_close {
+            lwarn!(
+                "Game quit after {} frames out of {}.",
+                summaries.len(),
+                n_frames
+            );
+            break;
+        }
+
+        crate::begin_frame(&mut game_state);
+        game_loop::tick_game(&mut game_state, &mut game_resources)?;
+        unsafe {
+            game_state.engine_state.frame_alloc.dealloc_all();
+        }
+
+        summaries.push(Frame_Summary {
+            frame: game_state.engine_state.cur_frame,
+            levels: game_state.gameplay_system.hash_active_levels()?,
+        });
+
+        desync = game_state
+            .engine_state
+            .replay_input_provider
+            .as_ref()
+            .and_then(|rip| rip.desync().cloned());
+        if desync.is_some() {
+            break;
+        }
+    }
+
+    game_state
+        .engine_state
+        .systems
+        .long_task_mgr
+        .begin_shutdown();
+    inle_gfx::render_window::shutdown(&mut game_state.window);
+    game_state
+        .engine_state
+        .systems
+        .long_task_mgr
+        .block_until_shutdown_complete();
+
+    if let Some(desync) = desync {
+        return Err(desync.to_string().into());
+    }
+
+    Ok(summaries)
+}
+
+/// Writes the summaries as tab-separated values: one line per frame and level with the frame
+/// number, the level id, the entity count and the hash of each component storage.
+pub fn write_summary(summaries: &[Frame_Summary], output: &mut dyn Write) -> io::Result<()> {
+    if let Some(level) = summaries.first().and_then(|s| s.levels.first()) {
+        write!(output, "frame\tlevel\tentities")?;
+        for (name, _) in &level.storage_hashes {
+            write!(output, "\t{}", name)?;
+        }
+        writeln!(output)?;
+    }
+
+    for summary in summaries {
+        for level in &summary.levels {
+            write!(
+                output,
+                "{}\t{}\t{}",
+                summary.frame, level.level_id, level.n_entities
+            )?;
+            for (_, hash) in &level.storage_hashes {
+                write!(output, "\t{:016x}", hash)?;
+            }
+            writeln!(output)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use inle_replay::replay_data::Replay_Header;
+    use inle_serialize::{Binary_Serializable, Byte_Stream};
+
+    #[test]
+    fn same_replay_gives_same_summaries() {
+        // A replay with no input: the game just runs on its own from the replay's seed.
+        let header = Replay_Header {
+            ms_per_frame: 16.,
+            ..Default::default()
+        };
+        let mut data = Byte_Stream::new();
+        header.serialize(&mut data).unwrap();
+        let replay_file =
+            std::env::temp_dir().join(format!("ecs_headless_test_{}.replay", std::process::id()));
+        std::fs::write(&replay_file, data.as_ref()).unwrap();
+
+        let first = run_replay(&replay_file, 30);
+        let second = run_replay(&replay_file, 30);
+        let _ = std::fs::remove_file(&replay_file);
+
+        let first = first.unwrap();
+        assert_eq!(first.len(), 30);
+        assert_eq!(first, second.unwrap());
+    }
+}
@@ -1,13 +1,32 @@
+use crate::gfx::multi_sprite_animation_system::C_Multi_Renderable_Animation;
+use crate::load::load_system;
 use crate::spatial::World_Chunks;
+use crate::systems::ai::test_ai_system::C_Test_Ai;
+use crate::systems::camera_system::C_Camera_Follow;
+use crate::systems::controllable_system::C_Controllable;
+use crate::systems::gravity_system::C_Gravity;
+use crate::systems::ground_detection_system::C_Ground_Detection;
 use inle_common::stringid::String_Id;
+use inle_ecs::components::base::{C_Local_Transform2D, C_Spatial2D};
 use inle_ecs::ecs_query::Ecs_Query;
 use inle_ecs::ecs_world::{Ecs_World, Entity};
-use inle_gfx::components::C_Camera2D;
+use inle_ecs::snapshot::Component_Serialization_Registry;
+use inle_gfx::components::{C_Animated_Sprite, C_Camera2D, C_Multi_Renderable, C_Renderable};
 use inle_gfx::light::Lights;
+use inle_gfx::tilemap::C_Tilemap;
 use inle_math::vector::Vec2f;
+use inle_physics::collider::C_Collider;
 use inle_physics::phys_world::Physics_World;
+use inle_serialize::{Binary_Serializable, Byte_Stream};
+use std::io;
 use std::sync::{Arc, Mutex, MutexGuard};
 
+#[cfg(debug_assertions)]
+use {
+    crate::debug::entity_debug::C_Debug_Data,
+    crate::debug::systems::position_history_system::C_Position_History,
+};
+
 // A Level is what gets loaded and unloaded
 pub struct Level {
     pub id: String_Id,
@@ -22,6 +41,70 @@ pub struct Level {
 }
 
 impl Level {
+    /// Saves the whole state of the level (except its World_Chunks, which are rebuilt on restore).
+    /// Note that the textures and shaders used by the level are saved by handle, so they must
+    /// already be loaded when restoring the snapshot.
+    pub fn serialize_snapshot(
+        &self,
+        registry: &Component_Serialization_Registry,
+        output: &mut Byte_Stream,
+    ) -> io::Result<()> {
+        trace!("level::serialize_snapshot");
+
+        self.id.serialize(output)?;
+        self.world.serialize_snapshot(registry, output)?;
+        self.phys_world.serialize(output)?;
+        self.lights.serialize(output)?;
+        self.cameras.serialize(output)?;
+        output.write_u32(self.active_camera as u32)?;
+        self.data.serialize(output)
+    }
+
+    /// Replaces the state of this level with the one saved by serialize_snapshot().
+    /// If the snapshot is invalid, the level is left untouched.
+    pub fn restore_snapshot(
+        &mut self,
+        registry: &Component_Serialization_Registry,
+        input: &mut Byte_Stream,
+    ) -> io::Result<()> {
+        trace!("level::restore_snapshot");
+
+        let id = String_Id::deserialize(input)?;
+        let world = Ecs_World::deserialize_snapshot(registry, input)?;
+        let phys_world = Physics_World::deserialize(input)?;
+        let lights = Lights::deserialize(input)?;
+        let cameras = Vec::<Entity>::deserialize(input)?;
+        let active_camera = input.read_u32()? as usize;
+        let data = Level_Data::deserialize(input)?;
+
+        if active_camera >= cameras.len().max(1)
+            || !cameras.iter().all(|&c| world.is_valid_entity(c))
+        {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+
+        if id != self.id {
+            lwarn!(
+                "Restoring a snapshot of level {} into level {}",
+                id,
+                self.id
+            );
+        }
+
+        self.id = id;
+        self.world = world;
+        self.phys_world = phys_world;
+        self.lights = lights;
+        self.cameras = cameras;
+        self.active_camera = active_camera;
+        self.data = data;
+
+        self.chunks.clear();
+        load_system::fill_world_chunks(&mut self.chunks, &mut self.world, &self.phys_world);
+
+        Ok(())
+    }
+
     // @Temporary: we need to better decide how to handle cameras
     pub fn get_camera_transform(&self) -> inle_math::transform::Transform2D {
         let query = Ecs_Query::new(&self.world).read::<C_Camera2D>();
@@ -81,9 +164,163 @@ pub struct Level_Data {
     pub player_spawn_point: Spawn_Point,
     // @Temporary: probably we'll want something less generic
     pub ai_spawn_points: Vec<Spawn_Point>,
+    /// Where the level's critical path leads, if it has one.
+    pub exit_point: Option<Spawn_Point>,
+    /// Name of the music track of this level, as set by its rooms.
+    // @Incomplete: we don't play music yet.
+    pub music: Option<String>,
 }
 
 #[derive(Default)]
 pub struct Spawn_Point {
     pub position: Vec2f,
 }
+
+impl Binary_Serializable for Spawn_Point {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.position.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Self {
+            position: Vec2f::deserialize(input)?,
+        })
+    }
+}
+
+impl Binary_Serializable for Level_Data {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.player_spawn_point.serialize(output)?;
+        self.ai_spawn_points.serialize(output)?;
+        self.exit_point.serialize(output)?;
+        self.music.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Self {
+            player_spawn_point: Spawn_Point::deserialize(input)?,
+            ai_spawn_points: Vec::deserialize(input)?,
+            exit_point: Option::deserialize(input)?,
+            music: Option::deserialize(input)?,
+        })
+    }
+}
+
+/// Returns a registry containing all the components that are saved in level snapshots.
+/// The names must never change, or old snapshots won't load anymore.
+pub fn new_comp_serialization_registry() -> Component_Serialization_Registry {
+    let mut registry = Component_Serialization_Registry::default();
+    registry.register::<C_Spatial2D>(sid!("C_Spatial2D"));
+    registry.register::<C_Local_Transform2D>(sid!("C_Local_Transform2D"));
+    registry.register::<C_Collider>(sid!("C_Collider"));
+    registry.register::<C_Renderable>(sid!("C_Renderable"));
+    registry.register::<C_Multi_Renderable>(sid!("C_Multi_Renderable"));
+    registry.register::<C_Animated_Sprite>(sid!("C_Animated_Sprite"));
+    registry.register::<C_Camera2D>(sid!("C_Camera2D"));
+    registry.register::<C_Tilemap>(sid!("C_Tilemap"));
+    registry.register::<C_Camera_Follow>(sid!("C_Camera_Follow"));
+    registry.register::<C_Controllable>(sid!("C_Controllable"));
+    registry.register::<C_Gravity>(sid!("C_Gravity"));
+    registry.register::<C_Ground_Detection>(sid!("C_Ground_Detection"));
+    registry.register::<C_Multi_Renderable_Animation>(sid!("C_Multi_Renderable_Animation"));
+    registry.register::<C_Test_Ai>(sid!("C_Test_Ai"));
+    #[cfg(debug_assertions)]
+    {
+        registry.register::<C_Debug_Data>(sid!("C_Debug_Data"));
+        registry.register::<C_Position_History>(sid!("C_Position_History"));
+    }
+    registry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use inle_cfg::Cfg_Var;
+    use inle_math::transform::Transform2D;
+    use inle_physics::collider::{Collider, Collision_Shape};
+    use inle_physics::phys_world::Phys_Data;
+
+    fn new_empty_level() -> Level {
+        Level {
+            id: sid!("test"),
+            world: Ecs_World::new(),
+            chunks: World_Chunks::new(),
+            cameras: vec![],
+            active_camera: 0,
+            lights: Lights::default(),
+            phys_world: Physics_World::new(),
+            data: Level_Data::default(),
+        }
+    }
+
+    #[test]
+    fn enemies_keep_their_ai_across_snapshots() {
+        let mut level = new_empty_level();
+        let mut enemies = vec![];
+        for i in 0..3 {
+            let pos = v2!(i as f32 * 100., 0.);
+            let enemy = level.world.new_entity();
+            level.world.add_component(
+                enemy,
+                C_Spatial2D {
+                    transform: Transform2D::from_pos(pos),
+                    frame_starting_pos: pos,
+                    ..Default::default()
+                },
+            );
+            let cld = Collider {
+                shape: Collision_Shape::Rect {
+                    width: 16.,
+                    height: 16.,
+                },
+                ..Default::default()
+            };
+            let body =
+                level
+                    .phys_world
+                    .new_physics_body_with_rigidbody(cld, enemy, Phys_Data::default());
+            let gnd_check = Collider {
+                shape: Collision_Shape::Circle { radius: 1. },
+                is_static: true,
+                ..Default::default()
+            };
+            let left_gnd_check = level.phys_world.add_collider(gnd_check.clone(), enemy);
+            let right_gnd_check = level.phys_world.add_collider(gnd_check, enemy);
+            level.world.add_component(
+                enemy,
+                C_Collider {
+                    phys_body_handle: body,
+                },
+            );
+            level.world.add_component(
+                enemy,
+                C_Test_Ai::new(left_gnd_check, right_gnd_check, Cfg_Var::new_from_val(50.)),
+            );
+            level
+                .world
+                .add_component(enemy, C_Multi_Renderable_Animation::default());
+            enemies.push(enemy);
+        }
+
+        let registry = new_comp_serialization_registry();
+        let mut saved = Byte_Stream::new();
+        level.serialize_snapshot(&registry, &mut saved).unwrap();
+        saved.seek(0);
+
+        let mut restored = new_empty_level();
+        restored.restore_snapshot(&registry, &mut saved).unwrap();
+        for &enemy in &enemies {
+            assert!(restored.world.has_component::<C_Test_Ai>(enemy));
+            assert!(restored
+                .world
+                .has_component::<C_Multi_Renderable_Animation>(enemy));
+        }
+
+        // The restored level, ground check handles included, saves back to the same bytes.
+        let mut resaved = Byte_Stream::new();
+        restored
+            .serialize_snapshot(&registry, &mut resaved)
+            .unwrap();
+        assert_eq!(saved.as_ref(), resaved.as_ref());
+    }
+}
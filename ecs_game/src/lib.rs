@@ -15,10 +15,6 @@ extern crate inle_math;
 #[macro_use]
 extern crate inle_ecs;
 
-#[cfg(debug_assertions)]
-#[macro_use]
-extern crate lazy_static;
-
 mod cmdline;
 mod collisions;
 mod directions;
@@ -38,6 +34,10 @@ mod systems;
 #[cfg(debug_assertions)]
 mod debug;
 
+// Replays are only available in debug builds.
+#[cfg(all(feature = "headless", debug_assertions))]
+pub mod headless;
+
 use game_state::*;
 use inle_core::{sleep, time};
 use std::convert::TryInto;
@@ -59,6 +59,38 @@ fn new_string_from_c_char_ptr(c_char_ptr: *const c_char) -> String {
     String::from(str_slice)
 }
 
+/// Prepares the engine for a new frame: must be called before every tick_game().
+fn begin_frame(game_state: &mut Game_State) {
+    inle_gfx::render_window::start_new_frame(&mut game_state.window);
+
+    #[cfg(debug_assertions)]
+    {
+        inle_diagnostics::prelude::DEBUG_TRACERS
+            .lock()
+            .unwrap()
+            .values_mut()
+            .for_each(|t| t.lock().unwrap().start_frame());
+
+        let log = &mut game_state.engine_state.debug_systems.log;
+
+        if !game_state.engine_state.time.paused {
+            if game_state.engine_state.time.was_paused() {
+                // Just resumed
+                game_state
+                    .engine_state
+                    .debug_systems
+                    .debug_ui
+                    .frame_scroller
+                    .manually_selected = false;
+                log.reset_from_frame(game_state.engine_state.cur_frame);
+                // The history's frames are numbered like the log's, so it's reset with it.
+                game_state.engine_state.debug_systems.rewind_history.clear();
+            }
+            log.start_frame();
+        }
+    }
+}
+
 /////////////////////////////////////////////////////////////////////////////
 //                        FOREIGN FUNCTION API                             //
 /////////////////////////////////////////////////////////////////////////////
@@ -124,32 +156,7 @@ where
 
     let t_before_work = Instant::now();
 
-    inle_gfx::render_window::start_new_frame(&mut game_state.window);
-
-    #[cfg(debug_assertions)]
-    {
-        inle_diagnostics::prelude::DEBUG_TRACERS
-            .lock()
-            .unwrap()
-            .values_mut()
-            .for_each(|t| t.lock().unwrap().start_frame());
-
-        let log = &mut game_state.engine_state.debug_systems.log;
-
-        if !game_state.engine_state.time.paused {
-            if game_state.engine_state.time.was_paused() {
-                // Just resumed
-                game_state
-                    .engine_state
-                    .debug_systems
-                    .debug_ui
-                    .frame_scroller
-                    .manually_selected = false;
-                log.reset_from_frame(game_state.engine_state.cur_frame);
-            }
-            log.start_frame();
-        }
-    }
+    begin_frame(game_state);
 
     let game_resources = &mut *game_resources;
 
@@ -221,7 +228,7 @@ where
         if print_draw_stats && game_state.engine_state.cur_frame % 100 == 0 {
             ldebug!(
                 "Draw calls this frame: {}. Time taken: {:?}",
-                game_state.window.gl.n_draw_calls_this_frame,
+                inle_gfx::render_window::n_draw_calls_this_frame(&game_state.window),
                 t_before_work.elapsed()
             );
         }
@@ -288,7 +295,8 @@ pub unsafe extern "C" fn game_shutdown(
     #[cfg(debug_assertions)]
     {
         use inle_debug::console::save_console_hist;
-        let engine_state = &(*game_state).engine_state;
+        let engine_state = &mut (*game_state).engine_state;
+        inle_app::app::stop_recording(engine_state);
         save_console_hist(
             &engine_state.debug_systems.console.lock().unwrap(),
             &engine_state.env,
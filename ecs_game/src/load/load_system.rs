@@ -20,6 +20,7 @@ use inle_core::env::Env_Info;
 use inle_core::rand;
 use inle_ecs::components::base::C_Spatial2D;
 use inle_ecs::ecs_world::{Ecs_World, Entity};
+use inle_ecs::prefab::Prefab_Registry;
 use inle_gfx::components::{C_Animated_Sprite, C_Camera2D, C_Multi_Renderable, C_Renderable};
 use inle_gfx::light::{Ambient_Light, Light_Command, Lights, Point_Light, Rect_Light};
 use inle_math::rect::Rect;
@@ -53,16 +54,18 @@ pub fn level_load_sync(
     };
 
     linfo!("Loading level {} ...", level_id);
+    // NOTE: this must come before the entities, so rooms can override the default ambient light.
+    init_demo_lights(&mut level.lights, &engine_state.config, cvars);
     init_demo_entities(
         &mut game_resources.gfx,
         &mut game_resources.shader_cache,
+        &game_resources.prefabs,
         &engine_state.env,
         &mut engine_state.rng,
         &engine_state.config,
         &mut level,
         gs_cfg,
     );
-    init_demo_lights(&mut level.lights, &engine_state.config, cvars);
     fill_world_chunks(&mut level.chunks, &mut level.world, &level.phys_world);
     lok!(
         "Loaded level {}. N. entities = {}, n. cameras = {}",
@@ -98,29 +101,36 @@ fn init_demo_lights(lights: &mut Lights, cfg: &inle_cfg::Config, cvars: &crate::
 fn init_demo_entities(
     gres: &mut Gfx_Resources,
     shader_cache: &mut Shader_Cache,
+    prefabs: &Prefab_Registry,
     env: &Env_Info,
     rng: &mut rand::Default_Rng,
     cfg: &inle_cfg::Config,
     level: &mut Level,
     gs_cfg: Gameplay_System_Config,
 ) {
-    use super::proc_gen;
+    use super::{proc_gen, room_loader};
 
-    proc_gen::generate_random_level(gres, shader_cache, env, rng, cfg, level, gs_cfg);
+    proc_gen::generate_random_level(gres, shader_cache, prefabs, env, rng, cfg, level, gs_cfg);
 
     let camera = create_camera(level, cfg);
 
-    // Create player
-    let player = entities::create_jelly(
-        &mut level.world,
-        &mut level.phys_world,
+    let mut instantiate_args = room_loader::Room_Instantiate_Args {
+        ecs_world: &mut level.world,
+        phys_world: &mut level.phys_world,
         gres,
         shader_cache,
+        lights: &mut level.lights,
+        prefabs,
         env,
         cfg,
+    };
+    let player = room_loader::instantiate_prefab(
+        sid!("player"),
         &Transform2D::from_pos(level.data.player_spawn_point.position),
-        true,
-    );
+        &[],
+        &mut instantiate_args,
+    )
+    .unwrap_or_else(|| fatal!("Failed to create the player!"));
 
     level.world.add_component(
         camera,
@@ -131,19 +141,25 @@ fn init_demo_entities(
     );
 
     // Create AI
-    proc_gen::generate_enemies(gres, shader_cache, env, cfg, level);
+    proc_gen::generate_enemies(gres, shader_cache, prefabs, env, cfg, level);
 }
 
-fn fill_world_chunks(chunks: &mut World_Chunks, world: &mut Ecs_World, phys_world: &Physics_World) {
+pub fn fill_world_chunks(
+    chunks: &mut World_Chunks,
+    world: &mut Ecs_World,
+    phys_world: &Physics_World,
+) {
     foreach_entity!(world,
         read: C_Collider;
         write: C_Spatial2D;
         |entity, (collider,): (&C_Collider,), (spatial,): (&mut C_Spatial2D,)| {
         let pos = spatial.transform.position();
+        let rotation = spatial.transform.rotation();
         spatial.frame_starting_pos = pos;
         let body_handle = collider.phys_body_handle;
         for (collider, cld_handle) in phys_world.get_all_colliders_with_handles(body_handle) {
-            chunks.add_collider(cld_handle, pos, collider.shape.extent());
+            let cld_pos = pos + collider.offset.rotated(rotation);
+            chunks.add_collider(cld_handle, cld_pos, collider.shape.extent());
         }
     });
 }
@@ -1,3 +1,4 @@
 pub mod load_system;
+pub mod prefab_loader;
 mod proc_gen;
-mod room_loader;
+pub mod room_loader;
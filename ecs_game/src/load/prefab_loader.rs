@@ -0,0 +1,348 @@
+use crate::collisions::Game_Collision_Layer;
+use crate::systems::controllable_system::C_Controllable;
+use crate::systems::gravity_system::C_Gravity;
+use crate::systems::ground_detection_system::C_Ground_Detection;
+use inle_cfg::{Cfg_Var, Config};
+use inle_common::colors;
+use inle_core::env::Env_Info;
+use inle_ecs::ecs_world::{Ecs_World, Entity};
+use inle_ecs::prefab::{Prefab_Component, Prefab_Component_Loader, Prefab_Error};
+use inle_gfx::components::{C_Animated_Sprite, C_Renderable};
+use inle_gfx::light::{Light_Command, Lights, Point_Light, Rect_Light};
+use inle_gfx::render;
+use inle_math::rect::Rect;
+use inle_math::transform::Transform2D;
+use inle_math::vector::Vec2f;
+use inle_physics::collider::{C_Collider, Collider, Collision_Shape};
+use inle_physics::phys_world::{Phys_Data, Physics_World};
+use inle_resources::gfx::{Gfx_Resources, Shader_Cache};
+
+#[cfg(debug_assertions)]
+use {
+    crate::debug::entity_debug::C_Debug_Data,
+    crate::debug::systems::position_history_system::C_Position_History,
+};
+
+/// Creates the game components described in prefab files. Supported components are:
+/// - renderable: diffuse, normals, shader, z_index, n_frames, shininess, cast_shadows, color,
+///   width, height (default to the texture frame size), offset, repeated, smooth
+/// - animated_sprite: n_frames, frame_time
+/// - collider: width + height or radius, offset, layer, is_static, inv_mass, restitution,
+///   static_friction, dyn_friction, continuous_collision
+/// - point_light: offset, radius, attenuation, color, intensity
+/// - rect_light: offset, width, height, radius, attenuation, color, intensity
+/// - gravity: acceleration
+/// - controllable: acceleration, jump_impulse, dampening, horiz_max_speed, vert_max_speed, max_jumps
+/// - ground_detection
+/// - debug_name: name (ignored in release)
+/// - position_history: sampling_interval_us, min_delta_pos (ignored in release)
+///
+/// The fields of gravity and controllable are the paths of the cfg vars to use.
+///
+/// Multiple colliders all go into the same physics body.
+/// A loader should be used to instantiate a single prefab, so it can roll back the lights it queued.
+pub struct Game_Prefab_Loader<'r, 's, 'a> {
+    phys_world: &'a mut Physics_World,
+    gres: &'a mut Gfx_Resources<'r>,
+    shader_cache: &'a mut Shader_Cache<'s>,
+    lights: &'a mut Lights,
+    env: &'a Env_Info,
+    cfg: &'a Config,
+
+    /// How many light commands were queued before we started loading.
+    first_light_cmd: usize,
+}
+
+impl<'r, 's, 'a> Game_Prefab_Loader<'r, 's, 'a> {
+    pub fn new(
+        phys_world: &'a mut Physics_World,
+        gres: &'a mut Gfx_Resources<'r>,
+        shader_cache: &'a mut Shader_Cache<'s>,
+        lights: &'a mut Lights,
+        env: &'a Env_Info,
+        cfg: &'a Config,
+    ) -> Self {
+        let first_light_cmd = lights.n_queued_commands();
+        Self {
+            phys_world,
+            gres,
+            shader_cache,
+            lights,
+            env,
+            cfg,
+            first_light_cmd,
+        }
+    }
+}
+
+impl Prefab_Component_Loader for Game_Prefab_Loader<'_, '_, '_> {
+    fn load_component(
+        &mut self,
+        world: &mut Ecs_World,
+        entity: Entity,
+        transform: &Transform2D,
+        comp: &Prefab_Component,
+    ) -> Result<(), Prefab_Error> {
+        match comp.name.as_str() {
+            "renderable" => self.load_renderable(world, entity, comp),
+            "animated_sprite" => {
+                world.add_component(
+                    entity,
+                    C_Animated_Sprite {
+                        n_frames: comp.get_or("n_frames", 1_i32)? as _,
+                        frame_time: comp.get_f32_or("frame_time", 0.1)?,
+                        ..Default::default()
+                    },
+                );
+                Ok(())
+            }
+            "collider" => self.load_collider(world, entity, transform, comp),
+            "point_light" => {
+                let offset = comp.get_vec2f_or("offset", Vec2f::default())?;
+                self.lights
+                    .queue_command(Light_Command::Add_Point_Light(Point_Light {
+                        position: transform.position() + offset,
+                        radius: comp.get_f32_or("radius", 100.)?,
+                        attenuation: comp.get_f32_or("attenuation", 1.)?,
+                        color: get_color_or(comp, "color", colors::WHITE)?,
+                        intensity: comp.get_f32_or("intensity", 1.)?,
+                    }));
+                Ok(())
+            }
+            "rect_light" => {
+                let pos = transform.position() + comp.get_vec2f_or("offset", Vec2f::default())?;
+                let width = comp.get_f32_or("width", 0.)?;
+                let height = comp.get_f32_or("height", 0.)?;
+                self.lights
+                    .queue_command(Light_Command::Add_Rect_Light(Rect_Light {
+                        rect: Rect::new(pos.x - width * 0.5, pos.y - height * 0.5, width, height),
+                        radius: comp.get_f32_or("radius", 100.)?,
+                        attenuation: comp.get_f32_or("attenuation", 1.)?,
+                        color: get_color_or(comp, "color", colors::WHITE)?,
+                        intensity: comp.get_f32_or("intensity", 1.)?,
+                    }));
+                Ok(())
+            }
+            "gravity" => {
+                world.add_component(
+                    entity,
+                    C_Gravity {
+                        acceleration: self.get_cfg_var(comp, "acceleration")?,
+                    },
+                );
+                Ok(())
+            }
+            "controllable" => {
+                world.add_component(
+                    entity,
+                    C_Controllable {
+                        acceleration: self.get_cfg_var(comp, "acceleration")?,
+                        jump_impulse: self.get_cfg_var(comp, "jump_impulse")?,
+                        dampening: self.get_cfg_var(comp, "dampening")?,
+                        horiz_max_speed: self.get_cfg_var(comp, "horiz_max_speed")?,
+                        vert_max_speed: self.get_cfg_var(comp, "vert_max_speed")?,
+                        max_jumps: self.get_cfg_var(comp, "max_jumps")?,
+                        ..Default::default()
+                    },
+                );
+                Ok(())
+            }
+            "ground_detection" => {
+                world.add_component(entity, C_Ground_Detection::default());
+                Ok(())
+            }
+            "debug_name" => {
+                #[cfg(debug_assertions)]
+                {
+                    let name: String = comp.get("name")?;
+                    world.add_component(
+                        entity,
+                        C_Debug_Data {
+                            entity_name: name.as_str().into(),
+                            ..Default::default()
+                        },
+                    );
+                }
+                Ok(())
+            }
+            "position_history" => {
+                #[cfg(debug_assertions)]
+                {
+                    let sampling_interval_us = comp.get_or("sampling_interval_us", 200_i32)?;
+                    world.add_component(
+                        entity,
+                        C_Position_History::new(
+                            std::time::Duration::from_micros(sampling_interval_us.max(0) as u64),
+                            comp.get_f32_or("min_delta_pos", 0.1)?,
+                        ),
+                    );
+                }
+                Ok(())
+            }
+            _ => Err(Prefab_Error::new(format!(
+                "unknown component {} at line {}",
+                comp.name, comp.line
+            ))),
+        }
+    }
+
+    fn unload_components(&mut self, world: &mut Ecs_World, entity: Entity) {
+        if let Some(collider) = world.get_component::<C_Collider>(entity) {
+            self.phys_world
+                .remove_physics_body(collider.phys_body_handle);
+        }
+        self.lights
+            .discard_queued_commands_after(self.first_light_cmd);
+    }
+}
+
+impl Game_Prefab_Loader<'_, '_, '_> {
+    fn load_renderable(
+        &mut self,
+        world: &mut Ecs_World,
+        entity: Entity,
+        comp: &Prefab_Component,
+    ) -> Result<(), Prefab_Error> {
+        let diffuse: String = comp.get("diffuse")?;
+        let shader: String = comp.get_or(
+            "shader",
+            String::from(crate::gfx::shaders::SHD_SPRITE_UNLIT),
+        )?;
+
+        let offset = comp.get_vec2f_or("offset", Vec2f::default())?;
+        let mut renderable = C_Renderable::new_with_diffuse(self.gres, self.env, &diffuse)
+            .with_shader(self.shader_cache, self.env, &shader)
+            .with_z_index(comp.get_or("z_index", 0_i32)? as _)
+            .with_n_frames(comp.get_or("n_frames", 1_i32)?)
+            .with_shininess(comp.get_f32_or("shininess", 0.)?)
+            .with_cast_shadows(comp.get_or("cast_shadows", false)?)
+            .with_color(get_color_or(comp, "color", colors::WHITE)?)
+            .with_local_transform(&Transform2D::from_pos(offset));
+        let mut textures = vec![renderable.material.texture];
+        if comp.get_raw("normals").is_some() {
+            let normals: String = comp.get("normals")?;
+            renderable = renderable.with_normals(self.gres, self.env, &normals);
+            textures.push(renderable.material.normals);
+        }
+        renderable.rect.width = comp.get_f32_or("width", renderable.rect.width as f32)? as i32;
+        renderable.rect.height = comp.get_f32_or("height", renderable.rect.height as f32)? as i32;
+
+        let repeated = comp.get_or("repeated", false)?;
+        let smooth: Option<bool> = if comp.get_raw("smooth").is_some() {
+            Some(comp.get("smooth")?)
+        } else {
+            None
+        };
+        for texture in textures {
+            // Textures are shared, so only touch them if the prefab asks for it.
+            let texture = self.gres.get_texture_mut(texture);
+            if repeated {
+                render::set_texture_repeated(texture, true);
+            }
+            if let Some(smooth) = smooth {
+                render::set_texture_smooth(texture, smooth);
+            }
+        }
+
+        world.add_component(entity, renderable);
+
+        Ok(())
+    }
+
+    fn load_collider(
+        &mut self,
+        world: &mut Ecs_World,
+        entity: Entity,
+        transform: &Transform2D,
+        comp: &Prefab_Component,
+    ) -> Result<(), Prefab_Error> {
+        let scale = transform.scale();
+        let shape = if comp.get_raw("radius").is_some() {
+            Collision_Shape::Circle {
+                radius: comp.get_f32_or("radius", 0.)? * scale.x,
+            }
+        } else {
+            Collision_Shape::Rect {
+                width: comp.get_f32_or("width", 0.)? * scale.x,
+                height: comp.get_f32_or("height", 0.)? * scale.y,
+            }
+        };
+
+        let layer_name: String = comp.get_or("layer", String::from("entities"))?;
+        let layer = match layer_name.as_str() {
+            "entities" => Game_Collision_Layer::Entities,
+            "ground" => Game_Collision_Layer::Ground,
+            "sky" => Game_Collision_Layer::Sky,
+            "ground_check" => Game_Collision_Layer::Ground_Check,
+            _ => {
+                return Err(Prefab_Error::new(format!(
+                    "invalid collision layer {} at line {}",
+                    layer_name, comp.line
+                )))
+            }
+        };
+
+        let cld = Collider {
+            shape,
+            offset: comp.get_vec2f_or("offset", Vec2f::default())?,
+            is_static: comp.get_or("is_static", false)?,
+            layer: layer as _,
+            ..Default::default()
+        };
+        let phys_data = Phys_Data {
+            inv_mass: comp.get_f32_or("inv_mass", 1.)?,
+            restitution: comp.get_f32_or("restitution", 0.9)?,
+            static_friction: comp.get_f32_or("static_friction", 0.5)?,
+            dyn_friction: comp.get_f32_or("dyn_friction", 0.3)?,
+            continuous_collision: comp.get_or("continuous_collision", false)?,
+        };
+
+        let existing_body = world
+            .get_component::<C_Collider>(entity)
+            .map(|collider| collider.phys_body_handle);
+        if let Some(body) = existing_body {
+            let cld_handle = self.phys_world.add_collider(cld, entity);
+            self.phys_world
+                .get_physics_body_mut(body)
+                .unwrap()
+                .rigidbody_colliders
+                .push((cld_handle, phys_data));
+        } else {
+            world.add_component(
+                entity,
+                C_Collider {
+                    phys_body_handle: self
+                        .phys_world
+                        .new_physics_body_with_rigidbody(cld, entity, phys_data),
+                },
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl Game_Prefab_Loader<'_, '_, '_> {
+    fn get_cfg_var<T>(&self, comp: &Prefab_Component, key: &str) -> Result<Cfg_Var<T>, Prefab_Error>
+    where
+        T: Default
+            + Into<inle_cfg::value::Cfg_Value>
+            + std::convert::TryFrom<inle_cfg::value::Cfg_Value>,
+    {
+        let path: String = comp.get(key)?;
+        Ok(Cfg_Var::new(&path, self.cfg))
+    }
+}
+
+/// Colors are written as hex values, e.g. 0xFFAA00FF
+fn get_color_or(
+    comp: &Prefab_Component,
+    key: &str,
+    default: colors::Color,
+) -> Result<colors::Color, Prefab_Error> {
+    if comp.get_raw(key).is_some() {
+        Ok(colors::color_from_hex(comp.get(key)?))
+    } else {
+        Ok(default)
+    }
+}
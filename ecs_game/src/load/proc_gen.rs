@@ -1,6 +1,8 @@
 // @Temporary
 #![allow(warnings)]
 
+use super::room_loader::{self, Entity_Info, Room, Spawn_Kind};
+use crate::directions::{square_directions, Square_Direction};
 use crate::entities;
 use crate::gameplay_system::Gameplay_System_Config;
 use crate::gfx::multi_sprite_animation_system::C_Multi_Renderable_Animation;
@@ -23,6 +25,7 @@ use inle_core::env::Env_Info;
 use inle_core::rand;
 use inle_ecs::components::base::C_Spatial2D;
 use inle_ecs::ecs_world::{Ecs_World, Entity};
+use inle_ecs::prefab::Prefab_Registry;
 use inle_gfx::components::{C_Animated_Sprite, C_Camera2D, C_Multi_Renderable, C_Renderable};
 use inle_gfx::light::{Light_Command, Lights, Point_Light, Rect_Light};
 use inle_math::rect::Rect;
@@ -30,94 +33,213 @@ use inle_math::transform::Transform2D;
 use inle_physics::collider::{C_Collider, Collider, Collision_Shape};
 use inle_physics::phys_world::Physics_World;
 use inle_resources::gfx::{Gfx_Resources, Shader_Cache};
+use smallvec::SmallVec;
 
-struct Room_Grid {
-    size: Vec2u, // number of rooms in both directions
-    first_room_center: Vec2f,
+// @Temporary
+const TILE_SIZE: f32 = 32.0;
+const LEVEL_GEN_CFG_PATH: &str = "game/gameplay/level_gen";
+const MAX_LAYOUT_ATTEMPTS: usize = 100;
+
+/// Parameters of the level generator, read from the game/gameplay/level_gen config section.
+#[derive(Copy, Clone, Debug)]
+pub struct Level_Gen_Config {
+    /// If 0, a random seed is used (and logged, so it can be pinned).
+    pub seed: u64,
+    /// Number of rooms in both directions
+    pub grid_size: Vec2u,
+    /// If true, there is always a path of connected rooms from the player's room (in the leftmost
+    /// column) to the level exit (on the right side of the rightmost column).
+    pub critical_path: bool,
+    /// Probability that a cell outside of the critical path has a room.
+    pub room_density: f32,
+    /// Max number of enemies in the level
+    pub enemy_budget: usize,
+    /// Max number of entities with a light in the level
+    pub light_budget: usize,
+}
+
+impl Level_Gen_Config {
+    pub fn from_cfg(cfg: &Config) -> Self {
+        let path = |name| format!("{}/{}", LEVEL_GEN_CFG_PATH, name);
+        let read_i32 = |name| Cfg_Var::<i32>::new(&path(name), cfg).read(cfg);
+
+        Self {
+            seed: u64::from(read_i32("seed") as u32),
+            grid_size: v2!(
+                read_i32("grid_width").max(1) as u32,
+                read_i32("grid_height").max(1) as u32
+            ),
+            critical_path: Cfg_Var::<bool>::new(&path("critical_path"), cfg).read(cfg),
+            room_density: Cfg_Var::<f32>::new(&path("room_density"), cfg).read(cfg),
+            enemy_budget: read_i32("enemy_budget").max(0) as usize,
+            light_budget: read_i32("light_budget").max(0) as usize,
+        }
+    }
+}
+
+/// A tile of the room in a grid cell
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Grid_Tile {
+    cell: Vec2u,
+    tile: Vec2u,
+}
+
+/// What the level generator decided, before anything gets instantiated.
+#[derive(Debug, PartialEq)]
+struct Level_Layout {
+    grid_size: Vec2u,
+    /// The index into the room pool of each cell's room (row by row), if the cell has one.
+    cells: Vec<Option<usize>>,
+    /// For each cell, the indices of the entities of its room that must be instantiated.
+    cell_entities: Vec<Vec<usize>>,
+    /// From the player's cell to the exit's cell (just the player's cell if no critical path
+    /// was required).
+    critical_path: Vec<Vec2u>,
+    player_spawn: Grid_Tile,
+    exit: Option<Grid_Tile>,
+    enemy_spawns: Vec<Grid_Tile>,
 }
 
 pub fn generate_random_level(
     gres: &mut Gfx_Resources,
     shader_cache: &mut Shader_Cache,
+    prefabs: &Prefab_Registry,
     env: &Env_Info,
     rng: &mut rand::Default_Rng,
     cfg: &Config,
     level: &mut Level,
     gs_cfg: Gameplay_System_Config,
 ) {
-    use inle_math::angle;
-    use inle_resources::gfx::shader_path;
-
-    entities::create_background(&mut level.world, gres, shader_cache, env, cfg);
+    room_loader::instantiate_prefab(
+        sid!("background"),
+        &Transform2D::default(),
+        &[],
+        &mut room_loader::Room_Instantiate_Args {
+            ecs_world: &mut level.world,
+            phys_world: &mut level.phys_world,
+            gres,
+            shader_cache,
+            lights: &mut level.lights,
+            prefabs,
+            env,
+            cfg,
+        },
+    );
 
-    const GRID: Room_Grid = Room_Grid {
-        size: v2!(3, 3),
-        first_room_center: v2!(0., 0.),
-    };
+    let mut gen_cfg = Level_Gen_Config::from_cfg(cfg);
+    // --nentities can raise the enemy budget (the player is one of the entities).
+    gen_cfg.enemy_budget = gen_cfg
+        .enemy_budget
+        .max(gs_cfg.n_entities_to_spawn.saturating_sub(1));
+    if gen_cfg.seed == 0 {
+        // The cfg reads the seed as an i32, so the logged seed must fit in 32 bits to be pinned.
+        gen_cfg.seed = u64::from(rng.next() as u32);
+    }
+    // Each level gets its own seed, or they would all be the same.
+    let level_seed = gen_cfg.seed ^ u64::from(level.id.val());
+    linfo!(
+        "Generating level {} with seed {} (its RNG is seeded with seed ^ level id = {})",
+        level.id,
+        gen_cfg.seed,
+        level_seed
+    );
+    let mut level_rng = rand::new_rng_with_seed(rand::seed_from_u64(level_seed));
 
-    create_room_grid(GRID, level, gres, shader_cache, env, cfg);
+    let room_dir = inle_core::env::asset_dir_path(env, "rooms");
+    let mut room_pool = room_loader::load_room_pool(&room_dir);
+    if room_pool.rooms.is_empty() {
+        fatal!("No rooms found in {}", room_dir.display());
+    }
 
-    level.data.player_spawn_point = Spawn_Point {
-        position: v2!(20., 20.),
-    };
+    // Rooms are laid out in a grid, so they must all have the same size.
+    let room_size = room_pool.rooms[0].size;
+    let tile_size = room_pool.rooms[0].meta.tile_size;
+    room_pool.rooms.retain(|room| {
+        let fits = room.size == room_size && room.meta.tile_size == tile_size;
+        if !fits {
+            lwarn!(
+                "Ignoring a room of size {:?}: rooms must all be {:?} tiles of size {:?}.",
+                room.size,
+                room_size,
+                tile_size
+            );
+        }
+        fits
+    });
+
+    let layout = generate_layout(&room_pool.rooms, &gen_cfg, &mut level_rng, |ent| {
+        entity_has_light(ent, prefabs)
+    })
+    .unwrap_or_else(|| {
+        fatal!(
+            "Failed to generate level {} with seed {}: no room layout satisfies the constraints.",
+            level.id,
+            gen_cfg.seed
+        )
+    });
+
+    create_room_grid(
+        &layout,
+        &room_pool.rooms,
+        level,
+        gres,
+        shader_cache,
+        prefabs,
+        env,
+        cfg,
+    );
+}
 
-    level
-        .data
-        .ai_spawn_points
-        .reserve(gs_cfg.n_entities_to_spawn - 1);
-    for i in 0..gs_cfg.n_entities_to_spawn - 1 {
-        let x = rand::rand_01(rng);
-        let y = rand::rand_01(rng);
-        let pos = v2!(x * 500., 1. * y * 500.);
-        level
-            .data
-            .ai_spawn_points
-            .push(Spawn_Point { position: pos });
-    }
+fn entity_has_light(ent: &Entity_Info, prefabs: &Prefab_Registry) -> bool {
+    room_loader::prefab_from_chr(ent.ent_type)
+        .and_then(|prefab| prefabs.get_prefab(prefab))
+        .map_or(false, |prefab| {
+            prefab
+                .components
+                .iter()
+                .any(|comp| comp.name == "point_light")
+        })
 }
 
 pub fn generate_enemies(
     gres: &mut Gfx_Resources,
     shader_cache: &mut Shader_Cache,
+    prefabs: &Prefab_Registry,
     env: &Env_Info,
     cfg: &Config,
     level: &mut Level,
 ) {
+    let mut instantiate_args = room_loader::Room_Instantiate_Args {
+        ecs_world: &mut level.world,
+        phys_world: &mut level.phys_world,
+        gres,
+        shader_cache,
+        lights: &mut level.lights,
+        prefabs,
+        env,
+        cfg,
+    };
+
     // This is @Temporary
     for spawn_point in &level.data.ai_spawn_points {
-        create_enemy(
-            gres,
-            shader_cache,
-            env,
-            cfg,
-            &mut level.world,
-            &mut level.phys_world,
-            spawn_point.position,
-        );
+        create_enemy(&mut instantiate_args, spawn_point.position);
     }
 }
 
-fn create_enemy(
-    gres: &mut Gfx_Resources,
-    shader_cache: &mut Shader_Cache,
-    env: &Env_Info,
-    cfg: &Config,
-    world: &mut Ecs_World,
-    phys_world: &mut Physics_World,
-    position: Vec2f,
-) {
+fn create_enemy(instantiate_args: &mut room_loader::Room_Instantiate_Args, position: Vec2f) {
     use crate::systems::ai::test_ai_system::C_Test_Ai;
 
-    let enemy = entities::create_jelly(
-        world,
-        phys_world,
-        gres,
-        shader_cache,
-        env,
-        cfg,
+    let enemy = match room_loader::instantiate_prefab(
+        sid!("jelly"),
         &Transform2D::from_pos(position),
-        false,
-    );
+        &[],
+        instantiate_args,
+    ) {
+        Some(enemy) => enemy,
+        None => return,
+    };
+    let world = &mut *instantiate_args.ecs_world;
+    let phys_world = &mut *instantiate_args.phys_world;
 
     let phys_body_handle = world
         .get_component::<C_Collider>(enemy)
@@ -145,7 +267,7 @@ fn create_enemy(
     phys_body.trigger_colliders.push(right_cld_handle);
     phys_body.trigger_colliders.push(left_cld_handle);
 
-    let speed = Cfg_Var::new("game/gameplay/ai/test/speed", cfg);
+    let speed = Cfg_Var::new("game/gameplay/ai/test/speed", instantiate_args.cfg);
     world.add_component(
         enemy,
         C_Test_Ai::new(left_cld_handle, right_cld_handle, speed),
@@ -153,119 +275,355 @@ fn create_enemy(
 }
 
 fn create_room_grid(
-    grid: Room_Grid,
+    layout: &Level_Layout,
+    rooms: &[Room],
     level: &mut Level,
     gres: &mut Gfx_Resources,
     shader_cache: &mut Shader_Cache,
+    prefabs: &Prefab_Registry,
     env: &Env_Info,
     cfg: &Config,
 ) {
-    use super::room_loader;
-
-    let room_dir = inle_core::env::asset_dir_path(env, "rooms");
-    let room_pool = room_loader::load_room_pool(&room_dir);
-    assert!(!room_pool.rooms.is_empty());
-
-    let mut instantiate_args = super::room_loader::Room_Instantiate_Args {
+    let mut instantiate_args = room_loader::Room_Instantiate_Args {
         ecs_world: &mut level.world,
         phys_world: &mut level.phys_world,
         gres,
         shader_cache,
         lights: &mut level.lights,
+        prefabs,
         env,
         cfg,
     };
 
-    // @Temporary
-    const TILE_SIZE: f32 = 32.0;
-    let room_size = room_pool.rooms[0].size;
+    // All rooms have the same size and tile size.
+    let tile_size = rooms[0].meta.tile_size.unwrap_or(TILE_SIZE);
+    let room_size = Vec2f::from(rooms[0].size) * tile_size;
+    // The top-left cell's room is centered on the origin.
+    let room_setup = |cell: Vec2u| room_loader::Room_Setup {
+        room_offset: room_size * -0.5
+            + v2!(cell.x as f32 * room_size.x, cell.y as f32 * room_size.y),
+        tile_size,
+    };
+
+    let grid_width = layout.grid_size.x;
+    for (i, room_idx) in layout.cells.iter().enumerate() {
+        if let Some(room_idx) = *room_idx {
+            let cell = v2!(i as u32 % grid_width, i as u32 / grid_width);
+            room_loader::instantiate_room(
+                &rooms[room_idx],
+                &room_setup(cell),
+                &layout.cell_entities[i],
+                &mut instantiate_args,
+            );
+        }
+    }
+
+    let position = |grid_tile: Grid_Tile| room_setup(grid_tile.cell).tile_center(grid_tile.tile);
+    level.data.player_spawn_point = Spawn_Point {
+        position: position(layout.player_spawn),
+    };
+    level.data.exit_point = layout.exit.map(|exit| Spawn_Point {
+        position: position(exit),
+    });
+    level.data.ai_spawn_points = layout
+        .enemy_spawns
+        .iter()
+        .map(|&spawn| Spawn_Point {
+            position: position(spawn),
+        })
+        .collect();
+
+    let player_room = layout.cells[cell_index(layout.player_spawn.cell, layout.grid_size)].unwrap();
+    level.data.music = rooms[player_room].meta.music.clone();
+}
+
+fn generate_layout(
+    rooms: &[Room],
+    gen_cfg: &Level_Gen_Config,
+    rng: &mut rand::Default_Rng,
+    has_light: impl Fn(&Entity_Info) -> bool,
+) -> Option<Level_Layout> {
+    trace!("proc_gen::generate_layout");
+
+    let (cells, critical_path) =
+        (0..MAX_LAYOUT_ATTEMPTS).find_map(|_| generate_cells(rooms, gen_cfg, rng))?;
+    let grid_size = gen_cfg.grid_size;
+    let room_at = |cell: Vec2u| &rooms[cells[cell_index(cell, grid_size)].unwrap()];
+
+    let start = critical_path[0];
+    let start_room = room_at(start);
+    let player_tile = start_room
+        .spawn_points
+        .iter()
+        .find(|spawn| spawn.kind == Spawn_Kind::Player)
+        .map_or(v2!(start_room.size.x / 2, start_room.size.y / 2), |spawn| {
+            spawn.tile
+        });
+
+    let exit = if gen_cfg.critical_path {
+        let goal = *critical_path.last().unwrap();
+        let goal_room = room_at(goal);
+        Some(Grid_Tile {
+            cell: goal,
+            tile: v2!(
+                goal_room.size.x - 1,
+                u32::from(goal_room.exits[Square_Direction::Right as usize][0])
+            ),
+        })
+    } else {
+        None
+    };
+
+    // Lights are picked at random among all the rooms' ones, until the budget is spent.
+    let mut cell_entities = vec![vec![]; cells.len()];
+    let mut lights = vec![];
+    let mut enemy_spawns = vec![];
+    let mut free_tiles = vec![];
+    for (i, room_idx) in cells.iter().enumerate() {
+        let room_idx = match room_idx {
+            Some(idx) => *idx,
+            None => continue,
+        };
+        let room = &rooms[room_idx];
+        let cell = v2!(i as u32 % grid_size.x, i as u32 / grid_size.x);
+
+        for (ent_idx, ent) in room.entities.iter().enumerate() {
+            if has_light(ent) {
+                lights.push((i, ent_idx));
+            } else {
+                cell_entities[i].push(ent_idx);
+            }
+        }
 
-    for x in 0..grid.size.x {
-        for y in 0..grid.size.y {
-            // @Incomplete: room_halfsize is not really necessarily the actual room
-            // half size, because right now a room's size is decided entirely by
-            // the text file it's loaded from. We should probably decide a fixed
-            // room size (or at least make all rooms a multiple of that size).
-            let offset = v2!(
-                x as f32 * (room_size.x as f32 * TILE_SIZE),
-                y as f32 * (room_size.y as f32 * TILE_SIZE)
+        // No enemies in the player's room
+        if cell != start {
+            enemy_spawns.extend(
+                room.spawn_points
+                    .iter()
+                    .filter(|spawn| spawn.kind == Spawn_Kind::Enemy)
+                    .map(|spawn| Grid_Tile {
+                        cell,
+                        tile: spawn.tile,
+                    }),
             );
-            let room_setup = room_loader::Room_Setup {
-                room_offset: grid.first_room_center - Vec2f::from(room_size) * TILE_SIZE * 0.5
-                    + offset,
-                tile_size: TILE_SIZE,
-            };
-            room_loader::instantiate_room(&room_pool.rooms[0], &room_setup, &mut instantiate_args);
+            free_tiles.extend(room_free_tiles(room).map(|tile| Grid_Tile { cell, tile }));
+        }
+    }
+
+    for (i, ent_idx) in pick_random(lights, gen_cfg.light_budget, rng) {
+        cell_entities[i].push(ent_idx);
+    }
+    for entities in &mut cell_entities {
+        entities.sort_unstable();
+    }
+
+    // Enemies go to the rooms' spawn points first, then to random free tiles outside the player's room.
+    let mut enemy_spawns = pick_random(enemy_spawns, gen_cfg.enemy_budget, rng);
+    let n_missing_enemies = gen_cfg.enemy_budget - enemy_spawns.len();
+    enemy_spawns.extend(pick_random(free_tiles, n_missing_enemies, rng));
+
+    Some(Level_Layout {
+        grid_size,
+        cells,
+        cell_entities,
+        critical_path,
+        player_spawn: Grid_Tile {
+            cell: start,
+            tile: player_tile,
+        },
+        exit,
+        enemy_spawns,
+    })
+}
+
+/// Picks a room for each cell. If a critical path is required, the rooms along it are picked so that
+/// each one connects to the next. Returns None if there is no suitable room for some cell of the path.
+fn generate_cells(
+    rooms: &[Room],
+    gen_cfg: &Level_Gen_Config,
+    rng: &mut rand::Default_Rng,
+) -> Option<(Vec<Option<usize>>, Vec<Vec2u>)> {
+    let grid_size = gen_cfg.grid_size;
+    let mut cells = vec![None; (grid_size.x * grid_size.y) as usize];
+
+    let start = v2!(0, rand_index(rng, grid_size.y as usize) as u32);
+    let path = if gen_cfg.critical_path {
+        let goal = v2!(
+            grid_size.x - 1,
+            rand_index(rng, grid_size.y as usize) as u32
+        );
+        random_path(start, goal, grid_size, rng)
+    } else {
+        vec![start]
+    };
+
+    for (i, &cell) in path.iter().enumerate() {
+        let prev = if i > 0 { Some(path[i - 1]) } else { None };
+        let next = path.get(i + 1).copied();
+        let candidates: Vec<usize> = (0..rooms.len())
+            .filter(|&room_idx| {
+                let room = &rooms[room_idx];
+                let connects_to_prev = prev.map_or(true, |prev| {
+                    let prev_room = &rooms[cells[cell_index(prev, grid_size)].unwrap()];
+                    rooms_connect(room, prev_room, direction_between(cell, prev))
+                });
+                let has_next_exit = match next {
+                    Some(next) => !room.exits[direction_between(cell, next) as usize].is_empty(),
+                    // The last room of the critical path has the level exit on its right.
+                    None => {
+                        !gen_cfg.critical_path
+                            || !room.exits[Square_Direction::Right as usize].is_empty()
+                    }
+                };
+                connects_to_prev && has_next_exit
+            })
+            .collect();
+
+        if candidates.is_empty() {
+            return None;
+        }
+        cells[cell_index(cell, grid_size)] = Some(candidates[rand_index(rng, candidates.len())]);
+    }
+
+    for cell in cells.iter_mut().filter(|cell| cell.is_none()) {
+        if rand::rand_01(rng) < gen_cfg.room_density {
+            *cell = Some(rand_index(rng, rooms.len()));
+        }
+    }
+
+    Some((cells, path))
+}
+
+/// Returns a random path of adjacent cells from `start` to `goal`, never visiting a cell twice.
+fn random_path(
+    start: Vec2u,
+    goal: Vec2u,
+    grid_size: Vec2u,
+    rng: &mut rand::Default_Rng,
+) -> Vec<Vec2u> {
+    let mut visited = vec![false; (grid_size.x * grid_size.y) as usize];
+    visited[cell_index(start, grid_size)] = true;
+    let mut path = vec![start];
+
+    // Randomized depth-first search: the stack is the path.
+    while let Some(&cell) = path.last() {
+        if cell == goal {
+            break;
+        }
+        let unvisited: SmallVec<[Vec2u; 4]> = square_directions()
+            .iter()
+            .filter_map(|&dir| neighbour(cell, dir, grid_size))
+            .filter(|&next| !visited[cell_index(next, grid_size)])
+            .collect();
+        if unvisited.is_empty() {
+            path.pop();
+        } else {
+            let next = unvisited[rand_index(rng, unvisited.len())];
+            visited[cell_index(next, grid_size)] = true;
+            path.push(next);
         }
     }
+
+    debug_assert_eq!(path.last(), Some(&goal));
+    path
+}
+
+/// Whether `a` has an exit towards `dir` that lines up with one of `b`'s exits, if `b` is
+/// placed next to `a` in direction `dir`.
+fn rooms_connect(a: &Room, b: &Room, dir: Square_Direction) -> bool {
+    let b_exits = &b.exits[dir.opposite() as usize];
+    a.exits[dir as usize]
+        .iter()
+        .any(|coord| b_exits.contains(coord))
+}
+
+/// The non-solid tiles of `room` that are not on its border.
+fn room_free_tiles(room: &Room) -> impl Iterator<Item = Vec2u> {
+    let solid = room.solid_mask();
+    let size = room.size;
+    (1..size.y.saturating_sub(1))
+        .flat_map(move |y| (1..size.x.saturating_sub(1)).map(move |x| v2!(x, y)))
+        .filter(move |tile| !solid[(tile.y * size.x + tile.x) as usize])
+}
+
+/// Picks up to `n` random elements of `items`.
+fn pick_random<T>(mut items: Vec<T>, n: usize, rng: &mut rand::Default_Rng) -> Vec<T> {
+    let n = n.min(items.len());
+    // Partial Fisher-Yates shuffle
+    for i in 0..n {
+        let j = i + rand_index(rng, items.len() - i);
+        items.swap(i, j);
+    }
+    items.truncate(n);
+    items
+}
+
+fn rand_index(rng: &mut rand::Default_Rng, len: usize) -> usize {
+    debug_assert!(len > 0);
+    (rng.next() % len as u64) as usize
+}
+
+fn cell_index(cell: Vec2u, grid_size: Vec2u) -> usize {
+    (cell.y * grid_size.x + cell.x) as usize
+}
+
+fn neighbour(cell: Vec2u, dir: Square_Direction, grid_size: Vec2u) -> Option<Vec2u> {
+    match dir {
+        Square_Direction::Up => cell.y.checked_sub(1).map(|y| v2!(cell.x, y)),
+        Square_Direction::Right => Some(v2!(cell.x + 1, cell.y)).filter(|c| c.x < grid_size.x),
+        Square_Direction::Down => Some(v2!(cell.x, cell.y + 1)).filter(|c| c.y < grid_size.y),
+        Square_Direction::Left => cell.x.checked_sub(1).map(|x| v2!(x, cell.y)),
+    }
+}
+
+/// The direction from `from` to the adjacent cell `to`.
+fn direction_between(from: Vec2u, to: Vec2u) -> Square_Direction {
+    if to.y < from.y {
+        Square_Direction::Up
+    } else if to.x > from.x {
+        Square_Direction::Right
+    } else if to.y > from.y {
+        Square_Direction::Down
+    } else {
+        Square_Direction::Left
+    }
 }
 
 fn create_room(
     center: Vec2f,
     room_halfsize: Vec2f,
     wall_thickness: f32,
-    level: &mut Level,
-    gres: &mut Gfx_Resources,
-    shader_cache: &mut Shader_Cache,
-    env: &Env_Info,
-    cfg: &Config,
+    instantiate_args: &mut room_loader::Room_Instantiate_Args,
 ) {
     use entities::create_wall;
 
-    let world = &mut level.world;
-    let phys_world = &mut level.phys_world;
-
     // bot
     create_wall(
-        world,
-        phys_world,
-        gres,
-        shader_cache,
-        env,
+        instantiate_args,
         &Transform2D::from_pos(center + v2!(-room_halfsize.x - wall_thickness, room_halfsize.y)),
         v2!(room_halfsize.x + wall_thickness, wall_thickness),
-        cfg,
     );
     create_wall(
-        world,
-        phys_world,
-        gres,
-        shader_cache,
-        env,
+        instantiate_args,
         &Transform2D::from_pos(center + v2!(2.0 * wall_thickness, room_halfsize.y)),
         v2!(room_halfsize.x - wall_thickness, wall_thickness),
-        cfg,
     );
     // top
     create_wall(
-        world,
-        phys_world,
-        gres,
-        shader_cache,
-        env,
+        instantiate_args,
         &Transform2D::from_pos(center + v2!(-room_halfsize.x, -room_halfsize.y - wall_thickness)),
         v2!(room_halfsize.x, wall_thickness),
-        cfg,
     );
     create_wall(
-        world,
-        phys_world,
-        gres,
-        shader_cache,
-        env,
+        instantiate_args,
         &Transform2D::from_pos(
             center + v2!(2.0 * wall_thickness, -room_halfsize.y - wall_thickness),
         ),
         v2!(room_halfsize.x - wall_thickness, wall_thickness),
-        cfg,
     );
     // left
     create_wall(
-        world,
-        phys_world,
-        gres,
-        shader_cache,
-        env,
+        instantiate_args,
         &Transform2D::from_pos(
             center
                 + v2!(
@@ -274,80 +632,49 @@ fn create_room(
                 ),
         ),
         v2!(wall_thickness, room_halfsize.y + wall_thickness),
-        cfg,
     );
     create_wall(
-        world,
-        phys_world,
-        gres,
-        shader_cache,
-        env,
+        instantiate_args,
         &Transform2D::from_pos(
             center + v2!(-room_halfsize.x - wall_thickness, 2.0 * wall_thickness),
         ),
         v2!(wall_thickness, room_halfsize.y - wall_thickness),
-        cfg,
     );
     // right
     create_wall(
-        world,
-        phys_world,
-        gres,
-        shader_cache,
-        env,
+        instantiate_args,
         &Transform2D::from_pos(center + v2!(room_halfsize.x, -room_halfsize.y)),
         v2!(wall_thickness, room_halfsize.y),
-        cfg,
     );
     create_wall(
-        world,
-        phys_world,
-        gres,
-        shader_cache,
-        env,
+        instantiate_args,
         &Transform2D::from_pos(center + v2!(room_halfsize.x, 2.0 * wall_thickness)),
         v2!(wall_thickness, room_halfsize.y - wall_thickness),
-        cfg,
     );
 
     // Central
     create_wall(
-        world,
-        phys_world,
-        gres,
-        shader_cache,
-        env,
+        instantiate_args,
         &Transform2D::from_pos(center + v2!(-room_halfsize.x * 0.5, room_halfsize.y * 0.2)),
         v2!(wall_thickness * 4.0, wall_thickness),
-        cfg,
     );
     create_wall(
-        world,
-        phys_world,
-        gres,
-        shader_cache,
-        env,
+        instantiate_args,
         &Transform2D::from_pos(center + v2!(room_halfsize.x * 0.2, -room_halfsize.y * 0.2)),
         v2!(wall_thickness * 4.0, wall_thickness),
-        cfg,
     );
     create_wall(
-        world,
-        phys_world,
-        gres,
-        shader_cache,
-        env,
+        instantiate_args,
         &Transform2D::from_pos(center + v2!(-room_halfsize.x * 0.3, -room_halfsize.y * 0.65)),
         v2!(wall_thickness * 4.0, wall_thickness),
-        cfg,
     );
 
     create_room_lights(
         center,
         room_halfsize,
         wall_thickness,
-        &mut level.lights,
-        cfg,
+        instantiate_args.lights,
+        instantiate_args.cfg,
     );
 }
 
@@ -468,3 +795,155 @@ fn create_room_lights(
     //};
     //lights.add_point_light(light);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn room(grid: &[&str]) -> Room {
+        room_with_spawns(grid, &[])
+    }
+
+    fn room_with_spawns(grid: &[&str], spawns: &[&str]) -> Room {
+        let lines = std::iter::once("BEGIN")
+            .chain(grid.iter().copied())
+            .chain(std::iter::once("END"))
+            .chain(std::iter::once("/spawns"))
+            .chain(spawns.iter().copied())
+            .map(String::from);
+        room_loader::parse_room_lines(lines).unwrap()
+    }
+
+    fn test_rooms() -> Vec<Room> {
+        vec![
+            // Exits on all sides
+            room(&[
+                "w w   w w",
+                "w   t    ",
+                "         ",
+                "w t   t w",
+                "w w   w w",
+            ]),
+            // Exits on the left and right only
+            room(&[
+                "w w w w w",
+                "w   t   w",
+                "         ",
+                "w       w",
+                "w w w w w",
+            ]),
+            // No exits at all
+            room(&[
+                "w w w w w",
+                "w   t   w",
+                "w       w",
+                "w       w",
+                "w w w w w",
+            ]),
+            // Exits on all sides, with an enemy spawn point
+            room_with_spawns(
+                &[
+                    "w w   w w",
+                    "w        ",
+                    "         ",
+                    "w       w",
+                    "w w   w w",
+                ],
+                &["enemy 2 2"],
+            ),
+        ]
+    }
+
+    fn gen_cfg(seed: u64) -> Level_Gen_Config {
+        Level_Gen_Config {
+            seed,
+            grid_size: v2!(4, 3),
+            critical_path: true,
+            room_density: 0.5,
+            enemy_budget: 3,
+            light_budget: 4,
+        }
+    }
+
+    fn layout_with_seed(rooms: &[Room], seed: u64) -> Level_Layout {
+        let mut rng = rand::new_rng_with_seed(rand::seed_from_u64(seed));
+        generate_layout(rooms, &gen_cfg(seed), &mut rng, |ent| ent.ent_type == 't').unwrap()
+    }
+
+    #[test]
+    fn same_seed_same_layout() {
+        let rooms = test_rooms();
+        for seed in 1..20 {
+            assert_eq!(
+                layout_with_seed(&rooms, seed),
+                layout_with_seed(&rooms, seed)
+            );
+        }
+        assert_ne!(layout_with_seed(&rooms, 1), layout_with_seed(&rooms, 2));
+    }
+
+    #[test]
+    fn critical_path_connects_player_to_exit() {
+        let rooms = test_rooms();
+        for seed in 1..100 {
+            let layout = layout_with_seed(&rooms, seed);
+            let grid_size = layout.grid_size;
+            let room_at = |cell| layout.cells[cell_index(cell, grid_size)].map(|idx| &rooms[idx]);
+
+            // Flood fill from the player's cell through the connected rooms
+            let mut reached = vec![false; layout.cells.len()];
+            let mut to_visit = vec![layout.player_spawn.cell];
+            reached[cell_index(layout.player_spawn.cell, grid_size)] = true;
+            while let Some(cell) = to_visit.pop() {
+                let room = room_at(cell).unwrap();
+                for dir in square_directions() {
+                    if let Some(next) = neighbour(cell, dir, grid_size) {
+                        let next_idx = cell_index(next, grid_size);
+                        if reached[next_idx] {
+                            continue;
+                        }
+                        if let Some(next_room) = room_at(next) {
+                            if rooms_connect(room, next_room, dir) {
+                                reached[next_idx] = true;
+                                to_visit.push(next);
+                            }
+                        }
+                    }
+                }
+            }
+
+            let exit = layout.exit.unwrap();
+            assert_eq!(exit.cell.x, grid_size.x - 1, "seed {}", seed);
+            assert!(reached[cell_index(exit.cell, grid_size)], "seed {}", seed);
+            // The exit leads out of the grid
+            let exit_room = room_at(exit.cell).unwrap();
+            assert_eq!(exit.tile.x, exit_room.size.x - 1);
+            assert!(
+                exit_room.exits[Square_Direction::Right as usize].contains(&(exit.tile.y as u8))
+            );
+        }
+    }
+
+    #[test]
+    fn budgets_are_respected() {
+        let rooms = test_rooms();
+        let mut n_room_spawns_used = 0;
+        for seed in 1..20 {
+            let layout = layout_with_seed(&rooms, seed);
+            let n_lights: usize = layout.cell_entities.iter().map(Vec::len).sum();
+            assert!(n_lights <= 4);
+            assert_eq!(layout.enemy_spawns.len(), 3);
+            for spawn in &layout.enemy_spawns {
+                assert_ne!(spawn.cell, layout.player_spawn.cell, "seed {}", seed);
+                let room = &rooms[layout.cells[cell_index(spawn.cell, layout.grid_size)].unwrap()];
+                assert!(!room.solid_mask()[(spawn.tile.y * room.size.x + spawn.tile.x) as usize]);
+                n_room_spawns_used += room
+                    .spawn_points
+                    .iter()
+                    .filter(|room_spawn| room_spawn.tile == spawn.tile)
+                    .count();
+            }
+        }
+        assert!(n_room_spawns_used > 0);
+    }
+}
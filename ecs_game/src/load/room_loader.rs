@@ -1,10 +1,16 @@
+use super::prefab_loader::Game_Prefab_Loader;
 use crate::directions::Square_Direction;
 use crate::entities;
 use inle_cfg::config::Config;
 use inle_common::colors;
+use inle_common::stringid::String_Id;
+use inle_common::variant::Variant;
 use inle_core::env::Env_Info;
 use inle_ecs::ecs_world::{Ecs_World, Entity};
-use inle_gfx::light::{Light_Command, Lights, Point_Light};
+use inle_ecs::prefab::{Prefab_Component, Prefab_Registry};
+use inle_gfx::light::{Ambient_Light, Light_Command, Lights};
+use inle_gfx::render::Z_Index;
+use inle_gfx::tilemap::Tile_Id;
 use inle_math::transform::Transform2D;
 use inle_math::vector::{Vec2f, Vec2u};
 use inle_physics::phys_world::Physics_World;
@@ -15,11 +21,42 @@ use std::fmt::Display;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::path::Path;
+use std::str::FromStr;
+
+// A room file is made of sections, each starting with a /header line.
+// Empty lines and everything after a '#' are ignored (except inside grids).
+//
+//     /meta
+//     tile_size 32
+//     ambient_light 0x202040FF 0.8   # color, intensity
+//     music music1.ogg
+//
+//     /layer solid                   # background, solid or decoration
+//     BEGIN
+//     w w w w   w w
+//     w   t       w
+//     w w w w w w w
+//     END
+//
+//     /entity 2 1                    # tile coordinates of an entity placed in any layer
+//     point_light.radius 200         # overrides a field of the entity's prefab
+//
+//     /spawns
+//     player 4 1                     # tile coordinates
+//     enemy 5 1
+//
+// Grids have one character per tile, interleaved with spaces. All layers must have the same number
+// of rows. Only the tiles of the solid layers have colliders, and exits are the holes in their border.
+// For backwards compatibility, a grid outside of any section is a solid layer.
 
 type Room_Side_Coord_Type = u8;
 
 const MAX_ROOM_SIDE_SIZE: u32 = Room_Side_Coord_Type::MAX as _;
 const ROOM_FILE_EXTENSION: &str = "txt";
+const HEADER_SEPARATOR: &str = "/";
+const COMMENT_START: char = '#';
+const GRID_BEGIN: &str = "BEGIN";
+const GRID_END: &str = "END";
 
 // @Temporary: should we have a hardcoded enum for all entity types?
 // Or something else?
@@ -30,6 +67,60 @@ pub type Entity_Type = char;
 pub struct Entity_Info {
     pub ent_type: Entity_Type,
     pub tile: Vec2u,
+    /// Overrides for the entity's prefab, from its /entity section.
+    pub properties: Vec<Prefab_Component>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Room_Layer_Kind {
+    Background,
+    Solid,
+    Decoration,
+}
+
+impl Room_Layer_Kind {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "background" => Some(Self::Background),
+            "solid" => Some(Self::Solid),
+            "decoration" => Some(Self::Decoration),
+            _ => None,
+        }
+    }
+
+    fn z_index(self) -> Z_Index {
+        match self {
+            Self::Background => -1,
+            Self::Solid => 0,
+            Self::Decoration => 1,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Room_Layer {
+    pub kind: Room_Layer_Kind,
+    /// Only the non-empty tiles
+    pub tiles: Vec<(Vec2u, Tile_Id)>,
+}
+
+#[derive(Default, Debug)]
+pub struct Room_Metadata {
+    pub tile_size: Option<f32>,
+    pub ambient_light: Option<Ambient_Light>,
+    pub music: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Spawn_Kind {
+    Player,
+    Enemy,
+}
+
+#[derive(Debug)]
+pub struct Room_Spawn_Point {
+    pub kind: Spawn_Kind,
+    pub tile: Vec2u,
 }
 
 #[derive(Default)]
@@ -41,20 +132,65 @@ pub struct Room_Pool {
 pub struct Room {
     pub size: Vec2u,
 
+    pub meta: Room_Metadata,
+
+    pub layers: Vec<Room_Layer>,
+
     pub entities: Vec<Entity_Info>,
 
+    pub spawn_points: Vec<Room_Spawn_Point>,
+
     // Indexed by Square_Direction
     pub exits: [SmallVec<[Room_Side_Coord_Type; 4]>; 4],
 }
 
+impl Room {
+    /// Returns whether each tile (row by row) is solid in any of the solid layers.
+    pub fn solid_mask(&self) -> Vec<bool> {
+        let mut solid = vec![false; (self.size.x * self.size.y) as usize];
+        for layer in self
+            .layers
+            .iter()
+            .filter(|layer| layer.kind == Room_Layer_Kind::Solid)
+        {
+            for (tile, _) in &layer.tiles {
+                solid[(tile.y * self.size.x + tile.x) as usize] = true;
+            }
+        }
+        solid
+    }
+}
+
 #[derive(Debug)]
 pub struct Room_Load_Err {
     msg: Cow<'static, str>,
+    /// 1-based line and column of the room file where the error is, if any.
+    pub location: Option<(usize, usize)>,
+}
+
+impl Room_Load_Err {
+    fn new<T: Into<Cow<'static, str>>>(msg: T) -> Self {
+        Self {
+            msg: msg.into(),
+            location: None,
+        }
+    }
+
+    fn at<T: Into<Cow<'static, str>>>(line: usize, column: usize, msg: T) -> Self {
+        Self {
+            msg: msg.into(),
+            location: Some((line, column)),
+        }
+    }
 }
 
 impl Display for Room_Load_Err {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", self.msg)
+        if let Some((line, column)) = self.location {
+            write!(f, "{}:{}: {}", line, column, self.msg)
+        } else {
+            write!(f, "{}", self.msg)
+        }
     }
 }
 
@@ -66,12 +202,19 @@ pub struct Room_Setup {
     pub tile_size: f32,
 }
 
+impl Room_Setup {
+    pub fn tile_center(&self, tile: Vec2u) -> Vec2f {
+        self.room_offset + (Vec2f::from(tile) + v2!(0.5, 0.5)) * self.tile_size
+    }
+}
+
 pub struct Room_Instantiate_Args<'r, 's, 'a> {
     pub ecs_world: &'a mut Ecs_World,
     pub phys_world: &'a mut Physics_World,
     pub gres: &'a mut Gfx_Resources<'r>,
     pub shader_cache: &'a mut Shader_Cache<'s>,
     pub lights: &'a mut Lights,
+    pub prefabs: &'a Prefab_Registry,
     pub env: &'a Env_Info,
     pub cfg: &'a Config,
 }
@@ -95,174 +238,733 @@ pub fn load_room_pool(directory: &Path) -> Room_Pool {
     }) {
         match load_room_from_file(&entry.path()) {
             Ok(room) => room_pool.rooms.push(room),
-            Err(err) => lerr!("Error loading room: {}", err),
+            Err(err) => lerr!("Error loading room {}:{}", entry.path().display(), err),
         }
     }
 
     room_pool
 }
 
-#[allow(clippy::collapsible_else_if)]
 pub fn load_room_from_file(filepath: &Path) -> Result<Room, Room_Load_Err> {
-    let file = File::open(filepath).map_err(|err| Room_Load_Err {
-        msg: Cow::Owned(format!("Error opening file: {}", err)),
-    })?;
-    let lines = BufReader::new(file).lines().filter_map(|l| l.ok());
+    let file = File::open(filepath)
+        .map_err(|err| Room_Load_Err::new(format!("Error opening file: {}", err)))?;
+    let lines = BufReader::new(file).lines().map_while(Result::ok);
+
+    let room = parse_room_lines(lines)?;
+    ldebug!(
+        "Room {}: size = {:?}, exits = {:?}",
+        filepath.display(),
+        room.size,
+        room.exits
+    );
+
+    Ok(room)
+}
 
+enum Section {
+    None,
+    Meta,
+    Layer {
+        kind: Room_Layer_Kind,
+        header_line: usize,
+        /// None until we find BEGIN
+        grid: Option<Vec<(usize, String)>>,
+    },
+    /// Index into the pending entity properties
+    Entity(usize),
+    Spawns,
+}
+
+/// The properties of an /entity section. They're assigned to their entity once all layers are
+/// parsed, since the section may come before the layer the entity is in.
+struct Entity_Properties {
+    tile: Vec2u,
+    line: usize,
+    components: Vec<Prefab_Component>,
+}
+
+pub(super) fn parse_room_lines(lines: impl Iterator<Item = String>) -> Result<Room, Room_Load_Err> {
     let mut room = Room::default();
+    let mut section = Section::None;
+    let mut entity_props: Vec<Entity_Properties> = vec![];
+    // Line of each spawn point, in case it's out of the room.
+    let mut spawn_lines = vec![];
 
-    let mut within_begin_and_end = false;
-    let mut room_lines = vec![];
     for (lineno, line) in lines.enumerate() {
-        if within_begin_and_end {
-            if line == "END" {
-                break;
+        let lineno = lineno + 1;
+
+        // Grid lines are taken verbatim
+        if let Section::Layer {
+            kind,
+            grid: Some(grid),
+            ..
+        } = &mut section
+        {
+            if line.trim_end() == GRID_END {
+                let grid = std::mem::take(grid);
+                parse_layer_grid(*kind, lineno, &grid, &mut room)?;
+                section = Section::None;
             } else {
-                room_lines.push(line);
+                grid.push((lineno, line));
+            }
+            continue;
+        }
+
+        let code = match line.find(COMMENT_START) {
+            Some(comment_start) => &line[..comment_start],
+            None => &line[..],
+        };
+        let tokens = tokenize(code);
+        let (first_col, first_tok) = match tokens.first() {
+            Some(&tok) => tok,
+            None => continue,
+        };
+
+        if let Some(header) = first_tok.strip_prefix(HEADER_SEPARATOR) {
+            if let Section::Layer { header_line, .. } = section {
+                return Err(Room_Load_Err::at(
+                    header_line,
+                    1,
+                    format!("layer has no grid (expected {})", GRID_BEGIN),
+                ));
+            }
+            section = parse_section_header(header, &tokens, lineno, &mut entity_props)?;
+            continue;
+        }
+
+        match &mut section {
+            Section::None => {
+                if first_tok == GRID_BEGIN {
+                    section = Section::Layer {
+                        kind: Room_Layer_Kind::Solid,
+                        header_line: lineno,
+                        grid: Some(vec![]),
+                    };
+                } else {
+                    return Err(Room_Load_Err::at(
+                        lineno,
+                        first_col,
+                        "line is outside of any section",
+                    ));
+                }
+            }
+            Section::Layer { grid, .. } => {
+                if first_tok == GRID_BEGIN {
+                    *grid = Some(vec![]);
+                } else {
+                    return Err(Room_Load_Err::at(
+                        lineno,
+                        first_col,
+                        format!("expected {}, found {}", GRID_BEGIN, first_tok),
+                    ));
+                }
+            }
+            Section::Meta => parse_meta_line(&tokens, lineno, &mut room.meta)?,
+            Section::Entity(idx) => {
+                parse_entity_property(code, &tokens, lineno, &mut entity_props[*idx])?
+            }
+            Section::Spawns => {
+                let kind = match first_tok {
+                    "player" => Spawn_Kind::Player,
+                    "enemy" => Spawn_Kind::Enemy,
+                    _ => {
+                        return Err(Room_Load_Err::at(
+                            lineno,
+                            first_col,
+                            format!("unknown spawn point kind {}", first_tok),
+                        ))
+                    }
+                };
+                let tile = parse_tile_coords(&tokens, 1, lineno)?;
+                room.spawn_points.push(Room_Spawn_Point { kind, tile });
+                spawn_lines.push(lineno);
             }
+        }
+    }
+
+    if let Section::Layer { header_line, .. } = section {
+        return Err(Room_Load_Err::at(
+            header_line,
+            1,
+            format!("layer grid has no {}", GRID_END),
+        ));
+    }
+
+    if room.layers.is_empty() {
+        return Err(Room_Load_Err::new("Room is empty."));
+    }
+
+    for props in entity_props {
+        let tile = props.tile;
+        if let Some(entity) = room.entities.iter_mut().find(|e| e.tile == tile) {
+            entity.properties.extend(props.components);
         } else {
-            if line == "BEGIN" {
-                within_begin_and_end = true;
-            } else {
-                lwarn!(
-                    "{}:{}: ignored line {} before 'BEGIN'.",
-                    filepath.display(),
+            return Err(Room_Load_Err::at(
+                props.line,
+                1,
+                format!("there is no entity at tile ({}, {})", tile.x, tile.y),
+            ));
+        }
+    }
+
+    for (spawn, line) in room.spawn_points.iter().zip(spawn_lines) {
+        if spawn.tile.x >= room.size.x || spawn.tile.y >= room.size.y {
+            return Err(Room_Load_Err::at(
+                line,
+                1,
+                format!(
+                    "spawn point ({}, {}) is outside the room",
+                    spawn.tile.x, spawn.tile.y
+                ),
+            ));
+        }
+    }
+
+    find_exits(&mut room);
+
+    Ok(room)
+}
+
+/// Splits `line` on whitespace, returning each token with its 1-based column.
+fn tokenize(line: &str) -> Vec<(usize, &str)> {
+    let mut tokens = vec![];
+    let mut start = None;
+    for (col, (idx, chr)) in line.char_indices().enumerate() {
+        match (start, chr.is_whitespace()) {
+            (None, false) => start = Some((col, idx)),
+            (Some((start_col, start_idx)), true) => {
+                tokens.push((start_col + 1, &line[start_idx..idx]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some((start_col, start_idx)) = start {
+        tokens.push((start_col + 1, &line[start_idx..]));
+    }
+    tokens
+}
+
+fn parse_token<T: FromStr>(
+    tokens: &[(usize, &str)],
+    idx: usize,
+    lineno: usize,
+    what: &str,
+) -> Result<T, Room_Load_Err> {
+    let (col, tok) = tokens.get(idx).copied().ok_or_else(|| {
+        let end_col = tokens
+            .last()
+            .map_or(1, |(col, tok)| col + tok.chars().count());
+        Room_Load_Err::at(lineno, end_col, format!("missing {}", what))
+    })?;
+    tok.parse()
+        .map_err(|_| Room_Load_Err::at(lineno, col, format!("invalid {}: {}", what, tok)))
+}
+
+fn parse_tile_coords(
+    tokens: &[(usize, &str)],
+    first_idx: usize,
+    lineno: usize,
+) -> Result<Vec2u, Room_Load_Err> {
+    Ok(v2!(
+        parse_token(tokens, first_idx, lineno, "tile x")?,
+        parse_token(tokens, first_idx + 1, lineno, "tile y")?
+    ))
+}
+
+fn parse_section_header(
+    header: &str,
+    tokens: &[(usize, &str)],
+    lineno: usize,
+    entity_props: &mut Vec<Entity_Properties>,
+) -> Result<Section, Room_Load_Err> {
+    match header {
+        "meta" => Ok(Section::Meta),
+        "layer" => {
+            let name: String = parse_token(tokens, 1, lineno, "layer name")?;
+            let kind = Room_Layer_Kind::from_name(&name).ok_or_else(|| {
+                Room_Load_Err::at(lineno, tokens[1].0, format!("unknown layer {}", name))
+            })?;
+            Ok(Section::Layer {
+                kind,
+                header_line: lineno,
+                grid: None,
+            })
+        }
+        "entity" => {
+            let tile = parse_tile_coords(tokens, 1, lineno)?;
+            entity_props.push(Entity_Properties {
+                tile,
+                line: lineno,
+                components: vec![],
+            });
+            Ok(Section::Entity(entity_props.len() - 1))
+        }
+        "spawns" => Ok(Section::Spawns),
+        _ => Err(Room_Load_Err::at(
+            lineno,
+            tokens[0].0,
+            format!("unknown section {}", header),
+        )),
+    }
+}
+
+fn parse_meta_line(
+    tokens: &[(usize, &str)],
+    lineno: usize,
+    meta: &mut Room_Metadata,
+) -> Result<(), Room_Load_Err> {
+    let (key_col, key) = tokens[0];
+    match key {
+        "tile_size" => {
+            let tile_size: f32 = parse_token(tokens, 1, lineno, "tile size")?;
+            if tile_size < 1. {
+                return Err(Room_Load_Err::at(
                     lineno,
-                    line
-                );
+                    tokens[1].0,
+                    "tile size must be at least 1",
+                ));
             }
+            meta.tile_size = Some(tile_size);
+        }
+        "ambient_light" => {
+            let color: String = parse_token(tokens, 1, lineno, "ambient light color")?;
+            let color = color
+                .strip_prefix("0x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| {
+                    Room_Load_Err::at(
+                        lineno,
+                        tokens[1].0,
+                        format!("invalid color {} (expected 0xRRGGBBAA)", color),
+                    )
+                })?;
+            meta.ambient_light = Some(Ambient_Light {
+                color: colors::color_from_hex(color),
+                intensity: parse_token(tokens, 2, lineno, "ambient light intensity")?,
+            });
+        }
+        "music" => meta.music = Some(parse_token(tokens, 1, lineno, "music name")?),
+        _ => {
+            return Err(Room_Load_Err::at(
+                lineno,
+                key_col,
+                format!("unknown metadata {}", key),
+            ))
         }
     }
 
-    if room_lines.is_empty() {
-        return Err(Room_Load_Err {
-            msg: Cow::Borrowed("Room is empty."),
-        });
+    Ok(())
+}
+
+fn parse_entity_property(
+    code: &str,
+    tokens: &[(usize, &str)],
+    lineno: usize,
+    props: &mut Entity_Properties,
+) -> Result<(), Room_Load_Err> {
+    let (key_col, key) = tokens[0];
+    let (comp_name, field) = match key.split_once('.') {
+        Some((comp_name, field)) if !comp_name.is_empty() && !field.is_empty() => {
+            (comp_name, field)
+        }
+        _ => {
+            return Err(Room_Load_Err::at(
+                lineno,
+                key_col,
+                format!("expected component.field, found {}", key),
+            ))
+        }
+    };
+    // The value is the whole rest of the line, as it may contain spaces (e.g. a 2D vector).
+    let value = code.trim_start()[key.len()..].trim();
+    if value.is_empty() {
+        return Err(Room_Load_Err::at(
+            lineno,
+            key_col + key.chars().count(),
+            format!("missing value for {}", key),
+        ));
     }
 
-    room.size.y = room_lines.len() as _;
-    assert!(
-        room.size.y <= MAX_ROOM_SIDE_SIZE,
-        "Vertical size of room {} is too big! ({}/{})",
-        filepath.display(),
-        room.size.y,
-        MAX_ROOM_SIDE_SIZE
-    );
+    let comp = match props.components.iter().position(|c| c.name == comp_name) {
+        Some(idx) => &mut props.components[idx],
+        None => {
+            props.components.push(Prefab_Component {
+                name: String::from(comp_name),
+                fields: vec![],
+                line: lineno,
+            });
+            props.components.last_mut().unwrap()
+        }
+    };
+    comp.fields
+        .push((String::from(field), Variant::from(value)));
+
+    Ok(())
+}
 
-    // NOTE: since room lines are interleaved with spaces we must take that into account:
+fn parse_layer_grid(
+    kind: Room_Layer_Kind,
+    end_line: usize,
+    grid: &[(usize, String)],
+    room: &mut Room,
+) -> Result<(), Room_Load_Err> {
+    if grid.is_empty() {
+        return Err(Room_Load_Err::at(end_line, 1, "layer grid is empty"));
+    }
+    let first_line = grid[0].0;
+
+    let height = grid.len() as u32;
+    // NOTE: since grid lines are interleaved with spaces we must take that into account:
     // w w w w w -> line len = 9, room width = 5
-    room.size.x = room_lines[0].len() as u32 / 2 + 1;
-    assert!(
-        room.size.x <= MAX_ROOM_SIDE_SIZE,
-        "Horizontal size of room {} is too big! ({}/{})",
-        filepath.display(),
-        room.size.x,
-        MAX_ROOM_SIDE_SIZE
-    );
+    let width = grid
+        .iter()
+        .map(|(_, line)| (line.chars().count() as u32).div_ceil(2))
+        .max()
+        .unwrap();
+    if width > MAX_ROOM_SIDE_SIZE || height > MAX_ROOM_SIDE_SIZE {
+        return Err(Room_Load_Err::at(
+            first_line,
+            1,
+            format!(
+                "layer is too big ({}x{}, max side is {})",
+                width, height, MAX_ROOM_SIDE_SIZE
+            ),
+        ));
+    }
 
-    for (room_lineno, line) in room_lines.iter().enumerate() {
-        parse_room_line(
-            room_lineno as u32, // y coords go downward!
-            line,
-            &mut room,
-        )?;
+    if room.layers.is_empty() {
+        room.size = v2!(width, height);
+    } else if height != room.size.y {
+        return Err(Room_Load_Err::at(
+            first_line,
+            1,
+            format!(
+                "layer has {} rows, but the previous ones have {}",
+                height, room.size.y
+            ),
+        ));
+    } else {
+        room.size.x = room.size.x.max(width);
     }
-    ldebug!("Room size = {:?}, exits = {:?}", room.size, room.exits);
 
-    Ok(room)
-}
+    let mut layer = Room_Layer {
+        kind,
+        tiles: vec![],
+    };
+    for (room_y, (lineno, line)) in grid.iter().enumerate() {
+        for (i, chr) in line.chars().enumerate() {
+            if chr == ' ' {
+                continue;
+            }
+            if i % 2 == 1 {
+                return Err(Room_Load_Err::at(
+                    *lineno,
+                    i + 1,
+                    "tiles must be separated by spaces",
+                ));
+            }
 
-fn parse_room_line(room_y: u32, line: &str, room: &mut Room) -> Result<(), Room_Load_Err> {
-    let is_top_row = room_y == 0;
-    let is_bottom_row = room_y == room.size.y - 1;
-    for (room_x, entity_chr) in line.chars().step_by(2).enumerate() {
-        let coords = v2!(room_x as _, room_y);
-        let entity_info = Entity_Info {
-            ent_type: entity_chr,
-            tile: v2!(room_x as _, room_y as _),
-        };
-        let is_left_column = room_x == 0;
-        let is_right_column = room_x == room.size.x as usize - 1;
-        if entity_chr != ' ' {
-            room.entities.push(entity_info);
-        } else if is_top_row {
-            room.exits[Square_Direction::Up as usize].push(room_x as _);
-        } else if is_bottom_row {
-            room.exits[Square_Direction::Down as usize].push(room_x as _);
-        } else if is_left_column {
-            room.exits[Square_Direction::Left as usize].push(room_y as _);
-        } else if is_right_column {
-            room.exits[Square_Direction::Right as usize].push(room_y as _);
+            // y coords go downward!
+            let tile = v2!(i as u32 / 2, room_y as u32);
+            if let Some(tile_id) = tile_from_chr(chr) {
+                layer.tiles.push((tile, tile_id));
+            } else if prefab_from_chr(chr).is_some() {
+                room.entities.push(Entity_Info {
+                    ent_type: chr,
+                    tile,
+                    properties: vec![],
+                });
+            } else {
+                return Err(Room_Load_Err::at(
+                    *lineno,
+                    i + 1,
+                    format!("unknown tile or entity '{}'", chr),
+                ));
+            }
         }
     }
+    room.layers.push(layer);
 
     Ok(())
 }
 
+/// Exits are the border tiles that are not solid.
+fn find_exits(room: &mut Room) {
+    let Vec2u {
+        x: width,
+        y: height,
+    } = room.size;
+    let solid = room.solid_mask();
+
+    for room_y in 0..height {
+        for room_x in 0..width {
+            if solid[(room_y * width + room_x) as usize] {
+                continue;
+            }
+            if room_y == 0 {
+                room.exits[Square_Direction::Up as usize].push(room_x as _);
+            } else if room_y == height - 1 {
+                room.exits[Square_Direction::Down as usize].push(room_x as _);
+            } else if room_x == 0 {
+                room.exits[Square_Direction::Left as usize].push(room_y as _);
+            } else if room_x == width - 1 {
+                room.exits[Square_Direction::Right as usize].push(room_y as _);
+            }
+        }
+    }
+}
+
+fn tile_from_chr(chr: char) -> Option<Tile_Id> {
+    match chr {
+        'w' => Some(entities::WALL_TILE),
+        _ => None,
+    }
+}
+
+pub fn prefab_from_chr(chr: Entity_Type) -> Option<String_Id> {
+    match chr {
+        't' => Some(sid!("torch")),
+        _ => None,
+    }
+}
+
+/// Instantiates all the layers of `room`, but only the entities listed in `entity_indices`
+/// (indices into room.entities).
 pub fn instantiate_room(
     room: &Room,
     room_setup: &Room_Setup,
+    entity_indices: &[usize],
     instantiate_args: &mut Room_Instantiate_Args,
 ) {
-    for ent_info in &room.entities {
-        if load_entity_from_chr(
-            ent_info.ent_type,
-            ent_info.tile,
-            room_setup,
-            instantiate_args,
+    // Each layer is a tilemap
+    for layer in room.layers.iter().filter(|layer| !layer.tiles.is_empty()) {
+        let mut tilemap = entities::new_room_tilemap(
+            instantiate_args.gres,
+            instantiate_args.shader_cache,
+            instantiate_args.env,
+            room.size,
+            room_setup.tile_size as u32,
         )
-        .is_none()
-        {
+        .with_z_index(layer.kind.z_index());
+        if layer.kind != Room_Layer_Kind::Solid {
+            tilemap = tilemap.with_solid_tiles(&[]);
+        }
+        for &(tile, tile_id) in &layer.tiles {
+            tilemap.set_tile(tile.x, tile.y, tile_id);
+        }
+
+        entities::create_tilemap(
+            instantiate_args.ecs_world,
+            instantiate_args.phys_world,
+            &Transform2D::from_pos(room_setup.room_offset),
+            tilemap,
+        );
+    }
+
+    for ent_info in entity_indices.iter().map(|&idx| &room.entities[idx]) {
+        if let Some(prefab) = prefab_from_chr(ent_info.ent_type) {
+            let transform = Transform2D::from_pos(
+                Vec2f::from(ent_info.tile) * room_setup.tile_size + room_setup.room_offset,
+            );
+            instantiate_prefab(prefab, &transform, &ent_info.properties, instantiate_args);
+        } else {
             lerr!("Unknown entity '{}'", ent_info.ent_type);
         }
     }
+
+    if let Some(ambient_light) = room.meta.ambient_light {
+        instantiate_args
+            .lights
+            .queue_command(Light_Command::Change_Ambient_Light(ambient_light));
+    }
 }
 
-// Note: Some(INVALID) means 'empty tile', None means error.
-fn load_entity_from_chr(
-    entity_chr: char,
-    grid_coords: Vec2u,
-    room_setup: &Room_Setup,
+/// Instantiates `prefab` at `transform`, logging an error and returning None if that fails.
+pub fn instantiate_prefab(
+    prefab: String_Id,
+    transform: &Transform2D,
+    properties: &[Prefab_Component],
     instantiate_args: &mut Room_Instantiate_Args,
 ) -> Option<Entity> {
-    let transform = Transform2D::from_pos(
-        Vec2f::from(grid_coords) * room_setup.tile_size + room_setup.room_offset,
+    let mut loader = Game_Prefab_Loader::new(
+        instantiate_args.phys_world,
+        instantiate_args.gres,
+        instantiate_args.shader_cache,
+        instantiate_args.lights,
+        instantiate_args.env,
+        instantiate_args.cfg,
     );
-    match entity_chr {
-        'w' => Some(entities::create_wall(
-            instantiate_args.ecs_world,
-            instantiate_args.phys_world,
-            instantiate_args.gres,
-            instantiate_args.shader_cache,
-            instantiate_args.env,
-            &transform,
-            v2!(room_setup.tile_size, room_setup.tile_size),
-            instantiate_args.cfg,
-        )),
-
-        't' => {
-            instantiate_args
-                .lights
-                .queue_command(Light_Command::Add_Point_Light(Point_Light {
-                    position: transform.position(),
-                    radius: 150.,
-                    attenuation: 1.0,
-                    color: colors::YELLOW,
-                    intensity: 1.0,
-                }));
-            Some(entities::create_torch(
-                instantiate_args.ecs_world,
-                instantiate_args.phys_world,
-                instantiate_args.gres,
-                instantiate_args.shader_cache,
-                instantiate_args.env,
-                instantiate_args.cfg,
-                &transform,
-            ))
+    match instantiate_args.prefabs.instantiate_with_overrides(
+        prefab,
+        instantiate_args.ecs_world,
+        transform,
+        properties,
+        &mut loader,
+    ) {
+        Ok(entity) => Some(entity),
+        Err(err) => {
+            lerr!("{}", err);
+            None
         }
+    }
+}
 
-        ' ' => Some(Entity::INVALID),
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        _ => None,
+    fn parse(lines: &[&str]) -> Result<Room, Room_Load_Err> {
+        parse_room_lines(lines.iter().map(|&l| String::from(l)))
+    }
+
+    fn err_location(lines: &[&str]) -> Option<(usize, usize)> {
+        parse(lines).err().unwrap().location
+    }
+
+    #[test]
+    fn parse_legacy_room() {
+        let room = parse(&["BEGIN", "w w   w", "w   t  ", "w w w w", "END"]).unwrap();
+
+        assert_eq!(room.size, v2!(4, 3));
+        assert_eq!(room.layers.len(), 1);
+        assert_eq!(room.layers[0].kind, Room_Layer_Kind::Solid);
+        assert_eq!(room.layers[0].tiles.len(), 8);
+        assert_eq!(room.entities.len(), 1);
+        assert_eq!(room.entities[0].tile, v2!(2, 1));
+        assert_eq!(&room.exits[Square_Direction::Up as usize][..], &[2]);
+        assert_eq!(&room.exits[Square_Direction::Right as usize][..], &[1]);
+        assert!(room.exits[Square_Direction::Down as usize].is_empty());
+        assert!(room.exits[Square_Direction::Left as usize].is_empty());
+    }
+
+    #[test]
+    fn parse_room_v2() {
+        let room = parse(&[
+            "# A test room",
+            "/meta",
+            "tile_size 16",
+            "ambient_light 0x20204080 0.5 # dim",
+            "music music1.ogg",
+            "",
+            "/entity 1 1",
+            "point_light.radius 200",
+            "point_light.offset 1 -2",
+            "",
+            "/layer background",
+            "BEGIN",
+            "w w w",
+            "w w w",
+            "END",
+            "/layer solid",
+            "BEGIN",
+            "w   w",
+            "w w w",
+            "END",
+            "/layer decoration",
+            "BEGIN",
+            "       ",
+            "  t    ",
+            "END",
+            "",
+            "/spawns",
+            "player 1 0",
+            "enemy 0 0",
+        ])
+        .unwrap();
+
+        assert_eq!(room.size, v2!(4, 2));
+        assert_eq!(room.meta.tile_size, Some(16.));
+        let ambient = room.meta.ambient_light.unwrap();
+        assert_eq!(ambient.color, colors::rgba(0x20, 0x20, 0x40, 0x80));
+        assert_eq!(ambient.intensity, 0.5);
+        assert_eq!(room.meta.music.as_deref(), Some("music1.ogg"));
+
+        let kinds: Vec<_> = room.layers.iter().map(|l| l.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                Room_Layer_Kind::Background,
+                Room_Layer_Kind::Solid,
+                Room_Layer_Kind::Decoration
+            ]
+        );
+        assert!(room.layers[2].tiles.is_empty());
+
+        assert_eq!(room.entities.len(), 1);
+        let torch = &room.entities[0];
+        assert_eq!(torch.tile, v2!(1, 1));
+        assert_eq!(torch.properties.len(), 1);
+        assert_eq!(torch.properties[0].name, "point_light");
+        assert_eq!(torch.properties[0].get_f32_or("radius", 0.).unwrap(), 200.);
+        assert_eq!(
+            torch.properties[0]
+                .get_vec2f_or("offset", Vec2f::default())
+                .unwrap(),
+            v2!(1., -2.)
+        );
+
+        assert_eq!(room.spawn_points.len(), 2);
+        assert_eq!(room.spawn_points[0].kind, Spawn_Kind::Player);
+        assert_eq!(room.spawn_points[0].tile, v2!(1, 0));
+        assert_eq!(room.spawn_points[1].kind, Spawn_Kind::Enemy);
+
+        // Only the solid layer counts for the exits
+        assert_eq!(&room.exits[Square_Direction::Up as usize][..], &[1, 3]);
+    }
+
+    #[test]
+    fn room_errors_have_location() {
+        assert_eq!(err_location(&["BEGIN", "w x", "END"]), Some((2, 3)));
+        assert_eq!(err_location(&["BEGIN", "ww", "END"]), Some((2, 2)));
+        assert_eq!(err_location(&["", "  w w"]), Some((2, 3)));
+        assert_eq!(err_location(&["/layer lava"]), Some((1, 8)));
+        assert_eq!(err_location(&["/rooms"]), Some((1, 1)));
+        assert_eq!(err_location(&["/meta", "tile_size big"]), Some((2, 11)));
+        assert_eq!(err_location(&["/meta", "tile_size"]), Some((2, 10)));
+        assert_eq!(
+            err_location(&["/meta", "ambient_light red 1"]),
+            Some((2, 15))
+        );
+        assert_eq!(err_location(&["/layer solid", "w w"]), Some((2, 1)));
+        assert_eq!(err_location(&["/layer solid", "BEGIN", "w"]), Some((1, 1)));
+        assert_eq!(
+            err_location(&["/layer solid", "/meta", "BEGIN", "w", "END"]),
+            Some((1, 1))
+        );
+        assert_eq!(
+            err_location(&["BEGIN", "w", "END", "/entity 0 0", "radius 3"]),
+            Some((5, 1))
+        );
+        assert_eq!(
+            err_location(&["BEGIN", "w", "END", "/entity 0 0", "point_light.radius 3"]),
+            Some((4, 1))
+        );
+        assert_eq!(
+            err_location(&["BEGIN", "w", "END", "/spawns", "boss 0 0"]),
+            Some((5, 1))
+        );
+        assert_eq!(
+            err_location(&["BEGIN", "w", "END", "/spawns", "player 3 0"]),
+            Some((5, 1))
+        );
+        assert_eq!(
+            err_location(&[
+                "BEGIN",
+                "w",
+                "END",
+                "/layer solid",
+                "BEGIN",
+                "w",
+                "w",
+                "END"
+            ]),
+            Some((6, 1))
+        );
+        assert!(parse(&["/meta", "music a.ogg"])
+            .err()
+            .unwrap()
+            .location
+            .is_none());
     }
 }
@@ -35,14 +35,15 @@ pub fn update(
         spatial.transform.translate_v(translation);
 
         let pos = spatial.transform.position();
+        let rotation = spatial.transform.rotation();
         let starting_pos = spatial.frame_starting_pos;
         if (pos - starting_pos).magnitude2() > std::f32::EPSILON {
             if let Some(collider) = ecs_world.get_component::<C_Collider>(entity) {
                 for (collider, handle) in phys_world.get_all_colliders_with_handles(collider.phys_body_handle) {
                     moved.push(Moved_Collider {
                         handle,
-                        prev_pos: starting_pos,
-                        new_pos: pos,
+                        prev_pos: starting_pos + collider.offset.rotated(collider.rotation),
+                        new_pos: pos + collider.offset.rotated(rotation),
                         extent: collider.shape.extent(),
                     });
                 }
@@ -13,8 +13,8 @@ use std::collections::HashMap;
 use {inle_debug::painter::Debug_Painter, std::collections::HashSet};
 
 // @Speed: tune these numbers
-const CHUNK_WIDTH: f32 = 200.;
-const CHUNK_HEIGHT: f32 = 200.;
+pub const CHUNK_WIDTH: f32 = 200.;
+pub const CHUNK_HEIGHT: f32 = 200.;
 
 #[derive(Default, Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Chunk_Coords {
@@ -107,6 +107,14 @@ impl World_Chunks {
         }
     }
 
+    /// Removes all colliders from the chunks, e.g. before refilling them after a level is restored.
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        with_cb_data(&mut self.to_destroy, |to_destroy: &mut Vec<Entity>| {
+            to_destroy.clear();
+        });
+    }
+
     pub fn n_chunks(&self) -> usize {
         self.chunks.len()
     }
@@ -98,35 +98,16 @@ impl Persistent_Game_State for Debug_Base_State {
                     engine_state.time.step(&step_delta);
                     gs.step(&step_delta, engine_state, game_resources, window);
                     gs.levels.foreach_active_level(|level| {
-                        use inle_physics::physics;
-                        let mut _ignored = physics::Collision_System_Debug_Data::default();
-                        physics::update_collisions(
-                            &mut level.world,
-                            &level.chunks,
-                            &mut level.phys_world,
+                        let mut _ignored =
+                            inle_physics::physics::Collision_System_Debug_Data::default();
+                        crate::game_loop::update_level_physics(
+                            level,
+                            step_delta,
                             &engine_state.systems.physics_settings,
                             &mut engine_state.systems.evt_register,
                             &mut engine_state.frame_alloc,
                             &mut _ignored,
                         );
-                        let mut moved =
-                            inle_alloc::temp::excl_temp_array(&mut engine_state.frame_alloc);
-                        crate::movement_system::update(
-                            &step_delta,
-                            &mut level.world,
-                            &level.phys_world,
-                            &mut moved,
-                        );
-                        let moved = unsafe { moved.into_read_only() };
-                        for mov in &moved {
-                            level.chunks.update_collider(
-                                mov.handle,
-                                mov.prev_pos,
-                                mov.new_pos,
-                                mov.extent,
-                                &mut engine_state.frame_alloc,
-                            );
-                        }
                     });
                 }
                 (name, Action_Kind::Pressed) if *name == sid!("print_em_debug_info") => {
@@ -3,13 +3,14 @@ use inle_ecs::components::base::C_Spatial2D;
 use inle_ecs::ecs_world::Ecs_World;
 use inle_physics::collider::C_Collider;
 use inle_physics::phys_world::{Collider_Handle, Physics_World};
+use inle_serialize::{Binary_Serializable, Byte_Stream};
+use std::io;
 
 pub struct C_Test_Ai {
     speed: Cfg_Var<f32>,
     left_gnd_check: Collider_Handle,
     right_gnd_check: Collider_Handle,
     going_left: bool,
-    frames_since_latest_impact: u32,
 }
 
 impl C_Test_Ai {
@@ -23,29 +24,44 @@ impl C_Test_Ai {
             left_gnd_check,
             right_gnd_check,
             going_left: false,
-            frames_since_latest_impact: 0,
         }
     }
 }
 
+/// The ground check handles stay valid across snapshots since the Physics_World is saved with them.
+impl Binary_Serializable for C_Test_Ai {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.speed.serialize(output)?;
+        self.left_gnd_check.serialize(output)?;
+        self.right_gnd_check.serialize(output)?;
+        self.going_left.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Self {
+            speed: Cfg_Var::deserialize(input)?,
+            left_gnd_check: Collider_Handle::deserialize(input)?,
+            right_gnd_check: Collider_Handle::deserialize(input)?,
+            going_left: bool::deserialize(input)?,
+        })
+    }
+}
+
 pub fn update(ecs_world: &mut Ecs_World, phys_world: &Physics_World, config: &inle_cfg::Config) {
     foreach_entity!(ecs_world,
         read: C_Collider;
         write: C_Spatial2D, C_Test_Ai;
     |_entity, (cld,): (&C_Collider,), (spatial, ai): (&mut C_Spatial2D, &mut C_Test_Ai)| {
-        // Check wall impact
-        ai.frames_since_latest_impact += 1;
-        if ai.frames_since_latest_impact > 1 {
-            let phys_body = phys_world.get_physics_body(cld.phys_body_handle).unwrap();
-            let collisions = phys_world.get_collisions(phys_body.rigidbody_colliders[0].0);
-            for collision in collisions {
-                let other_cld = phys_world.get_collider(collision.other_collider).unwrap();
-                // @Incomplete: solid check
-                if collision.info.normal.x.abs() > 0.8 {
-                    ai.going_left = !ai.going_left;
-                    ai.frames_since_latest_impact = 0;
-                    break;
-                }
+        // Check wall impact (only when we first touch the wall, or we'd keep turning around
+        // while still in contact with it)
+        let phys_body = phys_world.get_physics_body(cld.phys_body_handle).unwrap();
+        let collisions = phys_world.get_collisions(phys_body.rigidbody_colliders[0].0);
+        for collision in collisions.iter().filter(|collision| collision.is_new) {
+            let other_cld = phys_world.get_collider(collision.other_collider).unwrap();
+            // Only walls make us turn around, not other entities bumping into us.
+            if other_cld.is_static && collision.info.normal.x.abs() > 0.8 {
+                ai.going_left = !ai.going_left;
+                break;
             }
         }
 
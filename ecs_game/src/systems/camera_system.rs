@@ -3,6 +3,8 @@ use inle_ecs::components::base::C_Spatial2D;
 use inle_ecs::ecs_world::{Ecs_World, Entity};
 use inle_gfx::components::C_Camera2D;
 use inle_math::vector::Vec2f;
+use inle_serialize::{Binary_Serializable, Byte_Stream};
+use std::io;
 use std::time::Duration;
 
 #[derive(Copy, Clone, Debug)]
@@ -24,6 +26,40 @@ pub struct C_Camera_Follow {
     pub lerp_factor: Cfg_Var<f32>,
 }
 
+const TARGET_NONE: u8 = 0x0;
+const TARGET_POSITION: u8 = 0x1;
+const TARGET_ENTITY: u8 = 0x2;
+
+impl Binary_Serializable for C_Camera_Follow {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        match self.target {
+            Camera_Follow_Target::None => output.write_u8(TARGET_NONE)?,
+            Camera_Follow_Target::Position(pos) => {
+                output.write_u8(TARGET_POSITION)?;
+                pos.serialize(output)?;
+            }
+            Camera_Follow_Target::Entity(entity) => {
+                output.write_u8(TARGET_ENTITY)?;
+                entity.serialize(output)?;
+            }
+        }
+        self.lerp_factor.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        let target = match input.read_u8()? {
+            TARGET_NONE => Camera_Follow_Target::None,
+            TARGET_POSITION => Camera_Follow_Target::Position(Vec2f::deserialize(input)?),
+            TARGET_ENTITY => Camera_Follow_Target::Entity(Entity::deserialize(input)?),
+            _ => return Err(io::ErrorKind::InvalidData.into()),
+        };
+        Ok(Self {
+            target,
+            lerp_factor: Cfg_Var::deserialize(input)?,
+        })
+    }
+}
+
 pub fn update(dt: &Duration, world: &mut Ecs_World, cfg: &Config) {
     foreach_entity!(world,
         read: C_Camera_Follow;
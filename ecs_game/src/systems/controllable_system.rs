@@ -7,6 +7,8 @@ use inle_input::axes::Virtual_Axes;
 use inle_input::input_state::{Action_Kind, Game_Action};
 use inle_math::math::clamp;
 use inle_math::vector::Vec2f;
+use inle_serialize::{Binary_Serializable, Byte_Stream};
+use std::io;
 use std::time::Duration;
 
 #[derive(Copy, Clone, Debug, Default)]
@@ -23,6 +25,34 @@ pub struct C_Controllable {
     pub n_jumps_done: u32,
 }
 
+impl Binary_Serializable for C_Controllable {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.translation_this_frame.serialize(output)?;
+        self.speed.serialize(output)?;
+        self.acceleration.serialize(output)?;
+        self.jump_impulse.serialize(output)?;
+        self.dampening.serialize(output)?;
+        self.horiz_max_speed.serialize(output)?;
+        self.vert_max_speed.serialize(output)?;
+        self.max_jumps.serialize(output)?;
+        self.n_jumps_done.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Self {
+            translation_this_frame: Vec2f::deserialize(input)?,
+            speed: Cfg_Var::deserialize(input)?,
+            acceleration: Cfg_Var::deserialize(input)?,
+            jump_impulse: Cfg_Var::deserialize(input)?,
+            dampening: Cfg_Var::deserialize(input)?,
+            horiz_max_speed: Cfg_Var::deserialize(input)?,
+            vert_max_speed: Cfg_Var::deserialize(input)?,
+            max_jumps: Cfg_Var::deserialize(input)?,
+            n_jumps_done: u32::deserialize(input)?,
+        })
+    }
+}
+
 pub fn update(
     dt: &Duration,
     actions: &[Game_Action],
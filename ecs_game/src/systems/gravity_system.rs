@@ -1,6 +1,9 @@
 use inle_cfg::{var::Cfg_Var, Config};
 use inle_ecs::components::base::C_Spatial2D;
 use inle_ecs::ecs_world::Ecs_World;
+use inle_physics::phys_world::Physics_World;
+use inle_serialize::{Binary_Serializable, Byte_Stream};
+use std::io;
 use std::time::Duration;
 
 #[derive(Copy, Clone, Debug)]
@@ -9,12 +12,145 @@ pub struct C_Gravity {
     pub acceleration: Cfg_Var<f32>,
 }
 
-pub fn update(dt: &Duration, world: &mut Ecs_World, cfg: &Config) {
+impl Binary_Serializable for C_Gravity {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.acceleration.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Self {
+            acceleration: Cfg_Var::deserialize(input)?,
+        })
+    }
+}
+
+pub fn update(dt: &Duration, world: &mut Ecs_World, phys_world: &Physics_World, cfg: &Config) {
     let secs = dt.as_secs_f32();
     foreach_entity!(world,
         read: C_Gravity;
         write: C_Spatial2D;
-        |_e, (gravity,): (&C_Gravity,), (spatial,): (&mut C_Spatial2D,)| {
+        |e, (gravity,): (&C_Gravity,), (spatial,): (&mut C_Spatial2D,)| {
+        // A sleeping body is resting on something, so gravity can't move it: giving it a
+        // velocity would only wake it up every tick.
+        if phys_world.is_sleeping(e) {
+            return;
+        }
         spatial.velocity += secs * v2!(0.0, gravity.acceleration.read(cfg));
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collisions::{init_collision_layers, Game_Collision_Layer};
+    use crate::spatial::World_Chunks;
+    use inle_alloc::temp::{excl_temp_array, Temp_Allocator};
+    use inle_math::transform::Transform2D;
+    use inle_math::vector::Vec2f;
+    use inle_physics::collider::{C_Collider, Collider, Collision_Shape};
+    use inle_physics::phys_world::Phys_Data;
+    use inle_physics::physics::{self, Physics_Settings};
+
+    #[test]
+    fn gravity_body_resting_on_static_floor_stays_asleep() {
+        let cfg = Config::new_from_dir(std::path::Path::new(""));
+        let mut world = Ecs_World::new();
+        let mut phys_world = Physics_World::new();
+        let mut chunks = World_Chunks::new();
+
+        let mut add_body = |world: &mut Ecs_World, pos: Vec2f, shape, is_static| {
+            let entity = world.new_entity();
+            world.add_component(
+                entity,
+                C_Spatial2D {
+                    transform: Transform2D::from_pos(pos),
+                    frame_starting_pos: pos,
+                    ..Default::default()
+                },
+            );
+            let cld = Collider {
+                shape,
+                is_static,
+                layer: if is_static {
+                    Game_Collision_Layer::Ground
+                } else {
+                    Game_Collision_Layer::Entities
+                } as _,
+                ..Default::default()
+            };
+            let extent = cld.shape.extent();
+            let phys_data = Phys_Data {
+                inv_mass: if is_static { 0. } else { 1. },
+                restitution: 0.,
+                ..Default::default()
+            };
+            let body = phys_world.new_physics_body_with_rigidbody(cld, entity, phys_data);
+            let cld_handle = phys_world
+                .get_physics_body(body)
+                .unwrap()
+                .rigidbody_colliders[0]
+                .0;
+            chunks.add_collider(cld_handle, pos, extent);
+            world.add_component(
+                entity,
+                C_Collider {
+                    phys_body_handle: body,
+                },
+            );
+            entity
+        };
+        add_body(
+            &mut world,
+            v2!(0., 10.),
+            Collision_Shape::Rect {
+                width: 100.,
+                height: 10.,
+            },
+            true,
+        );
+        let ball = add_body(
+            &mut world,
+            v2!(0., 0.),
+            Collision_Shape::Circle { radius: 5. },
+            false,
+        );
+        world.add_component(
+            ball,
+            C_Gravity {
+                acceleration: Cfg_Var::new_from_val(500.),
+            },
+        );
+
+        let mut settings = Physics_Settings::default();
+        init_collision_layers(&mut settings.collision_matrix);
+        let mut temp_alloc = Temp_Allocator::with_capacity(1024 * 1024);
+        #[cfg(debug_assertions)]
+        let mut debug_data = physics::Collision_System_Debug_Data::default();
+        let dt = Duration::from_millis(16);
+        let mut tick = |world: &mut Ecs_World, phys_world: &mut Physics_World| {
+            update(&dt, world, phys_world, &cfg);
+            physics::update_collisions(
+                &dt,
+                world,
+                &chunks,
+                phys_world,
+                &settings,
+                &mut temp_alloc,
+                #[cfg(debug_assertions)]
+                &mut debug_data,
+            );
+            let mut moved = excl_temp_array(&mut temp_alloc);
+            crate::movement_system::update(&dt, world, phys_world, &mut moved);
+        };
+
+        for _ in 0..300 {
+            tick(&mut world, &mut phys_world);
+        }
+        assert!(phys_world.is_sleeping(ball));
+
+        for _ in 0..60 {
+            tick(&mut world, &mut phys_world);
+            assert!(phys_world.is_sleeping(ball));
+        }
+    }
+}
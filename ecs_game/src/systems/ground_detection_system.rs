@@ -2,6 +2,8 @@ use inle_ecs::ecs_world::Ecs_World;
 use inle_physics::collider::C_Collider;
 use inle_physics::phys_world::Physics_World;
 use inle_physics::physics::Physics_Settings;
+use inle_serialize::{Binary_Serializable, Byte_Stream};
+use std::io;
 
 #[derive(Copy, Clone, Debug, Default)]
 pub struct C_Ground_Detection {
@@ -10,6 +12,22 @@ pub struct C_Ground_Detection {
     pub just_left_ground: bool,
 }
 
+impl Binary_Serializable for C_Ground_Detection {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.touching_ground.serialize(output)?;
+        self.just_touched_ground.serialize(output)?;
+        self.just_left_ground.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Self {
+            touching_ground: bool::deserialize(input)?,
+            just_touched_ground: bool::deserialize(input)?,
+            just_left_ground: bool::deserialize(input)?,
+        })
+    }
+}
+
 const GROUND_Y_COMP_THRESHOLD: f32 = -0.9;
 
 pub fn update(
@@ -7,3 +7,4 @@ pub mod gravity_system;
 //pub mod ground_collision_calculation_system;
 //pub mod pixel_collision_system;
 pub mod ground_detection_system;
+pub mod tilemap_system;
@@ -0,0 +1,129 @@
+use crate::collisions::Game_Collision_Layer;
+use crate::spatial::World_Chunks;
+use inle_ecs::components::base::C_Spatial2D;
+use inle_ecs::ecs_query::Ecs_Query;
+use inle_ecs::ecs_world::{Ecs_World, Entity};
+use inle_gfx::tilemap::C_Tilemap;
+use inle_math::vector::Vec2f;
+use inle_physics::collider::{C_Collider, Collider, Collision_Shape};
+use inle_physics::phys_world::{Phys_Data, Physics_World};
+
+/// Rebuilds the colliders of all the tilemaps whose solid tiles changed.
+/// Every rect of adjacent solid tiles becomes a single static collider of the tilemap's physics body.
+pub fn update(world: &mut Ecs_World, phys_world: &mut Physics_World, chunks: &mut World_Chunks) {
+    trace!("tilemap_system::update");
+
+    // We run once per tick, so anything changed since the previous tick wasn't seen yet.
+    let since = world.cur_tick().saturating_sub(1);
+    let query = Ecs_Query::new(world)
+        .read::<C_Spatial2D>()
+        .read::<C_Collider>()
+        .write::<C_Tilemap>()
+        .changed::<C_Tilemap>(since);
+    if query.entities().is_empty() {
+        return;
+    }
+
+    let storages = query.storages();
+    let spatials = storages.begin_read::<C_Spatial2D>();
+    let colliders = storages.begin_read::<C_Collider>();
+    let mut tilemaps = storages.begin_write::<C_Tilemap>();
+    for &entity in query.entities() {
+        // NOTE: only borrow the tilemap mutably if we're going to change it, or it would be
+        // marked as changed again.
+        if !tilemaps.get(entity).unwrap().solidity_changed {
+            continue;
+        }
+
+        let collider = colliders.must_get(entity);
+        if phys_world
+            .get_physics_body(collider.phys_body_handle)
+            .is_none()
+        {
+            lerr!("Tilemap {:?} has an invalid physics body.", entity);
+            continue;
+        }
+
+        let pos = spatials.must_get(entity).transform.position();
+        let tilemap = tilemaps.must_get_mut(entity);
+        remove_tilemap_colliders(collider, pos, phys_world, chunks);
+        add_tilemap_colliders(entity, collider, tilemap, pos, phys_world, chunks);
+        tilemap.solidity_changed = false;
+    }
+}
+
+fn remove_tilemap_colliders(
+    collider: &C_Collider,
+    pos: Vec2f,
+    phys_world: &mut Physics_World,
+    chunks: &mut World_Chunks,
+) {
+    let old_colliders = std::mem::take(
+        &mut phys_world
+            .get_physics_body_mut(collider.phys_body_handle)
+            .unwrap()
+            .rigidbody_colliders,
+    );
+
+    let mut to_wake = vec![];
+    for &(handle, _) in &old_colliders {
+        // Whatever was resting on the removed colliders must notice they're gone.
+        to_wake.extend(
+            phys_world
+                .get_collisions(handle)
+                .iter()
+                .filter_map(|coll| phys_world.get_collider(coll.other_collider))
+                .map(|other| other.entity),
+        );
+
+        if let Some(cld) = phys_world.get_collider(handle) {
+            chunks.remove_collider(handle, pos + cld.offset, cld.shape.extent());
+        }
+        phys_world.remove_collider(handle);
+    }
+
+    for other in to_wake {
+        phys_world.wake_up(other);
+    }
+}
+
+fn add_tilemap_colliders(
+    entity: Entity,
+    collider: &C_Collider,
+    tilemap: &C_Tilemap,
+    pos: Vec2f,
+    phys_world: &mut Physics_World,
+    chunks: &mut World_Chunks,
+) {
+    let tile_size = tilemap.tile_size() as f32;
+    for rect in tilemap.solid_rects() {
+        let size = v2!(rect.width as f32, rect.height as f32) * tile_size;
+        let cld = Collider {
+            shape: Collision_Shape::Rect {
+                width: size.x,
+                height: size.y,
+            },
+            layer: Game_Collision_Layer::Ground as _,
+            // NOTE: the tilemap's pivot is its top-left corner.
+            offset: v2!(rect.x as f32, rect.y as f32) * tile_size + size * 0.5,
+            is_static: true,
+            ..Default::default()
+        };
+        let offset = cld.offset;
+        let extent = cld.shape.extent();
+
+        let handle = phys_world.add_collider(cld, entity);
+        phys_world
+            .get_physics_body_mut(collider.phys_body_handle)
+            .unwrap()
+            .rigidbody_colliders
+            .push((
+                handle,
+                Phys_Data {
+                    inv_mass: 0.,
+                    ..Default::default()
+                },
+            ));
+        chunks.add_collider(handle, pos + offset, extent);
+    }
+}
@@ -1,4 +1,6 @@
+use inle_serialize::{Binary_Serializable, Byte_Stream};
 use std::convert::TryFrom;
+use std::io;
 use std::vec::Vec;
 
 pub type Index_Type = u32;
@@ -14,6 +16,19 @@ impl Generational_Index {
     pub const INVALID: Generational_Index = Generational_Index { index: 0, gen: 0 };
 }
 
+impl Binary_Serializable for Generational_Index {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        output.write_u32(self.index)?;
+        output.write_u32(self.gen)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        let index = input.read_u32()?;
+        let gen = input.read_u32()?;
+        Ok(Generational_Index { index, gen })
+    }
+}
+
 impl std::fmt::Debug for Generational_Index {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "(idx: {}, gen: {})", self.index, self.gen)
@@ -138,6 +153,43 @@ impl Generational_Allocator {
     }
 }
 
+/// The whole allocator state is serialized (including the free slots order), so a
+/// deserialized allocator keeps the same live indices and hands out the same new indices
+/// as the original one.
+impl Binary_Serializable for Generational_Allocator {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.gens.serialize(output)?;
+        self.alive.serialize(output)?;
+        let free_slots: Vec<u32> = self.free_slots.iter().map(|&slot| slot as u32).collect();
+        free_slots.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        let gens = Vec::<Gen_Type>::deserialize(input)?;
+        let alive = Vec::<bool>::deserialize(input)?;
+        let free_slots: Vec<usize> = Vec::<u32>::deserialize(input)?
+            .into_iter()
+            .map(|slot| slot as usize)
+            .collect();
+
+        let n_dead = alive.iter().filter(|&&a| !a).count();
+        if gens.len() != alive.len()
+            || free_slots.len() != n_dead
+            || free_slots
+                .iter()
+                .any(|&slot| slot >= alive.len() || alive[slot])
+        {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+
+        Ok(Generational_Allocator {
+            gens,
+            free_slots,
+            alive,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -312,6 +364,28 @@ mod tests {
         assert_invariant_free_slots_alive(&alloc);
     }
 
+    #[test]
+    fn gen_alloc_serialize() {
+        let mut alloc = Generational_Allocator::new(4);
+        let e1 = alloc.allocate();
+        let e2 = alloc.allocate();
+        let e3 = alloc.allocate();
+        alloc.deallocate(e1);
+        alloc.deallocate(e3);
+
+        let mut bs = Byte_Stream::new();
+        alloc.serialize(&mut bs).unwrap();
+        bs.seek(0);
+        let mut restored = Generational_Allocator::deserialize(&mut bs).unwrap();
+        assert_invariant_free_slots_alive(&restored);
+
+        assert!(restored.is_valid(e2));
+        assert!(!restored.is_valid(e1));
+        assert!(!restored.is_valid(e3));
+        assert_eq!(restored.allocate(), alloc.allocate());
+        assert_eq!(restored.allocate(), alloc.allocate());
+    }
+
     #[test]
     fn gen_alloc_invalid_is_invalid() {
         let alloc = Generational_Allocator::new(4);
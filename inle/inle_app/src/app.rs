@@ -18,6 +18,7 @@ use {
     crate::systems::Debug_Systems,
     inle_cfg::Cfg_Var,
     inle_common::colors,
+    inle_common::stringid::String_Id,
     inle_diagnostics::tracer,
     inle_replay::{replay_data::Replay_Data, replay_input_provider::Replay_Input_Provider},
     std::convert::TryInto,
@@ -121,6 +122,26 @@ pub fn start_config_watch(env: &Env_Info, config: &mut inle_cfg::Config) -> Mayb
     Ok(())
 }
 
+#[cfg(debug_assertions)]
+pub fn start_prefab_watch(
+    env: &Env_Info,
+    prefabs: &mut inle_ecs::prefab::Prefab_Registry,
+) -> Maybe_Error {
+    use notify::RecursiveMode;
+
+    let prefab_watcher = Box::new(inle_ecs::prefab::Prefab_Watch_Handler::new(prefabs));
+    let prefab_watcher_cfg = inle_fs::file_watcher::File_Watch_Config {
+        interval: Duration::from_secs(1),
+        recursive_mode: RecursiveMode::NonRecursive,
+    };
+    inle_fs::file_watcher::start_file_watch(
+        inle_core::env::asset_dir_path(env, "prefabs").to_path_buf(),
+        prefab_watcher_cfg,
+        vec![prefab_watcher],
+    )?;
+    Ok(())
+}
+
 pub fn init_engine_systems(
     window: &Render_Window_Handle,
     engine_state: &mut Engine_State,
@@ -144,11 +165,19 @@ pub fn init_engine_systems(
 }
 
 #[cfg(debug_assertions)]
-pub fn start_recording(engine_state: &mut Engine_State) -> Maybe_Error {
+pub fn start_recording(engine_state: &mut Engine_State, level_id: String_Id) -> Maybe_Error {
+    engine_state
+        .debug_systems
+        .replay_recording_system
+        .start_recording_thread(&engine_state.env, &engine_state.config, level_id)
+}
+
+#[cfg(debug_assertions)]
+pub fn stop_recording(engine_state: &mut Engine_State) {
     engine_state
         .debug_systems
         .replay_recording_system
-        .start_recording_thread(&engine_state.env, &engine_state.config)
+        .stop_recording();
 }
 
 #[cfg(debug_assertions)]
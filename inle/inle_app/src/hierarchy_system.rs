@@ -0,0 +1,107 @@
+use inle_ecs::components::base::{C_Local_Transform2D, C_Spatial2D};
+use inle_ecs::ecs_world::{Ecs_World, Entity};
+use inle_math::transform::Transform2D;
+
+/// Recomputes the world transform of all entities having a parent and a C_Local_Transform2D
+/// by combining it with their parent's C_Spatial2D transform.
+/// Parents are always updated before their children.
+pub fn update(ecs_world: &mut Ecs_World) {
+    trace!("hierarchy_system::update");
+
+    let hierarchy = ecs_world.hierarchy();
+    if hierarchy.is_empty() {
+        return;
+    }
+
+    let (mut spatials, locals) = match (
+        ecs_world.write_component_storage::<C_Spatial2D>(),
+        ecs_world.read_component_storage::<C_Local_Transform2D>(),
+    ) {
+        (Some(s), Some(l)) => (s, l),
+        _ => return,
+    };
+
+    let mut stack: Vec<(Entity, Transform2D)> = vec![];
+    for root in hierarchy.roots() {
+        if let Some(spatial) = spatials.get(root) {
+            let root_transform = spatial.transform;
+            stack.extend(
+                hierarchy
+                    .children(root)
+                    .iter()
+                    .map(|&child| (child, root_transform)),
+            );
+        }
+
+        while let Some((entity, parent_transform)) = stack.pop() {
+            let transform = match (locals.get(entity), spatials.get_mut(entity)) {
+                (Some(local), Some(spatial)) => {
+                    spatial.transform = parent_transform.combine(&local.transform);
+                    spatial.transform
+                }
+                // Entities without a local transform are not driven by their parent,
+                // but they still drive their own children.
+                (None, Some(spatial)) => spatial.transform,
+                _ => continue,
+            };
+
+            stack.extend(
+                hierarchy
+                    .children(entity)
+                    .iter()
+                    .map(|&child| (child, transform)),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use inle_math::angle::deg;
+    use inle_math::vector::Vec2f;
+
+    #[test]
+    fn propagate_transforms() {
+        let mut world = Ecs_World::new();
+        let parent = world.new_entity();
+        let child = world.new_entity();
+        let grandchild = world.new_entity();
+
+        let mut parent_spatial = C_Spatial2D::default();
+        parent_spatial.transform.set_position(10., 0.);
+        parent_spatial.transform.set_rotation(deg(90.));
+        world.add_component(parent, parent_spatial);
+
+        for &e in &[child, grandchild] {
+            world.add_component(e, C_Spatial2D::default());
+            world.add_component(
+                e,
+                C_Local_Transform2D {
+                    transform: Transform2D::from_pos(Vec2f::new(5., 0.)),
+                },
+            );
+        }
+        world.set_parent(child, Some(parent));
+        world.set_parent(grandchild, Some(child));
+
+        update(&mut world);
+
+        let child_pos = world
+            .get_component::<C_Spatial2D>(child)
+            .unwrap()
+            .transform
+            .position();
+        let grandchild_pos = world
+            .get_component::<C_Spatial2D>(grandchild)
+            .unwrap()
+            .transform
+            .position();
+        assert!((child_pos - Vec2f::new(10., 5.)).magnitude() < 0.001, "{:?}", child_pos);
+        assert!(
+            (grandchild_pos - Vec2f::new(10., 10.)).magnitude() < 0.001,
+            "{:?}",
+            grandchild_pos
+        );
+    }
+}
@@ -19,5 +19,6 @@ extern crate inle_math;
 pub mod animation_system;
 pub mod app;
 pub mod app_config;
+pub mod hierarchy_system;
 pub mod render_system;
 pub mod systems;
@@ -8,7 +8,8 @@ use inle_gfx::material::Material;
 use inle_gfx::render::batcher::Batches;
 use inle_gfx::render::{self, Z_Index};
 use inle_gfx::render_window::Render_Window_Handle;
-use inle_math::transform::Transform2D;
+use inle_gfx::tilemap::C_Tilemap;
+use inle_math::transform::{lerp_transform, Transform2D};
 use inle_resources::gfx::{Gfx_Resources, Shader_Cache};
 
 #[cfg(debug_assertions)]
@@ -41,6 +42,9 @@ pub struct Render_System_Update_Args<'a> {
     pub ecs_world: &'a Ecs_World,
     pub frame_alloc: &'a mut temp::Temp_Allocator,
     pub render_cfg: Render_System_Config,
+    /// How far we are between the latest simulated tick and the next one, in [0, 1].
+    /// Entities are drawn interpolated by this much between their previous and current transform.
+    pub tick_interpolation: f32,
     pub camera: &'a Transform2D,
     pub gres: &'a Gfx_Resources<'a>,
     pub shader_cache: &'a Shader_Cache<'a>,
@@ -54,7 +58,9 @@ pub fn update(args: Render_System_Update_Args) {
         batches,
         ecs_world,
         render_cfg,
+        tick_interpolation,
         window,
+        camera,
         gres,
         shader_cache,
         #[cfg(debug_assertions)]
@@ -64,6 +70,28 @@ pub fn update(args: Render_System_Update_Args) {
 
     trace!("render_system::update");
 
+    ////
+    //// Tilemaps
+    ////
+    {
+        trace!("draw_tilemaps");
+
+        let query = Ecs_Query::new(ecs_world)
+            .read::<C_Spatial2D>()
+            .read::<C_Tilemap>();
+        let storages = query.storages();
+        if !query.entities().is_empty() {
+            let spatials = storages.begin_read::<C_Spatial2D>();
+            let tilemaps = storages.begin_read::<C_Tilemap>();
+
+            for &entity in query.entities() {
+                let pos = interpolated_transform(spatials.must_get(entity), tick_interpolation)
+                    .position();
+                render::render_tilemap_ws(window, batches, tilemaps.must_get(entity), pos, camera);
+            }
+        }
+    }
+
     ////
     //// Renderables
     ////
@@ -83,7 +111,8 @@ pub fn update(args: Render_System_Update_Args) {
 
             for &entity in query.entities() {
                 let rend = renderables.must_get(entity);
-                let spatial = spatials.must_get(entity);
+                let transform =
+                    interpolated_transform(spatials.must_get(entity), tick_interpolation);
 
                 let C_Renderable {
                     material,
@@ -93,7 +122,7 @@ pub fn update(args: Render_System_Update_Args) {
                     sprite_local_transform,
                 } = rend;
 
-                let visual_transform = spatial.transform.combine(sprite_local_transform);
+                let visual_transform = transform.combine(sprite_local_transform);
                 let mut_in_debug!(material) = *material;
 
                 #[cfg(debug_assertions)]
@@ -102,7 +131,7 @@ pub fn update(args: Render_System_Update_Args) {
                     batches,
                     gres,
                     shader_cache,
-                    &spatial.transform,
+                    &transform,
                     &visual_transform,
                     &mut material,
                     src_rect,
@@ -152,7 +181,8 @@ pub fn update(args: Render_System_Update_Args) {
 
             for &entity in query.entities() {
                 let rend = multi_renderables.must_get(entity);
-                let spatial = spatials.must_get(entity);
+                let transform =
+                    interpolated_transform(spatials.must_get(entity), tick_interpolation);
 
                 let C_Multi_Renderable {
                     renderables,
@@ -168,7 +198,7 @@ pub fn update(args: Render_System_Update_Args) {
                         sprite_local_transform,
                     } = &renderables[i as usize];
 
-                    let visual_transform = spatial.transform.combine(sprite_local_transform);
+                    let visual_transform = transform.combine(sprite_local_transform);
                     let mut_in_debug!(material) = *material;
 
                     #[cfg(debug_assertions)]
@@ -178,7 +208,7 @@ pub fn update(args: Render_System_Update_Args) {
                             batches,
                             gres,
                             shader_cache,
-                            &spatial.transform,
+                            &transform,
                             &visual_transform,
                             &mut material,
                             src_rect,
@@ -205,6 +235,13 @@ pub fn update(args: Render_System_Update_Args) {
     }
 }
 
+fn interpolated_transform(spatial: &C_Spatial2D, t: f32) -> Transform2D {
+    match &spatial.prev_transform {
+        Some(prev) => lerp_transform(prev, &spatial.transform, t),
+        None => spatial.transform,
+    }
+}
+
 #[cfg(debug_assertions)]
 fn get_min_max_z(
     entities: &[Entity],
@@ -9,7 +9,7 @@ use std::sync::{Arc, Mutex};
 use {
     inle_core::env::Env_Info,
     inle_core::rand::Default_Rng_Seed,
-    inle_debug::{calipers, console, debug_ui, log, painter::Debug_Painter},
+    inle_debug::{calipers, console, debug_ui, log, painter::Debug_Painter, rewind},
     inle_replay::recording_system,
     inle_resources::gfx::Gfx_Resources,
 };
@@ -42,6 +42,7 @@ pub struct Debug_Systems {
     pub console: Arc<Mutex<console::Console>>,
     pub log: log::Debug_Log,
     pub calipers: calipers::Debug_Calipers,
+    pub rewind_history: rewind::Rewind_History,
 
     pub show_overlay: Overlay_Shown,
     pub trace_overlay_update_t: f32,
@@ -71,12 +72,20 @@ impl Debug_Systems {
         let debug_log_size =
             inle_cfg::Cfg_Var::<i32>::new("engine/debug/log/hist_size_seconds", cfg).read(cfg);
         let fps = (1000. / ms_per_frame + 0.5) as i32;
+        let rewind_max_mem_mb =
+            inle_cfg::Cfg_Var::<i32>::new("engine/debug/rewind/max_mem_mb", cfg).read(cfg);
+        let rewind_keyframe_interval =
+            inle_cfg::Cfg_Var::<i32>::new("engine/debug/rewind/keyframe_interval", cfg).read(cfg);
+        let replay_state_checksum_interval =
+            inle_cfg::Cfg_Var::<i32>::new("engine/debug/replay/state_checksum_interval", cfg)
+                .read(cfg);
         Debug_Systems {
             debug_ui: debug_ui::Debug_Ui_System::default(),
             replay_recording_system: recording_system::Replay_Recording_System::new(
                 recording_system::Replay_Recording_System_Config {
                     ms_per_frame,
                     rng_seed,
+                    state_checksum_interval: replay_state_checksum_interval.max(0) as u64,
                 },
             ),
             painters: HashMap::default(),
@@ -86,6 +95,10 @@ impl Debug_Systems {
             console: Arc::new(Mutex::new(console::Console::new())),
             log: log::Debug_Log::with_hist_len((debug_log_size * fps) as _),
             calipers: calipers::Debug_Calipers::default(),
+            rewind_history: rewind::Rewind_History::new(rewind::Rewind_History_Config {
+                max_mem_bytes: rewind_max_mem_mb.max(0) as usize * 1024 * 1024,
+                keyframe_interval: rewind_keyframe_interval.max(1) as u32,
+            }),
             traced_fn: String::default(),
         }
     }
@@ -66,6 +66,22 @@ impl Config {
             change_tx: Some(change_tx),
         }
     }
+
+    /// Returns a hash of all the cfg vars and their current values, which does not depend
+    /// on the order in which they were loaded.
+    pub fn content_hash(&self) -> u64 {
+        use inle_common::hash::{fnv1a_64_update, FNV1A_64_OFFSET_BASIS};
+
+        let mut entries = self.cfg_var_table.iter().collect::<Vec<_>>();
+        entries.sort_unstable_by_key(|(id, _)| **id);
+
+        entries
+            .iter()
+            .fold(FNV1A_64_OFFSET_BASIS, |hash, (id, value)| {
+                let hash = fnv1a_64_update(hash, &id.val().to_le_bytes());
+                fnv1a_64_update(hash, format!("{:?}", value).as_bytes())
+            })
+    }
 }
 
 #[cfg(debug_assertions)]
@@ -1,6 +1,7 @@
 use super::config::Config;
 use super::value::Cfg_Value;
 use inle_common::stringid::String_Id;
+use inle_serialize::{Binary_Serializable, Byte_Stream};
 use std::any::type_name;
 use std::convert::{From, Into, TryFrom};
 use std::fmt::{Debug, Display};
@@ -44,7 +45,11 @@ fn read_cfg_str(path_id: String_Id, cfg: &Config) -> &String {
 #[derive(Debug, Clone)]
 enum Cfg_Var_Content<T> {
     Fixed(T),
-    Hot_Reloadable(String_Id),
+    /// `value` is what the var resolved to when it was created, i.e. what a release build holds.
+    Hot_Reloadable {
+        path: String_Id,
+        value: T,
+    },
 }
 
 #[cfg(debug_assertions)]
@@ -89,18 +94,16 @@ impl<T> Cfg_Var<T>
 where
     T: Default + Into<Cfg_Value> + TryFrom<Cfg_Value>,
 {
-    pub fn new(path: &str, _cfg: &Config) -> Self {
+    pub fn new(path: &str, cfg: &Config) -> Self {
         if path.starts_with('/') {
             fatal!("Cfg_Var path passed to Cfg_Var::new should not start with '/': please trim it!\nOffender: {}", path);
         }
+        let path = String_Id::from(path);
         Self {
-            content: Cfg_Var_Content::Hot_Reloadable(String_Id::from(path)),
-        }
-    }
-
-    fn new_from_sid(id: String_Id) -> Self {
-        Self {
-            content: Cfg_Var_Content::Hot_Reloadable(id),
+            content: Cfg_Var_Content::Hot_Reloadable {
+                path,
+                value: read_cfg(path, cfg),
+            },
         }
     }
 
@@ -115,7 +118,7 @@ where
 
     pub fn has_changed(&self, cfg: &Config) -> bool {
         match self.content {
-            Cfg_Var_Content::Hot_Reloadable(id) => cfg.has_changed(id),
+            Cfg_Var_Content::Hot_Reloadable { path, .. } => cfg.has_changed(path),
             _ => unreachable!(),
         }
     }
@@ -144,7 +147,7 @@ macro_rules! impl_cfg_vars {
                 pub fn read(self, cfg: &Config) -> $type {
                     match self.content {
                         Cfg_Var_Content::Fixed(x) => x,
-                        Cfg_Var_Content::Hot_Reloadable(id) => read_cfg(id, cfg),
+                        Cfg_Var_Content::Hot_Reloadable { path, .. } => read_cfg(path, cfg),
                     }
                 }
 
@@ -164,7 +167,7 @@ macro_rules! impl_cfg_vars {
                 where 'c: 's {
                     match &self.content {
                         Cfg_Var_Content::Fixed(x) => &x,
-                        Cfg_Var_Content::Hot_Reloadable(id) => read_cfg_str(*id, cfg),
+                        Cfg_Var_Content::Hot_Reloadable { path, .. } => read_cfg_str(*path, cfg),
                     }
                 }
 
@@ -182,6 +185,61 @@ macro_rules! impl_cfg_vars {
 impl_cfg_vars!(copy: bool, i32, u32, f32);
 impl_cfg_vars!(noncopy: String);
 
+const CFG_VAR_VALUE: u8 = 0x0;
+
+/// Cfg_Vars are always saved by value, followed by their hot-reload path (if they have one),
+/// so debug and release builds share the same format. In release the path is ignored, while
+/// debug builds re-bind the loaded var to its path, so it keeps following the config.
+impl<T> Binary_Serializable for Cfg_Var<T>
+where
+    T: Default + Into<Cfg_Value> + Binary_Serializable,
+{
+    #[cfg(debug_assertions)]
+    fn serialize(&self, output: &mut Byte_Stream) -> std::io::Result<()> {
+        output.write_u8(CFG_VAR_VALUE)?;
+        match &self.content {
+            Cfg_Var_Content::Fixed(x) => {
+                x.serialize(output)?;
+                None::<String_Id>.serialize(output)
+            }
+            Cfg_Var_Content::Hot_Reloadable { path, value } => {
+                value.serialize(output)?;
+                Some(*path).serialize(output)
+            }
+        }
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn serialize(&self, output: &mut Byte_Stream) -> std::io::Result<()> {
+        output.write_u8(CFG_VAR_VALUE)?;
+        self.0.serialize(output)?;
+        None::<String_Id>.serialize(output)
+    }
+
+    #[cfg(debug_assertions)]
+    fn deserialize(input: &mut Byte_Stream) -> std::io::Result<Self> {
+        if input.read_u8()? != CFG_VAR_VALUE {
+            return Err(std::io::ErrorKind::InvalidData.into());
+        }
+        let value = T::deserialize(input)?;
+        let content = match Option::<String_Id>::deserialize(input)? {
+            Some(path) => Cfg_Var_Content::Hot_Reloadable { path, value },
+            None => Cfg_Var_Content::Fixed(value),
+        };
+        Ok(Self { content })
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn deserialize(input: &mut Byte_Stream) -> std::io::Result<Self> {
+        if input.read_u8()? != CFG_VAR_VALUE {
+            return Err(std::io::ErrorKind::InvalidData.into());
+        }
+        let value = T::deserialize(input)?;
+        let _path = Option::<String_Id>::deserialize(input)?;
+        Ok(Self(value))
+    }
+}
+
 impl<T: Display> Display for Cfg_Var<T>
 where
     T: Default + Into<Cfg_Value>,
@@ -190,7 +248,7 @@ where
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match &self.content {
             Cfg_Var_Content::Fixed(x) => write!(f, "{}", x),
-            Cfg_Var_Content::Hot_Reloadable(id) => write!(f, "(REF {})", id),
+            Cfg_Var_Content::Hot_Reloadable { path, .. } => write!(f, "(REF {})", path),
         }
     }
 
@@ -253,6 +311,36 @@ mod tests {
         assert_eq!(var.read(&config), "foo");
     }
 
+    #[test]
+    fn cfg_var_serialize_by_value() {
+        let env = Env_Info::gather().expect("Failed to gather env info!");
+        let config = Config::new_from_dir(&test_env::get_test_cfg_root(&env));
+
+        let var = Cfg_Var::<i32>::new("test/entry_int", &config);
+        let mut bs = Byte_Stream::new();
+        var.serialize(&mut bs).unwrap();
+
+        // The value comes right after the tag in both builds.
+        bs.seek(0);
+        assert_eq!(bs.read_u8().unwrap(), CFG_VAR_VALUE);
+        assert_eq!(i32::deserialize(&mut bs).unwrap(), 42);
+
+        bs.seek(0);
+        let loaded = Cfg_Var::<i32>::deserialize(&mut bs).unwrap();
+        assert_eq!(loaded.read(&config), 42);
+        #[cfg(debug_assertions)]
+        assert_eq!(loaded.to_string(), var.to_string());
+
+        // A var saved without a path (e.g. by a release build) loads as a fixed value.
+        let mut bs = Byte_Stream::new();
+        bs.write_u8(CFG_VAR_VALUE).unwrap();
+        7_i32.serialize(&mut bs).unwrap();
+        None::<String_Id>.serialize(&mut bs).unwrap();
+        bs.seek(0);
+        let loaded = Cfg_Var::<i32>::deserialize(&mut bs).unwrap();
+        assert_eq!(loaded.read(&config), 7);
+    }
+
     #[test]
     #[should_panic]
     fn cfg_incompatible_type() {
@@ -1,8 +1,9 @@
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::ops::BitAnd;
 use std::vec::Vec;
 
-#[derive(Clone, PartialEq, Default)]
+#[derive(Clone, Default)]
 pub struct Bit_Set {
     fast_bits: u64,
     slow_bits: Vec<u64>,
@@ -71,6 +72,46 @@ impl Bit_Set {
     }
 }
 
+impl Bit_Set {
+    /// Returns true if every bit set in `other` is also set in `self`.
+    pub fn contains_all(&self, other: &Bit_Set) -> bool {
+        if (self.fast_bits & other.fast_bits) != other.fast_bits {
+            return false;
+        }
+        other.slow_bits.iter().enumerate().all(|(i, &bits)| {
+            let my_bits = self.slow_bits.get(i).copied().unwrap_or(0);
+            (my_bits & bits) == bits
+        })
+    }
+
+    /// Returns the slow bits without the trailing zero elements, so that two bitsets
+    /// with the same bits set compare (and hash) equal regardless of their allocated size.
+    fn significant_slow_bits(&self) -> &[u64] {
+        let len = self
+            .slow_bits
+            .iter()
+            .rposition(|&bits| bits != 0)
+            .map_or(0, |i| i + 1);
+        &self.slow_bits[..len]
+    }
+}
+
+impl PartialEq for Bit_Set {
+    fn eq(&self, other: &Self) -> bool {
+        self.fast_bits == other.fast_bits
+            && self.significant_slow_bits() == other.significant_slow_bits()
+    }
+}
+
+impl Eq for Bit_Set {}
+
+impl Hash for Bit_Set {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.fast_bits.hash(state);
+        self.significant_slow_bits().hash(state);
+    }
+}
+
 impl BitAnd for &Bit_Set {
     type Output = Bit_Set;
 
@@ -200,4 +241,35 @@ mod tests {
         b.set(100, false);
         assert_ne!(&a, &b);
     }
+
+    #[test]
+    fn bitset_equality_ignores_unset_slow_bits() {
+        let mut a = Bit_Set::default();
+        let mut b = Bit_Set::default();
+
+        a.set(3, true);
+        b.set(3, true);
+        b.set(500, true);
+        b.set(500, false);
+        assert_eq!(&a, &b);
+    }
+
+    #[test]
+    fn bitset_contains_all() {
+        let mut a = Bit_Set::default();
+        let mut b = Bit_Set::default();
+
+        a.set(1, true);
+        a.set(70, true);
+        a.set(200, true);
+        b.set(1, true);
+        b.set(200, true);
+        assert!(a.contains_all(&b));
+        assert!(!b.contains_all(&a));
+
+        b.set(300, true);
+        assert!(!a.contains_all(&b));
+
+        assert!(a.contains_all(&Bit_Set::default()));
+    }
 }
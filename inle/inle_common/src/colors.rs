@@ -2,6 +2,7 @@
 mod sfml;
 
 use inle_math::angle::{rad, Angle};
+use inle_serialize::{Binary_Serializable, Byte_Stream};
 use std::f32::consts::{FRAC_PI_3, PI};
 
 include!("colors_def.rs");
@@ -195,6 +196,16 @@ impl PartialEq<Color> for Color3 {
     }
 }
 
+impl Binary_Serializable for Color {
+    fn serialize(&self, output: &mut Byte_Stream) -> std::io::Result<()> {
+        output.write_u32(color_to_hex(*self))
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> std::io::Result<Self> {
+        Ok(color_from_hex(input.read_u32()?))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
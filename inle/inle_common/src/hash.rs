@@ -0,0 +1,29 @@
+pub const FNV1A_64_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV1A_64_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Feeds `bytes` into an existing FNV-1a hash. This allows hashing data that is not
+/// contiguous in memory: start from FNV1A_64_OFFSET_BASIS and update the hash with each chunk.
+pub fn fnv1a_64_update(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(FNV1A_64_PRIME)
+    })
+}
+
+pub fn fnv1a_64(bytes: &[u8]) -> u64 {
+    fnv1a_64_update(FNV1A_64_OFFSET_BASIS, bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a_64_incremental() {
+        assert_eq!(fnv1a_64(b""), FNV1A_64_OFFSET_BASIS);
+        assert_eq!(fnv1a_64(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(
+            fnv1a_64_update(fnv1a_64(b"A test "), b"string"),
+            fnv1a_64(b"A test string")
+        );
+    }
+}
@@ -75,6 +75,16 @@ macro_rules! sid {
     };
 }
 
+impl inle_serialize::Binary_Serializable for String_Id {
+    fn serialize(&self, output: &mut inle_serialize::Byte_Stream) -> std::io::Result<()> {
+        output.write_u32(self.0)
+    }
+
+    fn deserialize(input: &mut inle_serialize::Byte_Stream) -> std::io::Result<Self> {
+        Ok(String_Id(input.read_u32()?))
+    }
+}
+
 impl std::fmt::Display for String_Id {
     #[cfg(not(debug_assertions))]
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -29,6 +29,23 @@ pub struct Rand_Xoshiro256 {
     state: [u64; 4],
 }
 
+impl Binary_Serializable for Rand_Xoshiro256 {
+    fn serialize(&self, out: &mut Byte_Stream) -> std::io::Result<()> {
+        for x in &self.state {
+            out.write_u64(*x)?;
+        }
+        Ok(())
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> std::io::Result<Self> {
+        let mut state = [0; 4];
+        for x in &mut state {
+            *x = input.read_u64()?;
+        }
+        Ok(Rand_Xoshiro256 { state })
+    }
+}
+
 pub fn new_random_seed() -> std::io::Result<Default_Rng_Seed> {
     let mut seed_buf = [0u8; 32];
     // @Robustness: consider hashing in the system time or something like that.
@@ -44,6 +61,21 @@ pub fn new_rng_with_seed(seed: Default_Rng_Seed) -> Rand_Xoshiro256 {
     Rand_Xoshiro256::new_with_seed(seed.0)
 }
 
+/// Expands a 64-bit seed (e.g. one written in a config file) into a full seed, using splitmix64.
+pub fn seed_from_u64(seed: u64) -> Default_Rng_Seed {
+    let mut state = seed;
+    let mut res = [0u8; 32];
+    for chunk in res.chunks_exact_mut(8) {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        chunk.copy_from_slice(&z.to_be_bytes());
+    }
+    Default_Rng_Seed(res)
+}
+
 pub fn rand_01(rng: &mut Rand_Xoshiro256) -> f32 {
     (rng.next() >> 32) as f32 / u32::max_value() as f32
 }
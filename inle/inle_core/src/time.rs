@@ -30,6 +30,10 @@ pub struct Time {
     was_paused: bool,
 
     stepping: bool,
+
+    /// If set, update() advances the real time by this amount instead of measuring it,
+    /// so the game runs at a fixed rate regardless of how long the frames actually take.
+    pub fixed_real_dt: Option<Duration>,
 }
 
 impl Default for Time {
@@ -46,6 +50,7 @@ impl Default for Time {
             paused: false,
             was_paused: false,
             stepping: false,
+            fixed_real_dt: None,
         }
     }
 }
@@ -64,7 +69,10 @@ impl Time {
 
         // Update real time
         let prev_real_time = self.real_time;
-        self.real_time = self.start_time.elapsed();
+        self.real_time = match self.fixed_real_dt {
+            Some(dt) => prev_real_time + dt,
+            None => self.start_time.elapsed(),
+        };
         self.real_dt = self.real_time - prev_real_time;
 
         // Update game time
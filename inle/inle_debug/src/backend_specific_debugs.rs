@@ -4,5 +4,9 @@ use inle_gfx::render_window::Render_Window_Handle;
 mod buf_alloc_debug;
 
 pub fn draw_backend_specific_debug(window: &Render_Window_Handle, painter: &mut Debug_Painter) {
-    buf_alloc_debug::debug_draw_buffer_allocators(&window.gl.buffer_allocators, painter);
+    if let Some(buffer_allocators) =
+        inle_gfx_backend::render_window::backend::buffer_allocators(window)
+    {
+        buf_alloc_debug::debug_draw_buffer_allocators(buffer_allocators, painter);
+    }
 }
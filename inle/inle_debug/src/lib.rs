@@ -26,3 +26,4 @@ pub mod log;
 pub mod log_window;
 pub mod overlay;
 pub mod painter;
+pub mod rewind;
@@ -0,0 +1,292 @@
+use std::collections::VecDeque;
+use std::convert::TryInto;
+
+/// Minimum number of equal bytes that ends a changed run in a delta.
+/// Shorter runs are cheaper to store as changed bytes than to open a new run.
+const MIN_UNCHANGED_RUN: usize = 8;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Rewind_History_Config {
+    /// The history drops its oldest frames when it uses more memory than this.
+    /// 0 means that no frame is recorded.
+    pub max_mem_bytes: usize,
+    /// A full snapshot is stored once every `keyframe_interval` frames: the frames in between
+    /// only store their difference from that keyframe.
+    pub keyframe_interval: u32,
+}
+
+/// Contains a keyframe and the deltas of the frames following it.
+struct Snapshot_Group {
+    first_frame: u64,
+    keyframe: Vec<u8>,
+    deltas: Vec<Vec<u8>>,
+}
+
+impl Snapshot_Group {
+    fn last_frame(&self) -> u64 {
+        self.first_frame + self.deltas.len() as u64
+    }
+
+    fn mem_used(&self) -> usize {
+        self.keyframe.len() + self.deltas.iter().map(Vec::len).sum::<usize>()
+    }
+}
+
+/// Holds the snapshots of the latest consecutive frames, whatever their content is.
+/// Used to rewind the game to a past frame.
+pub struct Rewind_History {
+    cfg: Rewind_History_Config,
+    groups: VecDeque<Snapshot_Group>,
+    pub mem_used: usize,
+}
+
+impl Rewind_History {
+    pub fn new(cfg: Rewind_History_Config) -> Self {
+        Self {
+            cfg,
+            groups: VecDeque::default(),
+            mem_used: 0,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.cfg.max_mem_bytes > 0
+    }
+
+    /// Records the snapshot of `frame`. If `frame` does not directly follow the latest recorded
+    /// frame, all frames from `frame` onward are discarded and a new keyframe is started.
+    pub fn push_frame(&mut self, frame: u64, data: &[u8]) {
+        trace!("rewind::push_frame");
+
+        if self.cfg.max_mem_bytes == 0 {
+            return;
+        }
+
+        if let Some((first, last)) = self.frame_range() {
+            if frame <= first {
+                self.clear();
+            } else if frame <= last {
+                self.truncate_after(frame - 1);
+            }
+        }
+
+        let keyframe_interval = self.cfg.keyframe_interval.max(1) as usize;
+        match self.groups.back_mut() {
+            Some(group)
+                if group.last_frame() + 1 == frame
+                    && group.deltas.len() + 1 < keyframe_interval =>
+            {
+                let delta = encode_delta(&group.keyframe, data);
+                self.mem_used += delta.len();
+                group.deltas.push(delta);
+            }
+            _ => {
+                self.mem_used += data.len();
+                self.groups.push_back(Snapshot_Group {
+                    first_frame: frame,
+                    keyframe: data.to_vec(),
+                    deltas: vec![],
+                });
+            }
+        }
+
+        // Always keep at least the group we just pushed to.
+        while self.mem_used > self.cfg.max_mem_bytes && self.groups.len() > 1 {
+            let group = self.groups.pop_front().unwrap();
+            self.mem_used -= group.mem_used();
+        }
+    }
+
+    /// Returns the snapshot recorded for `frame`, if it's still in the history.
+    pub fn get_frame(&self, frame: u64) -> Option<Vec<u8>> {
+        trace!("rewind::get_frame");
+
+        let group = self
+            .groups
+            .iter()
+            .find(|g| (g.first_frame..=g.last_frame()).contains(&frame))?;
+        if frame == group.first_frame {
+            Some(group.keyframe.clone())
+        } else {
+            let delta = &group.deltas[(frame - group.first_frame - 1) as usize];
+            Some(decode_delta(&group.keyframe, delta))
+        }
+    }
+
+    /// Returns the first and last recorded frames.
+    pub fn frame_range(&self) -> Option<(u64, u64)> {
+        let first = self.groups.front()?.first_frame;
+        let last = self.groups.back()?.last_frame();
+        Some((first, last))
+    }
+
+    /// Discards all frames after `frame`.
+    pub fn truncate_after(&mut self, frame: u64) {
+        while let Some(group) = self.groups.back_mut() {
+            if group.first_frame > frame {
+                self.mem_used -= group.mem_used();
+                self.groups.pop_back();
+            } else {
+                let n_deltas = (frame - group.first_frame) as usize;
+                if n_deltas < group.deltas.len() {
+                    self.mem_used -= group.deltas[n_deltas..].iter().map(Vec::len).sum::<usize>();
+                    group.deltas.truncate(n_deltas);
+                }
+                break;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.groups.clear();
+        self.mem_used = 0;
+    }
+}
+
+/// A delta is encoded as the total length of the data followed by a sequence of
+/// (n_unchanged: u32, n_changed: u32, changed bytes), where the unchanged bytes are
+/// taken from the keyframe.
+fn encode_delta(keyframe: &[u8], data: &[u8]) -> Vec<u8> {
+    let unchanged_run_len = |start: usize| -> usize {
+        data[start..]
+            .iter()
+            .zip(&keyframe[start.min(keyframe.len())..])
+            .take_while(|(a, b)| a == b)
+            .count()
+    };
+
+    let mut delta = Vec::with_capacity(16);
+    delta.extend_from_slice(&(data.len() as u32).to_le_bytes());
+
+    let mut i = 0;
+    while i < data.len() {
+        let n_unchanged = unchanged_run_len(i);
+        let changed_start = i + n_unchanged;
+        let mut changed_end = changed_start;
+        while changed_end < data.len() {
+            let run = unchanged_run_len(changed_end);
+            if run >= MIN_UNCHANGED_RUN || changed_end + run == data.len() {
+                break;
+            }
+            changed_end += run.max(1);
+        }
+
+        delta.extend_from_slice(&(n_unchanged as u32).to_le_bytes());
+        delta.extend_from_slice(&((changed_end - changed_start) as u32).to_le_bytes());
+        delta.extend_from_slice(&data[changed_start..changed_end]);
+        i = changed_end;
+    }
+
+    delta
+}
+
+fn decode_delta(keyframe: &[u8], delta: &[u8]) -> Vec<u8> {
+    let read_u32 =
+        |pos: usize| u32::from_le_bytes(delta[pos..pos + 4].try_into().unwrap()) as usize;
+
+    let len = read_u32(0);
+    let mut data = Vec::with_capacity(len);
+    let mut pos = 4;
+    while pos < delta.len() {
+        let n_unchanged = read_u32(pos);
+        let n_changed = read_u32(pos + 4);
+        pos += 8;
+
+        let start = data.len();
+        data.extend_from_slice(&keyframe[start..start + n_unchanged]);
+        data.extend_from_slice(&delta[pos..pos + n_changed]);
+        pos += n_changed;
+    }
+    debug_assert_eq!(data.len(), len);
+
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_history(max_mem_bytes: usize, keyframe_interval: u32) -> Rewind_History {
+        Rewind_History::new(Rewind_History_Config {
+            max_mem_bytes,
+            keyframe_interval,
+        })
+    }
+
+    fn frame_data(frame: u64, len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|i| if i % 37 == 0 { frame as u8 } else { i as u8 })
+            .collect()
+    }
+
+    #[test]
+    fn delta_roundtrip() {
+        let keyframe = frame_data(0, 200);
+        for data in &[
+            frame_data(0, 200),
+            frame_data(1, 200),
+            frame_data(2, 150),
+            frame_data(3, 300),
+            vec![],
+            (0..255).rev().collect(),
+        ] {
+            assert_eq!(
+                decode_delta(&keyframe, &encode_delta(&keyframe, data)),
+                *data
+            );
+        }
+    }
+
+    #[test]
+    fn rewind_get_frames() {
+        let mut history = new_history(1 << 20, 4);
+        for frame in 10..20 {
+            history.push_frame(frame, &frame_data(frame, 100 + frame as usize));
+        }
+
+        assert_eq!(history.frame_range(), Some((10, 19)));
+        for frame in 10..20 {
+            assert_eq!(
+                history.get_frame(frame),
+                Some(frame_data(frame, 100 + frame as usize))
+            );
+        }
+        assert_eq!(history.get_frame(9), None);
+        assert_eq!(history.get_frame(20), None);
+    }
+
+    #[test]
+    fn rewind_mem_budget() {
+        let mut history = new_history(1000, 4);
+        for frame in 0..100 {
+            history.push_frame(frame, &frame_data(frame, 200));
+            assert!(history.mem_used <= 1000);
+        }
+
+        let (first, last) = history.frame_range().unwrap();
+        assert_eq!(last, 99);
+        assert!(first > 0);
+        assert_eq!(history.get_frame(first), Some(frame_data(first, 200)));
+    }
+
+    #[test]
+    fn rewind_overwrite_past() {
+        let mut history = new_history(1 << 20, 3);
+        for frame in 0..10 {
+            history.push_frame(frame, &frame_data(frame, 50));
+        }
+        history.push_frame(5, &frame_data(42, 60));
+        assert_eq!(history.frame_range(), Some((0, 5)));
+        assert_eq!(history.get_frame(5), Some(frame_data(42, 60)));
+        assert_eq!(history.get_frame(4), Some(frame_data(4, 50)));
+
+        let mem_used = history.mem_used;
+        history.truncate_after(1);
+        assert_eq!(history.frame_range(), Some((0, 1)));
+        assert!(history.mem_used < mem_used);
+
+        history.push_frame(0, &frame_data(0, 10));
+        assert_eq!(history.frame_range(), Some((0, 0)));
+        assert_eq!(history.mem_used, 10);
+    }
+}
@@ -1,17 +1,75 @@
 use crate::ecs_world::Entity;
 use anymap::any::UncheckedAnyExt;
 use anymap::Map;
-use std::any::type_name;
+use inle_common::bitset::Bit_Set;
+use std::any::{type_name, TypeId};
+use std::collections::HashMap;
 use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 
+/// Index of a Component_Storage inside the Component_Manager.
+/// Used as the bit index in the archetypes' signatures.
+pub type Component_Type_Id = usize;
+
+pub type Archetype_Id = usize;
+
+/// A monotonically increasing counter used to track when components were added, changed or removed.
+/// The Component_Manager starts at tick 1, so that `since = 0` matches everything.
+pub type Change_Tick = u32;
+
+/// How many ticks we remember a component removal for.
+const REMOVED_COMPONENTS_TICKS_KEPT: Change_Tick = 2;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Component_Ticks {
+    pub added: Change_Tick,
+    /// Note that a component is considered changed whenever it's mutably accessed, whether or not
+    /// it was actually modified.
+    pub changed: Change_Tick,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Tick_Filter {
+    Added,
+    Changed,
+}
+
+/// An Archetype is the set of all entities having exactly the same set of components.
+/// Queries match archetypes rather than single entities, so they never need to look at
+/// entities that don't have the requested components.
+pub struct Archetype {
+    /// The n-th bit is set if the entities in this archetype have the component whose type id is n.
+    pub signature: Bit_Set,
+    pub entities: Vec<Entity>,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Archetype_Slot {
+    archetype: Archetype_Id,
+    /// Index inside archetype.entities
+    idx: usize,
+}
+
 pub struct Component_Manager {
     storages: Map<dyn Component_Storage_Interface>,
+    n_storages: usize,
+    cur_tick: Change_Tick,
+
+    archetypes: Vec<Archetype>,
+    archetype_by_signature: HashMap<Bit_Set, Archetype_Id>,
+
+    /// Indexed by entity index. None if the entity has no components.
+    entity_archetype: Vec<Option<Archetype_Slot>>,
 }
 
 impl Component_Manager {
     pub fn new() -> Self {
         Self {
             storages: Map::new(),
+            n_storages: 0,
+            cur_tick: 1,
+            archetypes: vec![],
+            archetype_by_signature: HashMap::new(),
+            entity_archetype: vec![],
         }
     }
 
@@ -44,67 +102,179 @@ impl Component_Manager {
 
     #[inline]
     pub fn add_component<T: 'static>(&mut self, entity: Entity, data: T) {
+        let n_storages = &mut self.n_storages;
+        let cur_tick = self.cur_tick;
         let storage = self
             .storages
             .entry::<Component_Storage<T>>()
-            .or_insert_with(Component_Storage::<T>::default);
-
-        let mut components = storage.components.write().unwrap();
-        let cur_components_len = components.len();
+            .or_insert_with(|| {
+                let storage = Component_Storage::<T>::new(*n_storages, cur_tick);
+                *n_storages += 1;
+                storage
+            });
 
         // Ensure the entity doesn't have this component already
-        {
-            let slot = storage.entity_comp_index.get(entity.index as usize);
-            if slot.copied().flatten().is_some() {
-                fatal!(
-                    "Component {:?} added twice to entity {:?}!",
-                    type_name::<T>(),
-                    entity
-                );
-            }
-
-            if storage.entity_comp_index.len() <= entity.index as usize {
-                storage
-                    .entity_comp_index
-                    .resize(entity.index as usize + 1, None);
-            }
-            storage
-                .entity_comp_index
-                .insert(entity.index as usize, Some(cur_components_len));
+        if storage.has_component(entity) {
+            fatal!(
+                "Component {:?} added twice to entity {:?}!",
+                type_name::<T>(),
+                entity
+            );
         }
 
-        #[cfg(debug_assertions)]
-        {
-            if storage.entity_comp_generation.len() <= entity.index as usize {
-                storage
-                    .entity_comp_generation
-                    .resize(entity.index as usize + 1, 0);
-            }
-            storage
-                .entity_comp_generation
-                .insert(entity.index as usize, entity.gen);
-        }
+        storage.insert(entity, data);
 
-        components.push(data);
+        let type_id = storage.type_id;
+        self.move_to_archetype(entity, |signature| signature.set(type_id, true));
     }
 
     #[inline]
     pub fn remove_component<T: 'static>(&mut self, entity: Entity) {
-        if let Some(storage) = self.get_component_storage_mut::<T>() {
-            storage.remove_component(entity);
+        let type_id = if let Some(storage) = self.get_component_storage_mut::<T>() {
+            if !storage.remove(entity) {
+                return;
+            }
+            storage.type_id
         } else {
             lerr!(
                 "Tried to remove inexisting component {:?} from entity {:?}",
                 type_name::<T>(),
                 entity
             );
-        }
+            return;
+        };
+
+        self.move_to_archetype(entity, |signature| signature.set(type_id, false));
     }
 
     #[inline]
     pub fn remove_all_components(&mut self, entity: Entity) {
+        let signature = match self.entity_archetype_slot(entity) {
+            Some(slot) => self.archetypes[slot.archetype].signature.clone(),
+            None => return,
+        };
+
         for raw_storage in self.storages.as_mut().iter_mut() {
-            raw_storage.remove_component(entity);
+            if signature.get(raw_storage.comp_type_id()) {
+                raw_storage.remove_component(entity);
+            }
+        }
+
+        self.remove_from_archetype(entity);
+    }
+
+    #[inline]
+    pub fn cur_tick(&self) -> Change_Tick {
+        self.cur_tick
+    }
+
+    /// Only meant to be used on a Component_Manager that has no storages yet.
+    pub fn reset_cur_tick(&mut self, tick: Change_Tick) {
+        debug_assert_eq!(self.n_storages, 0);
+        self.cur_tick = tick;
+    }
+
+    pub fn advance_tick(&mut self) {
+        self.cur_tick += 1;
+        for raw_storage in self.storages.as_mut().iter_mut() {
+            raw_storage.set_cur_tick(self.cur_tick);
+        }
+    }
+
+    /// Returns true if `entity` has (at least) all the components in `signature`.
+    pub fn entity_matches_signature(&self, entity: Entity, signature: &Bit_Set) -> bool {
+        match self.entity_archetype_slot(entity) {
+            Some(slot) => {
+                let archetype = &self.archetypes[slot.archetype];
+                // The slot may belong to a newer entity reusing the same index.
+                archetype.entities[slot.idx] == entity
+                    && archetype.signature.contains_all(signature)
+            }
+            None => *signature == Bit_Set::default(),
+        }
+    }
+
+    /// Returns the type id of T's storage, or None if no entity ever had a T component.
+    #[inline]
+    pub fn get_component_type_id<T: 'static>(&self) -> Option<Component_Type_Id> {
+        self.get_component_storage::<T>()
+            .map(|storage| storage.type_id)
+    }
+
+    /// Returns all archetypes whose signature contains all bits of `signature`.
+    pub fn matching_archetypes<'a>(
+        &'a self,
+        signature: &'a Bit_Set,
+    ) -> impl Iterator<Item = &'a Archetype> + 'a {
+        self.archetypes
+            .iter()
+            .filter(move |arch| arch.signature.contains_all(signature))
+    }
+
+    #[inline]
+    fn entity_archetype_slot(&self, entity: Entity) -> Option<Archetype_Slot> {
+        self.entity_archetype
+            .get(entity.index as usize)
+            .copied()
+            .flatten()
+    }
+
+    /// Moves `entity` from its current archetype to the one whose signature is obtained by
+    /// applying `change_signature` to the current one.
+    fn move_to_archetype<F>(&mut self, entity: Entity, change_signature: F)
+    where
+        F: FnOnce(&mut Bit_Set),
+    {
+        trace!("comp_mgr::move_to_archetype");
+
+        let mut signature = match self.entity_archetype_slot(entity) {
+            Some(slot) => self.archetypes[slot.archetype].signature.clone(),
+            None => Bit_Set::default(),
+        };
+        change_signature(&mut signature);
+
+        self.remove_from_archetype(entity);
+
+        if signature == Bit_Set::default() {
+            // Entities without components don't belong to any archetype.
+            return;
+        }
+
+        let archetypes = &mut self.archetypes;
+        let archetype = *self
+            .archetype_by_signature
+            .entry(signature)
+            .or_insert_with_key(|signature| {
+                archetypes.push(Archetype {
+                    signature: signature.clone(),
+                    entities: vec![],
+                });
+                archetypes.len() - 1
+            });
+
+        let entities = &mut self.archetypes[archetype].entities;
+        entities.push(entity);
+        let slot = Archetype_Slot {
+            archetype,
+            idx: entities.len() - 1,
+        };
+
+        let entity_idx = entity.index as usize;
+        if self.entity_archetype.len() <= entity_idx {
+            self.entity_archetype.resize(entity_idx + 1, None);
+        }
+        self.entity_archetype[entity_idx] = Some(slot);
+    }
+
+    fn remove_from_archetype(&mut self, entity: Entity) {
+        if let Some(slot) = self.entity_archetype_slot(entity) {
+            let entities = &mut self.archetypes[slot.archetype].entities;
+            debug_assert_eq!(entities[slot.idx], entity);
+            entities.swap_remove(slot.idx);
+            if let Some(&moved) = entities.get(slot.idx) {
+                self.entity_archetype[moved.index as usize] = Some(slot);
+            }
+            self.entity_archetype[entity.index as usize] = None;
         }
     }
 
@@ -131,6 +301,19 @@ impl Component_Manager {
             .get::<Component_Storage<T>>()
             .map(|storage| storage.lock_for_write())
     }
+
+    /// Returns the component type, its name and the number of components of each storage.
+    pub(crate) fn storages_summary(
+        &self,
+    ) -> impl Iterator<Item = (TypeId, &'static str, usize)> + '_ {
+        self.storages.as_ref().iter().map(|storage| {
+            (
+                storage.comp_type(),
+                storage.comp_type_name(),
+                storage.n_components(),
+            )
+        })
+    }
 }
 
 #[cfg(debug_assertions)]
@@ -151,9 +334,23 @@ impl Component_Manager {
 }
 
 pub struct Component_Storage<T> {
+    type_id: Component_Type_Id,
+
     /// Indexed by entity_comp_index
     components: RwLock<Vec<T>>,
 
+    /// Parallel to `components`: the entity owning each component.
+    comp_entities: Vec<Entity>,
+
+    /// Parallel to `components`.
+    comp_ticks: RwLock<Vec<Component_Ticks>>,
+
+    /// Entities whose component was removed recently, along with the tick of the removal.
+    /// Entries older than REMOVED_COMPONENTS_TICKS_KEPT ticks are discarded.
+    removed: Vec<(Entity, Change_Tick)>,
+
+    cur_tick: Change_Tick,
+
     /// Indexed by entity index.
     entity_comp_index: Vec<Option<usize>>,
 
@@ -164,6 +361,20 @@ pub struct Component_Storage<T> {
 }
 
 impl<T> Component_Storage<T> {
+    fn new(type_id: Component_Type_Id, cur_tick: Change_Tick) -> Self {
+        Self {
+            type_id,
+            components: RwLock::new(vec![]),
+            comp_entities: vec![],
+            comp_ticks: RwLock::new(vec![]),
+            removed: vec![],
+            cur_tick,
+            entity_comp_index: vec![],
+            #[cfg(debug_assertions)]
+            entity_comp_generation: vec![],
+        }
+    }
+
     pub fn get_component(&self, entity: Entity) -> Option<Component_Read<T>> {
         if let Some(slot) = self.entity_comp_index.get(entity.index as usize) {
             slot.map(|idx| {
@@ -179,22 +390,88 @@ impl<T> Component_Storage<T> {
         if let Some(slot) = self.entity_comp_index.get(entity.index as usize) {
             slot.map(move |idx| {
                 let comps = self.components.write().unwrap();
+                self.comp_ticks.write().unwrap()[idx].changed = self.cur_tick;
                 Component_Write { lock: comps, idx }
             })
         } else {
             None
         }
     }
-}
 
-impl<T> Default for Component_Storage<T> {
-    fn default() -> Self {
-        Self {
-            components: RwLock::new(vec![]),
-            entity_comp_index: vec![],
-            #[cfg(debug_assertions)]
-            entity_comp_generation: vec![],
+    fn insert(&mut self, entity: Entity, data: T) {
+        let components = self.components.get_mut().unwrap();
+        let entity_idx = entity.index as usize;
+
+        if self.entity_comp_index.len() <= entity_idx {
+            self.entity_comp_index.resize(entity_idx + 1, None);
         }
+        self.entity_comp_index[entity_idx] = Some(components.len());
+
+        #[cfg(debug_assertions)]
+        {
+            if self.entity_comp_generation.len() <= entity_idx {
+                self.entity_comp_generation.resize(entity_idx + 1, 0);
+            }
+            self.entity_comp_generation[entity_idx] = entity.gen;
+        }
+
+        components.push(data);
+        self.comp_entities.push(entity);
+        self.comp_ticks.get_mut().unwrap().push(Component_Ticks {
+            added: self.cur_tick,
+            changed: self.cur_tick,
+        });
+    }
+
+    /// Returns true if the component was actually removed.
+    fn remove(&mut self, entity: Entity) -> bool {
+        let idx = match self.entity_comp_index.get_mut(entity.index as usize) {
+            Some(slot) if slot.is_some() => slot.take().unwrap(),
+            _ => {
+                lerr!(
+                    "Tried to remove inexisting component {:?} from entity {:?}",
+                    type_name::<T>(),
+                    entity
+                );
+                return false;
+            }
+        };
+
+        // Keep the components packed: move the last one into the hole.
+        self.components.get_mut().unwrap().swap_remove(idx);
+        self.comp_entities.swap_remove(idx);
+        self.comp_ticks.get_mut().unwrap().swap_remove(idx);
+        self.removed.push((entity, self.cur_tick));
+        if let Some(moved) = self.comp_entities.get(idx) {
+            self.entity_comp_index[moved.index as usize] = Some(idx);
+        }
+
+        true
+    }
+
+    /// Returns the ticks of entity's component, or None if the entity doesn't have it.
+    pub fn get_ticks(&self, entity: Entity) -> Option<Component_Ticks> {
+        let idx = self
+            .entity_comp_index
+            .get(entity.index as usize)
+            .copied()
+            .flatten()?;
+        Some(self.comp_ticks.read().unwrap()[idx])
+    }
+
+    /// Calls `f` with the entities owning a component and their components, in storage order.
+    pub fn with_all_components<R>(&self, f: impl FnOnce(&[Entity], &[T]) -> R) -> R {
+        let components = self.components.read().unwrap();
+        f(&self.comp_entities, &components)
+    }
+
+    /// Returns all entities whose component was removed at tick `since` or later.
+    /// Note that these entities may not be alive anymore.
+    pub fn removed_since(&self, since: Change_Tick) -> impl Iterator<Item = Entity> + '_ {
+        self.removed
+            .iter()
+            .filter(move |(_, tick)| *tick >= since)
+            .map(|(entity, _)| *entity)
     }
 }
 
@@ -239,11 +516,33 @@ pub struct Component_Storage_Read<'a, T> {
 
 pub struct Component_Storage_Write<'a, T> {
     components: RwLockWriteGuard<'a, Vec<T>>,
+    comp_ticks: RwLockWriteGuard<'a, Vec<Component_Ticks>>,
+    cur_tick: Change_Tick,
     entity_comp_index: &'a [Option<usize>],
     #[cfg(debug_assertions)]
     entity_comp_generation: &'a [inle_alloc::gen_alloc::Gen_Type],
 }
 
+pub(crate) struct Component_Ticks_Read<'a> {
+    comp_ticks: RwLockReadGuard<'a, Vec<Component_Ticks>>,
+    entity_comp_index: &'a [Option<usize>],
+}
+
+impl Component_Ticks_Read<'_> {
+    /// Removes from `entities` all those whose component doesn't pass `filter` since the tick `since`.
+    /// All `entities` must have this component.
+    pub fn retain(&self, entities: &mut Vec<Entity>, filter: Tick_Filter, since: Change_Tick) {
+        entities.retain(|entity| {
+            let idx = self.entity_comp_index[entity.index as usize].unwrap();
+            let ticks = &self.comp_ticks[idx];
+            match filter {
+                Tick_Filter::Added => ticks.added >= since,
+                Tick_Filter::Changed => ticks.changed >= since,
+            }
+        });
+    }
+}
+
 impl<T> Component_Storage<T> {
     pub fn lock_for_read(&self) -> Component_Storage_Read<'_, T> {
         trace!("Component_Storage::lock_for_read");
@@ -261,6 +560,8 @@ impl<T> Component_Storage<T> {
 
         Component_Storage_Write {
             components: self.components.write().unwrap(),
+            comp_ticks: self.comp_ticks.write().unwrap(),
+            cur_tick: self.cur_tick,
             entity_comp_index: &self.entity_comp_index,
             #[cfg(debug_assertions)]
             entity_comp_generation: &self.entity_comp_generation,
@@ -273,6 +574,12 @@ impl<T> Component_Storage_Read<'_, T> {
     pub fn get(&self, entity: Entity) -> Option<&T> {
         trace!("Component_Storage_Read::get");
 
+        let idx = self
+            .entity_comp_index
+            .get(entity.index as usize)
+            .copied()
+            .flatten()?;
+
         #[cfg(debug_assertions)]
         {
             assert_gen_consistency(
@@ -282,7 +589,7 @@ impl<T> Component_Storage_Read<'_, T> {
                 type_name::<T>(),
             );
         }
-        self.entity_comp_index[entity.index as usize].map(|idx| &self.components[idx])
+        Some(&self.components[idx])
     }
 
     #[inline]
@@ -308,6 +615,12 @@ impl<T> Component_Storage_Write<'_, T> {
     pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
         trace!("Component_Storage_Write::get_mut");
 
+        let idx = self
+            .entity_comp_index
+            .get(entity.index as usize)
+            .copied()
+            .flatten()?;
+
         #[cfg(debug_assertions)]
         {
             assert_gen_consistency(
@@ -317,7 +630,31 @@ impl<T> Component_Storage_Write<'_, T> {
                 type_name::<T>(),
             );
         }
-        self.entity_comp_index[entity.index as usize].map(move |idx| &mut self.components[idx])
+        self.comp_ticks[idx].changed = self.cur_tick;
+        Some(&mut self.components[idx])
+    }
+
+    /// Like get_mut, but doesn't mark the component as changed.
+    #[inline]
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        trace!("Component_Storage_Write::get");
+
+        let idx = self
+            .entity_comp_index
+            .get(entity.index as usize)
+            .copied()
+            .flatten()?;
+
+        #[cfg(debug_assertions)]
+        {
+            assert_gen_consistency(
+                self.entity_comp_index,
+                self.entity_comp_generation,
+                entity,
+                type_name::<T>(),
+            );
+        }
+        Some(&self.components[idx])
     }
 
     #[inline]
@@ -334,6 +671,7 @@ impl<T> Component_Storage_Write<'_, T> {
             );
         }
         let idx = self.entity_comp_index[entity.index as usize].unwrap();
+        self.comp_ticks[idx].changed = self.cur_tick;
         &mut self.components[idx]
     }
 }
@@ -378,6 +716,12 @@ impl<'a, T: 'a> IntoIterator for &'a mut Component_Storage_Write<'a, T> {
     type Item = &'a mut T;
 
     fn into_iter(self) -> Self::IntoIter {
+        // Conservatively consider all components changed
+        let cur_tick = self.cur_tick;
+        for ticks in self.comp_ticks.iter_mut() {
+            ticks.changed = cur_tick;
+        }
+
         Self::IntoIter {
             inner: self.components.iter_mut(),
         }
@@ -415,8 +759,24 @@ fn assert_gen_consistency(
 // so we can iterate on them in an untyped fashion and still be
 // able to do stuff on them (e.g. remove_all_components)
 pub(super) trait Component_Storage_Interface: anymap::any::Any {
+    fn comp_type_id(&self) -> Component_Type_Id;
+
+    /// The TypeId of the stored components (rather than of the storage).
+    fn comp_type(&self) -> TypeId;
+
+    fn comp_type_name(&self) -> &'static str;
+
+    fn n_components(&self) -> usize;
+
     fn remove_component(&mut self, entity: Entity);
 
+    fn set_cur_tick(&mut self, tick: Change_Tick);
+
+    /// Locks the component ticks for reading, so they can be used to filter entities.
+    fn lock_ticks_for_read(&self) -> Component_Ticks_Read<'_>;
+
+    fn removed_since(&self, since: Change_Tick, out: &mut Vec<Entity>);
+
     #[cfg(debug_assertions)]
     fn has_component(&self, entity: Entity) -> bool;
 
@@ -425,18 +785,45 @@ pub(super) trait Component_Storage_Interface: anymap::any::Any {
 }
 
 impl<T: 'static> Component_Storage_Interface for Component_Storage<T> {
+    fn comp_type_id(&self) -> Component_Type_Id {
+        self.type_id
+    }
+
+    fn comp_type(&self) -> TypeId {
+        TypeId::of::<T>()
+    }
+
+    fn comp_type_name(&self) -> &'static str {
+        type_name::<T>()
+    }
+
+    fn n_components(&self) -> usize {
+        self.comp_entities.len()
+    }
+
     fn remove_component(&mut self, entity: Entity) {
-        if let Some(slot) = self.entity_comp_index.get_mut(entity.index as usize) {
-            *slot = None;
-        } else {
-            lerr!(
-                "Tried to remove inexisting component {:?} from entity {:?}",
-                type_name::<T>(),
-                entity
-            );
+        self.remove(entity);
+    }
+
+    fn set_cur_tick(&mut self, tick: Change_Tick) {
+        self.cur_tick = tick;
+        let oldest_kept = tick.saturating_sub(REMOVED_COMPONENTS_TICKS_KEPT - 1);
+        self.removed.retain(|(_, t)| *t >= oldest_kept);
+    }
+
+    fn lock_ticks_for_read(&self) -> Component_Ticks_Read<'_> {
+        trace!("Component_Storage::lock_ticks_for_read");
+
+        Component_Ticks_Read {
+            comp_ticks: self.comp_ticks.read().unwrap(),
+            entity_comp_index: &self.entity_comp_index,
         }
     }
 
+    fn removed_since(&self, since: Change_Tick, out: &mut Vec<Entity>) {
+        out.extend(Component_Storage::<T>::removed_since(self, since));
+    }
+
     // @Cleanup: I wonder if this is the best way to do this.
     // Should we just expose the trait and have all methods be
     // trait methods? But would that mean that we'd have to go through
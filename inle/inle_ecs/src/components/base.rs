@@ -1,9 +1,52 @@
 use inle_math::transform::Transform2D;
 use inle_math::vector::Vec2f;
+use inle_serialize::{Binary_Serializable, Byte_Stream};
+use std::io;
 
 #[derive(Copy, Clone, Debug, PartialEq, Default)]
 pub struct C_Spatial2D {
     pub transform: Transform2D,
     pub velocity: Vec2f,
     pub frame_starting_pos: Vec2f,
+    /// The transform at the start of the latest simulation tick, used to interpolate the
+    /// rendered transform between ticks. None if the entity was created after that.
+    pub prev_transform: Option<Transform2D>,
+}
+
+/// The transform of an entity relative to its parent.
+/// Entities with a parent and this component get their C_Spatial2D's transform
+/// recomputed from their parent's every frame.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub struct C_Local_Transform2D {
+    pub transform: Transform2D,
+}
+
+impl Binary_Serializable for C_Spatial2D {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.transform.serialize(output)?;
+        self.velocity.serialize(output)?;
+        self.frame_starting_pos.serialize(output)?;
+        self.prev_transform.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Self {
+            transform: Transform2D::deserialize(input)?,
+            velocity: Vec2f::deserialize(input)?,
+            frame_starting_pos: Vec2f::deserialize(input)?,
+            prev_transform: Option::deserialize(input)?,
+        })
+    }
+}
+
+impl Binary_Serializable for C_Local_Transform2D {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.transform.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Self {
+            transform: Transform2D::deserialize(input)?,
+        })
+    }
 }
@@ -1,27 +1,106 @@
 use crate::comp_mgr::{
-    Component_Manager, Component_Storage, Component_Storage_Interface, Component_Storage_Read,
-    Component_Storage_Write,
+    Change_Tick, Component_Manager, Component_Storage, Component_Storage_Interface,
+    Component_Storage_Read, Component_Storage_Write, Tick_Filter,
 };
 use crate::ecs_world::{Ecs_World, Entity};
 use anymap::any::UncheckedAnyExt;
+use inle_common::bitset::Bit_Set;
 use smallvec::SmallVec;
 use std::any::{type_name, TypeId};
+use std::cell::OnceCell;
 use std::collections::HashMap;
 
 pub struct Ecs_Query<'mgr, 'str> {
+    ecs_world: &'mgr Ecs_World,
     comp_mgr: &'mgr Component_Manager,
     storages: Storages<'str>,
-    entities: Vec<Entity>,
+
+    /// The set of components an entity must have to satisfy this query.
+    signature: Bit_Set,
+
+    /// True if some of the queried components are not owned by any entity.
+    never_matches: bool,
+
+    tick_filters: SmallVec<
+        [(
+            &'str dyn Component_Storage_Interface,
+            Tick_Filter,
+            Change_Tick,
+        ); 2],
+    >,
+    removed_filters: SmallVec<[(&'str dyn Component_Storage_Interface, Change_Tick); 1]>,
+
+    /// Lazily filled by entities(), so we only gather the entities once the query is fully built.
+    /// It's always filled before any storage is locked through storages(), so the component ticks
+    /// are never locked while this query holds a storage lock.
+    entities: OnceCell<Vec<Entity>>,
 }
 
 impl<'m, 's> Ecs_Query<'m, 's> {
+    /// Returns all entities satisfying this query. Only the archetypes matching the query
+    /// are visited, so this is proportional to the number of matched entities.
     #[inline]
     pub fn entities(&self) -> &[Entity] {
-        &self.entities
+        self.entities.get_or_init(|| {
+            trace!("ecs_query::gather_entities");
+
+            if self.never_matches {
+                return vec![];
+            }
+
+            // Lock all the ticks up front, in the same order the filters were added, before touching
+            // the archetypes: this way we never wait on a ticks lock while holding anything else.
+            let tick_filters: SmallVec<[_; 2]> = self
+                .tick_filters
+                .iter()
+                .map(|(storage, filter, since)| (storage.lock_ticks_for_read(), *filter, *since))
+                .collect();
+
+            let mut entities = vec![];
+            if let Some(((first_removed, since), other_removed)) =
+                self.removed_filters.split_first()
+            {
+                // Entities with removed components don't show up in the archetypes we want,
+                // so start from the removed list and check the signature manually.
+                first_removed.removed_since(*since, &mut entities);
+                entities.sort_unstable_by_key(|e| (e.index, e.gen));
+                entities.dedup();
+
+                let mut removed = vec![];
+                for (storage, since) in other_removed {
+                    removed.clear();
+                    storage.removed_since(*since, &mut removed);
+                    entities.retain(|e| removed.contains(e));
+                }
+
+                // The removed lists may hold stale handles whose index was already reused,
+                // so drop the dead entities before checking the signature of their index.
+                let ecs_world = self.ecs_world;
+                let comp_mgr = self.comp_mgr;
+                let signature = &self.signature;
+                entities.retain(|&e| {
+                    ecs_world.is_valid_entity(e) && comp_mgr.entity_matches_signature(e, signature)
+                });
+            } else {
+                for archetype in self.comp_mgr.matching_archetypes(&self.signature) {
+                    entities.extend_from_slice(&archetype.entities);
+                }
+            }
+
+            for (ticks, filter, since) in &tick_filters {
+                ticks.retain(&mut entities, *filter, *since);
+            }
+
+            entities
+        })
     }
 
+    /// Returns the storages to lock the queried components. The query entities are gathered
+    /// (if they weren't already) before returning, so the tick filters are done with their locks
+    /// by the time any storage is locked.
     #[inline]
     pub fn storages(&self) -> &Storages<'s> {
+        self.entities();
         &self.storages
     }
 }
@@ -92,15 +171,14 @@ where
         trace!("Ecs_Query::new");
 
         Self {
+            ecs_world,
             comp_mgr: &ecs_world.component_manager,
             storages: Storages::default(),
-            // @Speed: cloning the entire entity vector! This is not a good idea, find a better way.
-            // Since Ecs_Query never outlives the component manager, we may make it just an iterator
-            // over the ecs world's entities (a bit like the old version).
-            // However, for it to be efficient we'd need a fast way to determine if an entity has
-            // a specific set of component.
-            // Also, we may hinder our possibility to parallelize the iteration in the future...
-            entities: ecs_world.entities().to_vec(),
+            signature: Bit_Set::default(),
+            never_matches: false,
+            tick_filters: SmallVec::default(),
+            removed_filters: SmallVec::default(),
+            entities: OnceCell::new(),
         }
     }
 
@@ -114,29 +192,13 @@ where
                 .push_str(&format!("read {}, ", base_type_name::<T>()));
         }
 
-        let mut added = false;
-        if !self.entities.is_empty() {
-            if let Some(storage) = self.comp_mgr.get_component_storage::<T>() {
-                self.storages.reads.push(storage);
-                self.storages
-                    .read_indices
-                    .insert(TypeId::of::<T>(), self.storages.reads.len() - 1);
-
-                // @Speed: this may probably be accelerated with some dedicated data structure on Component_Manager
-                let comp_mgr = self.comp_mgr;
-                {
-                    trace!("ecs_query::entities_retain");
-                    self.entities.retain(|&e| comp_mgr.has_component::<T>(e));
-                }
-
-                added = true;
-            }
-        }
-
-        if !added {
-            // Instead of retaining, since we know no entity has this component, just clear the array.
-            self.entities.clear();
+        if let Some(storage) = self.comp_mgr.get_component_storage::<T>() {
+            self.storages.reads.push(storage);
+            self.storages
+                .read_indices
+                .insert(TypeId::of::<T>(), self.storages.reads.len() - 1);
         }
+        self.add_to_signature::<T>();
 
         self
     }
@@ -151,31 +213,88 @@ where
                 .push_str(&format!("write {}, ", base_type_name::<T>()));
         }
 
-        let mut added = false;
-        if !self.entities.is_empty() {
-            if let Some(storage) = self.comp_mgr.get_component_storage::<T>() {
-                self.storages.writes.push(storage);
-                self.storages
-                    .write_indices
-                    .insert(TypeId::of::<T>(), self.storages.writes.len() - 1);
+        if let Some(storage) = self.comp_mgr.get_component_storage::<T>() {
+            self.storages.writes.push(storage);
+            self.storages
+                .write_indices
+                .insert(TypeId::of::<T>(), self.storages.writes.len() - 1);
+        }
+        self.add_to_signature::<T>();
+
+        self
+    }
 
-                // @Speed: this may probably be accelerated with some dedicated data structure on Component_Manager
-                let comp_mgr = self.comp_mgr;
-                {
-                    trace!("ecs_query::entities_retain");
-                    self.entities.retain(|&e| comp_mgr.has_component::<T>(e));
-                }
+    /// Only matches entities whose T component was changed (or added) at tick `since` or later.
+    /// Note that a component is considered changed every time it's accessed mutably.
+    pub fn changed<T: 'static>(self, since: Change_Tick) -> Self {
+        trace!("ecs_query::changed");
 
-                added = true;
-            }
+        self.with_tick_filter::<T>(Tick_Filter::Changed, since)
+    }
+
+    /// Only matches entities whose T component was added at tick `since` or later.
+    pub fn added<T: 'static>(self, since: Change_Tick) -> Self {
+        trace!("ecs_query::added");
+
+        self.with_tick_filter::<T>(Tick_Filter::Added, since)
+    }
+
+    /// Only matches entities whose T component was removed at tick `since` or later.
+    /// Since such entities don't have T anymore, T must not be read or written by this query.
+    /// Entities that were destroyed in the meantime are not returned.
+    pub fn removed<T: 'static>(mut self, since: Change_Tick) -> Self {
+        trace!("ecs_query::removed");
+
+        #[cfg(debug_assertions)]
+        {
+            self.storages.human_readable_query.push_str(&format!(
+                "removed {} since {}, ",
+                base_type_name::<T>(),
+                since
+            ));
+        }
+
+        if let Some(storage) = self.comp_mgr.get_component_storage::<T>() {
+            self.removed_filters.push((storage, since));
+        } else {
+            self.never_matches = true;
+        }
+
+        self
+    }
+
+    fn with_tick_filter<T: 'static>(mut self, filter: Tick_Filter, since: Change_Tick) -> Self {
+        #[cfg(debug_assertions)]
+        {
+            self.storages.human_readable_query.push_str(&format!(
+                "{:?} {} since {}, ",
+                filter,
+                base_type_name::<T>(),
+                since
+            ));
         }
 
-        if !added {
-            self.entities.clear();
+        if let Some(storage) = self.comp_mgr.get_component_storage::<T>() {
+            self.tick_filters.push((storage, filter, since));
         }
+        self.add_to_signature::<T>();
 
         self
     }
+
+    fn add_to_signature<T: 'static>(&mut self) {
+        debug_assert!(
+            self.entities.get().is_none(),
+            "Ecs_Query was modified after its entities were gathered!"
+        );
+
+        if let Some(type_id) = self.comp_mgr.get_component_type_id::<T>() {
+            self.signature.set(type_id, true);
+        } else {
+            // Since we know no entity has this component, no entity can satisfy the query.
+            self.never_matches = true;
+        }
+    }
 }
 
 fn base_type_name<T>() -> &'static str {
@@ -233,4 +352,133 @@ mod tests {
             assert_eq!(d.v[d.v.len() - 1], "42, 0.3");
         });
     }
+
+    #[test]
+    fn ecs_query_matches_only_entities_with_all_components() {
+        #[derive(Copy, Clone, Debug, PartialEq)]
+        struct A(u32);
+        #[derive(Copy, Clone, Debug, PartialEq)]
+        struct B(u32);
+
+        let mut world = Ecs_World::new();
+        let e1 = world.new_entity();
+        let e2 = world.new_entity();
+        let e3 = world.new_entity();
+
+        world.add_component(e1, A(1));
+        world.add_component(e2, A(2));
+        world.add_component(e2, B(2));
+        world.add_component(e3, B(3));
+
+        {
+            let query = Ecs_Query::new(&world).read::<A>().read::<B>();
+            assert_eq!(query.entities(), &[e2]);
+        }
+
+        world.remove_component::<B>(e2);
+        world.add_component(e1, B(1));
+
+        let query = Ecs_Query::new(&world).read::<A>().read::<B>();
+        assert_eq!(query.entities(), &[e1]);
+        let bs = query.storages().begin_read::<B>();
+        assert_eq!(bs.must_get(e1), &B(1));
+        assert_eq!((&bs).into_iter().count(), 2);
+
+        let query = Ecs_Query::new(&world).read::<A>();
+        let mut entities = query.entities().to_vec();
+        entities.sort_by_key(|e| e.index);
+        assert_eq!(entities, vec![e1, e2]);
+
+        struct C;
+        let query = Ecs_Query::new(&world).read::<A>().read::<C>();
+        assert!(query.entities().is_empty());
+    }
+
+    #[test]
+    fn ecs_query_tick_filters() {
+        #[derive(Copy, Clone, Debug, PartialEq)]
+        struct A(u32);
+        #[derive(Copy, Clone, Debug, PartialEq)]
+        struct B(u32);
+
+        let mut world = Ecs_World::new();
+        let e1 = world.new_entity();
+        let e2 = world.new_entity();
+        world.add_component(e1, A(1));
+        world.add_component(e2, A(2));
+        world.add_component(e2, B(2));
+
+        let first_tick = world.cur_tick();
+        world.advance_tick();
+        let second_tick = world.cur_tick();
+
+        {
+            let query = Ecs_Query::new(&world).added::<A>(second_tick);
+            assert!(query.entities().is_empty());
+            let query = Ecs_Query::new(&world).added::<A>(first_tick);
+            assert_eq!(query.entities().len(), 2);
+        }
+
+        world.get_component_mut::<A>(e1).unwrap().0 = 10;
+        world.remove_component::<B>(e2);
+
+        {
+            let query = Ecs_Query::new(&world).changed::<A>(second_tick);
+            assert_eq!(query.entities(), &[e1]);
+
+            let query = Ecs_Query::new(&world).read::<A>().removed::<B>(second_tick);
+            assert_eq!(query.entities(), &[e2]);
+        }
+
+        world.advance_tick();
+        world.advance_tick();
+        let query = Ecs_Query::new(&world).removed::<B>(first_tick);
+        assert!(query.entities().is_empty());
+    }
+
+    #[test]
+    fn ecs_query_removed_skips_destroyed_entities() {
+        #[derive(Copy, Clone, Debug, PartialEq)]
+        struct A(u32);
+        #[derive(Copy, Clone, Debug, PartialEq)]
+        struct B(u32);
+
+        let mut world = Ecs_World::new();
+        let e1 = world.new_entity();
+        world.add_component(e1, A(1));
+        world.add_component(e1, B(1));
+
+        let since = world.cur_tick();
+        world.remove_component::<B>(e1);
+        world.destroy_entity(e1);
+        world.destroy_pending();
+        world.destroy_pending();
+
+        // Reuses e1's index.
+        let e2 = world.new_entity();
+        assert_eq!(e2.index, e1.index);
+        world.add_component(e2, A(2));
+
+        let query = Ecs_Query::new(&world).read::<A>().removed::<B>(since);
+        assert!(query.entities().is_empty());
+        let query = Ecs_Query::new(&world).removed::<B>(since);
+        assert!(query.entities().is_empty());
+    }
+
+    #[test]
+    fn ecs_query_tick_filters_dont_lock_ticks_while_writing() {
+        struct A(u32);
+
+        let mut world = Ecs_World::new();
+        let e = world.new_entity();
+        world.add_component(e, A(1));
+
+        let query = Ecs_Query::new(&world)
+            .write::<A>()
+            .changed::<A>(world.cur_tick());
+        // Locking the storage before asking for the entities must not deadlock on the ticks lock.
+        let mut comps = query.storages().begin_write::<A>();
+        assert_eq!(query.entities(), &[e]);
+        comps.must_get_mut(e).0 = 2;
+    }
 }
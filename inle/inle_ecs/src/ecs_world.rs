@@ -1,11 +1,18 @@
 use super::comp_mgr::{self, Component_Manager};
+use super::hierarchy::Entity_Hierarchy;
+use super::snapshot::{self, Component_Serialization_Registry};
 use inle_alloc::gen_alloc::{Generational_Allocator, Generational_Index};
 use inle_events::evt_register;
+use inle_serialize::{Binary_Serializable, Byte_Stream};
 use std::any::type_name;
 use std::collections::HashSet;
+use std::io;
 
 pub type Entity = Generational_Index;
 
+pub type Change_Tick = comp_mgr::Change_Tick;
+pub type Component_Ticks = comp_mgr::Component_Ticks;
+
 pub type Component_Storage<T> = comp_mgr::Component_Storage<T>;
 pub type Component_Storage_Read<'a, T> = comp_mgr::Component_Storage_Read<'a, T>;
 pub type Component_Storage_Write<'a, T> = comp_mgr::Component_Storage_Write<'a, T>;
@@ -24,6 +31,7 @@ pub struct Ecs_World {
     pub(super) component_manager: Component_Manager,
     entities_pending_destroy_notify: HashSet<Entity>,
     entities_pending_destroy: Vec<Entity>,
+    hierarchy: Entity_Hierarchy,
 }
 
 impl Ecs_World {
@@ -33,6 +41,7 @@ impl Ecs_World {
             component_manager: Component_Manager::new(),
             entities_pending_destroy_notify: HashSet::new(),
             entities_pending_destroy: vec![],
+            hierarchy: Entity_Hierarchy::default(),
         }
     }
 
@@ -44,9 +53,14 @@ impl Ecs_World {
         &self.entity_manager.entities
     }
 
+    /// Marks `entity` and all its descendants for destruction.
     pub fn destroy_entity(&mut self, entity: Entity) {
         debug_assert!(self.is_valid_entity(entity));
         self.entities_pending_destroy_notify.insert(entity);
+
+        let mut descendants = vec![];
+        self.hierarchy.get_descendants(entity, &mut descendants);
+        self.entities_pending_destroy_notify.extend(descendants);
     }
 
     pub fn notify_destroyed(&self, evt_register: &mut evt_register::Event_Register) {
@@ -58,6 +72,7 @@ impl Ecs_World {
         for &entity in &self.entities_pending_destroy {
             self.component_manager.remove_all_components(entity);
             self.entity_manager.destroy_entity(entity);
+            self.hierarchy.remove(entity);
         }
         let destroyed = std::mem::take(&mut self.entities_pending_destroy);
         self.entities_pending_destroy = self.entities_pending_destroy_notify.drain().collect();
@@ -69,7 +84,128 @@ impl Ecs_World {
             && !self.entities_pending_destroy.contains(&entity)
     }
 
+    /// Returns the tick that will be recorded for all components added, changed or removed
+    /// from now until the next call to advance_tick().
+    #[inline]
+    pub fn cur_tick(&self) -> Change_Tick {
+        self.component_manager.cur_tick()
+    }
+
+    /// Should be called once per frame, after all systems have run on this world.
+    pub fn advance_tick(&mut self) {
+        self.component_manager.advance_tick();
+    }
+
+    /// Sets the parent of `child`, or detaches it from its parent if `parent` is None.
+    /// Destroying an entity also destroys all its descendants.
+    pub fn set_parent(&mut self, child: Entity, parent: Option<Entity>) {
+        debug_assert!(self.is_valid_entity(child));
+        if let Some(parent) = parent {
+            debug_assert!(self.is_valid_entity(parent));
+        }
+        self.hierarchy.set_parent(child, parent);
+    }
+
+    #[inline]
+    pub fn get_parent(&self, entity: Entity) -> Option<Entity> {
+        self.hierarchy.parent(entity)
+    }
+
+    #[inline]
+    pub fn get_children(&self, entity: Entity) -> &[Entity] {
+        self.hierarchy.children(entity)
+    }
+
+    #[inline]
+    pub fn hierarchy(&self) -> &Entity_Hierarchy {
+        &self.hierarchy
+    }
+
+    /// Writes the whole state of the world to `output`, including the entity generations, so
+    /// that all Entity handles stay valid in the world loaded back by deserialize_snapshot().
+    /// Only the components registered in `registry` are saved.
+    pub fn serialize_snapshot(
+        &self,
+        registry: &Component_Serialization_Registry,
+        output: &mut Byte_Stream,
+    ) -> io::Result<()> {
+        trace!("serialize_snapshot");
+
+        output.write_u16(snapshot::SNAPSHOT_VERSION)?;
+        self.entity_manager.serialize(output)?;
+        output.write_u32(self.cur_tick())?;
+        registry.serialize_storages(self, output)?;
+
+        // Sort everything coming from hash maps so the snapshot is deterministic.
+        let mut parents = self.hierarchy.parents_with_children().collect::<Vec<_>>();
+        parents.sort_by_key(|(parent, _)| parent.index);
+        output.write_u32(parents.len() as u32)?;
+        for (parent, children) in parents {
+            parent.serialize(output)?;
+            children.to_vec().serialize(output)?;
+        }
+
+        self.entities_pending_destroy.serialize(output)?;
+        let mut pending_notify = self
+            .entities_pending_destroy_notify
+            .iter()
+            .copied()
+            .collect::<Vec<_>>();
+        pending_notify.sort_by_key(|e| e.index);
+        pending_notify.serialize(output)
+    }
+
+    pub fn deserialize_snapshot(
+        registry: &Component_Serialization_Registry,
+        input: &mut Byte_Stream,
+    ) -> io::Result<Ecs_World> {
+        trace!("deserialize_snapshot");
+
+        let version = input.read_u16()?;
+        if version != snapshot::SNAPSHOT_VERSION {
+            lerr!(
+                "Snapshot version is {}, but only version {} is supported.",
+                version,
+                snapshot::SNAPSHOT_VERSION
+            );
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+
+        let mut world = Ecs_World::new();
+        world.entity_manager = Entity_Manager::deserialize(input)?;
+        world.component_manager.reset_cur_tick(input.read_u32()?);
+        registry.deserialize_storages(&mut world, input)?;
+
+        let n_parents = input.read_u32()?;
+        for _ in 0..n_parents {
+            let parent = Entity::deserialize(input)?;
+            let children = Vec::<Entity>::deserialize(input)?;
+            if !world.is_valid_entity(parent) || !children.iter().all(|&c| world.is_valid_entity(c))
+            {
+                return Err(io::ErrorKind::InvalidData.into());
+            }
+            for child in children {
+                world.hierarchy.set_parent(child, Some(parent));
+            }
+        }
+
+        world.entities_pending_destroy = Vec::deserialize(input)?;
+        world.entities_pending_destroy_notify = Vec::deserialize(input)?.into_iter().collect();
+
+        Ok(world)
+    }
+
     pub fn add_component<T: 'static>(&mut self, entity: Entity, data: T) {
+        trace!("add_component");
+
+        if !self.entity_manager.is_valid_entity(entity) {
+            fatal!(
+                "add_component::<{}>: invalid entity {:?}",
+                type_name::<T>(),
+                entity
+            );
+        }
+
         self.component_manager.add_component::<T>(entity, data);
     }
 
@@ -189,6 +325,22 @@ impl Entity_Manager {
     }
 }
 
+impl Binary_Serializable for Entity_Manager {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.alloc.serialize(output)?;
+        self.entities.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        let alloc = Generational_Allocator::deserialize(input)?;
+        let entities = Vec::<Entity>::deserialize(input)?;
+        if entities.len() != alloc.live_size() || !entities.iter().all(|&e| alloc.is_valid(e)) {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        Ok(Entity_Manager { alloc, entities })
+    }
+}
+
 #[cfg(debug_assertions)]
 impl Ecs_World {
     pub fn get_comp_name_list_for_entity(&self, entity: Entity) -> Vec<&'static str> {
@@ -196,5 +348,5 @@ impl Ecs_World {
     }
 }
 
-#[cfg(tests)]
+#[cfg(test)]
 include!("./ecs_world_tests.rs");
@@ -3,6 +3,7 @@
 #[cfg(all(test, not(miri)))]
 mod tests {
     use super::*;
+    use crate::ecs_query::Ecs_Query;
 
     #[derive(Copy, Clone, Debug, Default)]
     struct C_Test {
@@ -14,11 +15,6 @@ mod tests {
         foo: i32,
     }
 
-    #[derive(Copy, Clone, Debug, Default)]
-    struct C_Test3 {
-        foo: i32,
-    }
-
     #[derive(Copy, Clone, Debug, PartialEq)]
     struct C_Test_NonZeroDefault {
         pub foo: i32,
@@ -33,62 +29,68 @@ mod tests {
     #[derive(Copy, Clone, Default)]
     struct C_ZST {}
 
+    /// Destroys `entity` right away rather than at the end of the next two frames.
+    fn destroy_now(em: &mut Ecs_World, entity: Entity) {
+        em.destroy_entity(entity);
+        em.destroy_pending();
+        em.destroy_pending();
+    }
+
     #[test]
     #[should_panic]
-    fn register_same_component_twice() {
+    fn add_same_component_twice() {
         let mut em = Ecs_World::new();
-        em.register_component::<C_Test>();
-        em.register_component::<C_Test>();
+        let e = em.new_entity();
+        em.add_component(e, C_Test::default());
+        em.add_component(e, C_Test::default());
     }
 
     #[test]
-    fn add_component_default_value() {
+    fn add_component_value() {
         let mut em = Ecs_World::new();
-        em.register_component::<C_Test_NonZeroDefault>();
 
         let e = em.new_entity();
-        let test = em.add_component::<C_Test_NonZeroDefault>(e);
-        assert_eq!(*test, C_Test_NonZeroDefault::default());
+        em.add_component(e, C_Test_NonZeroDefault::default());
+        assert_eq!(
+            *em.get_component::<C_Test_NonZeroDefault>(e).unwrap(),
+            C_Test_NonZeroDefault::default()
+        );
     }
 
     #[test]
     fn add_component_modify() {
         let mut em = Ecs_World::new();
-        em.register_component::<C_Test>();
 
         let e = em.new_entity();
-        let foo = em.add_component::<C_Test>(e);
-        assert_eq!(foo.foo, 0);
+        em.add_component(e, C_Test::default());
+        assert_eq!(em.get_component::<C_Test>(e).unwrap().foo, 0);
 
-        foo.foo = 42;
-        let foo = em.get_component::<C_Test>(e).unwrap();
-        assert_eq!(foo.foo, 42);
+        em.get_component_mut::<C_Test>(e).unwrap().foo = 42;
+        assert_eq!(em.get_component::<C_Test>(e).unwrap().foo, 42);
     }
 
     #[test]
     fn get_component() {
         let mut em = Ecs_World::new();
-        em.register_component::<C_Test>();
 
         let e = em.new_entity();
         assert!(em.get_component::<C_Test>(e).is_none());
 
-        em.add_component::<C_Test>(e);
+        em.add_component(e, C_Test::default());
         assert!(em.get_component::<C_Test>(e).is_some());
     }
 
     #[test]
     fn get_component_zero_sized() {
         let mut em = Ecs_World::new();
-        em.register_component::<C_ZST>();
 
         let e = em.new_entity();
         assert!(em.get_component::<C_ZST>(e).is_none());
 
         let e2 = em.new_entity();
 
-        em.add_component::<C_ZST>(e);
-        em.add_component::<C_ZST>(e2);
+        em.add_component(e, C_ZST {});
+        em.add_component(e2, C_ZST {});
         assert!(em.get_component::<C_ZST>(e).is_some());
         assert!(em.get_component::<C_ZST>(e2).is_some());
 
@@ -103,50 +105,32 @@ mod tests {
     #[test]
     fn get_component_mut() {
         let mut em = Ecs_World::new();
-        em.register_component::<C_Test>();
 
         let e = em.new_entity();
         assert!(em.get_component_mut::<C_Test>(e).is_none());
 
-        em.add_component::<C_Test>(e);
+        em.add_component(e, C_Test::default());
         assert!(em.get_component_mut::<C_Test>(e).is_some());
     }
 
-    #[test]
-    fn mutate_component() {
-        let mut em = Ecs_World::new();
-        em.register_component::<C_Test>();
-
-        let e = em.new_entity();
-        {
-            let mut c = em.add_component::<C_Test>(e);
-            c.foo = 4242;
-        }
-
-        assert!(em.get_component::<C_Test>(e).unwrap().foo == 4242);
-    }
-
     #[test]
     #[should_panic]
     fn add_component_inexisting_entity() {
         let mut em = Ecs_World::new();
-        em.register_component::<C_Test>();
-        em.add_component::<C_Test>(Entity { index: 0, gen: 1 });
+        em.add_component(Entity { index: 0, gen: 1 }, C_Test::default());
     }
 
     #[test]
     #[should_panic]
     fn get_component_inexisting_entity() {
-        let mut em = Ecs_World::new();
-        em.register_component::<C_Test>();
+        let em = Ecs_World::new();
         em.get_component::<C_Test>(Entity { index: 0, gen: 1 });
     }
 
     #[test]
     #[should_panic]
     fn get_component_mut_inexisting_entity() {
-        let mut em = Ecs_World::new();
-        em.register_component::<C_Test>();
+        let em = Ecs_World::new();
         em.get_component_mut::<C_Test>(Entity { index: 0, gen: 1 });
     }
 
@@ -154,20 +138,28 @@ mod tests {
     fn destroy_entity() {
         let mut em = Ecs_World::new();
         let e = em.new_entity();
+
         em.destroy_entity(e);
+        assert!(em.is_valid_entity(e));
+        em.destroy_pending();
+        assert!(!em.is_valid_entity(e));
+        em.destroy_pending();
         assert!(!em.is_valid_entity(e));
+        assert!(em.entities().is_empty());
     }
 
     #[test]
+    #[cfg(debug_assertions)]
     #[should_panic]
     fn double_free_entity() {
         let mut em = Ecs_World::new();
         let e = em.new_entity();
-        em.destroy_entity(e);
+        destroy_now(&mut em, e);
         em.destroy_entity(e);
     }
 
     #[test]
+    #[cfg(debug_assertions)]
     #[should_panic]
     fn destroy_inexisting_entity() {
         let mut em = Ecs_World::new();
@@ -178,20 +170,18 @@ mod tests {
     #[should_panic]
     fn add_component_destroyed_entity() {
         let mut em = Ecs_World::new();
-        em.register_component::<C_Test>();
         let e = em.new_entity();
-        em.destroy_entity(e);
-        em.add_component::<C_Test>(e);
+        destroy_now(&mut em, e);
+        em.add_component(e, C_Test::default());
     }
 
     #[test]
     #[should_panic]
     fn get_component_destroyed_entity() {
         let mut em = Ecs_World::new();
-        em.register_component::<C_Test>();
         let e = em.new_entity();
-        em.add_component::<C_Test>(e);
-        em.destroy_entity(e);
+        em.add_component(e, C_Test::default());
+        destroy_now(&mut em, e);
         em.get_component::<C_Test>(e);
     }
 
@@ -199,10 +189,9 @@ mod tests {
     #[should_panic]
     fn get_component_destroyed_and_recreated_entity() {
         let mut em = Ecs_World::new();
-        em.register_component::<C_Test>();
         let e = em.new_entity();
-        em.add_component::<C_Test>(e);
-        em.destroy_entity(e);
+        em.add_component(e, C_Test::default());
+        destroy_now(&mut em, e);
         em.new_entity();
         em.get_component::<C_Test>(e);
     }
@@ -210,31 +199,30 @@ mod tests {
     #[test]
     fn get_component_destroyed_and_recreated_entity_good() {
         let mut em = Ecs_World::new();
-        em.register_component::<C_Test>();
 
         let e1 = em.new_entity();
-        em.add_component::<C_Test>(e1);
-        em.destroy_entity(e1);
+        em.add_component(e1, C_Test::default());
+        destroy_now(&mut em, e1);
 
         let e2 = em.new_entity();
-        em.get_component::<C_Test>(e2);
+        assert_eq!(e2.index, e1.index);
+        assert!(em.get_component::<C_Test>(e2).is_none());
     }
 
     #[test]
     fn remove_component() {
         let mut em = Ecs_World::new();
-        em.register_component::<C_Test>();
         let e = em.new_entity();
-        em.add_component::<C_Test>(e);
+        em.add_component(e, C_Test::default());
         em.remove_component::<C_Test>(e);
+        assert!(!em.has_component::<C_Test>(e));
     }
 
     #[test]
     fn double_remove_component() {
         let mut em = Ecs_World::new();
-        em.register_component::<C_Test>();
         let e = em.new_entity();
-        em.add_component::<C_Test>(e);
+        em.add_component(e, C_Test::default());
         em.remove_component::<C_Test>(e);
         em.remove_component::<C_Test>(e);
     }
@@ -242,9 +230,8 @@ mod tests {
     #[test]
     fn get_removed_component() {
         let mut em = Ecs_World::new();
-        em.register_component::<C_Test>();
         let e = em.new_entity();
-        em.add_component::<C_Test>(e);
+        em.add_component(e, C_Test::default());
         em.remove_component::<C_Test>(e);
         assert!(em.get_component::<C_Test>(e).is_none());
     }
@@ -252,128 +239,90 @@ mod tests {
     #[test]
     fn remove_and_readd_component() {
         let mut em = Ecs_World::new();
-        em.register_component::<C_Test>();
         let e = em.new_entity();
-        em.add_component::<C_Test>(e);
+        em.add_component(e, C_Test::default());
         em.remove_component::<C_Test>(e);
-        em.add_component::<C_Test>(e);
-        em.get_component::<C_Test>(e);
+        em.add_component(e, C_Test { foo: 3 });
+        assert_eq!(em.get_component::<C_Test>(e).unwrap().foo, 3);
     }
 
     #[test]
     #[should_panic]
     fn remove_component_destroyed_and_recreated_entity() {
         let mut em = Ecs_World::new();
-        em.register_component::<C_Test>();
         let e = em.new_entity();
-        em.add_component::<C_Test>(e);
-        em.destroy_entity(e);
+        em.add_component(e, C_Test::default());
+        destroy_now(&mut em, e);
         em.new_entity();
         em.remove_component::<C_Test>(e);
     }
 
     #[test]
-    fn get_components_size() {
+    fn removed_components_keep_storage_packed() {
         let mut em = Ecs_World::new();
-        em.register_component::<C_Test>();
-        for _i in 0..10 {
-            let e = em.new_entity();
-            em.add_component::<C_Test>(e);
-        }
-        assert_eq!(em.get_components::<C_Test>().len(), 10);
-    }
+        let entities: Vec<Entity> = (0..10)
+            .map(|i| {
+                let e = em.new_entity();
+                em.add_component(e, C_Test { foo: i });
+                e
+            })
+            .collect();
 
-    #[test]
-    fn get_components_size_zst() {
-        let mut em = Ecs_World::new();
-        em.register_component::<C_ZST>();
-        for _i in 0..10 {
-            let e = em.new_entity();
-            em.add_component::<C_ZST>(e);
-        }
-        // get_components on a ZST component should always be zero-length
-        assert_eq!(em.get_components::<C_ZST>().len(), 0);
-    }
+        em.remove_component::<C_Test>(entities[2]);
+        em.remove_component::<C_Test>(entities[7]);
 
-    #[test]
-    fn get_components_size_empty() {
-        let mut em = Ecs_World::new();
-        em.register_component::<C_Test>();
-        assert_eq!(em.get_components::<C_Test>().len(), 0);
+        let storage = em.get_component_storage::<C_Test>().unwrap();
+        storage.with_all_components(|owners, comps| {
+            assert_eq!(owners.len(), 8);
+            assert_eq!(comps.len(), 8);
+            for (owner, comp) in owners.iter().zip(comps) {
+                let i = entities.iter().position(|e| e == owner).unwrap();
+                assert_eq!(comp.foo, i as i32);
+            }
+        });
     }
 
     #[test]
-    #[should_panic]
-    fn get_unregistered_components() {
+    fn read_unregistered_storage() {
         let em = Ecs_World::new();
-        em.get_components::<C_Test>();
+        assert!(em.read_component_storage::<C_Test>().is_none());
+        assert!(em.write_component_storage::<C_Test>().is_none());
     }
 
     #[test]
-    fn get_components_mut_mutability() {
+    fn write_storage_mutability() {
         let mut em = Ecs_World::new();
-        em.register_component::<C_Test>();
-        for _i in 0..10 {
-            let e = em.new_entity();
-            em.add_component::<C_Test>(e);
-        }
-        for (i, test) in em.get_components_mut::<C_Test>().iter_mut().enumerate() {
-            test.foo = i as i32;
-        }
-        for (i, test) in em.get_components::<C_Test>().iter().enumerate() {
-            assert_eq!(test.foo, i as i32);
-        }
-    }
+        let entities: Vec<Entity> = (0..10)
+            .map(|_| {
+                let e = em.new_entity();
+                em.add_component(e, C_Test::default());
+                e
+            })
+            .collect();
 
-    #[test]
-    fn get_components_mut_size() {
-        let mut em = Ecs_World::new();
-        em.register_component::<C_Test>();
-        for _i in 0..10 {
-            let e = em.new_entity();
-            em.add_component::<C_Test>(e);
+        {
+            let mut storage = em.write_component_storage::<C_Test>().unwrap();
+            for (i, &e) in entities.iter().enumerate() {
+                storage.must_get_mut(e).foo = i as i32;
+            }
         }
-        assert_eq!(em.get_components_mut::<C_Test>().len(), 10);
-    }
 
-    #[test]
-    fn get_components_mut_size_zst() {
-        let mut em = Ecs_World::new();
-        em.register_component::<C_ZST>();
-        for _i in 0..10 {
-            let e = em.new_entity();
-            em.add_component::<C_ZST>(e);
+        let storage = em.read_component_storage::<C_Test>().unwrap();
+        for (i, &e) in entities.iter().enumerate() {
+            assert_eq!(storage.get(e).unwrap().foo, i as i32);
         }
-        // get_components on a ZST component should always be zero-length
-        assert_eq!(em.get_components_mut::<C_ZST>().len(), 0);
-    }
-
-    #[test]
-    fn get_components_mut_size_empty() {
-        let mut em = Ecs_World::new();
-        em.register_component::<C_Test>();
-        assert_eq!(em.get_components_mut::<C_Test>().len(), 0);
-    }
-
-    #[test]
-    #[should_panic]
-    fn get_unregistered_components_mut() {
-        let mut em = Ecs_World::new();
-        em.get_components_mut::<C_Test>();
     }
 
     #[test]
     fn has_get_consistency() {
         let mut em = Ecs_World::new();
         let mut entities: Vec<Entity> = vec![];
-        em.register_component::<C_Test>();
-        em.register_component::<C_Test2>();
         for i in 0..100 {
             let e = em.new_entity();
             entities.push(e);
-            em.add_component::<C_Test>(e);
+            em.add_component(e, C_Test::default());
             if i % 2 == 0 {
-                em.add_component::<C_Test2>(e);
+                em.add_component(e, C_Test2::default());
             }
         }
 
@@ -383,27 +332,19 @@ mod tests {
                 .filter(|&&e| em.has_component::<C_Test>(e) && em.has_component::<C_Test2>(e))
                 .cloned()
                 .collect();
+            assert_eq!(filtered.len(), 50);
             for e in filtered {
                 assert!(em.get_component::<C_Test>(e).is_some());
                 assert!(em.get_component::<C_Test2>(e).is_some());
             }
         }
-        {
-            let filtered: Vec<Entity> = entities
-                .iter()
-                .filter(|&&e| em.has_component::<C_Test>(e))
-                .cloned()
-                .collect();
-            for e in filtered {
-                assert!(em.get_component::<C_Test>(e).is_some());
-            }
-        }
         {
             let filtered: Vec<Entity> = entities
                 .iter()
                 .filter(|&&e| em.has_component::<C_Test>(e) && !em.has_component::<C_Test2>(e))
                 .cloned()
                 .collect();
+            assert_eq!(filtered.len(), 50);
             for e in filtered {
                 assert!(em.get_component::<C_Test>(e).is_some());
                 assert!(em.get_component::<C_Test2>(e).is_none());
@@ -412,62 +353,37 @@ mod tests {
     }
 
     #[test]
-    fn components_map() {
-        let mut em = Ecs_World::new();
-        let mut entities: Vec<Entity> = vec![];
-        em.register_component::<C_Test>();
-
-        for _ in 0..10 {
-            let e = em.new_entity();
-            em.add_component::<C_Test>(e);
-            entities.push(e);
-        }
-
-        let map = em.get_components_map::<C_Test>();
-
-        for e in entities {
-            assert!(map.get_component(e).is_some());
-            assert_eq!(map.get_component(e).unwrap().foo, 0);
-        }
-    }
-
-    #[test]
-    fn components_map_unsafe() {
+    fn destroyed_entities_leave_their_archetype() {
         let mut em = Ecs_World::new();
-        let mut entities: Vec<Entity> = vec![];
-        em.register_component::<C_Test>();
-
-        for _ in 0..10 {
-            let e = em.new_entity();
-            em.add_component::<C_Test>(e);
-            entities.push(e);
+        let e1 = em.new_entity();
+        let e2 = em.new_entity();
+        for &e in &[e1, e2] {
+            em.add_component(e, C_Test::default());
+            em.add_component(e, C_Test2::default());
         }
 
-        let mut map = unsafe { em.get_components_map_unsafe::<C_Test>() };
-
-        for (i, e) in entities.iter().enumerate() {
-            let e = *e;
-            unsafe {
-                assert!(map.get_component(e).is_some());
-                assert!(map.get_component_mut(e).is_some());
-                assert_eq!(map.get_component(e).unwrap().foo, 0);
+        destroy_now(&mut em, e1);
+        let e3 = em.new_entity();
+        assert_eq!(e3.index, e1.index);
+        em.add_component(e3, C_Test::default());
 
-                map.get_component_mut(e).unwrap().foo = i as i32;
-            }
-        }
+        let query = Ecs_Query::new(&em).read::<C_Test>().read::<C_Test2>();
+        assert_eq!(query.entities(), &[e2]);
 
-        for (i, e) in entities.iter().enumerate() {
-            assert_eq!(unsafe { map.get_component(*e) }.unwrap().foo, i as i32);
-        }
+        let query = Ecs_Query::new(&em).read::<C_Test>();
+        let mut entities = query.entities().to_vec();
+        entities.sort_by_key(|e| e.index);
+        assert_eq!(entities, vec![e3, e2]);
     }
 
     #[test]
-    #[should_panic(
-        expected = "[ FATAL ] get_components_map_mut cannot be used on Zero Sized Types components!"
-    )]
-    fn components_map_zst() {
+    fn entities_without_components_match_only_empty_queries() {
         let mut em = Ecs_World::new();
-        em.register_component::<C_ZST>();
-        let _map = em.get_components_map::<C_ZST>();
+        let e = em.new_entity();
+        em.add_component(e, C_ZST {});
+        em.remove_component::<C_ZST>(e);
+
+        let query = Ecs_Query::new(&em).read::<C_ZST>();
+        assert!(query.entities().is_empty());
     }
 }
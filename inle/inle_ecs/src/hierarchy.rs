@@ -0,0 +1,172 @@
+use crate::ecs_world::Entity;
+use std::collections::HashMap;
+
+/// Keeps track of the parent/child relationships between entities.
+/// Each entity has at most one parent and any number of children.
+#[derive(Default)]
+pub struct Entity_Hierarchy {
+    parents: HashMap<Entity, Entity>,
+    children: HashMap<Entity, Vec<Entity>>,
+}
+
+impl Entity_Hierarchy {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.parents.is_empty()
+    }
+
+    #[inline]
+    pub fn parent(&self, entity: Entity) -> Option<Entity> {
+        self.parents.get(&entity).copied()
+    }
+
+    #[inline]
+    pub fn children(&self, entity: Entity) -> &[Entity] {
+        self.children.get(&entity).map_or(&[], |c| c.as_slice())
+    }
+
+    /// Returns all entities that have children but no parent.
+    pub fn roots(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.children
+            .keys()
+            .filter(move |e| !self.parents.contains_key(e))
+            .copied()
+    }
+
+    /// Returns all entities that have children, along with their children.
+    pub fn parents_with_children(&self) -> impl Iterator<Item = (Entity, &[Entity])> + '_ {
+        self.children
+            .iter()
+            .map(|(parent, children)| (*parent, children.as_slice()))
+    }
+
+    /// Returns true if `ancestor` is `entity` or one of its ancestors.
+    pub fn is_ancestor_of(&self, ancestor: Entity, entity: Entity) -> bool {
+        let mut cur = Some(entity);
+        while let Some(e) = cur {
+            if e == ancestor {
+                return true;
+            }
+            cur = self.parent(e);
+        }
+        false
+    }
+
+    /// Sets the parent of `child`, detaching it from its previous parent (if any).
+    /// Passing None makes `child` a root.
+    pub fn set_parent(&mut self, child: Entity, parent: Option<Entity>) {
+        if let Some(parent) = parent {
+            if self.is_ancestor_of(child, parent) {
+                fatal!(
+                    "Tried to make {:?} a child of {:?}, which would create a cycle!",
+                    child,
+                    parent
+                );
+            }
+        }
+
+        self.detach(child);
+
+        if let Some(parent) = parent {
+            self.parents.insert(child, parent);
+            self.children.entry(parent).or_default().push(child);
+        }
+    }
+
+    /// Appends to `out` all descendants of `entity` (excluding itself), parents before children.
+    pub fn get_descendants(&self, entity: Entity, out: &mut Vec<Entity>) {
+        let first = out.len();
+        out.extend_from_slice(self.children(entity));
+        let mut i = first;
+        while i < out.len() {
+            let e = out[i];
+            out.extend_from_slice(self.children(e));
+            i += 1;
+        }
+    }
+
+    /// Removes all the relationships involving `entity`. Its children become roots.
+    pub fn remove(&mut self, entity: Entity) {
+        self.detach(entity);
+        if let Some(children) = self.children.remove(&entity) {
+            for child in children {
+                self.parents.remove(&child);
+            }
+        }
+    }
+
+    fn detach(&mut self, child: Entity) {
+        if let Some(old_parent) = self.parents.remove(&child) {
+            if let Some(siblings) = self.children.get_mut(&old_parent) {
+                siblings.retain(|&e| e != child);
+                if siblings.is_empty() {
+                    self.children.remove(&old_parent);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs_world::Ecs_World;
+
+    #[test]
+    fn hierarchy_set_parent() {
+        let mut world = Ecs_World::new();
+        let a = world.new_entity();
+        let b = world.new_entity();
+        let c = world.new_entity();
+
+        let mut hierarchy = Entity_Hierarchy::default();
+        hierarchy.set_parent(b, Some(a));
+        hierarchy.set_parent(c, Some(b));
+        assert_eq!(hierarchy.parent(c), Some(b));
+        assert_eq!(hierarchy.children(a), &[b]);
+        assert_eq!(hierarchy.roots().collect::<Vec<_>>(), vec![a]);
+
+        let mut descendants = vec![];
+        hierarchy.get_descendants(a, &mut descendants);
+        assert_eq!(descendants, vec![b, c]);
+
+        hierarchy.set_parent(c, Some(a));
+        assert!(hierarchy.children(b).is_empty());
+        assert_eq!(hierarchy.children(a), &[b, c]);
+
+        hierarchy.remove(a);
+        assert!(hierarchy.is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn hierarchy_no_cycles() {
+        let mut world = Ecs_World::new();
+        let a = world.new_entity();
+        let b = world.new_entity();
+
+        let mut hierarchy = Entity_Hierarchy::default();
+        hierarchy.set_parent(b, Some(a));
+        hierarchy.set_parent(a, Some(b));
+    }
+
+    #[test]
+    fn destroy_entity_destroys_children() {
+        let mut world = Ecs_World::new();
+        let a = world.new_entity();
+        let b = world.new_entity();
+        let c = world.new_entity();
+        let d = world.new_entity();
+        world.set_parent(b, Some(a));
+        world.set_parent(c, Some(b));
+
+        world.destroy_entity(a);
+        world.destroy_pending();
+        let mut destroyed = world.destroy_pending();
+        destroyed.sort_by_key(|e| e.index);
+
+        assert_eq!(destroyed, vec![a, b, c]);
+        assert!(world.is_valid_entity(d));
+        assert!(world.hierarchy().is_empty());
+    }
+}
@@ -10,6 +10,9 @@ extern crate inle_common;
 #[macro_use]
 extern crate inle_diagnostics;
 
+#[macro_use]
+extern crate inle_math;
+
 #[macro_use]
 pub mod prelude;
 
@@ -18,5 +21,8 @@ mod comp_mgr;
 pub mod components;
 pub mod ecs_query;
 pub mod ecs_world;
+pub mod hierarchy;
+pub mod prefab;
+pub mod snapshot;
 
 pub use prelude::*;
@@ -0,0 +1,568 @@
+use crate::components::base::C_Spatial2D;
+use crate::ecs_world::{Ecs_World, Entity};
+use inle_common::stringid::String_Id;
+use inle_common::variant::Variant;
+use inle_math::transform::Transform2D;
+use inle_math::vector::Vec2f;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+#[cfg(debug_assertions)]
+use std::sync::mpsc::{self, Receiver, Sender};
+
+pub const PREFAB_FILE_EXTENSION: &str = "prefab";
+
+const HEADER_SEPARATOR: char = '/';
+const COMMENT_START: char = '#';
+
+/// A Prefab is a template for an entity, loaded from a text file with the same syntax as the cfg files:
+///
+/// ```text
+/// # comment
+/// /component_name
+/// field value
+/// other_field other value
+/// ```
+///
+/// Each /section describes a component. The same component may appear more than once
+/// (e.g. an entity with multiple colliders): how it's interpreted is up to the component loader.
+#[derive(Debug, Clone, Default)]
+pub struct Prefab {
+    pub components: Vec<Prefab_Component>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Prefab_Component {
+    pub name: String,
+    pub fields: Vec<(String, Variant)>,
+    /// The line where this component's header is, for error reporting.
+    pub line: usize,
+}
+
+#[derive(Debug)]
+pub struct Prefab_Error {
+    msg: Cow<'static, str>,
+}
+
+impl Prefab_Error {
+    pub fn new<T: Into<Cow<'static, str>>>(msg: T) -> Self {
+        Self { msg: msg.into() }
+    }
+}
+
+impl Display for Prefab_Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+impl std::error::Error for Prefab_Error {}
+
+impl Prefab_Component {
+    /// Creates a component with no fields, e.g. to build prefab overrides from code.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: String::from(name),
+            fields: vec![],
+            line: 0,
+        }
+    }
+
+    pub fn with_field<T: Into<Variant>>(mut self, key: &str, value: T) -> Self {
+        self.fields.push((String::from(key), value.into()));
+        self
+    }
+
+    pub fn get_raw(&self, key: &str) -> Option<&Variant> {
+        self.fields.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    pub fn get<T: TryFrom<Variant>>(&self, key: &str) -> Result<T, Prefab_Error> {
+        let raw = self.get_raw(key).ok_or_else(|| {
+            Prefab_Error::new(format!(
+                "component {} (line {}) is missing field {}",
+                self.name, self.line, key
+            ))
+        })?;
+        T::try_from(raw.clone()).map_err(|_| {
+            Prefab_Error::new(format!(
+                "field {} of component {} (line {}) has an invalid type ({:?})",
+                key, self.name, self.line, raw
+            ))
+        })
+    }
+
+    pub fn get_or<T: TryFrom<Variant>>(&self, key: &str, default: T) -> Result<T, Prefab_Error> {
+        if self.get_raw(key).is_some() {
+            self.get(key)
+        } else {
+            Ok(default)
+        }
+    }
+
+    /// Like get_or, but also accepts integer values.
+    pub fn get_f32_or(&self, key: &str, default: f32) -> Result<f32, Prefab_Error> {
+        match self.get_raw(key) {
+            None => Ok(default),
+            Some(Variant::Int(i)) => Ok(*i as f32),
+            Some(Variant::UInt(u)) => Ok(*u as f32),
+            Some(_) => self.get(key),
+        }
+    }
+
+    /// Parses a field of the form "x y".
+    pub fn get_vec2f_or(&self, key: &str, default: Vec2f) -> Result<Vec2f, Prefab_Error> {
+        let raw = match self.get_raw(key) {
+            None => return Ok(default),
+            Some(Variant::String(s)) => s,
+            Some(other) => {
+                return Err(Prefab_Error::new(format!(
+                    "field {} of component {} (line {}) should be a 2D vector (got {:?})",
+                    key, self.name, self.line, other
+                )))
+            }
+        };
+        let coords: Vec<f32> = raw
+            .split_whitespace()
+            .filter_map(|tok| tok.parse().ok())
+            .collect();
+        if coords.len() != 2 {
+            return Err(Prefab_Error::new(format!(
+                "field {} of component {} (line {}) should be a 2D vector (got {})",
+                key, self.name, self.line, raw
+            )));
+        }
+        Ok(v2!(coords[0], coords[1]))
+    }
+}
+
+pub fn load_prefab_from_file(path: &Path) -> Result<Prefab, Prefab_Error> {
+    let file = File::open(path)
+        .map_err(|err| Prefab_Error::new(format!("Error opening {}: {}", path.display(), err)))?;
+    let lines = BufReader::new(file).lines().map_while(Result::ok);
+    parse_prefab_lines(lines)
+        .map_err(|err| Prefab_Error::new(format!("{}: {}", path.display(), err)))
+}
+
+fn parse_prefab_lines(lines: impl Iterator<Item = String>) -> Result<Prefab, Prefab_Error> {
+    let mut prefab = Prefab::default();
+
+    for (lineno, mut line) in lines.enumerate() {
+        let lineno = lineno + 1;
+        if let Some(comment_start) = line.find(COMMENT_START) {
+            line.truncate(comment_start);
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix(HEADER_SEPARATOR) {
+            prefab.components.push(Prefab_Component {
+                name: String::from(header.trim()),
+                fields: vec![],
+                line: lineno,
+            });
+        } else if let Some(cur_comp) = prefab.components.last_mut() {
+            let mut tokens = line.splitn(2, ' ');
+            let key = tokens.next().unwrap();
+            let val = tokens.next().unwrap_or("").trim_start();
+            cur_comp
+                .fields
+                .push((String::from(key), Variant::from(val)));
+        } else {
+            return Err(Prefab_Error::new(format!(
+                "line {}: field found outside of any component",
+                lineno
+            )));
+        }
+    }
+
+    Ok(prefab)
+}
+
+fn apply_overrides(comp: &Prefab_Component, overrides: &[Prefab_Component]) -> Prefab_Component {
+    let mut result = comp.clone();
+    for ovr in overrides.iter().filter(|ovr| ovr.name == comp.name) {
+        for (key, val) in &ovr.fields {
+            if let Some(field) = result.fields.iter_mut().find(|(k, _)| k == key) {
+                field.1 = val.clone();
+            } else {
+                result.fields.push((key.clone(), val.clone()));
+            }
+        }
+    }
+    result
+}
+
+/// Implemented by whoever knows how to turn Prefab_Components into actual components
+/// (since inle_ecs doesn't know about most component types).
+pub trait Prefab_Component_Loader {
+    fn load_component(
+        &mut self,
+        world: &mut Ecs_World,
+        entity: Entity,
+        transform: &Transform2D,
+        component: &Prefab_Component,
+    ) -> Result<(), Prefab_Error>;
+
+    /// Called when instantiating a prefab fails after some of its components were loaded:
+    /// should undo whatever load_component did outside of `world` (e.g. creating physics bodies),
+    /// since the entity is going to be destroyed.
+    fn unload_components(&mut self, world: &mut Ecs_World, entity: Entity);
+}
+
+pub struct Prefab_Registry {
+    prefabs: HashMap<String_Id, Prefab>,
+
+    #[cfg(debug_assertions)]
+    change_rx: Receiver<(String_Id, Prefab)>,
+    #[cfg(debug_assertions)]
+    change_tx: Option<Sender<(String_Id, Prefab)>>,
+}
+
+impl Default for Prefab_Registry {
+    fn default() -> Self {
+        #[cfg(debug_assertions)]
+        let (change_tx, change_rx) = mpsc::channel();
+
+        Self {
+            prefabs: HashMap::default(),
+            #[cfg(debug_assertions)]
+            change_rx,
+            #[cfg(debug_assertions)]
+            change_tx: Some(change_tx),
+        }
+    }
+}
+
+/// The prefab name is its file name without the extension.
+fn prefab_name_from_path(path: &Path) -> Option<String_Id> {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .map(String_Id::from)
+}
+
+fn is_prefab_file(path: &Path) -> bool {
+    path.extension() == Some(std::ffi::OsStr::new(PREFAB_FILE_EXTENSION))
+}
+
+impl Prefab_Registry {
+    /// Loads all prefabs in `directory`. Prefabs that fail to load are skipped.
+    pub fn load_from_dir(&mut self, directory: &Path) {
+        let dir = match std::fs::read_dir(directory) {
+            Ok(dir) => dir,
+            Err(err) => {
+                lerr!(
+                    "Failed to read prefabs from directory {}: {}",
+                    directory.display(),
+                    err
+                );
+                return;
+            }
+        };
+
+        let mut n_loaded = 0;
+        for entry in dir.flatten().filter(|e| is_prefab_file(&e.path())) {
+            let path = entry.path();
+            match (prefab_name_from_path(&path), load_prefab_from_file(&path)) {
+                (Some(name), Ok(prefab)) => {
+                    self.prefabs.insert(name, prefab);
+                    n_loaded += 1;
+                }
+                (_, Err(err)) => lerr!("Error loading prefab: {}", err),
+                (None, _) => lerr!("Invalid prefab file name {}", path.display()),
+            }
+        }
+
+        lok!("Loaded {} prefabs from {}", n_loaded, directory.display());
+    }
+
+    pub fn add_prefab(&mut self, name: String_Id, prefab: Prefab) {
+        self.prefabs.insert(name, prefab);
+    }
+
+    pub fn get_prefab(&self, name: String_Id) -> Option<&Prefab> {
+        self.prefabs.get(&name)
+    }
+
+    /// Creates a new entity in `world` with a C_Spatial2D at `transform` plus all the components
+    /// described by prefab `name`. If any component fails to load, the entity is destroyed.
+    pub fn instantiate<L: Prefab_Component_Loader>(
+        &self,
+        name: String_Id,
+        world: &mut Ecs_World,
+        transform: &Transform2D,
+        loader: &mut L,
+    ) -> Result<Entity, Prefab_Error> {
+        self.instantiate_with_overrides(name, world, transform, &[], loader)
+    }
+
+    /// Like instantiate(), but the fields of each component in `overrides` replace (or are added to)
+    /// the fields of the prefab's components with the same name.
+    /// Overriding a component that the prefab doesn't have is an error.
+    pub fn instantiate_with_overrides<L: Prefab_Component_Loader>(
+        &self,
+        name: String_Id,
+        world: &mut Ecs_World,
+        transform: &Transform2D,
+        overrides: &[Prefab_Component],
+        loader: &mut L,
+    ) -> Result<Entity, Prefab_Error> {
+        trace!("prefab::instantiate");
+
+        let prefab = self
+            .prefabs
+            .get(&name)
+            .ok_or_else(|| Prefab_Error::new(format!("Unknown prefab {}", name)))?;
+
+        if let Some(ovr) = overrides
+            .iter()
+            .find(|ovr| !prefab.components.iter().any(|c| c.name == ovr.name))
+        {
+            return Err(Prefab_Error::new(format!(
+                "Prefab {} has no component {} to override (line {})",
+                name, ovr.name, ovr.line
+            )));
+        }
+
+        let entity = world.new_entity();
+        world.add_component(
+            entity,
+            C_Spatial2D {
+                transform: *transform,
+                ..Default::default()
+            },
+        );
+
+        for comp in &prefab.components {
+            let overridden;
+            let comp = if overrides.iter().any(|ovr| ovr.name == comp.name) {
+                overridden = apply_overrides(comp, overrides);
+                &overridden
+            } else {
+                comp
+            };
+            if let Err(err) = loader.load_component(world, entity, transform, comp) {
+                loader.unload_components(world, entity);
+                world.destroy_entity(entity);
+                return Err(Prefab_Error::new(format!(
+                    "Failed to instantiate prefab {}: {}",
+                    name, err
+                )));
+            }
+        }
+
+        Ok(entity)
+    }
+}
+
+#[cfg(debug_assertions)]
+impl Prefab_Registry {
+    pub fn get_change_interface(&mut self) -> Sender<(String_Id, Prefab)> {
+        self.change_tx
+            .take()
+            .expect("[ ERROR ] Called get_change_interface twice!")
+    }
+
+    /// Applies the changes to the prefab files that were detected by the Prefab_Watch_Handler.
+    /// Note that only the entities instantiated after this call are affected.
+    pub fn update(&mut self) {
+        let changes = self.change_rx.try_iter().collect::<Vec<_>>();
+        for (name, prefab) in changes {
+            linfo!("Reloaded prefab {}", name);
+            self.prefabs.insert(name, prefab);
+        }
+    }
+}
+
+#[cfg(debug_assertions)]
+pub struct Prefab_Watch_Handler {
+    prefab_change: Sender<(String_Id, Prefab)>,
+}
+
+#[cfg(debug_assertions)]
+impl Prefab_Watch_Handler {
+    pub fn new(registry: &mut Prefab_Registry) -> Self {
+        Self {
+            prefab_change: registry.get_change_interface(),
+        }
+    }
+}
+
+#[cfg(debug_assertions)]
+impl inle_fs::file_watcher::File_Watcher_Event_Handler for Prefab_Watch_Handler {
+    fn handle(&mut self, event: &notify::DebouncedEvent) {
+        use notify::DebouncedEvent;
+
+        match event {
+            DebouncedEvent::Write(path) | DebouncedEvent::Create(path)
+                if !inle_fs::utils::is_hidden(path) && is_prefab_file(path) =>
+            {
+                let name = match prefab_name_from_path(path) {
+                    Some(name) => name,
+                    None => return,
+                };
+                match load_prefab_from_file(path) {
+                    Ok(prefab) => self.prefab_change.send((name, prefab)).unwrap(),
+                    Err(err) => lerr!("Error reloading prefab: {}", err),
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_prefab() {
+        let lines = vec![
+            "# A test prefab",
+            "/renderable",
+            "diffuse torch.png # the texture",
+            "z_index -2",
+            "",
+            "/collider",
+            "offset 1.5 -2",
+            "/collider",
+            "radius 10",
+        ]
+        .into_iter()
+        .map(String::from);
+
+        let prefab = parse_prefab_lines(lines).unwrap();
+        assert_eq!(prefab.components.len(), 3);
+
+        let rend = &prefab.components[0];
+        assert_eq!(rend.name, "renderable");
+        assert_eq!(rend.line, 2);
+        assert_eq!(rend.get::<String>("diffuse").unwrap(), "torch.png");
+        assert_eq!(rend.get::<i32>("z_index").unwrap(), -2);
+        assert!(rend.get::<i32>("diffuse").is_err());
+        assert_eq!(rend.get_or("shininess", 1.0_f32).unwrap(), 1.0);
+
+        let cld = &prefab.components[1];
+        assert_eq!(
+            cld.get_vec2f_or("offset", Vec2f::default()).unwrap(),
+            v2!(1.5, -2.)
+        );
+        assert_eq!(prefab.components[2].get_f32_or("radius", 0.).unwrap(), 10.);
+    }
+
+    #[test]
+    fn parse_prefab_field_without_component() {
+        let lines = vec!["foo 2", "/bar"].into_iter().map(String::from);
+        assert!(parse_prefab_lines(lines).is_err());
+    }
+
+    #[test]
+    fn instantiate_prefab() {
+        #[derive(Copy, Clone, Debug, PartialEq)]
+        struct C_Health(i32);
+
+        /// Tracks the entities it loaded components for, like a real loader would do with
+        /// resources living outside the world (e.g. physics bodies).
+        #[derive(Default)]
+        struct Loader {
+            loaded: Vec<Entity>,
+        }
+        impl Prefab_Component_Loader for Loader {
+            fn load_component(
+                &mut self,
+                world: &mut Ecs_World,
+                entity: Entity,
+                _transform: &Transform2D,
+                component: &Prefab_Component,
+            ) -> Result<(), Prefab_Error> {
+                match component.name.as_str() {
+                    "health" => {
+                        world.add_component(entity, C_Health(component.get("hp")?));
+                        self.loaded.push(entity);
+                    }
+                    _ => return Err(Prefab_Error::new("unknown component")),
+                }
+                Ok(())
+            }
+
+            fn unload_components(&mut self, _world: &mut Ecs_World, entity: Entity) {
+                self.loaded.retain(|&e| e != entity);
+            }
+        }
+
+        let mut registry = Prefab_Registry::default();
+        let good = parse_prefab_lines(vec!["/health", "hp 3"].into_iter().map(String::from));
+        let bad = parse_prefab_lines(
+            vec!["/health", "hp 3", "/mana", "mp 3"]
+                .into_iter()
+                .map(String::from),
+        );
+        registry.add_prefab(String_Id::from("good"), good.unwrap());
+        registry.add_prefab(String_Id::from("bad"), bad.unwrap());
+
+        let mut world = Ecs_World::new();
+        let mut loader = Loader::default();
+        let transform = Transform2D::from_pos(v2!(3., 4.));
+        let e = registry
+            .instantiate(String_Id::from("good"), &mut world, &transform, &mut loader)
+            .unwrap();
+        assert_eq!(*world.get_component::<C_Health>(e).unwrap(), C_Health(3));
+        assert_eq!(
+            world.get_component::<C_Spatial2D>(e).unwrap().transform,
+            transform
+        );
+
+        assert!(registry
+            .instantiate(String_Id::from("bad"), &mut world, &transform, &mut loader)
+            .is_err());
+        // The health component loaded before the failure was rolled back.
+        assert_eq!(loader.loaded, vec![e]);
+        assert!(registry
+            .instantiate(String_Id::from("nope"), &mut world, &transform, &mut loader)
+            .is_err());
+
+        let overrides = [Prefab_Component::new("health").with_field("hp", "7")];
+        let e = registry
+            .instantiate_with_overrides(
+                String_Id::from("good"),
+                &mut world,
+                &transform,
+                &overrides,
+                &mut loader,
+            )
+            .unwrap();
+        assert_eq!(*world.get_component::<C_Health>(e).unwrap(), C_Health(7));
+        // The prefab itself is untouched
+        assert_eq!(
+            registry
+                .get_prefab(String_Id::from("good"))
+                .unwrap()
+                .components[0]
+                .get::<i32>("hp")
+                .unwrap(),
+            3
+        );
+
+        let bad_overrides = [Prefab_Component {
+            name: String::from("mana"),
+            fields: vec![],
+            line: 1,
+        }];
+        assert!(registry
+            .instantiate_with_overrides(
+                String_Id::from("good"),
+                &mut world,
+                &transform,
+                &bad_overrides,
+                &mut loader,
+            )
+            .is_err());
+    }
+}
@@ -0,0 +1,316 @@
+use crate::ecs_world::{Ecs_World, Entity};
+use inle_common::hash::fnv1a_64;
+use inle_common::stringid::String_Id;
+use inle_serialize::{Binary_Serializable, Byte_Stream};
+use std::any::{type_name, TypeId};
+use std::collections::HashMap;
+use std::io;
+
+/// Bumped whenever the snapshot layout changes.
+pub(crate) const SNAPSHOT_VERSION: u16 = 1;
+
+type Serialize_Fn = fn(&Ecs_World, &mut Byte_Stream) -> io::Result<()>;
+type Deserialize_Fn = fn(&mut Ecs_World, &mut Byte_Stream) -> io::Result<()>;
+
+struct Comp_Serialization_Entry {
+    name: String_Id,
+    type_id: TypeId,
+    serialize: Serialize_Fn,
+    deserialize: Deserialize_Fn,
+}
+
+/// Knows how to save and load the component types registered in it.
+/// Each type is identified by a name, which must be stable across builds, since it's
+/// what gets written in the snapshot.
+/// Only the registered components are saved in a world snapshot: the other ones are skipped.
+#[derive(Default)]
+pub struct Component_Serialization_Registry {
+    entries: Vec<Comp_Serialization_Entry>,
+    by_name: HashMap<String_Id, usize>,
+}
+
+impl Component_Serialization_Registry {
+    pub fn register<T: Binary_Serializable + 'static>(&mut self, name: String_Id) {
+        if self.by_name.contains_key(&name) {
+            fatal!(
+                "Component name {} registered twice for serialization (second time by {})!",
+                name,
+                type_name::<T>()
+            );
+        }
+
+        self.by_name.insert(name, self.entries.len());
+        self.entries.push(Comp_Serialization_Entry {
+            name,
+            type_id: TypeId::of::<T>(),
+            serialize: serialize_storage::<T>,
+            deserialize: deserialize_storage::<T>,
+        });
+    }
+
+    /// Returns the name and a hash of each registered storage of `world`, in registration order.
+    /// The hash only depends on the serialized content of the storage, so it's stable across runs.
+    /// Fails if `world` contains components that are not registered, as they can't be hashed.
+    pub fn hash_storages(&self, world: &Ecs_World) -> io::Result<Vec<(String_Id, u64)>> {
+        for (type_id, type_name, n_components) in world.component_manager.storages_summary() {
+            if n_components > 0 && !self.entries.iter().any(|entry| entry.type_id == type_id) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "cannot hash the {} components: they're not registered for serialization",
+                        type_name
+                    ),
+                ));
+            }
+        }
+
+        self.entries
+            .iter()
+            .map(|entry| {
+                let mut storage_data = Byte_Stream::new();
+                (entry.serialize)(world, &mut storage_data)?;
+                Ok((entry.name, fnv1a_64(storage_data.as_ref())))
+            })
+            .collect()
+    }
+
+    /// Writes all registered storages of `world` as: n_storages, then for each storage
+    /// its name, its length in bytes and its data.
+    pub(crate) fn serialize_storages(
+        &self,
+        world: &Ecs_World,
+        output: &mut Byte_Stream,
+    ) -> io::Result<()> {
+        output.write_u32(self.entries.len() as u32)?;
+        for entry in &self.entries {
+            let mut storage_data = Byte_Stream::new();
+            (entry.serialize)(world, &mut storage_data)?;
+
+            entry.name.serialize(output)?;
+            output.write_u32(storage_data.len() as u32)?;
+            output.write_bytes(storage_data.as_ref())?;
+        }
+        Ok(())
+    }
+
+    /// Reads back the storages written by serialize_storages(). Storages whose name is not
+    /// registered are skipped with a warning.
+    pub(crate) fn deserialize_storages(
+        &self,
+        world: &mut Ecs_World,
+        input: &mut Byte_Stream,
+    ) -> io::Result<()> {
+        let n_storages = input.read_u32()?;
+        for _ in 0..n_storages {
+            let name = String_Id::deserialize(input)?;
+            let len = input.read_u32()? as u64;
+            let storage_end = input.pos() + len;
+            if storage_end > input.len() as u64 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+
+            if let Some(&idx) = self.by_name.get(&name) {
+                (self.entries[idx].deserialize)(world, input)?;
+                if input.pos() != storage_end {
+                    lerr!(
+                        "Component storage {} has a different size than declared in the snapshot.",
+                        name
+                    );
+                    return Err(io::ErrorKind::InvalidData.into());
+                }
+            } else {
+                lwarn!(
+                    "Skipping component storage {} from snapshot: it's not registered.",
+                    name
+                );
+                input.seek(storage_end);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn serialize_storage<T: Binary_Serializable + 'static>(
+    world: &Ecs_World,
+    output: &mut Byte_Stream,
+) -> io::Result<()> {
+    if let Some(storage) = world.component_manager.get_component_storage::<T>() {
+        storage.with_all_components(|entities, components| {
+            output.write_u32(components.len() as u32)?;
+            for (entity, comp) in entities.iter().zip(components) {
+                entity.serialize(output)?;
+                comp.serialize(output)?;
+            }
+            Ok(())
+        })
+    } else {
+        output.write_u32(0)
+    }
+}
+
+fn deserialize_storage<T: Binary_Serializable + 'static>(
+    world: &mut Ecs_World,
+    input: &mut Byte_Stream,
+) -> io::Result<()> {
+    let n_comps = input.read_u32()?;
+    for _ in 0..n_comps {
+        let entity = Entity::deserialize(input)?;
+        if !world.is_valid_entity(entity) || world.has_component::<T>(entity) {
+            lerr!(
+                "Invalid entity {:?} for component {} in snapshot.",
+                entity,
+                type_name::<T>()
+            );
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        let comp = T::deserialize(input)?;
+        world.add_component(entity, comp);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::base::{C_Local_Transform2D, C_Spatial2D};
+    use inle_math::transform::Transform2D;
+
+    fn create_registry() -> Component_Serialization_Registry {
+        let mut registry = Component_Serialization_Registry::default();
+        registry.register::<C_Spatial2D>(String_Id::from("C_Spatial2D"));
+        registry.register::<C_Local_Transform2D>(String_Id::from("C_Local_Transform2D"));
+        registry
+    }
+
+    #[test]
+    fn snapshot_roundtrip() {
+        let registry = create_registry();
+
+        let mut world = Ecs_World::new();
+        let a = world.new_entity();
+        let dead = world.new_entity();
+        let b = world.new_entity();
+        let c = world.new_entity();
+        world.add_component(
+            a,
+            C_Spatial2D {
+                transform: Transform2D::from_pos(v2!(1., 2.)),
+                velocity: v2!(3., 0.),
+                ..Default::default()
+            },
+        );
+        world.add_component(b, C_Spatial2D::default());
+        world.add_component(
+            b,
+            C_Local_Transform2D {
+                transform: Transform2D::from_pos(v2!(0., 5.)),
+            },
+        );
+        world.set_parent(b, Some(a));
+        world.set_parent(c, Some(a));
+        world.destroy_entity(dead);
+        world.destroy_pending();
+        world.destroy_pending();
+        world.advance_tick();
+
+        let mut bs = Byte_Stream::new();
+        world.serialize_snapshot(&registry, &mut bs).unwrap();
+        bs.seek(0);
+        let mut restored = Ecs_World::deserialize_snapshot(&registry, &mut bs).unwrap();
+
+        assert_eq!(restored.entities(), world.entities());
+        assert!(!restored.is_valid_entity(dead));
+        assert_eq!(restored.cur_tick(), world.cur_tick());
+        assert_eq!(
+            *restored.get_component::<C_Spatial2D>(a).unwrap(),
+            *world.get_component::<C_Spatial2D>(a).unwrap()
+        );
+        assert_eq!(
+            *restored.get_component::<C_Local_Transform2D>(b).unwrap(),
+            *world.get_component::<C_Local_Transform2D>(b).unwrap()
+        );
+        assert!(!restored.has_component::<C_Spatial2D>(c));
+        assert_eq!(restored.get_children(a), &[b, c]);
+        assert_eq!(restored.get_parent(c), Some(a));
+
+        // New entities must be the same in both worlds
+        assert_eq!(restored.new_entity(), world.new_entity());
+    }
+
+    #[test]
+    fn snapshot_skips_unregistered_storages() {
+        let mut world = Ecs_World::new();
+        let e = world.new_entity();
+        world.add_component(e, C_Spatial2D::default());
+        world.add_component(e, C_Local_Transform2D::default());
+
+        let mut bs = Byte_Stream::new();
+        world
+            .serialize_snapshot(&create_registry(), &mut bs)
+            .unwrap();
+        bs.seek(0);
+
+        let mut registry = Component_Serialization_Registry::default();
+        registry.register::<C_Local_Transform2D>(String_Id::from("C_Local_Transform2D"));
+        let restored = Ecs_World::deserialize_snapshot(&registry, &mut bs).unwrap();
+        assert!(!restored.has_component::<C_Spatial2D>(e));
+        assert!(restored.has_component::<C_Local_Transform2D>(e));
+    }
+
+    #[test]
+    fn snapshot_storage_hashes() {
+        let registry = create_registry();
+
+        let mut world = Ecs_World::new();
+        let e = world.new_entity();
+        world.add_component(e, C_Spatial2D::default());
+
+        let mut bs = Byte_Stream::new();
+        world.serialize_snapshot(&registry, &mut bs).unwrap();
+        bs.seek(0);
+        let restored = Ecs_World::deserialize_snapshot(&registry, &mut bs).unwrap();
+
+        let hashes = registry.hash_storages(&world).unwrap();
+        assert_eq!(hashes.len(), 2);
+        assert_eq!(hashes[0].0, String_Id::from("C_Spatial2D"));
+        assert_eq!(hashes, registry.hash_storages(&restored).unwrap());
+
+        world.get_component_mut::<C_Spatial2D>(e).unwrap().velocity = v2!(1., 0.);
+        let new_hashes = registry.hash_storages(&world).unwrap();
+        assert_ne!(new_hashes[0], hashes[0]);
+        assert_eq!(new_hashes[1], hashes[1]);
+    }
+
+    #[test]
+    fn snapshot_storage_hashes_fail_on_unregistered_components() {
+        #[derive(Copy, Clone, Default)]
+        struct C_Unregistered;
+
+        let registry = create_registry();
+        let mut world = Ecs_World::new();
+        let e = world.new_entity();
+        world.add_component(e, C_Spatial2D::default());
+        world.add_component(e, C_Unregistered);
+        assert!(registry.hash_storages(&world).is_err());
+
+        // An empty storage has nothing to hash
+        world.remove_component::<C_Unregistered>(e);
+        assert!(registry.hash_storages(&world).is_ok());
+    }
+
+    #[test]
+    fn snapshot_truncated() {
+        let mut world = Ecs_World::new();
+        let e = world.new_entity();
+        world.add_component(e, C_Spatial2D::default());
+
+        let mut bs = Byte_Stream::new();
+        world
+            .serialize_snapshot(&create_registry(), &mut bs)
+            .unwrap();
+        let mut data = bs.as_ref().to_vec();
+        data.truncate(data.len() - 3);
+        let mut bs = Byte_Stream::new_from_vec(data);
+        assert!(Ecs_World::deserialize_snapshot(&create_registry(), &mut bs).is_err());
+    }
+}
@@ -11,7 +11,11 @@ pub trait Event {
 pub type Event_Callback<T> = Box<dyn FnMut(<T as Event>::Args, Option<&mut Event_Callback_Data>)>;
 pub type Event_Callback_Data = Arc<Mutex<dyn Any>>;
 
-type Observers<T> = Vec<(Event_Callback<T>, Option<Event_Callback_Data>)>;
+/// Note: this is a distinct type for every event (rather than an alias over the callbacks' type)
+/// so that events with the same Args don't share their observers.
+struct Observers<T: Event> {
+    callbacks: Vec<(Event_Callback<T>, Option<Event_Callback_Data>)>,
+}
 
 pub struct Event_Subscription_Handle<T> {
     idx: usize,
@@ -45,10 +49,11 @@ impl Event_Register {
         cb: Event_Callback<E>,
         cb_data: Option<Event_Callback_Data>,
     ) -> Event_Subscription_Handle<E> {
-        let obs = self
+        let obs = &mut self
             .observers
             .entry::<Observers<E>>()
-            .or_insert_with(Vec::default);
+            .or_insert_with(|| Observers { callbacks: vec![] })
+            .callbacks;
         obs.push((cb, cb_data));
         Event_Subscription_Handle {
             idx: obs.len() - 1,
@@ -59,8 +64,8 @@ impl Event_Register {
     pub fn unsubscribe<E: 'static + Event>(&mut self, handle: Event_Subscription_Handle<E>) {
         let idx = handle.idx;
         if let Some(obs) = self.observers.get_mut::<Observers<E>>() {
-            if idx < obs.len() {
-                let _ = obs.remove(idx);
+            if idx < obs.callbacks.len() {
+                let _ = obs.callbacks.remove(idx);
                 return;
             }
         }
@@ -72,7 +77,7 @@ impl Event_Register {
         trace!("Event_Register::raise");
 
         if let Some(obs) = self.observers.get_mut::<Observers<E>>() {
-            for (cb, cb_data) in obs {
+            for (cb, cb_data) in &mut obs.callbacks {
                 cb(args.clone(), cb_data.as_mut());
             }
         }
@@ -86,7 +91,7 @@ impl Event_Register {
         }
 
         if let Some(obs) = self.observers.get_mut::<Observers<E>>() {
-            for (cb, cb_data) in obs {
+            for (cb, cb_data) in &mut obs.callbacks {
                 for args in args_batch {
                     cb((*args).clone(), cb_data.as_mut());
                 }
@@ -226,4 +231,34 @@ mod tests {
 
         assert_eq!(res.lock().unwrap().len(), 5);
     }
+
+    #[test]
+    fn events_with_same_args() {
+        struct Evt_Test_Same_Args;
+
+        impl Event for Evt_Test_Same_Args {
+            type Args = (u32, i32);
+        }
+
+        let mut reg = Event_Register::new();
+        let res = wrap_cb_data(0);
+
+        reg.subscribe::<Evt_Test>(
+            Box::new(|_, res| {
+                with_cb_data(res.unwrap(), |res: &mut i32| *res += 1);
+            }),
+            Some(res.clone()),
+        );
+        reg.subscribe::<Evt_Test_Same_Args>(
+            Box::new(|_, res| {
+                with_cb_data(res.unwrap(), |res: &mut i32| *res += 10);
+            }),
+            Some(res.clone()),
+        );
+
+        reg.raise::<Evt_Test>(&(0, 0));
+
+        let mut res = res;
+        assert_eq!(with_cb_data(&mut res, |res: &mut i32| *res), 1);
+    }
 }
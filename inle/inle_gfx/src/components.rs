@@ -5,6 +5,8 @@ use inle_core::env::Env_Info;
 use inle_math::rect::Rect;
 use inle_math::transform::Transform2D;
 use inle_resources::gfx::{shader_path, tex_path, Gfx_Resources, Shader_Cache};
+use inle_serialize::{Binary_Serializable, Byte_Stream};
+use std::io;
 
 #[derive(Copy, Clone, Debug)]
 pub struct C_Renderable {
@@ -119,3 +121,74 @@ pub struct C_Animated_Sprite {
 pub struct C_Camera2D {
     pub transform: Transform2D,
 }
+
+impl Binary_Serializable for C_Renderable {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.material.serialize(output)?;
+        self.rect.serialize(output)?;
+        self.modulate.serialize(output)?;
+        self.z_index.serialize(output)?;
+        self.sprite_local_transform.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Self {
+            material: Material::deserialize(input)?,
+            rect: Rect::deserialize(input)?,
+            modulate: colors::Color::deserialize(input)?,
+            z_index: render::Z_Index::deserialize(input)?,
+            sprite_local_transform: Transform2D::deserialize(input)?,
+        })
+    }
+}
+
+/// Only the first n_renderables renderables are saved.
+impl Binary_Serializable for C_Multi_Renderable {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        output.write_u8(self.n_renderables)?;
+        for renderable in &self.renderables[..self.n_renderables as usize] {
+            renderable.serialize(output)?;
+        }
+        Ok(())
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        let n_renderables = input.read_u8()?;
+        if n_renderables as usize > Self::MAX_RENDERABLES {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        let mut res = Self::default();
+        for _ in 0..n_renderables {
+            res.add(C_Renderable::deserialize(input)?);
+        }
+        Ok(res)
+    }
+}
+
+impl Binary_Serializable for C_Animated_Sprite {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        output.write_u32(self.n_frames)?;
+        output.write_f32(self.frame_time)?;
+        output.write_f32(self.frame_time_elapsed)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Self {
+            n_frames: input.read_u32()?,
+            frame_time: input.read_f32()?,
+            frame_time_elapsed: input.read_f32()?,
+        })
+    }
+}
+
+impl Binary_Serializable for C_Camera2D {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.transform.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Self {
+            transform: Transform2D::deserialize(input)?,
+        })
+    }
+}
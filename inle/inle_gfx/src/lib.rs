@@ -19,4 +19,5 @@ pub mod material;
 pub mod particles;
 pub mod render;
 pub mod render_window;
+pub mod tilemap;
 pub mod vbuf_holder;
@@ -1,17 +1,31 @@
 use inle_common::colors::{self, Color};
+use inle_math::angle::{self, Angle};
 use inle_math::rect::Rectf;
 use inle_math::vector::Vec2f;
+use inle_resources::gfx::Texture_Handle;
+use inle_serialize::{Binary_Serializable, Byte_Stream};
+use std::io;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Point_Light {
     pub color: Color,
     pub position: Vec2f,
     pub radius: f32,
-    // Exponent used in 1/d^x (not actually used right now)
+    /// See `falloff`.
     pub attenuation: f32,
     pub intensity: f32,
 }
 
+/// How much of a light with the given `radius` and `attenuation` reaches a point `dist` away from it.
+/// The light fades from 1 to 0 at `radius`: linearly if `attenuation` is 0 and faster near the
+/// light the higher `attenuation` is. This is the formula used by the lit shaders.
+pub fn falloff(dist: f32, radius: f32, attenuation: f32) -> f32 {
+    if radius <= 0. {
+        return 0.;
+    }
+    (1. - dist / radius).max(0.).powf(1. + attenuation)
+}
+
 impl Default for Point_Light {
     fn default() -> Self {
         Self {
@@ -46,29 +60,246 @@ pub struct Rect_Light {
     // Light inside this rect is at its maximum intensity
     pub rect: Rectf,
     pub radius: f32,
+    /// See `falloff`.
     pub attenuation: f32,
 }
 
+/// A point light that only shines inside a cone.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Spot_Light {
+    pub color: Color,
+    pub intensity: f32,
+    pub position: Vec2f,
+    /// The direction of the cone's axis
+    pub direction: Angle,
+    /// The full aperture of the cone
+    pub cone_angle: Angle,
+    /// Fraction of the cone, from its edges inwards, over which the light fades out (in [0, 1]).
+    pub edge_softness: f32,
+    pub radius: f32,
+    /// See `falloff`.
+    pub attenuation: f32,
+}
+
+impl Default for Spot_Light {
+    fn default() -> Self {
+        Self {
+            color: colors::WHITE,
+            intensity: 1.,
+            position: v2!(0., 0.),
+            direction: Angle::default(),
+            cone_angle: angle::deg(60.),
+            edge_softness: 0.2,
+            radius: 0.,
+            attenuation: 1.,
+        }
+    }
+}
+
+/// A point light that projects a texture (its "cookie") onto the square of side 2 * `radius`
+/// centered on it. The cookies of all lights are taken from a single texture (the cookie atlas,
+/// set via Light_Command::Change_Cookie_Atlas): each light's color is multiplied by its cookie.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Cookie_Light {
+    pub color: Color,
+    pub intensity: f32,
+    pub position: Vec2f,
+    pub rotation: Angle,
+    /// The part of the cookie atlas holding this light's cookie, in normalized texture coordinates.
+    pub cookie_rect: Rectf,
+    pub radius: f32,
+    /// See `falloff`.
+    pub attenuation: f32,
+}
+
+impl Default for Cookie_Light {
+    fn default() -> Self {
+        Self {
+            color: colors::WHITE,
+            intensity: 1.,
+            position: v2!(0., 0.),
+            rotation: Angle::default(),
+            cookie_rect: Rectf::new(0., 0., 1., 1.),
+            radius: 0.,
+            attenuation: 1.,
+        }
+    }
+}
+
 // We're using 'Commands' rather than allowing direct access to the lights
 // so we can batch all the updates and we make clear that changing the lights
 // has performance implications (as the UBO needs to be updated etc).
+#[derive(Copy, Clone)]
 pub enum Light_Command {
     Add_Point_Light(Point_Light),
     Add_Rect_Light(Rect_Light),
+    Add_Spot_Light(Spot_Light),
+    Add_Cookie_Light(Cookie_Light),
     Change_Ambient_Light(Ambient_Light),
     Change_Point_Light(usize, Point_Light),
     Change_Rect_Light(usize, Rect_Light),
+    Change_Spot_Light(usize, Spot_Light),
+    Change_Cookie_Light(usize, Cookie_Light),
+    Change_Cookie_Atlas(Texture_Handle),
 }
 
 #[derive(Default)]
 pub struct Lights {
     point_lights: Vec<Point_Light>,
     rect_lights: Vec<Rect_Light>,
+    spot_lights: Vec<Spot_Light>,
+    cookie_lights: Vec<Cookie_Light>,
     ambient_light: Ambient_Light,
+    cookie_atlas: Texture_Handle,
 
     cmd_queue: Vec<Light_Command>,
 }
 
+impl Binary_Serializable for Point_Light {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.color.serialize(output)?;
+        self.position.serialize(output)?;
+        output.write_f32(self.radius)?;
+        output.write_f32(self.attenuation)?;
+        output.write_f32(self.intensity)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Self {
+            color: Color::deserialize(input)?,
+            position: Vec2f::deserialize(input)?,
+            radius: input.read_f32()?,
+            attenuation: input.read_f32()?,
+            intensity: input.read_f32()?,
+        })
+    }
+}
+
+impl Binary_Serializable for Rect_Light {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.color.serialize(output)?;
+        output.write_f32(self.intensity)?;
+        self.rect.serialize(output)?;
+        output.write_f32(self.radius)?;
+        output.write_f32(self.attenuation)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Self {
+            color: Color::deserialize(input)?,
+            intensity: input.read_f32()?,
+            rect: Rectf::deserialize(input)?,
+            radius: input.read_f32()?,
+            attenuation: input.read_f32()?,
+        })
+    }
+}
+
+impl Binary_Serializable for Spot_Light {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.color.serialize(output)?;
+        output.write_f32(self.intensity)?;
+        self.position.serialize(output)?;
+        self.direction.serialize(output)?;
+        self.cone_angle.serialize(output)?;
+        output.write_f32(self.edge_softness)?;
+        output.write_f32(self.radius)?;
+        output.write_f32(self.attenuation)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Self {
+            color: Color::deserialize(input)?,
+            intensity: input.read_f32()?,
+            position: Vec2f::deserialize(input)?,
+            direction: Angle::deserialize(input)?,
+            cone_angle: Angle::deserialize(input)?,
+            edge_softness: input.read_f32()?,
+            radius: input.read_f32()?,
+            attenuation: input.read_f32()?,
+        })
+    }
+}
+
+impl Binary_Serializable for Cookie_Light {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.color.serialize(output)?;
+        output.write_f32(self.intensity)?;
+        self.position.serialize(output)?;
+        self.rotation.serialize(output)?;
+        self.cookie_rect.serialize(output)?;
+        output.write_f32(self.radius)?;
+        output.write_f32(self.attenuation)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Self {
+            color: Color::deserialize(input)?,
+            intensity: input.read_f32()?,
+            position: Vec2f::deserialize(input)?,
+            rotation: Angle::deserialize(input)?,
+            cookie_rect: Rectf::deserialize(input)?,
+            radius: input.read_f32()?,
+            attenuation: input.read_f32()?,
+        })
+    }
+}
+
+/// The lights are saved as they will be after processing all queued commands, and they're
+/// loaded back as commands, so the renderer knows it has to update them.
+/// Note that the cookie atlas is saved by handle, so it must be loaded before deserializing.
+impl Binary_Serializable for Lights {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        let mut lights = Lights {
+            point_lights: self.point_lights.clone(),
+            rect_lights: self.rect_lights.clone(),
+            spot_lights: self.spot_lights.clone(),
+            cookie_lights: self.cookie_lights.clone(),
+            ambient_light: self.ambient_light,
+            cookie_atlas: self.cookie_atlas,
+            cmd_queue: self.cmd_queue.clone(),
+        };
+        lights.process_commands();
+
+        lights.ambient_light.color.serialize(output)?;
+        output.write_f32(lights.ambient_light.intensity)?;
+        lights.point_lights.serialize(output)?;
+        lights.rect_lights.serialize(output)?;
+        lights.spot_lights.serialize(output)?;
+        lights.cookie_lights.serialize(output)?;
+        lights.cookie_atlas.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        let ambient_light = Ambient_Light {
+            color: Color::deserialize(input)?,
+            intensity: input.read_f32()?,
+        };
+        let point_lights = Vec::<Point_Light>::deserialize(input)?;
+        let rect_lights = Vec::<Rect_Light>::deserialize(input)?;
+        let spot_lights = Vec::<Spot_Light>::deserialize(input)?;
+        let cookie_lights = Vec::<Cookie_Light>::deserialize(input)?;
+        let cookie_atlas = Texture_Handle::deserialize(input)?;
+
+        let mut lights = Lights::default();
+        lights.queue_command(Light_Command::Change_Ambient_Light(ambient_light));
+        for light in point_lights {
+            lights.queue_command(Light_Command::Add_Point_Light(light));
+        }
+        for light in rect_lights {
+            lights.queue_command(Light_Command::Add_Rect_Light(light));
+        }
+        for light in spot_lights {
+            lights.queue_command(Light_Command::Add_Spot_Light(light));
+        }
+        for light in cookie_lights {
+            lights.queue_command(Light_Command::Add_Cookie_Light(light));
+        }
+        lights.queue_command(Light_Command::Change_Cookie_Atlas(cookie_atlas));
+        Ok(lights)
+    }
+}
+
 impl Lights {
     pub fn point_lights(&self) -> &[Point_Light] {
         &self.point_lights
@@ -78,14 +309,35 @@ impl Lights {
         &self.rect_lights
     }
 
+    pub fn spot_lights(&self) -> &[Spot_Light] {
+        &self.spot_lights
+    }
+
+    pub fn cookie_lights(&self) -> &[Cookie_Light] {
+        &self.cookie_lights
+    }
+
     pub fn ambient_light(&self) -> &Ambient_Light {
         &self.ambient_light
     }
 
+    pub fn cookie_atlas(&self) -> Texture_Handle {
+        self.cookie_atlas
+    }
+
     pub fn queue_command(&mut self, cmd: Light_Command) {
         self.cmd_queue.push(cmd);
     }
 
+    pub fn n_queued_commands(&self) -> usize {
+        self.cmd_queue.len()
+    }
+
+    /// Drops all the commands queued after the first `n`, as if they were never queued.
+    pub fn discard_queued_commands_after(&mut self, n: usize) {
+        self.cmd_queue.truncate(n);
+    }
+
     /// Returns true if any commands were processed
     pub fn process_commands(&mut self) -> bool {
         let cmds = self.cmd_queue.split_off(0);
@@ -98,6 +350,12 @@ impl Lights {
                 Light_Command::Add_Rect_Light(light) => {
                     self.rect_lights.push(light);
                 }
+                Light_Command::Add_Spot_Light(light) => {
+                    self.spot_lights.push(light);
+                }
+                Light_Command::Add_Cookie_Light(light) => {
+                    self.cookie_lights.push(light);
+                }
                 Light_Command::Change_Ambient_Light(light) => {
                     self.ambient_light = light;
                 }
@@ -117,6 +375,25 @@ impl Lights {
                     );
                     self.rect_lights[idx] = light;
                 }
+                Light_Command::Change_Spot_Light(idx, light) => {
+                    assert!(
+                        idx < self.spot_lights.len(),
+                        "Invalid spot light index {}",
+                        idx
+                    );
+                    self.spot_lights[idx] = light;
+                }
+                Light_Command::Change_Cookie_Light(idx, light) => {
+                    assert!(
+                        idx < self.cookie_lights.len(),
+                        "Invalid cookie light index {}",
+                        idx
+                    );
+                    self.cookie_lights[idx] = light;
+                }
+                Light_Command::Change_Cookie_Atlas(atlas) => {
+                    self.cookie_atlas = atlas;
+                }
             }
         }
         are_there_cmds
@@ -210,3 +487,53 @@ fn get_all_lights_sorted_by_distance_within<L: Copy, E: Extend<L>>(
 
     result.extend(nearest_sorted);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falloff_depends_on_attenuation() {
+        assert_eq!(falloff(0., 10., 2.), 1.);
+        assert_eq!(falloff(5., 10., 0.), 0.5);
+        assert_eq!(falloff(5., 10., 1.), 0.25);
+        assert_eq!(falloff(10., 10., 0.), 0.);
+        assert_eq!(falloff(20., 10., 0.), 0.);
+        assert_eq!(falloff(0., 0., 0.), 0.);
+    }
+
+    #[test]
+    fn serialize_deserialize_lights() {
+        let spot_light = Spot_Light {
+            position: v2!(10., 20.),
+            direction: angle::deg(90.),
+            radius: 100.,
+            ..Default::default()
+        };
+        let cookie_light = Cookie_Light {
+            rotation: angle::deg(45.),
+            cookie_rect: Rectf::new(0.5, 0., 0.5, 0.5),
+            radius: 50.,
+            ..Default::default()
+        };
+
+        let mut lights = Lights::default();
+        lights.queue_command(Light_Command::Add_Point_Light(Point_Light::default()));
+        lights.queue_command(Light_Command::Add_Spot_Light(Spot_Light::default()));
+        lights.queue_command(Light_Command::Change_Spot_Light(0, spot_light));
+        lights.queue_command(Light_Command::Add_Cookie_Light(cookie_light));
+        lights.queue_command(Light_Command::Change_Cookie_Atlas(Some(sid!("cookies"))));
+
+        let mut byte_stream = Byte_Stream::new();
+        lights.serialize(&mut byte_stream).unwrap();
+        byte_stream.seek(0);
+        let mut deser = Lights::deserialize(&mut byte_stream).unwrap();
+        assert!(deser.process_commands());
+
+        assert_eq!(deser.point_lights(), &[Point_Light::default()]);
+        assert!(deser.rect_lights().is_empty());
+        assert_eq!(deser.spot_lights(), &[spot_light]);
+        assert_eq!(deser.cookie_lights(), &[cookie_light]);
+        assert_eq!(deser.cookie_atlas(), Some(sid!("cookies")));
+    }
+}
@@ -1,5 +1,7 @@
 use inle_common::colors;
 use inle_resources::gfx::{Shader_Handle, Texture_Handle};
+use inle_serialize::{Binary_Serializable, Byte_Stream};
+use std::io;
 
 #[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, Default)]
 pub struct Material {
@@ -36,3 +38,26 @@ impl Material {
         (sh as f32) / std::u16::MAX as f32 * Self::MAX_SHININESS
     }
 }
+
+/// Note that resources are saved by handle, so they must be loaded before deserializing.
+impl Binary_Serializable for Material {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.texture.serialize(output)?;
+        self.normals.serialize(output)?;
+        self.shader.serialize(output)?;
+        self.specular_color.serialize(output)?;
+        self.shininess.serialize(output)?;
+        self.cast_shadows.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Self {
+            texture: Texture_Handle::deserialize(input)?,
+            normals: Texture_Handle::deserialize(input)?,
+            shader: Shader_Handle::deserialize(input)?,
+            specular_color: colors::Color::deserialize(input)?,
+            shininess: u16::deserialize(input)?,
+            cast_shadows: bool::deserialize(input)?,
+        })
+    }
+}
@@ -1,4 +1,5 @@
 use crate::material::Material;
+use crate::tilemap::{C_Tilemap, EMPTY_TILE};
 use inle_common::colors::Color;
 use inle_common::paint_props::Paint_Properties;
 use inle_gfx_backend::render::backend;
@@ -11,6 +12,8 @@ use inle_math::vector::Vec2f;
 pub use inle_gfx_backend::render::{Primitive_Type, Uniform_Value};
 
 pub mod batcher;
+pub mod light_grid;
+pub mod shadow_maps;
 
 pub type Z_Index = i8;
 pub type Font<'a> = backend::Font<'a>;
@@ -90,6 +93,39 @@ pub fn render_texture_ws(
     );
 }
 
+/// Draws all the tiles of `tilemap` that may be visible from `camera`, chunk by chunk.
+/// `pos` is the world position of the tilemap's top-left corner.
+pub fn render_tilemap_ws(
+    window: &mut Render_Window_Handle,
+    batches: &mut batcher::Batches,
+    tilemap: &C_Tilemap,
+    pos: Vec2f,
+    camera: &Transform2D,
+) {
+    trace!("render_tilemap_ws");
+
+    let visible_viewport = inle_win::window::get_camera_viewport(window, camera);
+    for chunk in tilemap.visible_chunks(pos, &visible_viewport) {
+        for y in chunk.y..chunk.y + chunk.height {
+            for x in chunk.x..chunk.x + chunk.width {
+                let tile = tilemap.get_tile(x, y);
+                if tile == EMPTY_TILE {
+                    continue;
+                }
+                batcher::add_texture_ws(
+                    window,
+                    batches,
+                    &tilemap.material,
+                    &tilemap.tile_tex_rect(tile),
+                    tilemap.modulate,
+                    &Transform2D::from_pos(pos + tilemap.tile_local_center(x, y)),
+                    tilemap.z_index,
+                );
+            }
+        }
+    }
+}
+
 pub fn render_text<P>(
     window: &mut Render_Window_Handle,
     text: &Text,
@@ -459,3 +495,9 @@ pub fn bind_uniform_buffer(ubo: &Uniform_Buffer) {
 pub fn uniform_buffer_needs_transfer_to_gpu(ubo: &Uniform_Buffer) -> bool {
     backend::uniform_buffer_needs_transfer_to_gpu(ubo)
 }
+
+/// Returns false if nothing was ever written into `ubo` (e.g. because it was just created).
+#[inline]
+pub fn uniform_buffer_was_written(ubo: &Uniform_Buffer) -> bool {
+    backend::uniform_buffer_was_written(ubo)
+}
@@ -1,4 +1,10 @@
-use crate::light::{Lights, Point_Light, Rect_Light};
+use super::light_grid::{
+    Light_Grid, LIGHT_GRID_N_TILES, MAX_POINT_LIGHTS, MAX_RECT_LIGHTS, MAX_SPOT_LIGHTS,
+};
+use super::shadow_maps::{
+    occluder_extent, Occluder, Shadow_Maps, MAX_SHADOW_MAPS, SHADOW_MAP_RESOLUTION,
+};
+use crate::light::Lights;
 use crate::material::Material;
 use crate::render::{self, Primitive_Type};
 use crate::vbuf_holder::Vertex_Buffer_Holder;
@@ -9,33 +15,32 @@ use inle_gfx_backend::render::get_vp_matrix;
 use inle_gfx_backend::render::{Shader, Texture, Vertex};
 use inle_gfx_backend::render_window::Render_Window_Handle;
 use inle_math::angle::Angle;
-use inle_math::math::{lerp, lerp_clamped};
 use inle_math::matrix::Matrix3;
 use inle_math::rect;
 use inle_math::rect::{Rect, Rectf};
 use inle_math::transform::Transform2D;
 use inle_math::vector::Vec2f;
 use inle_resources::gfx::{Gfx_Resources, Shader_Cache};
-use smallvec::SmallVec;
 use std::collections::{BTreeMap, HashMap};
 use std::convert::TryFrom;
 
-const SHADOWS_PER_ENTITY: usize = 4;
 const VERTICES_PER_SPRITE: usize = 6;
-const MAX_POINT_LIGHTS: usize = 4;
-const MAX_RECT_LIGHTS: usize = 4;
 
 struct Sprite_Batch {
     pub vbuffer: Vertex_Buffer_Holder,
-    pub shadow_vbuffer: Option<Vertex_Buffer_Holder>,
     pub sprites: Vec<Sprite>,
 }
 
 #[derive(Default)]
 pub struct Batches {
     textures_ws: BTreeMap<super::Z_Index, HashMap<Material, Sprite_Batch>>,
-    point_lights_near_camera: SmallVec<[Point_Light; MAX_POINT_LIGHTS]>,
-    rect_lights_near_camera: SmallVec<[Rect_Light; MAX_RECT_LIGHTS]>,
+    light_grid: Light_Grid,
+    // Kept around to compare it with the new grid and to reuse its memory.
+    old_light_grid: Light_Grid,
+    shadow_maps: Shadow_Maps,
+    old_shadow_maps: Shadow_Maps,
+    // Kept around to reuse its memory.
+    occluders: Vec<Occluder>,
 }
 
 fn null_vertex() -> Vertex {
@@ -88,17 +93,6 @@ pub(super) fn add_texture_ws(
                         #[cfg(debug_assertions)]
                         format!("{:?}", material),
                     ),
-                    shadow_vbuffer: if material.cast_shadows {
-                        Some(Vertex_Buffer_Holder::with_initial_vertex_count(
-                            window,
-                            6 * 4 * 48,
-                            Primitive_Type::Triangles,
-                            #[cfg(debug_assertions)]
-                            format!("{:?}", material),
-                        ))
-                    } else {
-                        None
-                    },
                     sprites: vec![],
                 }
             })
@@ -147,8 +141,7 @@ fn encode_rot_and_alpha_as_color(rot: Angle, alpha: u8) -> Color {
 fn update_light_uniforms(
     ubo: &mut render::Uniform_Buffer,
     lights: &Lights,
-    point_lights_near_camera: &[Point_Light],
-    rect_lights_near_camera: &[Rect_Light],
+    light_grid: &Light_Grid,
 ) {
     trace!("update_light_uniforms");
 
@@ -158,10 +151,18 @@ fn update_light_uniforms(
     //     Ambient_Light ambient_light;
     //     Point_Light point_lights[MAX_POINT_LIGHTS];
     //     Rect_Light rect_lights[MAX_RECT_LIGHTS];
+    //     Spot_Light spot_lights[MAX_SPOT_LIGHTS];
+    //     Cookie_Light cookie_lights[MAX_COOKIE_LIGHTS];
     // };
+    //
+    // Only the lights in the light grid are written: the shaders never read past them.
 
-    let mut next_offset = 0;
     const COL_NORM: f32 = 1.0 / 255.0;
+    const AMBIENT_LIGHT_SIZE: usize = 16;
+    const POINT_LIGHT_SIZE: usize = 32;
+    const RECT_LIGHT_SIZE: usize = 48;
+    const SPOT_LIGHT_SIZE: usize = 48;
+    const COOKIE_LIGHT_SIZE: usize = 64;
 
     {
         #[repr(C)]
@@ -173,6 +174,7 @@ fn update_light_uniforms(
             intensity: f32,
         }
         unsafe impl render::Std140 for Ambient_Light {}
+        const_assert!(std::mem::size_of::<Ambient_Light>() == AMBIENT_LIGHT_SIZE);
 
         let light = lights.ambient_light();
         let ambient_light = Ambient_Light {
@@ -181,7 +183,7 @@ fn update_light_uniforms(
             g: light.color.g as f32 * COL_NORM,
             intensity: light.intensity,
         };
-        next_offset = render::write_into_uniform_buffer(ubo, next_offset, ambient_light);
+        render::write_into_uniform_buffer(ubo, 0, ambient_light);
     }
 
     {
@@ -197,13 +199,9 @@ fn update_light_uniforms(
             attenuation: f32,
         }
         unsafe impl render::Std140 for Point_Light {}
+        const_assert!(std::mem::size_of::<Point_Light>() == POINT_LIGHT_SIZE);
 
-        let mut point_lights = [Point_Light::default(); MAX_POINT_LIGHTS];
-        for (i, pl) in point_lights_near_camera
-            .iter()
-            .take(MAX_POINT_LIGHTS)
-            .enumerate()
-        {
+        for (i, pl) in light_grid.point_lights().iter().enumerate() {
             let point_light = Point_Light {
                 r: pl.color.r as f32 * COL_NORM,
                 g: pl.color.g as f32 * COL_NORM,
@@ -213,10 +211,12 @@ fn update_light_uniforms(
                 radius: pl.radius,
                 attenuation: pl.attenuation,
             };
-            point_lights[i] = point_light;
+            render::write_into_uniform_buffer(
+                ubo,
+                AMBIENT_LIGHT_SIZE + i * POINT_LIGHT_SIZE,
+                point_light,
+            );
         }
-
-        next_offset = render::write_array_into_uniform_buffer(ubo, next_offset, &point_lights);
     }
 
     {
@@ -235,13 +235,10 @@ fn update_light_uniforms(
             _pad2: f32,
         }
         unsafe impl render::Std140 for Rect_Light {}
+        const_assert!(std::mem::size_of::<Rect_Light>() == RECT_LIGHT_SIZE);
 
-        let mut rect_lights = [Rect_Light::default(); MAX_RECT_LIGHTS];
-        for (i, rl) in rect_lights_near_camera
-            .iter()
-            .take(MAX_RECT_LIGHTS)
-            .enumerate()
-        {
+        let start = AMBIENT_LIGHT_SIZE + MAX_POINT_LIGHTS * POINT_LIGHT_SIZE;
+        for (i, rl) in light_grid.rect_lights().iter().enumerate() {
             let rect_light = Rect_Light {
                 r: rl.color.r as f32 * COL_NORM,
                 g: rl.color.g as f32 * COL_NORM,
@@ -254,22 +251,207 @@ fn update_light_uniforms(
                 _pad1: 0.0,
                 _pad2: 0.0,
             };
-            rect_lights[i] = rect_light;
+            render::write_into_uniform_buffer(ubo, start + i * RECT_LIGHT_SIZE, rect_light);
+        }
+    }
+
+    {
+        #[repr(C)]
+        #[derive(Copy, Clone, Default)]
+        struct Spot_Light {
+            r: f32,
+            g: f32,
+            b: f32,
+            intensity: f32,
+            position: Vec2f,
+            radius: f32,
+            attenuation: f32,
+            direction: Vec2f,
+            // Cosines of the half cone angles where the light starts fading and where it ends.
+            cos_outer: f32,
+            cos_inner: f32,
+        }
+        unsafe impl render::Std140 for Spot_Light {}
+        const_assert!(std::mem::size_of::<Spot_Light>() == SPOT_LIGHT_SIZE);
+
+        let start = AMBIENT_LIGHT_SIZE
+            + MAX_POINT_LIGHTS * POINT_LIGHT_SIZE
+            + MAX_RECT_LIGHTS * RECT_LIGHT_SIZE;
+        for (i, sl) in light_grid.spot_lights().iter().enumerate() {
+            let half_cone = sl.cone_angle.as_rad() * 0.5;
+            let spot_light = Spot_Light {
+                r: sl.color.r as f32 * COL_NORM,
+                g: sl.color.g as f32 * COL_NORM,
+                b: sl.color.b as f32 * COL_NORM,
+                intensity: sl.intensity,
+                position: sl.position,
+                radius: sl.radius,
+                attenuation: sl.attenuation,
+                direction: v2!(sl.direction.as_rad().cos(), sl.direction.as_rad().sin()),
+                cos_outer: half_cone.cos(),
+                cos_inner: (half_cone * (1.0 - sl.edge_softness.clamp(0.0, 1.0))).cos(),
+            };
+            render::write_into_uniform_buffer(ubo, start + i * SPOT_LIGHT_SIZE, spot_light);
+        }
+    }
+
+    {
+        #[repr(C)]
+        #[derive(Copy, Clone, Default)]
+        struct Cookie_Light {
+            r: f32,
+            g: f32,
+            b: f32,
+            intensity: f32,
+            position: Vec2f,
+            radius: f32,
+            attenuation: f32,
+            cookie_rect: Rectf,
+            rotation: Vec2f,
+            _pad1: f32,
+            _pad2: f32,
+        }
+        unsafe impl render::Std140 for Cookie_Light {}
+        const_assert!(std::mem::size_of::<Cookie_Light>() == COOKIE_LIGHT_SIZE);
+
+        let start = AMBIENT_LIGHT_SIZE
+            + MAX_POINT_LIGHTS * POINT_LIGHT_SIZE
+            + MAX_RECT_LIGHTS * RECT_LIGHT_SIZE
+            + MAX_SPOT_LIGHTS * SPOT_LIGHT_SIZE;
+        for (i, cl) in light_grid.cookie_lights().iter().enumerate() {
+            let cookie_light = Cookie_Light {
+                r: cl.color.r as f32 * COL_NORM,
+                g: cl.color.g as f32 * COL_NORM,
+                b: cl.color.b as f32 * COL_NORM,
+                intensity: cl.intensity,
+                position: cl.position,
+                radius: cl.radius,
+                attenuation: cl.attenuation,
+                cookie_rect: cl.cookie_rect,
+                rotation: v2!(cl.rotation.as_rad().cos(), cl.rotation.as_rad().sin()),
+                _pad1: 0.0,
+                _pad2: 0.0,
+            };
+            render::write_into_uniform_buffer(ubo, start + i * COOKIE_LIGHT_SIZE, cookie_light);
+        }
+    }
+}
+
+fn update_light_grid_uniforms(ubo: &mut render::Uniform_Buffer, light_grid: &Light_Grid) {
+    trace!("update_light_grid_uniforms");
+
+    // Assuming this layout in GLSL:
+    //
+    // layout (std140) uniform LightGridBlock {
+    //     vec4 grid_origin_and_inv_tile_size;
+    //     uvec4 grid_tiles[LIGHT_GRID_N_TILES / 2];
+    //     uvec4 light_indices[MAX_LIGHT_INDICES / 8];
+    // };
+
+    #[repr(C)]
+    #[derive(Copy, Clone)]
+    struct Grid_Header {
+        origin: Vec2f,
+        inv_tile_size: Vec2f,
+    }
+    unsafe impl render::Std140 for Grid_Header {}
+
+    #[repr(C)]
+    #[derive(Copy, Clone)]
+    struct Uvec4([u32; 4]);
+    unsafe impl render::Std140 for Uvec4 {}
+
+    let tile_size = light_grid.tile_size();
+    let inv = |x: f32| if x > 0. { 1. / x } else { 0. };
+    let header = Grid_Header {
+        origin: light_grid.area().pos_min(),
+        inv_tile_size: v2!(inv(tile_size.x), inv(tile_size.y)),
+    };
+    let mut next_offset = render::write_into_uniform_buffer(ubo, 0, header);
+
+    debug_assert_eq!(light_grid.tiles().len(), LIGHT_GRID_N_TILES);
+    // Each tile is 2 u32, so they're packed in pairs.
+    for tiles in light_grid.tiles().chunks_exact(2) {
+        next_offset = render::write_into_uniform_buffer(
+            ubo,
+            next_offset,
+            Uvec4([tiles[0][0], tiles[0][1], tiles[1][0], tiles[1][1]]),
+        );
+    }
+
+    // Indices are u16, packed in pairs.
+    for indices in light_grid.light_indices().chunks(8) {
+        let mut packed = [0; 4];
+        for (i, &index) in indices.iter().enumerate() {
+            packed[i / 2] |= (index as u32) << (16 * (i % 2));
         }
+        next_offset = render::write_into_uniform_buffer(ubo, next_offset, Uvec4(packed));
+    }
+}
+
+fn update_shadow_uniforms(ubo: &mut render::Uniform_Buffer, shadow_maps: &Shadow_Maps) {
+    trace!("update_shadow_uniforms");
+
+    // Assuming this layout in GLSL:
+    //
+    // layout (std140) uniform ShadowsBlock {
+    //     uvec4 point_light_shadow_maps[MAX_POINT_LIGHTS / 16];
+    //     vec4 shadow_maps[MAX_SHADOW_MAPS * SHADOW_MAP_RESOLUTION / 4];
+    // };
 
-        render::write_array_into_uniform_buffer(ubo, next_offset, &rect_lights);
+    #[repr(C)]
+    #[derive(Copy, Clone)]
+    struct Uvec4([u32; 4]);
+    unsafe impl render::Std140 for Uvec4 {}
+
+    #[repr(C)]
+    #[derive(Copy, Clone)]
+    struct Vec4([f32; 4]);
+    unsafe impl render::Std140 for Vec4 {}
+
+    // Shadow map indices are u8, packed 16 per uvec4. We always write all of them, since
+    // the lights that have no shadow map anymore must be reset to 0.
+    let mut map_indices = [0u8; MAX_POINT_LIGHTS];
+    let point_light_maps = shadow_maps.point_light_maps();
+    map_indices[..point_light_maps.len()].copy_from_slice(point_light_maps);
+    let mut next_offset = 0;
+    for indices in map_indices.chunks_exact(16) {
+        let mut packed = [0; 4];
+        for (i, word) in indices.chunks_exact(4).enumerate() {
+            packed[i] = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        next_offset = render::write_into_uniform_buffer(ubo, next_offset, Uvec4(packed));
+    }
+
+    debug_assert!(shadow_maps.depths().len() <= MAX_SHADOW_MAPS * SHADOW_MAP_RESOLUTION);
+    for depths in shadow_maps.depths().chunks_exact(4) {
+        next_offset = render::write_into_uniform_buffer(
+            ubo,
+            next_offset,
+            Vec4([depths[0], depths[1], depths[2], depths[3]]),
+        );
     }
 }
 
+/// Returns true if we must write into `ubo`: either its content changed and we didn't write it
+/// yet this frame, or nothing was ever written into it (so it must be filled even if its
+/// content didn't change since the previous frame).
+fn ubo_needs_write(ubo: &render::Uniform_Buffer, content_changed: bool) -> bool {
+    !render::uniform_buffer_was_written(ubo)
+        || (content_changed && !render::uniform_buffer_needs_transfer_to_gpu(ubo))
+}
+
 fn set_shader_uniforms(
     window: &mut Render_Window_Handle,
     shader: &mut Shader,
     material: &Material,
     gres: &Gfx_Resources,
     lights: &Lights,
-    point_lights_near_camera: &[Point_Light],
-    rect_lights_near_camera: &[Rect_Light],
+    light_grid: &Light_Grid,
+    shadow_maps: &Shadow_Maps,
     lights_ubo_needs_update: bool,
+    shadows_ubo_needs_update: bool,
+    occluder_extent: f32,
     texture: &Texture,
     view_projection: &Matrix3<f32>,
 ) {
@@ -302,15 +484,29 @@ fn set_shader_uniforms(
 
     let lights_ubo = render::create_or_get_uniform_buffer(window, shader, c_str!("LightsBlock"));
     // Note: we only update the light uniforms if lights have changed AND we didn't update this particular UBO yet.
-    if lights_ubo_needs_update && !render::uniform_buffer_needs_transfer_to_gpu(lights_ubo) {
-        update_light_uniforms(
-            lights_ubo,
-            lights,
-            point_lights_near_camera,
-            rect_lights_near_camera,
-        );
+    if ubo_needs_write(lights_ubo, lights_ubo_needs_update) {
+        update_light_uniforms(lights_ubo, lights, light_grid);
     }
     render::bind_uniform_buffer(lights_ubo);
+
+    let grid_ubo = render::create_or_get_uniform_buffer(window, shader, c_str!("LightGridBlock"));
+    if ubo_needs_write(grid_ubo, lights_ubo_needs_update) {
+        update_light_grid_uniforms(grid_ubo, light_grid);
+    }
+    render::bind_uniform_buffer(grid_ubo);
+
+    let shadows_ubo = render::create_or_get_uniform_buffer(window, shader, c_str!("ShadowsBlock"));
+    if ubo_needs_write(shadows_ubo, shadows_ubo_needs_update) {
+        update_shadow_uniforms(shadows_ubo, shadow_maps);
+    }
+    render::bind_uniform_buffer(shadows_ubo);
+    // Occluders would shadow themselves, so they ignore the occluders nearer than their extent.
+    set_uniform(shader, c_str!("occluder_extent"), occluder_extent);
+
+    if lights.cookie_atlas().is_some() {
+        let cookies = gres.get_texture(lights.cookie_atlas());
+        set_uniform(shader, c_str!("cookies"), cookies);
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -347,52 +543,43 @@ pub fn draw_batches(
     let mut lights_ubo_needs_update = lights.process_commands();
 
     {
-        let mut old_point_lights_near_camera = SmallVec::<[Point_Light; MAX_POINT_LIGHTS]>::new();
-        std::mem::swap(
-            &mut old_point_lights_near_camera,
-            &mut batches.point_lights_near_camera,
-        );
-        // @Speed: should lights be spatially accelerated?
-        lights.get_all_point_lights_sorted_by_distance_within(
-            camera.position(),
-            visible_viewport.width * 2.0,
-            &mut batches.point_lights_near_camera,
-            MAX_POINT_LIGHTS,
-        );
-        batches
-            .point_lights_near_camera
-            .resize(MAX_POINT_LIGHTS, Point_Light::default());
-        lights_ubo_needs_update = lights_ubo_needs_update
-            || batches.point_lights_near_camera != old_point_lights_near_camera;
-
-        let mut old_rect_lights_near_camera = SmallVec::<[Rect_Light; MAX_RECT_LIGHTS]>::new();
-        std::mem::swap(
-            &mut old_rect_lights_near_camera,
-            &mut batches.rect_lights_near_camera,
-        );
-        // @Speed: should lights be spatially accelerated?
-        lights.get_all_rect_lights_sorted_by_distance_within(
-            camera.position(),
-            visible_viewport.width * 2.0,
-            &mut batches.rect_lights_near_camera,
-            MAX_POINT_LIGHTS,
-        );
-        batches
-            .rect_lights_near_camera
-            .resize(MAX_POINT_LIGHTS, Rect_Light::default());
-        lights_ubo_needs_update = lights_ubo_needs_update
-            || batches.rect_lights_near_camera != old_rect_lights_near_camera;
+        std::mem::swap(&mut batches.light_grid, &mut batches.old_light_grid);
+        batches.light_grid.build(lights, &visible_viewport);
+        lights_ubo_needs_update =
+            lights_ubo_needs_update || batches.light_grid != batches.old_light_grid;
     }
 
-    let point_lights_near_camera = &batches.point_lights_near_camera;
-    let rect_lights_near_camera = &batches.rect_lights_near_camera;
+    let shadows_ubo_needs_update;
+    {
+        std::mem::swap(&mut batches.shadow_maps, &mut batches.old_shadow_maps);
+        if draw_params.enable_shaders && draw_params.enable_shadows {
+            batches.occluders.clear();
+            for sprite_map in batches.textures_ws.values() {
+                for (material, batch) in sprite_map {
+                    if material.cast_shadows {
+                        batches
+                            .occluders
+                            .extend(batch.sprites.iter().map(sprite_quad));
+                    }
+                }
+            }
+            batches
+                .shadow_maps
+                .build(&batches.light_grid, &batches.occluders);
+        } else {
+            batches.shadow_maps.clear();
+        }
+        shadows_ubo_needs_update = batches.shadow_maps != batches.old_shadow_maps;
+    }
+
+    let light_grid = &batches.light_grid;
+    let shadow_maps = &batches.shadow_maps;
 
     // for each Z-index...
     for sprite_map in batches.textures_ws.values_mut() {
         // for each material...
         for (material, batch) in sprite_map {
             let vbuffer = &mut batch.vbuffer;
-            let shadow_vbuffer = &mut batch.shadow_vbuffer;
             let sprites = &mut batch.sprites;
 
             let n_sprites = sprites.len();
@@ -403,6 +590,15 @@ pub fn draw_batches(
 
             let texture = gres.get_texture(material.texture);
 
+            let occluder_extent = if material.cast_shadows && draw_params.enable_shadows {
+                sprites
+                    .iter()
+                    .map(|sprite| occluder_extent(&sprite_quad(sprite)))
+                    .fold(0., f32::max)
+            } else {
+                0.
+            };
+
             let shader = if draw_params.enable_shaders {
                 material.shader.map(|id| {
                     let shader = shader_cache.get_shader_mut(Some(id));
@@ -412,9 +608,11 @@ pub fn draw_batches(
                         material,
                         gres,
                         lights,
-                        point_lights_near_camera,
-                        rect_lights_near_camera,
+                        light_grid,
+                        shadow_maps,
                         lights_ubo_needs_update,
+                        shadows_ubo_needs_update,
+                        occluder_extent,
                         texture,
                         &view_projection,
                     );
@@ -424,66 +622,23 @@ pub fn draw_batches(
                 None
             };
             let has_shader = shader.is_some();
-            let cast_shadows = draw_params.enable_shadows && material.cast_shadows;
 
-            // This buffer holds both regular vertices and shadow vertices
             let mut vertices = temp::excl_temp_array(frame_alloc);
-
-            let n_vertices;
-            let n_shadow_vertices;
-            if cast_shadows {
-                let (nv, ns) = fill_vertices_with_shadows(
-                    texture,
-                    sprites,
-                    &visible_viewport,
-                    lights,
-                    has_shader,
-                    &mut vertices,
-                );
-                n_vertices = nv;
-                n_shadow_vertices = ns;
-                debug_assert_eq!(vertices.len(), nv + ns);
-            } else {
-                n_vertices = fill_vertices(
-                    texture,
-                    sprites,
-                    &visible_viewport,
-                    has_shader,
-                    &mut vertices,
-                );
-                n_shadow_vertices = 0;
-            }
+            let n_vertices = fill_vertices(
+                texture,
+                sprites,
+                &visible_viewport,
+                has_shader,
+                &mut vertices,
+            );
             let n_vertices = u32::try_from(n_vertices).unwrap();
-            let n_shadow_vertices = u32::try_from(n_shadow_vertices).unwrap();
-
-            debug_assert!(!((n_shadow_vertices > 0) && !cast_shadows));
 
             // Ensure the vbuffer has enough room to write in
             if n_vertices > super::vbuf_max_vertices(&vbuffer.vbuf) {
                 vbuffer.grow(window, n_vertices);
             }
 
-            if n_shadow_vertices > 0 {
-                // @Cleanup: we can probably get rid of shadow_vbuffer and just use vbuffer twice
-                let shadow_vbuffer = shadow_vbuffer.as_mut().unwrap();
-                if n_shadow_vertices > super::vbuf_max_vertices(&shadow_vbuffer.vbuf) {
-                    shadow_vbuffer.grow(window, n_shadow_vertices);
-                }
-                shadow_vbuffer.update(&vertices, n_shadow_vertices);
-
-                render::render_vbuf_ws_with_texture(
-                    window,
-                    &shadow_vbuffer.vbuf,
-                    &Transform2D::default(),
-                    camera,
-                    texture,
-                );
-            }
-
-            vbuffer.update(
-                &vertices.as_slice()[n_shadow_vertices as usize..],
-                n_vertices,
-            );
+            vbuffer.update(&vertices, n_vertices);
 
             if let Some(shader) = shader.map(|s| s as &_) {
                 render::render_vbuf_with_shader(window, &vbuffer.vbuf, shader);
@@ -500,22 +655,19 @@ pub fn draw_batches(
     }
 }
 
-fn fill_vertices_with_shadows(
-    texture: &Texture,
-    sprites: &[Sprite],
-    visible_viewport: &Rectf,
-    lights: &Lights,
-    has_shader: bool,
-    out_vertices: &mut Exclusive_Temp_Array<Vertex>,
-) -> (usize, usize) {
-    trace!("fill_vertices_with_shadows");
-
-    let n_shadow_vertices =
-        fill_shadow_vertices(texture, sprites, visible_viewport, lights, out_vertices);
-    let n_sprite_vertices =
-        fill_vertices(texture, sprites, visible_viewport, has_shader, out_vertices);
-
-    (n_sprite_vertices, n_shadow_vertices)
+/// Returns the world-space corners of the sprite.
+fn sprite_quad(sprite: &Sprite) -> [Vec2f; 4] {
+    let sprite_size = v2!(sprite.tex_rect.width as f32, sprite.tex_rect.height as f32);
+    let render_transform = sprite.transform;
+
+    // Note: beware of the order of multiplications!
+    // Scaling the local positions must be done BEFORE multiplying the matrix!
+    [
+        render_transform * (sprite_size * v2!(-0.5, -0.5)),
+        render_transform * (sprite_size * v2!(0.5, -0.5)),
+        render_transform * (sprite_size * v2!(0.5, 0.5)),
+        render_transform * (sprite_size * v2!(-0.5, 0.5)),
+    ]
 }
 
 fn fill_vertices(
@@ -544,15 +696,7 @@ fn fill_vertices(
             tex_rect.width as f32 / tw,
             tex_rect.height as f32 / th,
         );
-        let sprite_size = v2!(tex_rect.width as f32, tex_rect.height as f32);
-        let render_transform = *transform;
-
-        // Note: beware of the order of multiplications!
-        // Scaling the local positions must be done BEFORE multiplying the matrix!
-        let p1 = render_transform * (sprite_size * v2!(-0.5, -0.5));
-        let p2 = render_transform * (sprite_size * v2!(0.5, -0.5));
-        let p3 = render_transform * (sprite_size * v2!(0.5, 0.5));
-        let p4 = render_transform * (sprite_size * v2!(-0.5, 0.5));
+        let [p1, p2, p3, p4] = sprite_quad(sprite);
 
         let sprite_aabb = rect::aabb_of_points(&[p1, p2, p3, p4]);
         if rect::rects_intersection(visible_viewport, &sprite_aabb).is_none() {
@@ -583,137 +727,3 @@ fn fill_vertices(
 
     n_vertices_added
 }
-
-fn fill_shadow_vertices(
-    texture: &Texture,
-    sprites: &[Sprite],
-    visible_viewport: &Rectf,
-    lights: &Lights,
-    out_vertices: &mut Exclusive_Temp_Array<Vertex>,
-) -> usize {
-    // @Speed: duplicate calculation
-    let tex_size = render::get_texture_size(texture);
-    let (tw, th) = (tex_size.0 as f32, tex_size.1 as f32);
-
-    let mut n_vertices_added = 0;
-
-    for sprite in sprites {
-        // @Speed: calculating v1, v2, v3 and v4 is something we're doing twice per sprite in the case of
-        // materials that cast shadows. We should calculate them only once and save the results.
-        let Sprite {
-            tex_rect,
-            transform,
-            ..
-        } = sprite;
-
-        let uv = Rect::new(
-            tex_rect.x as f32 / tw,
-            tex_rect.y as f32 / th,
-            tex_rect.width as f32 / tw,
-            tex_rect.height as f32 / th,
-        );
-        let sprite_size = v2!(tex_rect.width as f32, tex_rect.height as f32);
-        let render_transform = *transform;
-        let p1 = render_transform * (sprite_size * v2!(-0.5, -0.5));
-        let p2 = render_transform * (sprite_size * v2!(0.5, -0.5));
-        let p3 = render_transform * (sprite_size * v2!(0.5, 0.5));
-        let p4 = render_transform * (sprite_size * v2!(-0.5, 0.5));
-
-        let sprite_aabb = rect::aabb_of_points(&[p1, p2, p3, p4]);
-        if rect::rects_intersection(visible_viewport, &sprite_aabb).is_none() {
-            continue;
-        }
-
-        let v1 = super::new_vertex(p1, colors::BLACK, v2!(uv.x, uv.y));
-        let v2 = super::new_vertex(p2, colors::BLACK, v2!(uv.x + uv.width, uv.y));
-        let v3 = super::new_vertex(p3, colors::BLACK, v2!(uv.x + uv.width, uv.y + uv.height));
-        let v4 = super::new_vertex(p4, colors::BLACK, v2!(uv.x, uv.y + uv.height));
-
-        let mut nearby_point_lights = SmallVec::<[Point_Light; SHADOWS_PER_ENTITY]>::new();
-        // @Speed: should lights be spatially accelerated?
-        lights.get_all_point_lights_sorted_by_distance_within(
-            render_transform.position(),
-            10000.,
-            &mut nearby_point_lights,
-            SHADOWS_PER_ENTITY,
-        );
-        debug_assert!(nearby_point_lights.len() <= SHADOWS_PER_ENTITY);
-        nearby_point_lights.resize(SHADOWS_PER_ENTITY, Point_Light::default());
-
-        for (light_idx, light) in nearby_point_lights.iter().enumerate() {
-            debug_assert!(light_idx < 4);
-            let light_pos = light.position;
-            let recp_radius2 = 1.0 / (light.radius * light.radius);
-
-            let mut v = [v1, v2, v3, v4];
-            let diff = [
-                light_pos - p1,
-                light_pos - p2,
-                light_pos - p3,
-                light_pos - p4,
-            ];
-            let dist2 = [
-                diff[0].magnitude2(),
-                diff[1].magnitude2(),
-                diff[2].magnitude2(),
-                diff[3].magnitude2(),
-            ];
-
-            const SHADOW_MAX_VALUE: f32 = 50.0;
-            const SHADOW_MAX_DIST: f32 = 100.0;
-
-            for v_idx in 0..v.len() {
-                if v_idx <= 1 {
-                    let offset =
-                        lerp_clamped(0., SHADOW_MAX_DIST, 1.0 / (dist2[v_idx].powf(0.6) + 0.1));
-                    v[v_idx].set_position(v[v_idx].position() - offset * diff[v_idx]);
-                }
-
-                let t = (1.0 - dist2[v_idx] * recp_radius2).max(0.0);
-                v[v_idx].set_color(colors::rgba(
-                    0,
-                    0,
-                    0,
-                    lerp(0.0, SHADOW_MAX_VALUE, t * t) as u8,
-                ));
-                //match v_idx {
-                //0 => v[v_idx].set_color(colors::rgba(255, 0, 0, 100)),
-                //1 => v[v_idx].set_color(colors::rgba(0, 255, 0, 100)),
-                //2 => v[v_idx].set_color(colors::rgba(0, 0, 255, 100)),
-                //3 => v[v_idx].set_color(colors::rgba(255, 255, 0, 100)),
-                //_ => unreachable!(),
-                //}
-            }
-            let ps = [
-                v[0].position(),
-                v[1].position(),
-                v[2].position(),
-                v[3].position(),
-            ];
-            let shadow_aabb = rect::aabb_of_points(&ps);
-            if rect::rects_intersection(visible_viewport, &shadow_aabb).is_none() {
-                continue;
-            }
-
-            // Fix vertex winding, since it may now be wrong  after moving them
-            let a = ps[1] - ps[0];
-            let b = ps[2] - ps[0];
-            let a_wedge_b = a.x * b.y - a.y * b.x;
-            if a_wedge_b.signum() < 0.0 {
-                v.swap(0, 1);
-                v.swap(2, 3);
-            }
-
-            out_vertices.push(v[0]);
-            out_vertices.push(v[1]);
-            out_vertices.push(v[2]);
-            out_vertices.push(v[2]);
-            out_vertices.push(v[3]);
-            out_vertices.push(v[0]);
-
-            n_vertices_added += 6;
-        }
-    }
-
-    n_vertices_added
-}
@@ -0,0 +1,500 @@
+use crate::light::{Cookie_Light, Lights, Point_Light, Rect_Light, Spot_Light};
+use inle_math::rect::{Rect, Rectf};
+use inle_math::vector::Vec2f;
+
+// @Volatile: these must match the LightsBlock and LightGridBlock layouts in the lit shaders.
+pub const LIGHT_GRID_WIDTH: usize = 16;
+pub const LIGHT_GRID_HEIGHT: usize = 9;
+pub const LIGHT_GRID_N_TILES: usize = LIGHT_GRID_WIDTH * LIGHT_GRID_HEIGHT;
+pub const MAX_POINT_LIGHTS: usize = 256;
+pub const MAX_RECT_LIGHTS: usize = 64;
+pub const MAX_SPOT_LIGHTS: usize = 64;
+pub const MAX_COOKIE_LIGHTS: usize = 16;
+pub const MAX_LIGHT_INDICES: usize = 4096;
+/// The number of lights of each type reaching a tile must fit in 8 bits.
+pub const MAX_LIGHTS_PER_TILE: usize = 255;
+
+// Tiles are packed 2 per uvec4 and light indices 8 per uvec4 (as pairs of u16).
+const_assert!(LIGHT_GRID_N_TILES.is_multiple_of(2));
+const_assert!(MAX_LIGHT_INDICES.is_multiple_of(8));
+const_assert!(MAX_POINT_LIGHTS <= u16::MAX as usize + 1);
+const_assert!(MAX_RECT_LIGHTS <= u16::MAX as usize + 1);
+const_assert!(MAX_SPOT_LIGHTS <= u16::MAX as usize + 1);
+const_assert!(MAX_COOKIE_LIGHTS <= u16::MAX as usize + 1);
+
+/// Splits an area of the world (usually the visible one) into a grid of tiles and, for each tile,
+/// keeps the list of the lights that reach it. This way each fragment only needs to look at the
+/// lights of its own tile rather than at all of them.
+#[derive(Default, PartialEq, Debug)]
+pub struct Light_Grid {
+    area: Rectf,
+    /// The lights reaching `area`. The tiles refer to them by their index in these arrays.
+    point_lights: Vec<Point_Light>,
+    rect_lights: Vec<Rect_Light>,
+    spot_lights: Vec<Spot_Light>,
+    cookie_lights: Vec<Cookie_Light>,
+    /// Each tile is packed as `[first_index, n_point | n_rect << 8 | n_spot << 16 | n_cookie << 24]`,
+    /// where `first_index` is where its lights start in `light_indices`.
+    tiles: Vec<[u32; 2]>,
+    /// For each tile, the indices of its point lights followed by the ones of its rect lights,
+    /// spot lights and cookie lights.
+    light_indices: Vec<u16>,
+}
+
+/// The indices of the lights of each type reaching a tile.
+#[derive(Default, PartialEq, Debug)]
+pub struct Tile_Lights<'a> {
+    pub point_lights: &'a [u16],
+    pub rect_lights: &'a [u16],
+    pub spot_lights: &'a [u16],
+    pub cookie_lights: &'a [u16],
+}
+
+trait Grid_Light: Copy {
+    const NAME: &'static str;
+    const MAX_VISIBLE: usize;
+
+    fn radius(&self) -> f32;
+    fn center(&self) -> Vec2f;
+    fn distance_to_rect(&self, rect: &Rectf) -> f32;
+}
+
+impl Grid_Light for Point_Light {
+    const NAME: &'static str = "point";
+    const MAX_VISIBLE: usize = MAX_POINT_LIGHTS;
+
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn center(&self) -> Vec2f {
+        self.position
+    }
+
+    fn distance_to_rect(&self, rect: &Rectf) -> f32 {
+        point_to_rect_distance(self.position, rect)
+    }
+}
+
+impl Grid_Light for Rect_Light {
+    const NAME: &'static str = "rect";
+    const MAX_VISIBLE: usize = MAX_RECT_LIGHTS;
+
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn center(&self) -> Vec2f {
+        self.rect.pos_center()
+    }
+
+    fn distance_to_rect(&self, rect: &Rectf) -> f32 {
+        rect_to_rect_distance(&self.rect, rect)
+    }
+}
+
+// @Speed: we treat spot lights as point lights, ignoring their cone.
+impl Grid_Light for Spot_Light {
+    const NAME: &'static str = "spot";
+    const MAX_VISIBLE: usize = MAX_SPOT_LIGHTS;
+
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn center(&self) -> Vec2f {
+        self.position
+    }
+
+    fn distance_to_rect(&self, rect: &Rectf) -> f32 {
+        point_to_rect_distance(self.position, rect)
+    }
+}
+
+impl Grid_Light for Cookie_Light {
+    const NAME: &'static str = "cookie";
+    const MAX_VISIBLE: usize = MAX_COOKIE_LIGHTS;
+
+    fn radius(&self) -> f32 {
+        // The cookie is projected on a square, whose corners are sqrt(2) * radius away.
+        self.radius * std::f32::consts::SQRT_2
+    }
+
+    fn center(&self) -> Vec2f {
+        self.position
+    }
+
+    fn distance_to_rect(&self, rect: &Rectf) -> f32 {
+        point_to_rect_distance(self.position, rect)
+    }
+}
+
+impl Light_Grid {
+    /// Rebuilds the grid so that it covers `area`, reusing the allocated memory.
+    pub fn build(&mut self, lights: &Lights, area: &Rectf) {
+        trace!("light_grid::build");
+
+        self.area = *area;
+
+        select_visible_lights(&mut self.point_lights, lights.point_lights(), area);
+        select_visible_lights(&mut self.rect_lights, lights.rect_lights(), area);
+        select_visible_lights(&mut self.spot_lights, lights.spot_lights(), area);
+        if lights.cookie_atlas().is_some() {
+            select_visible_lights(&mut self.cookie_lights, lights.cookie_lights(), area);
+        } else {
+            if !lights.cookie_lights().is_empty() {
+                lwarn_once!(
+                    "light_grid_no_cookie_atlas",
+                    "There are cookie lights but no cookie atlas: they will be ignored."
+                );
+            }
+            self.cookie_lights.clear();
+        }
+
+        self.tiles.clear();
+        self.light_indices.clear();
+
+        let tile_size = self.tile_size();
+        for y in 0..LIGHT_GRID_HEIGHT {
+            for x in 0..LIGHT_GRID_WIDTH {
+                let tile_rect = Rect::new(
+                    area.x + x as f32 * tile_size.x,
+                    area.y + y as f32 * tile_size.y,
+                    tile_size.x,
+                    tile_size.y,
+                );
+
+                let first_index = self.light_indices.len() as u32;
+                let n_lights = [
+                    push_light_indices(&mut self.light_indices, &self.point_lights, &tile_rect),
+                    push_light_indices(&mut self.light_indices, &self.rect_lights, &tile_rect),
+                    push_light_indices(&mut self.light_indices, &self.spot_lights, &tile_rect),
+                    push_light_indices(&mut self.light_indices, &self.cookie_lights, &tile_rect),
+                ];
+
+                self.tiles.push([first_index, u32::from_le_bytes(n_lights)]);
+            }
+        }
+    }
+
+    pub fn area(&self) -> &Rectf {
+        &self.area
+    }
+
+    pub fn tile_size(&self) -> Vec2f {
+        v2!(
+            self.area.width / LIGHT_GRID_WIDTH as f32,
+            self.area.height / LIGHT_GRID_HEIGHT as f32
+        )
+    }
+
+    pub fn point_lights(&self) -> &[Point_Light] {
+        &self.point_lights
+    }
+
+    pub fn rect_lights(&self) -> &[Rect_Light] {
+        &self.rect_lights
+    }
+
+    pub fn spot_lights(&self) -> &[Spot_Light] {
+        &self.spot_lights
+    }
+
+    pub fn cookie_lights(&self) -> &[Cookie_Light] {
+        &self.cookie_lights
+    }
+
+    /// The packed tiles, row by row.
+    pub fn tiles(&self) -> &[[u32; 2]] {
+        &self.tiles
+    }
+
+    pub fn light_indices(&self) -> &[u16] {
+        &self.light_indices
+    }
+
+    /// Returns the indices of the lights reaching tile (x, y).
+    pub fn tile_lights(&self, x: usize, y: usize) -> Tile_Lights<'_> {
+        debug_assert!(x < LIGHT_GRID_WIDTH && y < LIGHT_GRID_HEIGHT);
+
+        let [first_index, n_lights] = self.tiles[y * LIGHT_GRID_WIDTH + x];
+        let mut start = first_index as usize;
+        let mut next = |n: u8| {
+            let indices = &self.light_indices[start..start + n as usize];
+            start += n as usize;
+            indices
+        };
+        let [n_point, n_rect, n_spot, n_cookie] = n_lights.to_le_bytes();
+        Tile_Lights {
+            point_lights: next(n_point),
+            rect_lights: next(n_rect),
+            spot_lights: next(n_spot),
+            cookie_lights: next(n_cookie),
+        }
+    }
+}
+
+/// Fills `visible` with the `lights` that reach `area`. If they're too many, only the ones
+/// nearest to the center of `area` are kept.
+fn select_visible_lights<L: Grid_Light>(visible: &mut Vec<L>, lights: &[L], area: &Rectf) {
+    visible.clear();
+    visible.extend(
+        lights
+            .iter()
+            .filter(|l| l.radius() > 0. && l.distance_to_rect(area) < l.radius()),
+    );
+
+    if visible.len() > L::MAX_VISIBLE {
+        lwarn_once!(
+            &format!("light_grid_max_{}_lights", L::NAME),
+            "More than {} {} lights are visible: only the ones nearest to the camera will be used.",
+            L::MAX_VISIBLE,
+            L::NAME
+        );
+        let center = area.pos_center();
+        visible.sort_by(|a, b| {
+            a.center()
+                .distance2(center)
+                .partial_cmp(&b.center().distance2(center))
+                .unwrap()
+        });
+        visible.truncate(L::MAX_VISIBLE);
+    }
+}
+
+/// Pushes the indices of the `lights` reaching `tile` and returns how many were pushed.
+fn push_light_indices<L: Grid_Light>(
+    light_indices: &mut Vec<u16>,
+    lights: &[L],
+    tile: &Rectf,
+) -> u8 {
+    let mut n_pushed = 0;
+    for (i, light) in lights.iter().enumerate() {
+        if light.distance_to_rect(tile) >= light.radius() {
+            continue;
+        }
+        if n_pushed as usize == MAX_LIGHTS_PER_TILE {
+            lwarn_once!(
+                "light_grid_max_lights_per_tile",
+                "More than {} lights of the same type reach the same light grid tile: some will be ignored.",
+                MAX_LIGHTS_PER_TILE
+            );
+            break;
+        }
+        if light_indices.len() == MAX_LIGHT_INDICES {
+            lwarn_once!(
+                "light_grid_max_light_indices",
+                "The light grid is full (it has {} light indices): some lights will be ignored.",
+                MAX_LIGHT_INDICES
+            );
+            break;
+        }
+        light_indices.push(i as u16);
+        n_pushed += 1;
+    }
+    n_pushed
+}
+
+fn point_to_rect_distance(point: Vec2f, rect: &Rectf) -> f32 {
+    let dx = (rect.x - point.x)
+        .max(point.x - (rect.x + rect.width))
+        .max(0.);
+    let dy = (rect.y - point.y)
+        .max(point.y - (rect.y + rect.height))
+        .max(0.);
+    v2!(dx, dy).magnitude()
+}
+
+fn rect_to_rect_distance(a: &Rectf, b: &Rectf) -> f32 {
+    let dx = (a.x - (b.x + b.width)).max(b.x - (a.x + a.width)).max(0.);
+    let dy = (a.y - (b.y + b.height)).max(b.y - (a.y + a.height)).max(0.);
+    v2!(dx, dy).magnitude()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::Light_Command;
+
+    fn lights_with(point_lights: &[Point_Light], rect_lights: &[Rect_Light]) -> Lights {
+        let mut lights = Lights::default();
+        for &light in point_lights {
+            lights.queue_command(Light_Command::Add_Point_Light(light));
+        }
+        for &light in rect_lights {
+            lights.queue_command(Light_Command::Add_Rect_Light(light));
+        }
+        lights.process_commands();
+        lights
+    }
+
+    fn point_light(position: Vec2f, radius: f32) -> Point_Light {
+        Point_Light {
+            position,
+            radius,
+            ..Default::default()
+        }
+    }
+
+    // 10x10 tiles
+    fn grid_area() -> Rectf {
+        Rect::new(
+            0.,
+            0.,
+            10. * LIGHT_GRID_WIDTH as f32,
+            10. * LIGHT_GRID_HEIGHT as f32,
+        )
+    }
+
+    #[test]
+    fn lights_are_only_binned_into_the_tiles_they_reach() {
+        let lights = lights_with(
+            &[
+                point_light(v2!(15., 15.), 4.),
+                point_light(v2!(50., 50.), 1.),
+            ],
+            &[Rect_Light {
+                rect: Rect::new(102., 20.5, 16., 3.),
+                radius: 1.,
+                ..Default::default()
+            }],
+        );
+        let mut grid = Light_Grid::default();
+        grid.build(&lights, &grid_area());
+
+        assert_eq!(grid.tiles().len(), LIGHT_GRID_N_TILES);
+        assert_eq!(
+            grid.tile_lights(1, 1),
+            Tile_Lights {
+                point_lights: &[0],
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            grid.tile_lights(5, 5),
+            Tile_Lights {
+                point_lights: &[1],
+                ..Default::default()
+            }
+        );
+        assert_eq!(grid.tile_lights(0, 0), Tile_Lights::default());
+        assert_eq!(grid.tile_lights(2, 1), Tile_Lights::default());
+
+        for x in 10..12 {
+            assert_eq!(
+                grid.tile_lights(x, 2),
+                Tile_Lights {
+                    rect_lights: &[0],
+                    ..Default::default()
+                }
+            );
+        }
+        // The rect light's radius reaches into the row above
+        assert_eq!(
+            grid.tile_lights(10, 1),
+            Tile_Lights {
+                rect_lights: &[0],
+                ..Default::default()
+            }
+        );
+        assert_eq!(grid.tile_lights(12, 2), Tile_Lights::default());
+
+        let n_indices: usize = (0..LIGHT_GRID_HEIGHT)
+            .flat_map(|y| (0..LIGHT_GRID_WIDTH).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let tile = grid.tile_lights(x, y);
+                tile.point_lights.len() + tile.rect_lights.len()
+            })
+            .sum();
+        assert_eq!(n_indices, grid.light_indices().len());
+    }
+
+    #[test]
+    fn lights_outside_the_area_are_discarded() {
+        let lights = lights_with(
+            &[
+                point_light(v2!(-20., 5.), 10.),
+                point_light(v2!(-5., 5.), 10.),
+                point_light(v2!(5., 5.), 0.),
+            ],
+            &[],
+        );
+        let mut grid = Light_Grid::default();
+        grid.build(&lights, &grid_area());
+
+        assert_eq!(grid.point_lights(), &[point_light(v2!(-5., 5.), 10.)]);
+        assert_eq!(
+            grid.tile_lights(0, 0),
+            Tile_Lights {
+                point_lights: &[0],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn only_the_nearest_lights_are_kept_when_there_are_too_many() {
+        let area = grid_area();
+        let center = area.pos_center();
+        let point_lights = (0..MAX_POINT_LIGHTS + 10)
+            .rev()
+            .map(|i| point_light(center + v2!(0.1 * i as f32, 0.), 1000.))
+            .collect::<Vec<_>>();
+        let lights = lights_with(&point_lights, &[]);
+
+        let mut grid = Light_Grid::default();
+        grid.build(&lights, &area);
+
+        assert_eq!(grid.point_lights().len(), MAX_POINT_LIGHTS);
+        assert!(grid
+            .point_lights()
+            .iter()
+            .all(|l| l.position.x < center.x + 0.1 * MAX_POINT_LIGHTS as f32));
+        // Every light reaches every tile, but tiles can only hold so many.
+        assert_eq!(
+            grid.tile_lights(0, 0).point_lights.len(),
+            MAX_LIGHTS_PER_TILE
+        );
+        assert!(grid.light_indices().len() <= MAX_LIGHT_INDICES);
+    }
+
+    #[test]
+    fn cookie_lights_need_an_atlas() {
+        let mut lights = Lights::default();
+        lights.queue_command(Light_Command::Add_Spot_Light(Spot_Light {
+            position: v2!(15., 15.),
+            radius: 4.,
+            ..Default::default()
+        }));
+        lights.queue_command(Light_Command::Add_Cookie_Light(Cookie_Light {
+            position: v2!(15., 15.),
+            radius: 4.,
+            ..Default::default()
+        }));
+        lights.process_commands();
+
+        let mut grid = Light_Grid::default();
+        grid.build(&lights, &grid_area());
+        assert!(grid.cookie_lights().is_empty());
+        assert_eq!(
+            grid.tile_lights(1, 1),
+            Tile_Lights {
+                spot_lights: &[0],
+                ..Default::default()
+            }
+        );
+
+        lights.queue_command(Light_Command::Change_Cookie_Atlas(Some(sid!("cookies"))));
+        lights.process_commands();
+        grid.build(&lights, &grid_area());
+        assert_eq!(
+            grid.tile_lights(1, 1),
+            Tile_Lights {
+                spot_lights: &[0],
+                cookie_lights: &[0],
+                ..Default::default()
+            }
+        );
+        assert_eq!(grid.tile_lights(2, 2), Tile_Lights::default());
+    }
+}
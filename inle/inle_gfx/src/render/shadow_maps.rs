@@ -0,0 +1,266 @@
+use super::light_grid::{Light_Grid, MAX_POINT_LIGHTS};
+use inle_math::rect;
+use inle_math::vector::Vec2f;
+use std::f32::consts::{PI, TAU};
+
+// @Volatile: these must match the ShadowsBlock layout in the lit shaders.
+pub const MAX_SHADOW_MAPS: usize = 16;
+/// The number of directions each shadow map stores the nearest occluder for.
+pub const SHADOW_MAP_RESOLUTION: usize = 128;
+
+// Shadow map indices are packed 16 per uvec4 and depths 4 per vec4.
+const_assert!(MAX_POINT_LIGHTS.is_multiple_of(16));
+const_assert!((MAX_SHADOW_MAPS * SHADOW_MAP_RESOLUTION).is_multiple_of(4));
+const_assert!(MAX_SHADOW_MAPS < u8::MAX as usize);
+
+/// The world-space corners of something that blocks the light (usually a sprite).
+/// They must form a convex quad.
+pub type Occluder = [Vec2f; 4];
+
+/// Returns the longest distance between two corners of the occluder.
+pub fn occluder_extent(occluder: &Occluder) -> f32 {
+    (occluder[2] - occluder[0])
+        .magnitude()
+        .max((occluder[3] - occluder[1]).magnitude())
+}
+
+/// Holds a 1D shadow map for each point light that has some occluder in range.
+/// A shadow map tells, for each direction around the light, how far the light travels before
+/// hitting an occluder.
+#[derive(Default, PartialEq, Debug)]
+pub struct Shadow_Maps {
+    /// For each point light of the light grid, 1 + the index of its shadow map, or 0 if it has none.
+    point_light_maps: Vec<u8>,
+    /// The shadow maps, one after the other. Each depth is the distance of the nearest occluder
+    /// from the light divided by the light's radius, so 1 means there's no occluder.
+    depths: Vec<f32>,
+}
+
+impl Shadow_Maps {
+    /// Rebuilds the shadow maps of the point lights in `light_grid`, reusing the allocated memory.
+    pub fn build(&mut self, light_grid: &Light_Grid, occluders: &[Occluder]) {
+        trace!("shadow_maps::build");
+
+        self.clear();
+
+        let mut occluders_in_range: Vec<&Occluder> = vec![];
+        for (i, light) in light_grid.point_lights().iter().enumerate() {
+            self.point_light_maps.push(0);
+
+            if light.radius <= 0. {
+                continue;
+            }
+
+            occluders_in_range.clear();
+            occluders_in_range.extend(
+                occluders
+                    .iter()
+                    .filter(|occ| occluder_is_in_range(occ, light.position, light.radius)),
+            );
+            if occluders_in_range.is_empty() {
+                continue;
+            }
+
+            let n_maps = self.depths.len() / SHADOW_MAP_RESOLUTION;
+            if n_maps == MAX_SHADOW_MAPS {
+                lwarn_once!(
+                    "shadow_maps_max_maps",
+                    "More than {} point lights have occluders in range: only the first ones will cast shadows.",
+                    MAX_SHADOW_MAPS
+                );
+                continue;
+            }
+
+            self.point_light_maps[i] = n_maps as u8 + 1;
+            self.depths
+                .resize(self.depths.len() + SHADOW_MAP_RESOLUTION, 1.);
+            let map = &mut self.depths[n_maps * SHADOW_MAP_RESOLUTION..];
+            for occluder in &occluders_in_range {
+                let quad = occluder.map(|p| p - light.position);
+                for edge in 0..quad.len() {
+                    let edge_end = quad[(edge + 1) % quad.len()];
+                    draw_edge_into_shadow_map(map, quad[edge], edge_end, light.radius);
+                }
+            }
+        }
+    }
+
+    /// Removes all shadow maps, so no light casts shadows.
+    pub fn clear(&mut self) {
+        self.point_light_maps.clear();
+        self.depths.clear();
+    }
+
+    /// For each point light of the light grid, 1 + the index of its shadow map, or 0 if it has none.
+    pub fn point_light_maps(&self) -> &[u8] {
+        &self.point_light_maps
+    }
+
+    /// All the shadow maps, one after the other.
+    pub fn depths(&self) -> &[f32] {
+        &self.depths
+    }
+
+    /// Returns the shadow map of the `idx`-th point light of the light grid, if any.
+    pub fn point_light_shadow_map(&self, idx: usize) -> Option<&[f32]> {
+        let map = *self.point_light_maps.get(idx)? as usize;
+        if map == 0 {
+            None
+        } else {
+            let start = (map - 1) * SHADOW_MAP_RESOLUTION;
+            Some(&self.depths[start..start + SHADOW_MAP_RESOLUTION])
+        }
+    }
+}
+
+/// Returns the (fractional) shadow map bin corresponding to `angle`, which is in [-PI, PI].
+/// Bin `i` is centered on the direction `(i + 0.5) / SHADOW_MAP_RESOLUTION` of a full turn,
+/// starting from -PI.
+fn angle_to_bin(angle: f32) -> f32 {
+    (angle + PI) / TAU * SHADOW_MAP_RESOLUTION as f32 - 0.5
+}
+
+fn bin_to_angle(bin: i32) -> f32 {
+    (bin as f32 + 0.5) / SHADOW_MAP_RESOLUTION as f32 * TAU - PI
+}
+
+fn cross(a: Vec2f, b: Vec2f) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+/// Writes into `map` the distance of the edge `a`-`b` (relative to the light) for each direction
+/// it covers, unless something nearer was already there.
+fn draw_edge_into_shadow_map(map: &mut [f32], a: Vec2f, b: Vec2f, radius: f32) {
+    let angle_a = a.y.atan2(a.x);
+    let angle_b = b.y.atan2(b.x);
+    // The edge doesn't go through the light, so it covers less than half a turn.
+    let mut span = angle_b - angle_a;
+    if span > PI {
+        span -= TAU;
+    } else if span < -PI {
+        span += TAU;
+    }
+    let start = if span >= 0. { angle_a } else { angle_b };
+
+    let first_bin = angle_to_bin(start).ceil() as i32;
+    let last_bin = angle_to_bin(start + span.abs()).floor() as i32;
+    let edge = b - a;
+    for bin in first_bin..=last_bin {
+        let angle = bin_to_angle(bin);
+        let dir = v2!(angle.cos(), angle.sin());
+        let denom = cross(dir, edge);
+        if denom.abs() < f32::EPSILON {
+            continue;
+        }
+        let dist = cross(a, edge) / denom;
+        let depth = &mut map[bin.rem_euclid(SHADOW_MAP_RESOLUTION as i32) as usize];
+        *depth = depth.min((dist / radius).clamp(0., 1.));
+    }
+}
+
+fn occluder_is_in_range(occluder: &Occluder, light_pos: Vec2f, radius: f32) -> bool {
+    let aabb = rect::aabb_of_points(occluder);
+    let dx = (aabb.x - light_pos.x)
+        .max(light_pos.x - (aabb.x + aabb.width))
+        .max(0.);
+    let dy = (aabb.y - light_pos.y)
+        .max(light_pos.y - (aabb.y + aabb.height))
+        .max(0.);
+    // A light inside its occluder (e.g. a lamp's sprite) would be completely shadowed.
+    v2!(dx, dy).magnitude2() < radius * radius && !point_is_inside(occluder, light_pos)
+}
+
+fn point_is_inside(quad: &Occluder, point: Vec2f) -> bool {
+    let mut sign = 0.;
+    for i in 0..quad.len() {
+        let side = cross(quad[(i + 1) % quad.len()] - quad[i], point - quad[i]);
+        if side * sign < 0. {
+            return false;
+        }
+        if side != 0. {
+            sign = side;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::{Light_Command, Lights, Point_Light};
+    use inle_math::rect::Rect;
+
+    fn light_grid_with(point_lights: &[Point_Light]) -> Light_Grid {
+        let mut lights = Lights::default();
+        for &light in point_lights {
+            lights.queue_command(Light_Command::Add_Point_Light(light));
+        }
+        lights.process_commands();
+        let mut grid = Light_Grid::default();
+        grid.build(&lights, &Rect::new(-100., -100., 200., 200.));
+        grid
+    }
+
+    fn point_light(position: Vec2f, radius: f32) -> Point_Light {
+        Point_Light {
+            position,
+            radius,
+            ..Default::default()
+        }
+    }
+
+    fn square(center: Vec2f, half_size: f32) -> Occluder {
+        [
+            center + v2!(-half_size, -half_size),
+            center + v2!(half_size, -half_size),
+            center + v2!(half_size, half_size),
+            center + v2!(-half_size, half_size),
+        ]
+    }
+
+    fn depth_towards(map: &[f32], angle: f32) -> f32 {
+        map[angle_to_bin(angle).round() as usize % SHADOW_MAP_RESOLUTION]
+    }
+
+    #[test]
+    fn shadow_maps_store_the_nearest_occluder_in_each_direction() {
+        let grid = light_grid_with(&[point_light(v2!(0., 0.), 50.)]);
+        let occluders = [square(v2!(20., 0.), 5.), square(v2!(30., 0.), 5.)];
+
+        let mut shadow_maps = Shadow_Maps::default();
+        shadow_maps.build(&grid, &occluders);
+
+        let map = shadow_maps.point_light_shadow_map(0).unwrap();
+        // The nearest square's left edge is at x = 15.
+        assert!((depth_towards(map, 0.) - 15. / 50.).abs() < 1e-3);
+        assert_eq!(depth_towards(map, PI * 0.5), 1.);
+        assert_eq!(depth_towards(map, PI), 1.);
+        assert_eq!(depth_towards(map, -PI * 0.5), 1.);
+        // The square covers about 2 * atan(5 / 15) radians.
+        let n_shadowed = map.iter().filter(|&&d| d < 1.).count();
+        let expected = 2. * (5.0f32 / 15.).atan() / TAU * SHADOW_MAP_RESOLUTION as f32;
+        assert!((n_shadowed as f32 - expected).abs() <= 1.);
+    }
+
+    #[test]
+    fn only_lights_with_occluders_in_range_get_a_shadow_map() {
+        let grid = light_grid_with(&[
+            point_light(v2!(0., 0.), 50.),
+            point_light(v2!(-80., -80.), 10.),
+            point_light(v2!(20., 0.), 50.),
+        ]);
+        // The third light is inside the occluder.
+        let occluders = [square(v2!(20., 0.), 5.)];
+
+        let mut shadow_maps = Shadow_Maps::default();
+        shadow_maps.build(&grid, &occluders);
+
+        assert_eq!(shadow_maps.point_light_maps(), &[1, 0, 0]);
+        assert_eq!(shadow_maps.depths().len(), SHADOW_MAP_RESOLUTION);
+        assert!(shadow_maps.point_light_shadow_map(1).is_none());
+
+        shadow_maps.build(&grid, &[]);
+        assert_eq!(shadow_maps.point_light_maps(), &[0, 0, 0]);
+        assert!(shadow_maps.depths().is_empty());
+    }
+}
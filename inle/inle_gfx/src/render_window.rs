@@ -1,4 +1,5 @@
 use inle_common::colors::Color;
+use inle_gfx_backend::render::Image;
 use inle_gfx_backend::render_window::backend;
 use inle_input::mouse::{self, Mouse_State};
 use inle_math::rect::Rect;
@@ -91,6 +92,12 @@ pub fn start_new_frame(window: &mut Render_Window_Handle) {
     backend::start_new_frame(window);
 }
 
+/// Reads back the pixels currently in the window's framebuffer, e.g. to take a screenshot.
+pub fn read_framebuffer(window: &Render_Window_Handle) -> Image {
+    trace!("read_framebuffer");
+    backend::read_framebuffer(window)
+}
+
 #[inline(always)]
 pub fn shutdown(window: &mut Render_Window_Handle) {
     backend::shutdown(window);
@@ -100,3 +107,8 @@ pub fn shutdown(window: &mut Render_Window_Handle) {
 pub fn n_draw_calls_prev_frame(window: &Render_Window_Handle) -> u32 {
     backend::n_draw_calls_prev_frame(window)
 }
+
+#[inline]
+pub fn n_draw_calls_this_frame(window: &Render_Window_Handle) -> u32 {
+    backend::n_draw_calls_this_frame(window)
+}
@@ -0,0 +1,468 @@
+use crate::material::Material;
+use crate::render;
+use inle_common::colors;
+use inle_core::env::Env_Info;
+use inle_math::rect::{self, Rect, Rectf};
+use inle_math::vector::Vec2f;
+use inle_resources::gfx::{shader_path, tex_path, Gfx_Resources, Shader_Cache};
+use inle_serialize::{Binary_Serializable, Byte_Stream};
+use std::io;
+
+/// Index of a tile inside the tileset, plus one (0 is reserved for EMPTY_TILE).
+/// Tiles are numbered left-to-right, top-to-bottom.
+pub type Tile_Id = u16;
+
+pub const EMPTY_TILE: Tile_Id = 0;
+
+/// A grid of equally-sized tiles, all drawn from the same tileset texture.
+/// The tilemap's pivot is its top-left corner, and tiles are always axis-aligned: only the
+/// position of the owning entity is taken into account.
+#[derive(Clone, Debug)]
+pub struct C_Tilemap {
+    pub material: Material,
+    pub modulate: colors::Color,
+    pub z_index: render::Z_Index,
+
+    /// Side of a tile, both in the tileset texture and in the world.
+    tile_size: u32,
+    /// How many tiles fit in a row of the tileset texture.
+    tileset_columns: u32,
+    /// The tilemap is drawn in chunks of this size (in world units), aligned to multiples
+    /// of it in world space. A tile belongs to the chunk containing its top-left corner.
+    chunk_size: Vec2f,
+
+    width: u32,
+    height: u32,
+    /// Row-major
+    tiles: Vec<Tile_Id>,
+    solid_tiles: Vec<Tile_Id>,
+
+    /// Set whenever a tile edit changes which cells are solid. Whoever keeps data derived from the
+    /// solid cells (e.g. the colliders) should rebuild it and reset this flag.
+    pub solidity_changed: bool,
+}
+
+impl C_Tilemap {
+    pub fn new_with_tileset(
+        gres: &mut Gfx_Resources,
+        env: &Env_Info,
+        tileset: &str,
+        tile_size: u32,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        assert!(tile_size > 0);
+
+        let texture = gres.load_texture(&tex_path(env, tileset));
+        let (tw, _) = render::get_texture_size(gres.get_texture(texture));
+        C_Tilemap {
+            material: Material {
+                texture,
+                specular_color: colors::WHITE,
+                ..Default::default()
+            },
+            modulate: colors::WHITE,
+            z_index: 0,
+            tile_size,
+            tileset_columns: (tw / tile_size).max(1),
+            chunk_size: v2!(1., 1.) * (tile_size * 8) as f32,
+            width,
+            height,
+            tiles: vec![EMPTY_TILE; (width * height) as usize],
+            solid_tiles: vec![],
+            solidity_changed: true,
+        }
+    }
+
+    pub fn with_normals(mut self, gres: &mut Gfx_Resources, env: &Env_Info, normals: &str) -> Self {
+        let texture = gres.load_texture(&tex_path(env, normals));
+        self.material.normals = texture;
+        self
+    }
+
+    pub fn with_shader(
+        mut self,
+        shader_cache: &mut Shader_Cache,
+        env: &Env_Info,
+        shader: &str,
+    ) -> Self {
+        let shader = shader_cache.load_shader(&shader_path(env, shader));
+        self.material.shader = shader;
+        self
+    }
+
+    pub fn with_z_index(mut self, z_index: render::Z_Index) -> Self {
+        self.z_index = z_index;
+        self
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: Vec2f) -> Self {
+        assert!(chunk_size.x > 0. && chunk_size.y > 0.);
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Sets which tiles block movement.
+    pub fn with_solid_tiles(mut self, solid_tiles: &[Tile_Id]) -> Self {
+        self.solid_tiles = solid_tiles.to_vec();
+        self.solidity_changed = true;
+        self
+    }
+
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[inline]
+    pub fn tile_size(&self) -> u32 {
+        self.tile_size
+    }
+
+    #[inline]
+    pub fn get_tile(&self, x: u32, y: u32) -> Tile_Id {
+        debug_assert!(x < self.width && y < self.height);
+        self.tiles[(y * self.width + x) as usize]
+    }
+
+    pub fn set_tile(&mut self, x: u32, y: u32, tile: Tile_Id) {
+        assert!(
+            x < self.width && y < self.height,
+            "Tile ({}, {}) is out of bounds for tilemap of size {}x{}",
+            x,
+            y,
+            self.width,
+            self.height
+        );
+
+        let idx = (y * self.width + x) as usize;
+        if self.is_solid(self.tiles[idx]) != self.is_solid(tile) {
+            self.solidity_changed = true;
+        }
+        self.tiles[idx] = tile;
+    }
+
+    #[inline]
+    pub fn is_solid(&self, tile: Tile_Id) -> bool {
+        tile != EMPTY_TILE && self.solid_tiles.contains(&tile)
+    }
+
+    /// Returns the rect to sample from the tileset texture to draw `tile`.
+    pub fn tile_tex_rect(&self, tile: Tile_Id) -> Rect<i32> {
+        debug_assert!(tile != EMPTY_TILE);
+        let idx = (tile - 1) as u32;
+        let ts = self.tile_size as i32;
+        Rect::new(
+            (idx % self.tileset_columns) as i32 * ts,
+            (idx / self.tileset_columns) as i32 * ts,
+            ts,
+            ts,
+        )
+    }
+
+    /// Returns the center of the tile (x, y), relative to the tilemap's top-left corner.
+    #[inline]
+    pub fn tile_local_center(&self, x: u32, y: u32) -> Vec2f {
+        v2!(x as f32 + 0.5, y as f32 + 0.5) * self.tile_size as f32
+    }
+
+    /// Returns the ranges of tiles (in tile coordinates) of all the chunks that may be visible
+    /// within `visible`, given that the tilemap's top-left corner is at `pos`.
+    /// Every tile belongs to exactly one of the returned chunks.
+    pub fn visible_chunks(&self, pos: Vec2f, visible: &Rectf) -> Vec<Rect<u32>> {
+        trace!("tilemap::visible_chunks");
+
+        let ts = self.tile_size as f32;
+        let bounds = Rect::from_topleft_size(pos, v2!(self.width as f32, self.height as f32) * ts);
+        // Since a tile belongs to the chunk containing its top-left corner, a tile may be visible
+        // even if its chunk is up to one tile above or to the left of the visible rect.
+        let visible = Rect::new(
+            visible.x - ts,
+            visible.y - ts,
+            visible.width + ts,
+            visible.height + ts,
+        );
+        let area = match rect::rects_intersection(&bounds, &visible) {
+            Some(area) => area,
+            None => return vec![],
+        };
+
+        let Vec2f { x: cw, y: ch } = self.chunk_size;
+        let first_chunk_x = (area.x / cw).floor() as i32;
+        let first_chunk_y = (area.y / ch).floor() as i32;
+        let last_chunk_x = ((area.x + area.width) / cw).floor() as i32;
+        let last_chunk_y = ((area.y + area.height) / ch).floor() as i32;
+
+        // Index of the first tile whose top-left corner is at or after `world` along an axis
+        let first_tile_after = |world: f32, origin: f32, n_tiles: u32| -> u32 {
+            (((world - origin) / ts).ceil().max(0.) as u32).min(n_tiles)
+        };
+
+        let mut chunks = vec![];
+        for cy in first_chunk_y..=last_chunk_y {
+            let y0 = first_tile_after(cy as f32 * ch, pos.y, self.height);
+            let y1 = first_tile_after((cy + 1) as f32 * ch, pos.y, self.height);
+            if y0 >= y1 {
+                continue;
+            }
+            for cx in first_chunk_x..=last_chunk_x {
+                let x0 = first_tile_after(cx as f32 * cw, pos.x, self.width);
+                let x1 = first_tile_after((cx + 1) as f32 * cw, pos.x, self.width);
+                if x0 < x1 {
+                    chunks.push(Rect::new(x0, y0, x1 - x0, y1 - y0));
+                }
+            }
+        }
+
+        chunks
+    }
+
+    /// Returns a set of non-overlapping rects (in tile coordinates) covering exactly all the
+    /// solid tiles. Adjacent solid tiles are greedily merged into as few rects as possible,
+    /// growing each rect first horizontally and then vertically.
+    pub fn solid_rects(&self) -> Vec<Rect<u32>> {
+        trace!("tilemap::solid_rects");
+
+        let (w, h) = (self.width as usize, self.height as usize);
+        let mut covered = vec![false; w * h];
+        let is_free_solid = |covered: &[bool], x: usize, y: usize| {
+            !covered[y * w + x] && self.is_solid(self.tiles[y * w + x])
+        };
+
+        let mut rects = vec![];
+        for y in 0..h {
+            for x in 0..w {
+                if !is_free_solid(&covered, x, y) {
+                    continue;
+                }
+
+                let mut rw = 1;
+                while x + rw < w && is_free_solid(&covered, x + rw, y) {
+                    rw += 1;
+                }
+
+                let mut rh = 1;
+                while y + rh < h && (x..x + rw).all(|rx| is_free_solid(&covered, rx, y + rh)) {
+                    rh += 1;
+                }
+
+                for ry in y..y + rh {
+                    for rx in x..x + rw {
+                        covered[ry * w + rx] = true;
+                    }
+                }
+
+                rects.push(Rect::new(x as u32, y as u32, rw as u32, rh as u32));
+            }
+        }
+
+        rects
+    }
+}
+
+/// Note that resources are saved by handle, so they must be loaded before deserializing.
+impl Binary_Serializable for C_Tilemap {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.material.serialize(output)?;
+        self.modulate.serialize(output)?;
+        self.z_index.serialize(output)?;
+        output.write_u32(self.tile_size)?;
+        output.write_u32(self.tileset_columns)?;
+        self.chunk_size.serialize(output)?;
+        output.write_u32(self.width)?;
+        output.write_u32(self.height)?;
+        self.tiles.serialize(output)?;
+        self.solid_tiles.serialize(output)?;
+        self.solidity_changed.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        let material = Material::deserialize(input)?;
+        let modulate = colors::Color::deserialize(input)?;
+        let z_index = render::Z_Index::deserialize(input)?;
+        let tile_size = input.read_u32()?;
+        let tileset_columns = input.read_u32()?;
+        let chunk_size = Vec2f::deserialize(input)?;
+        let width = input.read_u32()?;
+        let height = input.read_u32()?;
+        let tiles = Vec::deserialize(input)?;
+        if tile_size == 0
+            || tileset_columns == 0
+            || chunk_size.x <= 0.
+            || chunk_size.y <= 0.
+            || tiles.len() != width as usize * height as usize
+        {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+
+        Ok(Self {
+            material,
+            modulate,
+            z_index,
+            tile_size,
+            tileset_columns,
+            chunk_size,
+            width,
+            height,
+            tiles,
+            solid_tiles: Vec::deserialize(input)?,
+            solidity_changed: bool::deserialize(input)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tilemap_from_rows(rows: &[&str]) -> C_Tilemap {
+        let width = rows[0].len() as u32;
+        let height = rows.len() as u32;
+        let mut tilemap = C_Tilemap {
+            material: Material::default(),
+            modulate: colors::WHITE,
+            z_index: 0,
+            tile_size: 10,
+            tileset_columns: 2,
+            chunk_size: v2!(30., 30.),
+            width,
+            height,
+            tiles: vec![EMPTY_TILE; (width * height) as usize],
+            solid_tiles: vec![1],
+            solidity_changed: false,
+        };
+        for (y, row) in rows.iter().enumerate() {
+            for (x, chr) in row.chars().enumerate() {
+                let tile = match chr {
+                    '#' => 1,
+                    'o' => 2,
+                    _ => EMPTY_TILE,
+                };
+                tilemap.set_tile(x as u32, y as u32, tile);
+            }
+        }
+        tilemap
+    }
+
+    fn covered_cells(rects: &[Rect<u32>]) -> Vec<(u32, u32)> {
+        let mut cells = vec![];
+        for r in rects {
+            for y in r.y..r.y + r.height {
+                for x in r.x..r.x + r.width {
+                    cells.push((x, y));
+                }
+            }
+        }
+        cells.sort_unstable();
+        cells
+    }
+
+    #[test]
+    fn solid_rects_cover_exactly_solid_tiles() {
+        let tilemap = tilemap_from_rows(&[
+            "##########",
+            "#...o....#",
+            "#..###...#",
+            "#..###..o#",
+            "##########",
+        ]);
+
+        let rects = tilemap.solid_rects();
+
+        let mut expected = vec![];
+        for y in 0..tilemap.height() {
+            for x in 0..tilemap.width() {
+                if tilemap.is_solid(tilemap.get_tile(x, y)) {
+                    expected.push((x, y));
+                }
+            }
+        }
+        expected.sort_unstable();
+
+        let covered = covered_cells(&rects);
+        // No overlaps
+        let mut deduped = covered.clone();
+        deduped.dedup();
+        assert_eq!(covered.len(), deduped.len());
+        assert_eq!(covered, expected);
+
+        // Top row, the two side columns, the block (which grows down into the bottom row)
+        // and the two halves of the bottom row.
+        assert_eq!(rects.len(), 6);
+    }
+
+    #[test]
+    fn set_tile_tracks_solidity_changes() {
+        let mut tilemap = tilemap_from_rows(&["#.", ".."]);
+        tilemap.solidity_changed = false;
+
+        // Non-solid to non-solid
+        tilemap.set_tile(1, 0, 2);
+        assert!(!tilemap.solidity_changed);
+
+        tilemap.set_tile(1, 1, 1);
+        assert!(tilemap.solidity_changed);
+        assert_eq!(
+            tilemap.solid_rects(),
+            vec![Rect::new(0, 0, 1, 1), Rect::new(1, 1, 1, 1)]
+        );
+    }
+
+    #[test]
+    fn visible_chunks_partition_tiles() {
+        let tilemap = tilemap_from_rows(&["#######"; 7]);
+        let pos = v2!(-15., 5.);
+        let everything = Rect::new(-1000., -1000., 2000., 2000.);
+
+        let chunks = tilemap.visible_chunks(pos, &everything);
+        let covered = covered_cells(&chunks);
+        let mut deduped = covered.clone();
+        deduped.dedup();
+        assert_eq!(covered.len(), deduped.len());
+        assert_eq!(covered.len(), 49);
+
+        // Every tile must be in the world chunk containing its top-left corner
+        for chunk in &chunks {
+            let topleft = |x: u32, y: u32| pos + v2!(x as f32, y as f32) * 10.;
+            let chunk_of = |p: Vec2f| ((p.x / 30.).floor() as i32, (p.y / 30.).floor() as i32);
+            let expected = chunk_of(topleft(chunk.x, chunk.y));
+            for y in chunk.y..chunk.y + chunk.height {
+                for x in chunk.x..chunk.x + chunk.width {
+                    assert_eq!(chunk_of(topleft(x, y)), expected);
+                }
+            }
+        }
+
+        // Only the chunks around the visible rect are returned
+        let visible = Rect::new(0., 10., 5., 5.);
+        let chunks = tilemap.visible_chunks(pos, &visible);
+        assert!(!chunks.is_empty());
+        assert!(covered_cells(&chunks).len() < 49);
+        assert!(covered_cells(&chunks).contains(&(1, 0)));
+
+        let nowhere = Rect::new(500., 500., 10., 10.);
+        assert!(tilemap.visible_chunks(pos, &nowhere).is_empty());
+    }
+
+    #[test]
+    fn serialize_deserialize_tilemap() {
+        let tilemap = tilemap_from_rows(&["#o.", ".#."]);
+
+        let mut byte_stream = Byte_Stream::new();
+        tilemap.serialize(&mut byte_stream).unwrap();
+        byte_stream.seek(0);
+        let deser = C_Tilemap::deserialize(&mut byte_stream).unwrap();
+
+        assert_eq!(deser.width(), 3);
+        assert_eq!(deser.height(), 2);
+        assert_eq!(deser.tiles, tilemap.tiles);
+        assert_eq!(deser.solid_tiles, tilemap.solid_tiles);
+        assert_eq!(deser.chunk_size, tilemap.chunk_size);
+        assert_eq!(deser.tile_tex_rect(2), Rect::new(10, 0, 10, 10));
+    }
+}
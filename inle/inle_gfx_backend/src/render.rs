@@ -8,16 +8,29 @@ pub mod sfml;
 #[cfg(feature = "gfx-null")]
 pub mod null;
 
-#[cfg(feature = "gfx-gl")]
+#[cfg(all(feature = "gfx-soft", not(feature = "gfx-null")))]
+pub mod soft;
+
+#[cfg(all(
+    feature = "gfx-gl",
+    not(any(feature = "gfx-null", feature = "gfx-soft"))
+))]
 pub mod gl;
 
 #[cfg(feature = "gfx-sfml")]
 pub use self::sfml as backend;
 
+// Note: the null and soft backends take precedence over gl, since the latter is enabled by default.
 #[cfg(feature = "gfx-null")]
 pub use self::null as backend;
 
-#[cfg(feature = "gfx-gl")]
+#[cfg(all(feature = "gfx-soft", not(feature = "gfx-null")))]
+pub use self::soft as backend;
+
+#[cfg(all(
+    feature = "gfx-gl",
+    not(any(feature = "gfx-null", feature = "gfx-soft"))
+))]
 pub use self::gl as backend;
 
 pub type Text = backend::Text;
@@ -63,6 +76,10 @@ pub trait Uniform_Value: Copy {
 }
 
 pub use backend::geom_shaders_are_available;
+pub use backend::get_image_bit_depth;
+pub use backend::get_image_bytes;
+pub use backend::get_image_color_type;
+pub use backend::get_image_size;
 pub use backend::get_texture_size;
 pub use backend::new_image;
 pub use backend::new_image_with_data;
@@ -780,6 +780,21 @@ pub fn get_image_size(image: &Image) -> (u32, u32) {
     (image.width, image.height)
 }
 
+#[inline]
+pub fn get_image_bytes(image: &Image) -> &[u8] {
+    &image.bytes
+}
+
+#[inline]
+pub fn get_image_color_type(image: &Image) -> Color_Type {
+    image.color_type
+}
+
+#[inline]
+pub fn get_image_bit_depth(image: &Image) -> u8 {
+    image.bit_depth
+}
+
 #[inline]
 pub fn get_text_string(text: &Text) -> &str {
     &text.string
@@ -1339,10 +1354,13 @@ pub fn set_texture_smooth(texture: &mut Texture, smooth: bool) {
 
 pub struct Uniform_Buffer {
     pub id: GLuint,
-    pub block_index: GLuint,
+    /// The binding point this buffer is bound to. Each buffer has its own.
+    pub binding: GLuint,
     pub mem: *mut u8,
     pub layout: std::alloc::Layout,
     pub needs_transfer_to_gpu: Cell<bool>,
+    /// False until something is written into `mem`, which is uninitialized before that.
+    pub written: bool,
 
     #[cfg(debug_assertions)]
     name: &'static CStr,
@@ -1355,12 +1373,13 @@ pub fn create_or_get_uniform_buffer<'window>(
 ) -> &'window mut Uniform_Buffer {
     trace!("create_or_get_uniform_buffer");
 
-    window
+    let block_index = unsafe { glcheck!(gl::GetUniformBlockIndex(shader.id, name.as_ptr())) };
+    let binding = window.gl.uniform_buffers.len() as GLuint;
+    let ubo = window
         .gl
         .uniform_buffers
         .entry(name)
         .or_insert_with(|| unsafe {
-            let block_index = glcheck!(gl::GetUniformBlockIndex(shader.id, name.as_ptr()));
             let mut block_size = 0;
             glcheck!(gl::GetActiveUniformBlockiv(
                 shader.id,
@@ -1380,14 +1399,25 @@ pub fn create_or_get_uniform_buffer<'window>(
 
             Uniform_Buffer {
                 id,
-                block_index,
+                binding,
                 mem,
                 layout,
                 needs_transfer_to_gpu: Cell::new(true),
+                written: false,
                 #[cfg(debug_assertions)]
                 name,
             }
-        })
+        });
+
+    // The same buffer may be used by several shaders, each with its own block index,
+    // so we must tell each of them where to find it (unless they don't use it at all).
+    if block_index != gl::INVALID_INDEX {
+        unsafe {
+            glcheck!(gl::UniformBlockBinding(shader.id, block_index, ubo.binding));
+        }
+    }
+
+    ubo
 }
 
 #[inline]
@@ -1407,6 +1437,7 @@ pub unsafe fn write_into_uniform_buffer(
     debug_assert!(!data.is_null());
 
     ubo.needs_transfer_to_gpu.set(true);
+    ubo.written = true;
 
     let write_p = ubo.mem.add(offset);
     let align_offset = write_p.align_offset(align);
@@ -1463,11 +1494,7 @@ pub fn bind_uniform_buffer(ubo: &Uniform_Buffer) {
             ubo.needs_transfer_to_gpu.set(false);
         }
 
-        glcheck!(gl::BindBufferBase(
-            gl::UNIFORM_BUFFER,
-            ubo.block_index,
-            ubo.id
-        ));
+        glcheck!(gl::BindBufferBase(gl::UNIFORM_BUFFER, ubo.binding, ubo.id));
     }
 }
 
@@ -1476,6 +1503,11 @@ pub fn uniform_buffer_needs_transfer_to_gpu(ubo: &Uniform_Buffer) -> bool {
     ubo.needs_transfer_to_gpu.get()
 }
 
+#[inline]
+pub fn uniform_buffer_was_written(ubo: &Uniform_Buffer) -> bool {
+    ubo.written
+}
+
 // -----------------------------------------------------------------------
 
 fn use_rect_shader_internal(color: Color, rect: &Rect<f32>, mvp: &Matrix3<f32>, shader: GLuint) {
@@ -1,74 +1,195 @@
-use super::{Primitive_Type, Render_Extra_Params};
+use super::{Primitive_Type, Uniform_Value};
 use crate::render_window::Render_Window_Handle;
-use inle_common::colors::{self, Color};
+use inle_common::colors::{self, Color, Color3};
 use inle_common::paint_props::Paint_Properties;
+use inle_math::matrix::Matrix3;
 use inle_math::rect::Rect;
 use inle_math::shapes;
 use inle_math::transform::Transform2D;
 use inle_math::vector::Vec2f;
+use std::cell::Cell;
+use std::ffi::CStr;
 use std::marker::PhantomData;
+use std::mem;
+
+// The null backend does no rendering at all, but it keeps all the CPU-side data that the rest
+// of the engine may read back (vertices, image pixels, texture and text sizes, uniform buffers),
+// so the game behaves the same as with a real backend.
+
+pub type Vertex = crate::backend_common::types::Vertex;
 
 pub struct Vertex_Buffer {
-    cur_vertices: u32,
     max_vertices: u32,
     primitive_type: Primitive_Type,
+    vertices: Vec<Vertex>,
+}
+
+#[derive(Copy, Clone, Debug)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Color_Type {
+    Grayscale,
+    RGB,
+    Indexed,
+    Grayscale_Alpha,
+    RGBA,
+}
+
+impl Color_Type {
+    fn n_channels(self) -> usize {
+        match self {
+            Color_Type::Grayscale | Color_Type::Indexed => 1,
+            Color_Type::Grayscale_Alpha => 2,
+            Color_Type::RGB => 3,
+            Color_Type::RGBA => 4,
+        }
+    }
 }
 
-#[derive(Copy, Clone)]
-pub struct Vertex {
-    pub position: Vec2f,
-    pub color: Color,
-    pub tex_coords: Vec2f,
+pub struct Image {
+    bytes: Vec<u8>,
+
+    width: u32,
+    height: u32,
+    color_type: Color_Type,
+    bit_depth: u8,
 }
-pub type Image = ();
+
+#[derive(Debug)]
+pub struct Texture<'a> {
+    width: u32,
+    height: u32,
+
+    _pd: PhantomData<&'a ()>,
+}
+
 pub struct Shader<'texture> {
     _pd: PhantomData<&'texture ()>,
 }
-pub struct Texture<'a> {
-    _pd: PhantomData<&'a ()>,
+
+impl Uniform_Value for f32 {
+    fn apply_to(self, _shader: &mut Shader, _name: &CStr) {}
 }
+
+impl Uniform_Value for Vec2f {
+    fn apply_to(self, _shader: &mut Shader, _name: &CStr) {}
+}
+
+impl Uniform_Value for &Matrix3<f32> {
+    fn apply_to(self, _shader: &mut Shader, _name: &CStr) {}
+}
+
+impl Uniform_Value for Color {
+    fn apply_to(self, _shader: &mut Shader, _name: &CStr) {}
+}
+
+impl Uniform_Value for Color3 {
+    fn apply_to(self, _shader: &mut Shader, _name: &CStr) {}
+}
+
+impl Uniform_Value for &Texture<'_> {
+    fn apply_to(self, _shader: &mut Shader, _name: &CStr) {}
+}
+
+#[inline]
+pub fn use_shader(_shader: &mut Shader) {}
+
 pub struct Font<'a> {
-    _pd: PhantomData<&'a ()>,
+    pub atlas: Texture<'a>,
+    pub metadata: Font_Metadata,
 }
-pub struct Text<'font> {
-    _pd: PhantomData<&'font ()>,
+
+pub struct Font_Metadata {
+    // @Temporary: we want to support more than ASCII
+    glyph_data: [Glyph_Data; 256],
+    pub atlas_size: (u32, u32),
+    pub max_glyph_height: f32,
 }
 
-// @Cleanup
-// we should actually have API functions for these, not methods...
-impl Texture<'_> {
-    pub fn from_file(_: &str) -> Option<Self> {
-        Some(Self { _pd: PhantomData })
+impl Font_Metadata {
+    pub fn with_atlas_size(width: u32, height: u32) -> Self {
+        Self {
+            atlas_size: (width, height),
+            glyph_data: [Glyph_Data::default(); 256],
+            max_glyph_height: 0.,
+        }
     }
-}
 
-impl Font<'_> {
-    pub fn from_file(_: &str) -> Option<Self> {
-        Some(Self { _pd: PhantomData })
+    pub fn add_glyph_data(&mut self, glyph_id: char, data: Glyph_Data) {
+        if (glyph_id as usize) < 256 {
+            self.glyph_data[glyph_id as usize] = data;
+            if data.plane_bounds.height() > self.max_glyph_height {
+                self.max_glyph_height = data.plane_bounds.height();
+            }
+        }
     }
-}
 
-impl Shader<'_> {
-    pub fn from_memory(_: Option<&str>, _: Option<&str>, _: Option<&str>) -> Option<Self> {
-        Some(Self { _pd: PhantomData })
+    fn get_glyph_data(&self, glyph: char) -> Option<&Glyph_Data> {
+        self.glyph_data.get(glyph as usize)
     }
 
-    pub fn from_file(_: Option<&str>, _: Option<&str>, _: Option<&str>) -> Option<Self> {
-        Some(Self { _pd: PhantomData })
+    fn scale_factor(&self, font_size: f32) -> f32 {
+        if self.max_glyph_height > 0. {
+            font_size / self.max_glyph_height
+        } else {
+            0.
+        }
     }
 }
 
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Glyph_Data {
+    pub advance: f32,
+
+    /// Bounding box relative to the baseline
+    pub plane_bounds: Glyph_Bounds,
+
+    /// Normalized coordinates (uv) inside atlas
+    pub normalized_atlas_bounds: Glyph_Bounds,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Glyph_Bounds {
+    pub left: f32,
+    pub bot: f32,
+    pub right: f32,
+    pub top: f32,
+}
+
+impl Glyph_Bounds {
+    fn height(&self) -> f32 {
+        self.top - self.bot
+    }
+}
+
+pub struct Text {
+    string: String,
+    size: Vec2f,
+}
+
+pub fn new_shader_internal(_vert_src: &[u8], _frag_src: &[u8], _shader_name: &str) -> u32 {
+    0
+}
+
+pub fn new_shader<'a>(
+    _vert_src: &[u8],
+    _frag_src: &[u8],
+    _shader_name: Option<&str>,
+) -> Shader<'a> {
+    Shader { _pd: PhantomData }
+}
+
 pub fn fill_color_rect<R>(
-    _window: &mut Render_Window_Handle,
+    window: &mut Render_Window_Handle,
     _paint_props: &Paint_Properties,
     _rect: R,
 ) where
     R: Into<Rect<f32>> + Copy + Clone + std::fmt::Debug,
 {
+    window.count_draw_call();
 }
 
 pub fn fill_color_rect_ws<T>(
-    _window: &mut Render_Window_Handle,
+    window: &mut Render_Window_Handle,
     _paint_props: &Paint_Properties,
     _rect: T,
     _transform: &Transform2D,
@@ -76,224 +197,434 @@ pub fn fill_color_rect_ws<T>(
 ) where
     T: std::convert::Into<Rect<f32>> + Copy + Clone + std::fmt::Debug,
 {
+    window.count_draw_call();
 }
 
 pub fn fill_color_circle(
-    _window: &mut Render_Window_Handle,
+    window: &mut Render_Window_Handle,
     _paint_props: &Paint_Properties,
     _circle: shapes::Circle,
 ) {
+    window.count_draw_call();
 }
 
 pub fn fill_color_circle_ws(
-    _window: &mut Render_Window_Handle,
+    window: &mut Render_Window_Handle,
     _paint_props: &Paint_Properties,
     _circle: shapes::Circle,
     _camera: &Transform2D,
 ) {
+    window.count_draw_call();
 }
 
 pub fn render_text(
-    _window: &mut Render_Window_Handle,
-    _text: &mut Text,
+    window: &mut Render_Window_Handle,
+    text: &Text,
     _paint_props: &Paint_Properties,
     _screen_pos: Vec2f,
 ) {
+    if !text.string.is_empty() {
+        window.count_draw_call();
+    }
 }
 
 pub fn render_text_ws(
-    _window: &mut Render_Window_Handle,
-    _text: &mut Text,
+    window: &mut Render_Window_Handle,
+    text: &Text,
     _paint_props: &Paint_Properties,
     _transform: &Transform2D,
     _camera: &Transform2D,
 ) {
+    if !text.string.is_empty() {
+        window.count_draw_call();
+    }
 }
 
-pub fn get_texture_size(_texture: &Texture) -> (u32, u32) {
-    (1, 1)
+#[inline]
+pub fn get_texture_size(texture: &Texture) -> (u32, u32) {
+    (texture.width, texture.height)
 }
 
-pub fn get_image_size(_image: &Image) -> (u32, u32) {
-    (1, 1)
+#[inline]
+pub fn get_image_size(image: &Image) -> (u32, u32) {
+    (image.width, image.height)
 }
 
-pub fn get_text_size(_text: &Text) -> Vec2f {
-    Vec2f::default()
+#[inline]
+pub fn get_image_bytes(image: &Image) -> &[u8] {
+    &image.bytes
 }
 
-pub fn vbuf_primitive_type(vbuf: &Vertex_Buffer) -> Primitive_Type {
-    vbuf.primitive_type
+#[inline]
+pub fn get_image_color_type(image: &Image) -> Color_Type {
+    image.color_type
 }
 
-pub fn new_image(_width: u32, _height: u32) -> Image {
-    ()
+#[inline]
+pub fn get_image_bit_depth(image: &Image) -> u8 {
+    image.bit_depth
 }
 
-pub fn new_vbuf(primitive: Primitive_Type, n_vertices: u32) -> Vertex_Buffer {
-    Vertex_Buffer {
-        cur_vertices: 0,
-        max_vertices: n_vertices,
-        primitive_type: primitive,
-    }
+#[inline]
+pub fn get_text_string(text: &Text) -> &str {
+    &text.string
 }
 
-pub fn start_draw_quads(n_quads: u32) -> Vertex_Buffer {
-    Vertex_Buffer {
-        cur_vertices: 0,
-        max_vertices: n_quads * 4,
-        primitive_type: Primitive_Type::Quads,
-    }
+#[inline]
+pub fn get_text_size(text: &Text) -> Vec2f {
+    text.size
 }
 
-pub fn start_draw_triangles(n_tris: u32) -> Vertex_Buffer {
-    Vertex_Buffer {
-        cur_vertices: 0,
-        max_vertices: n_tris * 3,
-        primitive_type: Primitive_Type::Triangles,
+#[inline]
+pub fn new_image(width: u32, height: u32, color_type: Color_Type) -> Image {
+    Image {
+        width,
+        height,
+        bytes: vec![0; color_type.n_channels() * (width * height) as usize],
+        color_type,
+        bit_depth: 8,
     }
 }
 
-pub fn start_draw_lines(n_lines: u32) -> Vertex_Buffer {
-    Vertex_Buffer {
-        cur_vertices: 0,
-        max_vertices: n_lines * 2,
-        primitive_type: Primitive_Type::Lines,
+#[inline]
+pub fn new_image_with_data(
+    width: u32,
+    height: u32,
+    color_type: Color_Type,
+    bit_depth: u8,
+    bytes: Vec<u8>,
+) -> Image {
+    Image {
+        bytes,
+        width,
+        height,
+        color_type,
+        bit_depth,
     }
 }
 
-pub fn start_draw_linestrip(n_vertices: u32) -> Vertex_Buffer {
-    Vertex_Buffer {
-        cur_vertices: 0,
-        max_vertices: n_vertices,
-        primitive_type: Primitive_Type::Line_Strip,
-    }
+#[inline(always)]
+pub fn vbuf_primitive_type(vbuf: &Vertex_Buffer) -> Primitive_Type {
+    vbuf.primitive_type
 }
 
-pub fn start_draw_points(n_vertices: u32) -> Vertex_Buffer {
+#[inline]
+pub fn new_vbuf_temp(
+    _window: &mut Render_Window_Handle,
+    primitive: Primitive_Type,
+    n_vertices: u32,
+) -> Vertex_Buffer {
     Vertex_Buffer {
-        cur_vertices: 0,
         max_vertices: n_vertices,
-        primitive_type: Primitive_Type::Points,
+        primitive_type: primitive,
+        vertices: Vec::with_capacity(n_vertices as usize),
     }
 }
 
-pub fn add_quad(vbuf: &mut Vertex_Buffer, _v1: &Vertex, _v2: &Vertex, _v3: &Vertex, _v4: &Vertex) {
-    vbuf.cur_vertices += 4;
+#[inline]
+pub fn new_vbuf(
+    window: &mut Render_Window_Handle,
+    primitive: Primitive_Type,
+    n_vertices: u32,
+) -> Vertex_Buffer {
+    new_vbuf_temp(window, primitive, n_vertices)
 }
 
-pub fn add_triangle(vbuf: &mut Vertex_Buffer, _v1: &Vertex, _v2: &Vertex, _v3: &Vertex) {
-    vbuf.cur_vertices += 3;
+#[inline]
+pub fn dealloc_vbuf(vbuf: &mut Vertex_Buffer) {
+    vbuf.max_vertices = 0;
+    vbuf.vertices.clear();
 }
 
-pub fn add_line(vbuf: &mut Vertex_Buffer, _from: &Vertex, _to: &Vertex) {
-    vbuf.cur_vertices += 2;
+#[inline]
+pub fn add_vertices(vbuf: &mut Vertex_Buffer, vertices: &[Vertex]) {
+    update_vbuf(vbuf, vertices, vbuf_cur_vertices(vbuf));
 }
 
-pub fn add_vertex(vbuf: &mut Vertex_Buffer, _v: &Vertex) {
-    vbuf.cur_vertices += 1;
-}
+#[inline]
+pub fn update_vbuf(vbuf: &mut Vertex_Buffer, vertices: &[Vertex], offset: u32) {
+    vbuf.vertices.truncate(offset as usize);
 
-pub fn update_vbuf(_vbuf: &mut Vertex_Buffer, _vertices: &[Vertex], _offset: u32) {}
+    let space_remaining = vbuf.max_vertices as usize - vbuf.vertices.len();
+    let vertices_to_copy = vertices.len().min(space_remaining);
+    vbuf.vertices.extend(&vertices[..vertices_to_copy]);
+}
 
+#[inline(always)]
 pub fn vbuf_cur_vertices(vbuf: &Vertex_Buffer) -> u32 {
-    vbuf.cur_vertices
+    vbuf.vertices.len() as _
 }
 
+#[inline(always)]
 pub fn vbuf_max_vertices(vbuf: &Vertex_Buffer) -> u32 {
     vbuf.max_vertices
 }
 
+#[inline(always)]
 pub fn set_vbuf_cur_vertices(vbuf: &mut Vertex_Buffer, cur_vertices: u32) {
-    vbuf.cur_vertices = cur_vertices;
-}
-
-pub fn copy_vbuf_to_vbuf(dest: &mut Vertex_Buffer, src: &Vertex_Buffer) -> bool {
-    dest.cur_vertices = src.cur_vertices;
-    true
+    vbuf.vertices
+        .resize(cur_vertices as usize, Vertex::default());
 }
 
+#[inline]
 pub fn new_vertex(pos: Vec2f, col: Color, tex_coords: Vec2f) -> Vertex {
     Vertex {
         position: pos,
-        color: col,
+        color: col.into(),
         tex_coords,
     }
 }
 
+fn render_vbuf_internal(window: &mut Render_Window_Handle, vbuf: &Vertex_Buffer) {
+    if vbuf_cur_vertices(vbuf) > 0 {
+        window.count_draw_call();
+    }
+}
+
+#[inline]
 pub fn render_vbuf(
-    _window: &mut Render_Window_Handle,
-    _vbuf: &Vertex_Buffer,
+    window: &mut Render_Window_Handle,
+    vbuf: &Vertex_Buffer,
     _transform: &Transform2D,
 ) {
+    render_vbuf_internal(window, vbuf);
 }
 
+#[inline]
 pub fn render_vbuf_ws(
-    _window: &mut Render_Window_Handle,
-    _vbuf: &Vertex_Buffer,
+    window: &mut Render_Window_Handle,
+    vbuf: &Vertex_Buffer,
     _transform: &Transform2D,
     _camera: &Transform2D,
 ) {
+    render_vbuf_internal(window, vbuf);
 }
 
-pub fn render_vbuf_ws_ex(
-    _window: &mut Render_Window_Handle,
-    _vbuf: &Vertex_Buffer,
+#[inline]
+pub fn render_vbuf_ws_with_texture(
+    window: &mut Render_Window_Handle,
+    vbuf: &Vertex_Buffer,
     _transform: &Transform2D,
     _camera: &Transform2D,
-    _extra_params: Render_Extra_Params,
+    _texture: &Texture,
 ) {
+    render_vbuf_internal(window, vbuf);
 }
 
-pub fn render_vbuf_texture(
-    _window: &mut Render_Window_Handle,
-    _vbuf: &Vertex_Buffer,
-    _texture: &Texture,
+#[inline]
+pub fn render_vbuf_with_shader(
+    window: &mut Render_Window_Handle,
+    vbuf: &Vertex_Buffer,
+    _shader: &Shader,
 ) {
+    render_vbuf_internal(window, vbuf);
 }
 
-pub fn create_text<'a>(_string: &str, _font: &'a Font, _size: u16) -> Text<'a> {
-    Text { _pd: PhantomData }
+#[inline]
+pub fn create_text(
+    _window: &mut Render_Window_Handle,
+    string: &str,
+    font: &Font,
+    size: u16,
+) -> Text {
+    // Compute the text size the same way the gl backend does when filling its vertices.
+    let scale_factor = font.metadata.scale_factor(size as f32);
+    let mut text_size = Vec2f::default();
+    let mut pos_x = 0f32;
+    for chr in string.chars() {
+        if let Some(glyph_data) = font.metadata.get_glyph_data(chr) {
+            let pb = &glyph_data.plane_bounds;
+            let right = pos_x + pb.right * scale_factor;
+            let bot = (1.0 - pb.bot) * scale_factor;
+            pos_x += scale_factor * glyph_data.advance;
+
+            text_size.x = text_size.x.max(right);
+            text_size.y = text_size.y.max(bot);
+        }
+    }
+
+    Text {
+        string: String::from(string),
+        size: text_size,
+    }
 }
 
-pub fn render_line(_window: &mut Render_Window_Handle, _start: &Vertex, _end: &Vertex) {}
+#[inline]
+pub fn render_line(window: &mut Render_Window_Handle, _start: &Vertex, _end: &Vertex) {
+    window.count_draw_call();
+}
 
-pub fn copy_texture_to_image(_texture: &Texture) -> Image {}
+#[inline]
+pub fn copy_texture_to_image(texture: &Texture) -> Image {
+    new_image(texture.width, texture.height, Color_Type::RGBA)
+}
 
-pub fn new_texture_from_image(_image: &Image, _rect: Option<Rect<i32>>) -> Option<Texture> {
-    Some(Texture { _pd: PhantomData })
+#[inline]
+pub fn new_texture_from_image<'tex>(image: &Image, _rect: Option<Rect<i32>>) -> Texture<'tex> {
+    Texture {
+        width: image.width,
+        height: image.height,
+        _pd: PhantomData,
+    }
 }
 
-pub fn get_image_pixel(_image: &Image, _x: u32, _y: u32) -> Color {
-    colors::TRANSPARENT
+#[inline]
+pub fn get_image_pixel(image: &Image, x: u32, y: u32) -> Color {
+    debug_assert_eq!(image.bit_depth, 8);
+
+    let b = &image.bytes[..];
+    let i = (image.width * y + x) as usize * image.color_type.n_channels();
+    match image.color_type {
+        Color_Type::Grayscale | Color_Type::Indexed => colors::rgb(b[i], b[i], b[i]),
+        Color_Type::Grayscale_Alpha => colors::rgba(b[i], b[i], b[i], b[i + 1]),
+        Color_Type::RGB => colors::rgb(b[i], b[i + 1], b[i + 2]),
+        Color_Type::RGBA => colors::rgba(b[i], b[i + 1], b[i + 2], b[i + 3]),
+    }
 }
 
-pub fn set_image_pixel(_image: &mut Image, _x: u32, _y: u32, _val: Color) {}
+#[inline]
+pub fn set_image_pixel(image: &mut Image, x: u32, y: u32, val: Color) {
+    debug_assert_eq!(image.bit_depth, 8);
+
+    let i = (y * image.width + x) as usize * image.color_type.n_channels();
+    image.bytes[i] = val.r;
+    match image.color_type {
+        Color_Type::Grayscale => {}
+        Color_Type::Grayscale_Alpha => {
+            image.bytes[i + 1] = val.a;
+        }
+        Color_Type::RGB => {
+            image.bytes[i + 1] = val.g;
+            image.bytes[i + 2] = val.b;
+        }
+        Color_Type::RGBA => {
+            image.bytes[i + 1] = val.g;
+            image.bytes[i + 2] = val.b;
+            image.bytes[i + 3] = val.a;
+        }
+        _ => unimplemented!(),
+    }
+}
 
-pub fn get_image_pixels(_image: &Image) -> &[Color] {
-    &[]
+#[inline]
+pub fn get_image_pixels(image: &Image) -> &[Color] {
+    const_assert!(mem::size_of::<Color>() == 4);
+    debug_assert_eq!(image.bytes.len() % 4, 0);
+    debug_assert_eq!(image.bit_depth, 8);
+    unsafe { std::slice::from_raw_parts(image.bytes.as_ptr() as *const _, image.bytes.len() / 4) }
 }
 
-pub fn swap_vbuf(_a: &mut Vertex_Buffer, _b: &mut Vertex_Buffer) -> bool {
+#[inline]
+pub fn swap_vbuf(a: &mut Vertex_Buffer, b: &mut Vertex_Buffer) -> bool {
+    mem::swap(a, b);
     true
 }
 
+#[inline]
 pub fn update_texture_pixels(_texture: &mut Texture, _rect: &Rect<u32>, _pixels: &[Color]) {}
 
+#[inline]
 pub fn shaders_are_available() -> bool {
-    false
+    true
 }
 
+#[inline]
 pub fn geom_shaders_are_available() -> bool {
     false
 }
 
-pub fn set_uniform_float(_shader: &mut Shader, _name: &str, _val: f32) {}
-
-pub fn set_uniform_vec2(_shader: &mut Shader, _name: &str, _val: Vec2f) {}
-
-pub fn set_uniform_color(_shader: &mut Shader, _name: &str, _val: Color) {}
-
-pub fn set_uniform_texture(_shader: &mut Shader, _name: &str, _val: &Texture) {}
-
+#[inline]
 pub fn set_texture_repeated(_texture: &mut Texture, _repeated: bool) {}
+
+#[inline]
+pub fn set_texture_smooth(_texture: &mut Texture, _smooth: bool) {}
+
+/// Since we have no shader to query the block size from, the buffer grows as it's written into.
+pub struct Uniform_Buffer {
+    mem: Vec<u8>,
+    needs_transfer_to_gpu: Cell<bool>,
+}
+
+pub fn create_or_get_uniform_buffer<'window>(
+    window: &'window mut Render_Window_Handle,
+    _shader: &Shader,
+    name: &'static CStr,
+) -> &'window mut Uniform_Buffer {
+    window
+        .uniform_buffers
+        .entry(name)
+        .or_insert_with(|| Uniform_Buffer {
+            mem: vec![],
+            needs_transfer_to_gpu: Cell::new(true),
+        })
+}
+
+/// # Safety
+/// `data` must be valid: it must be non-null, aligned and it must
+/// correspond to an allocation of at least `size` bytes.
+pub unsafe fn write_into_uniform_buffer(
+    ubo: &mut Uniform_Buffer,
+    offset: usize,
+    align: usize,
+    size: usize,
+    data: *const u8,
+) -> usize {
+    debug_assert!(!data.is_null());
+
+    ubo.needs_transfer_to_gpu.set(true);
+
+    let start = offset + (align - offset % align) % align;
+    let end = start + size;
+    if ubo.mem.len() < end {
+        ubo.mem.resize(end, 0);
+    }
+    std::ptr::copy_nonoverlapping(data, ubo.mem.as_mut_ptr().add(start), size);
+
+    end
+}
+
+pub fn bind_uniform_buffer(ubo: &Uniform_Buffer) {
+    ubo.needs_transfer_to_gpu.set(false);
+}
+
+#[inline]
+pub fn uniform_buffer_needs_transfer_to_gpu(ubo: &Uniform_Buffer) -> bool {
+    ubo.needs_transfer_to_gpu.get()
+}
+
+#[inline]
+pub fn uniform_buffer_was_written(ubo: &Uniform_Buffer) -> bool {
+    !ubo.mem.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_pixels_are_indexed_by_channel_count() {
+        let red = colors::rgba(200, 10, 20, 128);
+        let blue = colors::rgba(1, 2, 250, 64);
+        for &color_type in &[
+            Color_Type::Grayscale_Alpha,
+            Color_Type::RGB,
+            Color_Type::RGBA,
+        ] {
+            let mut image = new_image(3, 2, color_type);
+            set_image_pixel(&mut image, 1, 0, red);
+            set_image_pixel(&mut image, 2, 1, blue);
+
+            let expected = |c: Color| match color_type {
+                Color_Type::Grayscale_Alpha => colors::rgba(c.r, c.r, c.r, c.a),
+                Color_Type::RGB => colors::rgb(c.r, c.g, c.b),
+                _ => c,
+            };
+            assert_eq!(get_image_pixel(&image, 1, 0), expected(red));
+            assert_eq!(get_image_pixel(&image, 2, 1), expected(blue));
+            assert_eq!(
+                get_image_pixel(&image, 0, 0),
+                expected(colors::rgba(0, 0, 0, 0))
+            );
+        }
+    }
+}
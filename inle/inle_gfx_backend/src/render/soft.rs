@@ -0,0 +1,1615 @@
+use super::{Primitive_Type, Uniform_Value};
+use crate::backend_common::types::Glsl_Vec4;
+use crate::render::get_mvp_matrix;
+use crate::render_window::Render_Window_Handle;
+use inle_common::colors::{self, Color, Color3};
+use inle_common::paint_props::Paint_Properties;
+use inle_math::matrix::Matrix3;
+use inle_math::rect::Rect;
+use inle_math::shapes;
+use inle_math::transform::Transform2D;
+use inle_math::vector::Vec2f;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::ffi::{CStr, CString};
+use std::marker::PhantomData;
+use std::mem;
+use std::sync::Arc;
+
+pub(crate) mod raster;
+
+use self::raster::{Fragment_Shader, Rasterizer};
+
+// The soft backend renders everything on the CPU into the window's framebuffer, which is never
+// presented: it exists so rendering can be tested without a GPU.
+// Since it can't run GLSL, every custom shader is emulated by one of a few builtin models
+// (see Shader_Model), while the builtin gl shaders (rect, circle, text, vbuf) are replicated exactly.
+
+pub type Vertex = crate::backend_common::types::Vertex;
+
+pub struct Vertex_Buffer {
+    max_vertices: u32,
+    primitive_type: Primitive_Type,
+    vertices: Vec<Vertex>,
+}
+
+#[derive(Copy, Clone, Debug)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Color_Type {
+    Grayscale,
+    RGB,
+    Indexed,
+    Grayscale_Alpha,
+    RGBA,
+}
+
+impl Color_Type {
+    fn n_channels(self) -> usize {
+        match self {
+            Color_Type::Grayscale | Color_Type::Indexed => 1,
+            Color_Type::Grayscale_Alpha => 2,
+            Color_Type::RGB => 3,
+            Color_Type::RGBA => 4,
+        }
+    }
+}
+
+pub struct Image {
+    bytes: Vec<u8>,
+
+    width: u32,
+    height: u32,
+    color_type: Color_Type,
+    bit_depth: u8,
+}
+
+/// The texture data as seen by the shaders. Pixels are shared, so binding a texture is cheap.
+#[derive(Clone)]
+struct Sampler {
+    width: u32,
+    height: u32,
+    pixels: Arc<Vec<Color>>,
+    repeated: bool,
+    smooth: bool,
+}
+
+impl Sampler {
+    fn sample(&self, uv: Vec2f) -> Glsl_Vec4 {
+        if self.width == 0 || self.height == 0 {
+            return Glsl_Vec4::default();
+        }
+
+        let x = uv.x * self.width as f32;
+        let y = uv.y * self.height as f32;
+        if !self.smooth {
+            return self.texel(x.floor() as i32, y.floor() as i32);
+        }
+
+        let (x, y) = (x - 0.5, y - 0.5);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i32, y0 as i32);
+        let top = lerp_vec4(self.texel(x0, y0), self.texel(x0 + 1, y0), tx);
+        let bot = lerp_vec4(self.texel(x0, y0 + 1), self.texel(x0 + 1, y0 + 1), tx);
+        lerp_vec4(top, bot, ty)
+    }
+
+    fn texel(&self, x: i32, y: i32) -> Glsl_Vec4 {
+        let wrap = |c: i32, size: u32| {
+            if self.repeated {
+                c.rem_euclid(size as i32) as u32
+            } else {
+                c.clamp(0, size as i32 - 1) as u32
+            }
+        };
+        let (x, y) = (wrap(x, self.width), wrap(y, self.height));
+        self.pixels[(y * self.width + x) as usize].into()
+    }
+}
+
+pub struct Texture<'a> {
+    sampler: Sampler,
+
+    _pd: PhantomData<&'a ()>,
+}
+
+impl std::fmt::Debug for Texture<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Texture")
+            .field("width", &self.sampler.width)
+            .field("height", &self.sampler.height)
+            .finish()
+    }
+}
+
+/// How a custom shader is emulated.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Shader_Model {
+    /// Outputs the texture color (like sprite_unlit).
+    Unlit,
+    /// Lights the texture with the lights in the LightsBlock uniform buffer and the shadows in the
+    /// ShadowsBlock one, like sprite_flat (i.e. ignoring normal maps and specular).
+    Lit,
+}
+
+pub struct Shader<'texture> {
+    model: Shader_Model,
+    // Only floats, matrices and textures are read by the shader models we emulate.
+    floats: HashMap<CString, f32>,
+    matrices: HashMap<CString, Matrix3<f32>>,
+    textures: HashMap<CString, Sampler>,
+
+    _pd: PhantomData<&'texture ()>,
+}
+
+impl Uniform_Value for f32 {
+    fn apply_to(self, shader: &mut Shader, name: &CStr) {
+        shader.floats.insert(name.to_owned(), self);
+    }
+}
+
+impl Uniform_Value for Vec2f {
+    fn apply_to(self, _shader: &mut Shader, _name: &CStr) {}
+}
+
+impl Uniform_Value for &Matrix3<f32> {
+    fn apply_to(self, shader: &mut Shader, name: &CStr) {
+        shader.matrices.insert(name.to_owned(), *self);
+    }
+}
+
+impl Uniform_Value for Color {
+    fn apply_to(self, _shader: &mut Shader, _name: &CStr) {}
+}
+
+impl Uniform_Value for Color3 {
+    fn apply_to(self, _shader: &mut Shader, _name: &CStr) {}
+}
+
+impl Uniform_Value for &Texture<'_> {
+    fn apply_to(self, shader: &mut Shader, name: &CStr) {
+        shader
+            .textures
+            .insert(name.to_owned(), self.sampler.clone());
+    }
+}
+
+#[inline]
+pub fn use_shader(_shader: &mut Shader) {}
+
+pub struct Font<'a> {
+    pub atlas: Texture<'a>,
+    pub metadata: Font_Metadata,
+}
+
+pub struct Font_Metadata {
+    // @Temporary: we want to support more than ASCII
+    glyph_data: [Glyph_Data; 256],
+    pub atlas_size: (u32, u32),
+    pub max_glyph_height: f32,
+}
+
+impl Font_Metadata {
+    pub fn with_atlas_size(width: u32, height: u32) -> Self {
+        Self {
+            atlas_size: (width, height),
+            glyph_data: [Glyph_Data::default(); 256],
+            max_glyph_height: 0.,
+        }
+    }
+
+    pub fn add_glyph_data(&mut self, glyph_id: char, data: Glyph_Data) {
+        if (glyph_id as usize) < 256 {
+            self.glyph_data[glyph_id as usize] = data;
+            if data.plane_bounds.height() > self.max_glyph_height {
+                self.max_glyph_height = data.plane_bounds.height();
+            }
+        }
+    }
+
+    fn get_glyph_data(&self, glyph: char) -> Option<&Glyph_Data> {
+        self.glyph_data.get(glyph as usize)
+    }
+
+    fn scale_factor(&self, font_size: f32) -> f32 {
+        if self.max_glyph_height > 0. {
+            font_size / self.max_glyph_height
+        } else {
+            0.
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Glyph_Data {
+    pub advance: f32,
+
+    /// Bounding box relative to the baseline
+    pub plane_bounds: Glyph_Bounds,
+
+    /// Normalized coordinates (uv) inside atlas
+    pub normalized_atlas_bounds: Glyph_Bounds,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Glyph_Bounds {
+    pub left: f32,
+    pub bot: f32,
+    pub right: f32,
+    pub top: f32,
+}
+
+impl Glyph_Bounds {
+    fn height(&self) -> f32 {
+        self.top - self.bot
+    }
+}
+
+pub struct Text {
+    string: String,
+    vertices: Vec<Vertex>,
+    font_atlas: Sampler,
+    size: Vec2f,
+}
+
+pub fn new_shader_internal(_vert_src: &[u8], _frag_src: &[u8], _shader_name: &str) -> u32 {
+    0
+}
+
+pub fn new_shader<'a>(_vert_src: &[u8], frag_src: &[u8], _shader_name: Option<&str>) -> Shader<'a> {
+    const LIGHTS_BLOCK: &[u8] = b"LightsBlock";
+
+    let model = if frag_src
+        .windows(LIGHTS_BLOCK.len())
+        .any(|w| w == LIGHTS_BLOCK)
+    {
+        Shader_Model::Lit
+    } else {
+        Shader_Model::Unlit
+    };
+
+    Shader {
+        model,
+        floats: HashMap::default(),
+        matrices: HashMap::default(),
+        textures: HashMap::default(),
+        _pd: PhantomData,
+    }
+}
+
+fn draw(
+    window: &mut Render_Window_Handle,
+    mvp: &Matrix3<f32>,
+    primitive: Primitive_Type,
+    vertices: &[Vertex],
+    shader: &impl Fragment_Shader,
+) {
+    let mut rasterizer = Rasterizer {
+        framebuffer: &mut window.framebuffer,
+        viewport: window.viewport,
+        mvp,
+    };
+    rasterizer.draw_primitives(primitive, vertices, shader);
+    window.count_draw_call();
+}
+
+fn rect_vertices(rect: &Rect<f32>, color: Color) -> [Vertex; 6] {
+    let v1 = new_vertex(v2!(rect.x, rect.y), color, v2!(0., 0.));
+    let v2 = new_vertex(v2!(rect.x + rect.width, rect.y), color, v2!(1., 0.));
+    let v3 = new_vertex(
+        v2!(rect.x + rect.width, rect.y + rect.height),
+        color,
+        v2!(1., 1.),
+    );
+    let v4 = new_vertex(v2!(rect.x, rect.y + rect.height), color, v2!(0., 1.));
+    [v1, v2, v3, v3, v4, v1]
+}
+
+fn fill_color_rect_internal(
+    window: &mut Render_Window_Handle,
+    paint_props: &Paint_Properties,
+    rect: Rect<f32>,
+    mvp: &Matrix3<f32>,
+) {
+    let shader = |v: &Vertex| Some(v.color);
+
+    // Like the gl backend, the outline also fills the shape.
+    if paint_props.border_thick > 0. {
+        let outline_rect = Rect::new(
+            rect.x - paint_props.border_thick,
+            rect.y - paint_props.border_thick,
+            rect.width + 2. * paint_props.border_thick,
+            rect.height + 2. * paint_props.border_thick,
+        );
+        let vertices = rect_vertices(&outline_rect, paint_props.border_color);
+        draw(window, mvp, Primitive_Type::Triangles, &vertices, &shader);
+    }
+
+    let vertices = rect_vertices(&rect, paint_props.color);
+    draw(window, mvp, Primitive_Type::Triangles, &vertices, &shader);
+}
+
+pub fn fill_color_rect<R>(
+    window: &mut Render_Window_Handle,
+    paint_props: &Paint_Properties,
+    rect: R,
+) where
+    R: Into<Rect<f32>> + Copy + Clone + std::fmt::Debug,
+{
+    let mvp = get_mvp_screen_matrix(window, &Transform2D::default());
+    fill_color_rect_internal(window, paint_props, rect.into(), &mvp);
+}
+
+pub fn fill_color_rect_ws<T>(
+    window: &mut Render_Window_Handle,
+    paint_props: &Paint_Properties,
+    rect: T,
+    transform: &Transform2D,
+    camera: &Transform2D,
+) where
+    T: std::convert::Into<Rect<f32>> + Copy + Clone + std::fmt::Debug,
+{
+    let mvp = get_mvp_matrix(window, transform, camera);
+    fill_color_rect_internal(window, paint_props, rect.into(), &mvp);
+}
+
+fn fill_color_circle_internal(
+    window: &mut Render_Window_Handle,
+    paint_props: &Paint_Properties,
+    circle: shapes::Circle,
+    mvp: &Matrix3<f32>,
+) {
+    let rect = Rect::new(
+        circle.center.x - circle.radius,
+        circle.center.y - circle.radius,
+        2. * circle.radius,
+        2. * circle.radius,
+    );
+    let radius_squared = circle.radius * circle.radius;
+    let vertices = rect_vertices(&rect, paint_props.color);
+    draw(
+        window,
+        mvp,
+        Primitive_Type::Triangles,
+        &vertices,
+        &|v: &Vertex| {
+            let diff = v.position - circle.center;
+            if diff.dot(diff) > radius_squared {
+                None
+            } else {
+                Some(v.color)
+            }
+        },
+    );
+}
+
+pub fn fill_color_circle(
+    window: &mut Render_Window_Handle,
+    paint_props: &Paint_Properties,
+    circle: shapes::Circle,
+) {
+    let mvp = get_mvp_screen_matrix(window, &Transform2D::default());
+    fill_color_circle_internal(window, paint_props, circle, &mvp);
+}
+
+pub fn fill_color_circle_ws(
+    window: &mut Render_Window_Handle,
+    paint_props: &Paint_Properties,
+    circle: shapes::Circle,
+    camera: &Transform2D,
+) {
+    let mvp = get_mvp_matrix(window, &Transform2D::default(), camera);
+    fill_color_circle_internal(window, paint_props, circle, &mvp);
+}
+
+pub fn render_text(
+    window: &mut Render_Window_Handle,
+    text: &Text,
+    paint_props: &Paint_Properties,
+    screen_pos: Vec2f,
+) {
+    if text.string.is_empty() {
+        return;
+    }
+
+    let mvp = get_mvp_screen_matrix(window, &Transform2D::from_pos(screen_pos));
+    render_text_internal(window, text, paint_props, &mvp);
+}
+
+pub fn render_text_ws(
+    window: &mut Render_Window_Handle,
+    text: &Text,
+    paint_props: &Paint_Properties,
+    transform: &Transform2D,
+    camera: &Transform2D,
+) {
+    if text.string.is_empty() {
+        return;
+    }
+
+    let mvp = get_mvp_matrix(window, transform, camera);
+    render_text_internal(window, text, paint_props, &mvp);
+}
+
+fn render_text_internal(
+    window: &mut Render_Window_Handle,
+    text: &Text,
+    paint_props: &Paint_Properties,
+    mvp: &Matrix3<f32>,
+) {
+    // We have no derivatives to compute the smoothing width from, so we use a fixed one.
+    const SMOOTHING: f32 = 0.1;
+
+    let color = Glsl_Vec4::from(paint_props.color);
+    let atlas = &text.font_atlas;
+    let shader = |v: &Vertex| {
+        let sample = atlas.sample(v.tex_coords);
+        let sig_dist = median(sample.x, sample.y, sample.z);
+        let opacity = smoothstep(0.5 - SMOOTHING, 0.5 + SMOOTHING, sig_dist);
+        Some(Glsl_Vec4 {
+            x: color.x * v.color.x,
+            y: color.y * v.color.y,
+            z: color.z * v.color.z,
+            w: color.w * opacity,
+        })
+    };
+    draw(
+        window,
+        mvp,
+        Primitive_Type::Triangles,
+        &text.vertices,
+        &shader,
+    );
+}
+
+fn fill_text_vertices(string: &str, font: &Font, font_size: u16) -> (Vec<Vertex>, Vec2f) {
+    trace!("fill_text_vertices");
+
+    let mut vertices = Vec::with_capacity(6 * string.len());
+    let mut size = Vec2f::default();
+    let mut pos_x = 0.;
+    let scale_factor = font.metadata.scale_factor(font_size as f32);
+    for chr in string.chars() {
+        if let Some(glyph_data) = font.metadata.get_glyph_data(chr) {
+            let atlas_bounds = &glyph_data.normalized_atlas_bounds;
+            let pb = &glyph_data.plane_bounds;
+            let rect = Rect::new(
+                pos_x + pb.left * scale_factor,
+                // Offsetting the y so the text pivot is top-left rather than bottom-left
+                (1.0 - pb.top) * scale_factor,
+                (pb.right - pb.left) * scale_factor,
+                (pb.top - pb.bot) * scale_factor,
+            );
+
+            pos_x += scale_factor * glyph_data.advance;
+
+            let v1 = new_vertex(
+                v2!(rect.x, rect.y),
+                colors::WHITE,
+                v2!(atlas_bounds.left, atlas_bounds.top),
+            );
+            let v2 = new_vertex(
+                v2!(rect.x + rect.width, rect.y),
+                colors::WHITE,
+                v2!(atlas_bounds.right, atlas_bounds.top),
+            );
+            let v3 = new_vertex(
+                v2!(rect.x + rect.width, rect.y + rect.height),
+                colors::WHITE,
+                v2!(atlas_bounds.right, atlas_bounds.bot),
+            );
+            let v4 = new_vertex(
+                v2!(rect.x, rect.y + rect.height),
+                colors::WHITE,
+                v2!(atlas_bounds.left, atlas_bounds.bot),
+            );
+
+            size.x = size.x.max(rect.x + rect.width);
+            size.y = size.y.max(rect.y + rect.height);
+
+            vertices.extend_from_slice(&[v1, v2, v3, v3, v4, v1]);
+        }
+    }
+
+    (vertices, size)
+}
+
+#[inline]
+pub fn get_texture_size(texture: &Texture) -> (u32, u32) {
+    (texture.sampler.width, texture.sampler.height)
+}
+
+#[inline]
+pub fn get_image_size(image: &Image) -> (u32, u32) {
+    (image.width, image.height)
+}
+
+#[inline]
+pub fn get_image_bytes(image: &Image) -> &[u8] {
+    &image.bytes
+}
+
+#[inline]
+pub fn get_image_color_type(image: &Image) -> Color_Type {
+    image.color_type
+}
+
+#[inline]
+pub fn get_image_bit_depth(image: &Image) -> u8 {
+    image.bit_depth
+}
+
+#[inline]
+pub fn get_text_string(text: &Text) -> &str {
+    &text.string
+}
+
+#[inline]
+pub fn get_text_size(text: &Text) -> Vec2f {
+    text.size
+}
+
+#[inline]
+pub fn new_image(width: u32, height: u32, color_type: Color_Type) -> Image {
+    Image {
+        width,
+        height,
+        bytes: vec![0; color_type.n_channels() * (width * height) as usize],
+        color_type,
+        bit_depth: 8,
+    }
+}
+
+#[inline]
+pub fn new_image_with_data(
+    width: u32,
+    height: u32,
+    color_type: Color_Type,
+    bit_depth: u8,
+    bytes: Vec<u8>,
+) -> Image {
+    Image {
+        bytes,
+        width,
+        height,
+        color_type,
+        bit_depth,
+    }
+}
+
+#[inline(always)]
+pub fn vbuf_primitive_type(vbuf: &Vertex_Buffer) -> Primitive_Type {
+    vbuf.primitive_type
+}
+
+#[inline]
+pub fn new_vbuf_temp(
+    _window: &mut Render_Window_Handle,
+    primitive: Primitive_Type,
+    n_vertices: u32,
+) -> Vertex_Buffer {
+    Vertex_Buffer {
+        max_vertices: n_vertices,
+        primitive_type: primitive,
+        vertices: Vec::with_capacity(n_vertices as usize),
+    }
+}
+
+#[inline]
+pub fn new_vbuf(
+    window: &mut Render_Window_Handle,
+    primitive: Primitive_Type,
+    n_vertices: u32,
+) -> Vertex_Buffer {
+    new_vbuf_temp(window, primitive, n_vertices)
+}
+
+#[inline]
+pub fn dealloc_vbuf(vbuf: &mut Vertex_Buffer) {
+    vbuf.max_vertices = 0;
+    vbuf.vertices.clear();
+}
+
+#[inline]
+pub fn add_vertices(vbuf: &mut Vertex_Buffer, vertices: &[Vertex]) {
+    update_vbuf(vbuf, vertices, vbuf_cur_vertices(vbuf));
+}
+
+#[inline]
+pub fn update_vbuf(vbuf: &mut Vertex_Buffer, vertices: &[Vertex], offset: u32) {
+    vbuf.vertices.truncate(offset as usize);
+
+    let space_remaining = vbuf.max_vertices as usize - vbuf.vertices.len();
+    let vertices_to_copy = vertices.len().min(space_remaining);
+    vbuf.vertices.extend(&vertices[..vertices_to_copy]);
+}
+
+#[inline(always)]
+pub fn vbuf_cur_vertices(vbuf: &Vertex_Buffer) -> u32 {
+    vbuf.vertices.len() as _
+}
+
+#[inline(always)]
+pub fn vbuf_max_vertices(vbuf: &Vertex_Buffer) -> u32 {
+    vbuf.max_vertices
+}
+
+#[inline(always)]
+pub fn set_vbuf_cur_vertices(vbuf: &mut Vertex_Buffer, cur_vertices: u32) {
+    vbuf.vertices
+        .resize(cur_vertices as usize, Vertex::default());
+}
+
+#[inline]
+pub fn new_vertex(pos: Vec2f, col: Color, tex_coords: Vec2f) -> Vertex {
+    Vertex {
+        position: pos,
+        color: col.into(),
+        tex_coords,
+    }
+}
+
+#[inline]
+pub fn render_vbuf(
+    window: &mut Render_Window_Handle,
+    vbuf: &Vertex_Buffer,
+    transform: &Transform2D,
+) {
+    let mvp = get_mvp_screen_matrix(window, transform);
+    draw(
+        window,
+        &mvp,
+        vbuf.primitive_type,
+        &vbuf.vertices,
+        &|v: &Vertex| Some(v.color),
+    );
+}
+
+#[inline]
+pub fn render_vbuf_ws(
+    window: &mut Render_Window_Handle,
+    vbuf: &Vertex_Buffer,
+    transform: &Transform2D,
+    camera: &Transform2D,
+) {
+    let mvp = get_mvp_matrix(window, transform, camera);
+    draw(
+        window,
+        &mvp,
+        vbuf.primitive_type,
+        &vbuf.vertices,
+        &|v: &Vertex| Some(v.color),
+    );
+}
+
+#[inline]
+pub fn render_vbuf_ws_with_texture(
+    window: &mut Render_Window_Handle,
+    vbuf: &Vertex_Buffer,
+    transform: &Transform2D,
+    camera: &Transform2D,
+    texture: &Texture,
+) {
+    let mvp = get_mvp_matrix(window, transform, camera);
+    let sampler = &texture.sampler;
+    draw(
+        window,
+        &mvp,
+        vbuf.primitive_type,
+        &vbuf.vertices,
+        &|v: &Vertex| Some(mul_vec4(v.color, sampler.sample(v.tex_coords))),
+    );
+}
+
+/// Note: the vertices are expected to be in world space, like the batcher produces them.
+pub fn render_vbuf_with_shader(
+    window: &mut Render_Window_Handle,
+    vbuf: &Vertex_Buffer,
+    shader: &Shader,
+) {
+    let mvp = match shader
+        .matrices
+        .get(c_str!("vp"))
+        .or_else(|| shader.matrices.get(c_str!("mvp")))
+    {
+        Some(mvp) => *mvp,
+        None => {
+            lerr_once!(
+                "soft_shader_no_vp",
+                "Shader has no `vp` nor `mvp` uniform set: cannot render."
+            );
+            return;
+        }
+    };
+
+    let white = Sampler {
+        width: 1,
+        height: 1,
+        pixels: Arc::new(vec![colors::WHITE]),
+        repeated: false,
+        smooth: false,
+    };
+    let tex = shader.textures.get(c_str!("tex")).unwrap_or(&white);
+
+    match shader.model {
+        Shader_Model::Unlit => draw(
+            window,
+            &mvp,
+            vbuf.primitive_type,
+            &vbuf.vertices,
+            &|v: &Vertex| Some(tex.sample(v.tex_coords)),
+        ),
+        Shader_Model::Lit => {
+            // Take the buffers out of the window so we can borrow them while drawing into it.
+            let uniform_buffers = mem::take(&mut window.uniform_buffers);
+            let ubo_mem = |name: &CStr| {
+                uniform_buffers
+                    .get(name)
+                    .map(|ubo| &ubo.mem[..])
+                    .unwrap_or_default()
+            };
+            let lights = Lights_Block {
+                lights: ubo_mem(c_str!("LightsBlock")),
+                grid: ubo_mem(c_str!("LightGridBlock")),
+                cookies: shader.textures.get(c_str!("cookies")),
+                shadows: ubo_mem(c_str!("ShadowsBlock")),
+                occluder_extent: shader
+                    .floats
+                    .get(c_str!("occluder_extent"))
+                    .copied()
+                    .unwrap_or(0.),
+            };
+            draw(
+                window,
+                &mvp,
+                vbuf.primitive_type,
+                &vbuf.vertices,
+                &|v: &Vertex| {
+                    let pixel = tex.sample(v.tex_coords);
+                    let light = lights.light_at(v.position);
+                    Some(Glsl_Vec4 {
+                        x: light[0] * pixel.x,
+                        y: light[1] * pixel.y,
+                        z: light[2] * pixel.z,
+                        w: pixel.w,
+                    })
+                },
+            );
+            window.uniform_buffers = uniform_buffers;
+        }
+    }
+}
+
+// @Volatile: must match the LightsBlock, LightGridBlock and ShadowsBlock layouts in the lit shaders
+// (and the ones written by the batcher).
+const MAX_POINT_LIGHTS: usize = 256;
+const MAX_RECT_LIGHTS: usize = 64;
+const MAX_SPOT_LIGHTS: usize = 64;
+const LIGHT_GRID_WIDTH: usize = 16;
+const LIGHT_GRID_HEIGHT: usize = 9;
+const AMBIENT_LIGHT_SIZE: usize = 16;
+const POINT_LIGHT_SIZE: usize = 32;
+const RECT_LIGHT_SIZE: usize = 48;
+const SPOT_LIGHT_SIZE: usize = 48;
+const COOKIE_LIGHT_SIZE: usize = 64;
+const RECT_LIGHTS_START: usize = AMBIENT_LIGHT_SIZE + MAX_POINT_LIGHTS * POINT_LIGHT_SIZE;
+const SPOT_LIGHTS_START: usize = RECT_LIGHTS_START + MAX_RECT_LIGHTS * RECT_LIGHT_SIZE;
+const COOKIE_LIGHTS_START: usize = SPOT_LIGHTS_START + MAX_SPOT_LIGHTS * SPOT_LIGHT_SIZE;
+const LIGHT_GRID_HEADER_SIZE: usize = 16;
+const MAX_SHADOW_MAPS: usize = 16;
+const SHADOW_MAP_RESOLUTION: usize = 128;
+// Shadow map indices are u8
+const SHADOW_MAPS_START: usize = MAX_POINT_LIGHTS;
+const SHADOW_PENUMBRA_TAPS: i32 = 2;
+const SHADOW_BIAS: f32 = 0.01;
+// Each tile is 2 u32s
+const LIGHT_INDICES_START: usize =
+    LIGHT_GRID_HEADER_SIZE + 8 * LIGHT_GRID_WIDTH * LIGHT_GRID_HEIGHT;
+
+/// The raw content of the LightsBlock, LightGridBlock and ShadowsBlock uniform buffers.
+/// Any part of them that wasn't written reads as zero.
+struct Lights_Block<'a> {
+    lights: &'a [u8],
+    grid: &'a [u8],
+    cookies: Option<&'a Sampler>,
+    shadows: &'a [u8],
+    occluder_extent: f32,
+}
+
+fn read_u32(mem: &[u8], offset: usize) -> u32 {
+    mem.get(offset..offset + 4)
+        .map(|b| u32::from_ne_bytes(b.try_into().unwrap()))
+        .unwrap_or(0)
+}
+
+fn read_f32(mem: &[u8], offset: usize) -> f32 {
+    f32::from_bits(read_u32(mem, offset))
+}
+
+impl Lights_Block<'_> {
+    /// Computes the light color at `world_pos` like sprite_flat.frag does.
+    fn light_at(&self, world_pos: Vec2f) -> [f32; 3] {
+        // @Volatile: must match inle_gfx's light::falloff
+        let atten = |dist: f32, radius: f32, attenuation: f32| {
+            if radius <= 0. {
+                0.
+            } else {
+                (1. - dist / radius).max(0.).powf(1. + attenuation)
+            }
+        };
+        // Reads the floats of the light starting at `start` (some may belong to the next one).
+        let light = |start: usize| {
+            let mut l = [0.; 16];
+            for (i, x) in l.iter_mut().enumerate() {
+                *x = read_f32(self.lights, start + 4 * i);
+            }
+            l
+        };
+
+        let a = light(0);
+        let mut color = [a[0] * a[3], a[1] * a[3], a[2] * a[3]];
+
+        let grid_origin = v2!(read_f32(self.grid, 0), read_f32(self.grid, 4));
+        let inv_tile_size = v2!(read_f32(self.grid, 8), read_f32(self.grid, 12));
+        let tile_pos = (world_pos - grid_origin) * inv_tile_size;
+        let tile_x = (tile_pos.x.floor() as i32).clamp(0, LIGHT_GRID_WIDTH as i32 - 1) as usize;
+        let tile_y = (tile_pos.y.floor() as i32).clamp(0, LIGHT_GRID_HEIGHT as i32 - 1) as usize;
+        let tile_start = LIGHT_GRID_HEADER_SIZE + 8 * (tile_y * LIGHT_GRID_WIDTH + tile_x);
+        let first_light = read_u32(self.grid, tile_start);
+        let [n_point_lights, n_rect_lights, n_spot_lights, n_cookie_lights] =
+            read_u32(self.grid, tile_start + 4)
+                .to_le_bytes()
+                .map(u32::from);
+        let light_index = |i: u32| {
+            let pair = read_u32(self.grid, LIGHT_INDICES_START + 4 * (i as usize / 2));
+            ((pair >> (16 * (i % 2))) & 0xFFFF) as usize
+        };
+
+        for i in 0..n_point_lights {
+            // r, g, b, intensity, pos.x, pos.y, radius, attenuation
+            let idx = light_index(first_light + i);
+            let l = light(AMBIENT_LIGHT_SIZE + idx * POINT_LIGHT_SIZE);
+            let light_to_frag = world_pos - v2!(l[4], l[5]);
+            let dist = light_to_frag.magnitude();
+            let k =
+                l[3] * atten(dist, l[6], l[7]) * self.point_light_shadow(idx, light_to_frag, l[6]);
+            for c in 0..3 {
+                color[c] += l[c] * k;
+            }
+        }
+
+        let first_rect_light = first_light + n_point_lights;
+        for i in 0..n_rect_lights {
+            // r, g, b, intensity, min.x, min.y, max.x, max.y, radius, attenuation
+            let l = light(RECT_LIGHTS_START + light_index(first_rect_light + i) * RECT_LIGHT_SIZE);
+            let dist = point_to_rect_distance(world_pos, v2!(l[4], l[5]), v2!(l[6], l[7]));
+            let k = l[3] * atten(dist, l[8], l[9]);
+            for c in 0..3 {
+                color[c] += l[c] * k;
+            }
+        }
+
+        let first_spot_light = first_rect_light + n_rect_lights;
+        for i in 0..n_spot_lights {
+            // r, g, b, intensity, pos.x, pos.y, radius, attenuation, dir.x, dir.y, cos_outer, cos_inner
+            let l = light(SPOT_LIGHTS_START + light_index(first_spot_light + i) * SPOT_LIGHT_SIZE);
+            let light_to_frag = world_pos - v2!(l[4], l[5]);
+            let dist = light_to_frag.magnitude();
+            let cone = if dist > 0. {
+                let cos_a = (light_to_frag / dist).dot(v2!(l[8], l[9]));
+                let t = ((cos_a - l[10]) / (l[11] - l[10]).max(1e-4)).clamp(0., 1.);
+                t * t * (3. - 2. * t)
+            } else {
+                1.
+            };
+            let k = l[3] * atten(dist, l[6], l[7]) * cone;
+            for c in 0..3 {
+                color[c] += l[c] * k;
+            }
+        }
+
+        let first_cookie_light = first_spot_light + n_spot_lights;
+        for i in 0..n_cookie_lights {
+            // r, g, b, intensity, pos.x, pos.y, radius, attenuation,
+            // cookie_rect.x, cookie_rect.y, cookie_rect.w, cookie_rect.h, rot.cos, rot.sin
+            let l = light(
+                COOKIE_LIGHTS_START + light_index(first_cookie_light + i) * COOKIE_LIGHT_SIZE,
+            );
+            let cookies = match self.cookies {
+                Some(cookies) => cookies,
+                None => break,
+            };
+            let d = world_pos - v2!(l[4], l[5]);
+            let local = v2!(l[12] * d.x + l[13] * d.y, -l[13] * d.x + l[12] * d.y);
+            let uv = local / l[6] * 0.5 + v2!(0.5, 0.5);
+            if !(0. ..=1.).contains(&uv.x) || !(0. ..=1.).contains(&uv.y) {
+                continue;
+            }
+            let cookie = cookies.sample(v2!(l[8] + uv.x * l[10], l[9] + uv.y * l[11]));
+            let cookie = [
+                cookie.x * cookie.w,
+                cookie.y * cookie.w,
+                cookie.z * cookie.w,
+            ];
+            let k = l[3] * atten(d.magnitude(), l[6], l[7]);
+            for c in 0..3 {
+                color[c] += l[c] * cookie[c] * k;
+            }
+        }
+
+        color
+    }
+
+    /// Like point_light_shadow in the lit shaders.
+    fn point_light_shadow(&self, point_light_idx: usize, light_to_frag: Vec2f, radius: f32) -> f32 {
+        let map = self.shadows.get(point_light_idx).copied().unwrap_or(0) as usize;
+        if map == 0 {
+            return 1.;
+        }
+        let map = (map - 1).min(MAX_SHADOW_MAPS - 1);
+
+        let depth = |bin: i32| {
+            let bin = bin.rem_euclid(SHADOW_MAP_RESOLUTION as i32) as usize;
+            read_f32(
+                self.shadows,
+                SHADOW_MAPS_START + 4 * (map * SHADOW_MAP_RESOLUTION + bin),
+            )
+        };
+
+        let dist = (light_to_frag.magnitude() - self.occluder_extent) / radius - SHADOW_BIAS;
+        let angle = light_to_frag.y.atan2(light_to_frag.x);
+        let bin = (angle + std::f32::consts::PI) / std::f32::consts::TAU
+            * SHADOW_MAP_RESOLUTION as f32
+            - 0.5;
+        let first_bin = bin.floor();
+        let t = bin - first_bin;
+        let mut lit = 0.;
+        for i in -SHADOW_PENUMBRA_TAPS..=SHADOW_PENUMBRA_TAPS + 1 {
+            let weight = if i == -SHADOW_PENUMBRA_TAPS {
+                1. - t
+            } else if i == SHADOW_PENUMBRA_TAPS + 1 {
+                t
+            } else {
+                1.
+            };
+            if dist <= depth(first_bin as i32 + i) {
+                lit += weight;
+            }
+        }
+        lit / (2 * SHADOW_PENUMBRA_TAPS + 1) as f32
+    }
+}
+
+fn point_to_rect_distance(point: Vec2f, rect_min: Vec2f, rect_max: Vec2f) -> f32 {
+    let dx = (rect_min.x - point.x).max(point.x - rect_max.x).max(0.);
+    let dy = (rect_min.y - point.y).max(point.y - rect_max.y).max(0.);
+    v2!(dx, dy).magnitude()
+}
+
+#[inline]
+pub fn create_text(
+    _window: &mut Render_Window_Handle,
+    string: &str,
+    font: &Font,
+    size: u16,
+) -> Text {
+    let (vertices, text_size) = fill_text_vertices(string, font, size);
+    Text {
+        string: String::from(string),
+        vertices,
+        font_atlas: font.atlas.sampler.clone(),
+        size: text_size,
+    }
+}
+
+/// `start` and `end` are in screen coordinates.
+#[inline]
+pub fn render_line(window: &mut Render_Window_Handle, start: &Vertex, end: &Vertex) {
+    let mvp = get_mvp_screen_matrix(window, &Transform2D::default());
+    draw(
+        window,
+        &mvp,
+        Primitive_Type::Lines,
+        &[*start, *end],
+        &|v: &Vertex| Some(v.color),
+    );
+}
+
+#[inline]
+pub fn copy_texture_to_image(texture: &Texture) -> Image {
+    let sampler = &texture.sampler;
+    let mut bytes = Vec::with_capacity(4 * sampler.pixels.len());
+    for px in sampler.pixels.iter() {
+        bytes.extend_from_slice(&[px.r, px.g, px.b, px.a]);
+    }
+
+    Image {
+        width: sampler.width,
+        height: sampler.height,
+        bytes,
+        color_type: Color_Type::RGBA,
+        bit_depth: 8,
+    }
+}
+
+#[inline]
+pub fn new_texture_from_image<'tex>(image: &Image, _rect: Option<Rect<i32>>) -> Texture<'tex> {
+    let n_channels = image.color_type.n_channels();
+    // We only keep the most significant byte of 16-bit channels.
+    let bytes_per_channel = if image.bit_depth == 16 { 2 } else { 1 };
+    let n_pixels = (image.width * image.height) as usize;
+
+    debug_assert!(image.bytes.len() >= n_pixels * n_channels * bytes_per_channel);
+
+    let pixels = (0..n_pixels)
+        .map(|i| {
+            let ch = |c: usize| image.bytes[(i * n_channels + c) * bytes_per_channel];
+            match image.color_type {
+                Color_Type::Grayscale => colors::rgb(ch(0), ch(0), ch(0)),
+                Color_Type::Grayscale_Alpha => colors::rgba(ch(0), ch(0), ch(0), ch(1)),
+                Color_Type::RGB => colors::rgb(ch(0), ch(1), ch(2)),
+                Color_Type::RGBA => colors::rgba(ch(0), ch(1), ch(2), ch(3)),
+                Color_Type::Indexed => {
+                    lerr_once!(
+                        "soft_indexed_image",
+                        "Indexed images are not supported by the soft backend."
+                    );
+                    colors::WHITE
+                }
+            }
+        })
+        .collect();
+
+    Texture {
+        sampler: Sampler {
+            width: image.width,
+            height: image.height,
+            pixels: Arc::new(pixels),
+            repeated: false,
+            smooth: true,
+        },
+        _pd: PhantomData,
+    }
+}
+
+#[inline]
+pub fn get_image_pixel(image: &Image, x: u32, y: u32) -> Color {
+    debug_assert_eq!(image.bit_depth, 8);
+
+    let b = &image.bytes[..];
+    let i = (image.width * y + x) as usize * image.color_type.n_channels();
+    match image.color_type {
+        Color_Type::Grayscale | Color_Type::Indexed => colors::rgb(b[i], b[i], b[i]),
+        Color_Type::Grayscale_Alpha => colors::rgba(b[i], b[i], b[i], b[i + 1]),
+        Color_Type::RGB => colors::rgb(b[i], b[i + 1], b[i + 2]),
+        Color_Type::RGBA => colors::rgba(b[i], b[i + 1], b[i + 2], b[i + 3]),
+    }
+}
+
+#[inline]
+pub fn set_image_pixel(image: &mut Image, x: u32, y: u32, val: Color) {
+    debug_assert_eq!(image.bit_depth, 8);
+
+    let i = (y * image.width + x) as usize * image.color_type.n_channels();
+    image.bytes[i] = val.r;
+    match image.color_type {
+        Color_Type::Grayscale => {}
+        Color_Type::Grayscale_Alpha => {
+            image.bytes[i + 1] = val.a;
+        }
+        Color_Type::RGB => {
+            image.bytes[i + 1] = val.g;
+            image.bytes[i + 2] = val.b;
+        }
+        Color_Type::RGBA => {
+            image.bytes[i + 1] = val.g;
+            image.bytes[i + 2] = val.b;
+            image.bytes[i + 3] = val.a;
+        }
+        _ => unimplemented!(),
+    }
+}
+
+#[inline]
+pub fn get_image_pixels(image: &Image) -> &[Color] {
+    const_assert!(mem::size_of::<Color>() == 4);
+    debug_assert_eq!(image.bytes.len() % 4, 0);
+    debug_assert_eq!(image.bit_depth, 8);
+    unsafe { std::slice::from_raw_parts(image.bytes.as_ptr() as *const _, image.bytes.len() / 4) }
+}
+
+#[inline]
+pub fn swap_vbuf(a: &mut Vertex_Buffer, b: &mut Vertex_Buffer) -> bool {
+    mem::swap(a, b);
+    true
+}
+
+pub fn update_texture_pixels(texture: &mut Texture, rect: &Rect<u32>, pixels: &[Color]) {
+    let sampler = &mut texture.sampler;
+    let width = sampler.width;
+    // Shaders holding this texture keep seeing the old pixels until it's bound again, like in gl.
+    let tex_pixels = Arc::make_mut(&mut sampler.pixels);
+    for y in 0..rect.height {
+        let dst_start = ((rect.y + y) * width + rect.x) as usize;
+        let src_start = (y * rect.width) as usize;
+        tex_pixels[dst_start..dst_start + rect.width as usize]
+            .copy_from_slice(&pixels[src_start..src_start + rect.width as usize]);
+    }
+}
+
+#[inline]
+pub fn shaders_are_available() -> bool {
+    true
+}
+
+#[inline]
+pub fn geom_shaders_are_available() -> bool {
+    false
+}
+
+#[inline]
+pub fn set_texture_repeated(texture: &mut Texture, repeated: bool) {
+    texture.sampler.repeated = repeated;
+}
+
+#[inline]
+pub fn set_texture_smooth(texture: &mut Texture, smooth: bool) {
+    texture.sampler.smooth = smooth;
+}
+
+/// Since we have no shader to query the block size from, the buffer grows as it's written into.
+pub struct Uniform_Buffer {
+    mem: Vec<u8>,
+    needs_transfer_to_gpu: Cell<bool>,
+}
+
+pub fn create_or_get_uniform_buffer<'window>(
+    window: &'window mut Render_Window_Handle,
+    _shader: &Shader,
+    name: &'static CStr,
+) -> &'window mut Uniform_Buffer {
+    window
+        .uniform_buffers
+        .entry(name)
+        .or_insert_with(|| Uniform_Buffer {
+            mem: vec![],
+            needs_transfer_to_gpu: Cell::new(true),
+        })
+}
+
+/// # Safety
+/// `data` must be valid: it must be non-null, aligned and it must
+/// correspond to an allocation of at least `size` bytes.
+pub unsafe fn write_into_uniform_buffer(
+    ubo: &mut Uniform_Buffer,
+    offset: usize,
+    align: usize,
+    size: usize,
+    data: *const u8,
+) -> usize {
+    debug_assert!(!data.is_null());
+
+    ubo.needs_transfer_to_gpu.set(true);
+
+    let start = offset + (align - offset % align) % align;
+    let end = start + size;
+    if ubo.mem.len() < end {
+        ubo.mem.resize(end, 0);
+    }
+    std::ptr::copy_nonoverlapping(data, ubo.mem.as_mut_ptr().add(start), size);
+
+    end
+}
+
+pub fn bind_uniform_buffer(ubo: &Uniform_Buffer) {
+    ubo.needs_transfer_to_gpu.set(false);
+}
+
+#[inline]
+pub fn uniform_buffer_needs_transfer_to_gpu(ubo: &Uniform_Buffer) -> bool {
+    ubo.needs_transfer_to_gpu.get()
+}
+
+#[inline]
+pub fn uniform_buffer_was_written(ubo: &Uniform_Buffer) -> bool {
+    !ubo.mem.is_empty()
+}
+
+/// This is the equivalent of get_mvp_matrix() with a camera with scale 1, no rotation
+/// and positioned in (win_target_size.x / 2, win_target_size.y / 2).
+fn get_mvp_screen_matrix(window: &Render_Window_Handle, transform: &Transform2D) -> Matrix3<f32> {
+    let (width, height) = inle_win::window::get_window_target_size(window);
+    let view_projection = Matrix3::new(
+        2. / width as f32,
+        0.,
+        -1.,
+        0.,
+        -2. / height as f32,
+        1.,
+        0.,
+        0.,
+        1.,
+    );
+    view_projection * transform.get_matrix()
+}
+
+#[inline]
+fn mul_vec4(a: Glsl_Vec4, b: Glsl_Vec4) -> Glsl_Vec4 {
+    Glsl_Vec4 {
+        x: a.x * b.x,
+        y: a.y * b.y,
+        z: a.z * b.z,
+        w: a.w * b.w,
+    }
+}
+
+#[inline]
+fn lerp_vec4(a: Glsl_Vec4, b: Glsl_Vec4, t: f32) -> Glsl_Vec4 {
+    Glsl_Vec4 {
+        x: a.x + (b.x - a.x) * t,
+        y: a.y + (b.y - a.y) * t,
+        z: a.z + (b.z - a.z) * t,
+        w: a.w + (b.w - a.w) * t,
+    }
+}
+
+#[inline]
+fn median(r: f32, g: f32, b: f32) -> f32 {
+    r.min(g).max(r.max(g).min(b))
+}
+
+#[inline]
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0., 1.);
+    t * t * (3. - 2. * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{get_vp_matrix, set_uniform};
+    use crate::render_window::soft::{clear, create_render_window, n_draw_calls_this_frame};
+    use inle_win::window::{create_window, Create_Window_Args};
+
+    const WIN_SIZE: u32 = 8;
+
+    fn create_test_window() -> Render_Window_Handle {
+        let window = create_window(&Create_Window_Args::default(), (WIN_SIZE, WIN_SIZE), "");
+        let mut window = create_render_window(window);
+        clear(&mut window);
+        window
+    }
+
+    /// A camera that makes world coordinates coincide with screen coordinates.
+    fn screen_camera() -> Transform2D {
+        Transform2D::from_pos(v2!(WIN_SIZE as f32 * 0.5, WIN_SIZE as f32 * 0.5))
+    }
+
+    fn window_quad(window: &mut Render_Window_Handle, color: Color) -> Vertex_Buffer {
+        let mut vbuf = new_vbuf(window, Primitive_Type::Triangles, 6);
+        let size = WIN_SIZE as f32;
+        add_vertices(
+            &mut vbuf,
+            &rect_vertices(&Rect::new(0., 0., size, size), color),
+        );
+        vbuf
+    }
+
+    fn pixel(window: &Render_Window_Handle, x: u32, y: u32) -> Color {
+        let fb = &window.framebuffer;
+        fb.pixels[(y * fb.width + x) as usize]
+    }
+
+    fn new_white_texture<'a>() -> Texture<'a> {
+        let mut image = new_image(1, 1, Color_Type::RGB);
+        set_image_pixel(&mut image, 0, 0, colors::WHITE);
+        new_texture_from_image(&image, None)
+    }
+
+    #[test]
+    fn fill_color_rect_covers_exactly_its_pixels() {
+        let mut window = create_test_window();
+
+        fill_color_rect(&mut window, &colors::RED.into(), Rect::new(2., 2., 4., 4.));
+
+        for y in 0..WIN_SIZE {
+            for x in 0..WIN_SIZE {
+                let inside = (2..6).contains(&x) && (2..6).contains(&y);
+                let expected = if inside { colors::RED } else { colors::BLACK };
+                assert_eq!(pixel(&window, x, y), expected, "({}, {})", x, y);
+            }
+        }
+        assert_eq!(n_draw_calls_this_frame(&window), 1);
+    }
+
+    #[test]
+    fn translucent_colors_are_blended() {
+        let mut window = create_test_window();
+
+        let size = WIN_SIZE as f32;
+        fill_color_rect(
+            &mut window,
+            &colors::rgba(255, 0, 0, 128).into(),
+            Rect::new(0., 0., size, size),
+        );
+        // The two triangles of the rect must not blend twice on their shared edge.
+        for y in 0..WIN_SIZE {
+            for x in 0..WIN_SIZE {
+                assert_eq!(pixel(&window, x, y), colors::rgba(128, 0, 0, 191));
+            }
+        }
+    }
+
+    #[test]
+    fn counter_clockwise_triangles_are_culled() {
+        let mut window = create_test_window();
+
+        let size = WIN_SIZE as f32;
+        let mut vbuf = new_vbuf(&mut window, Primitive_Type::Triangles, 3);
+        add_vertices(
+            &mut vbuf,
+            &[
+                new_vertex(v2!(0., 0.), colors::WHITE, Vec2f::default()),
+                new_vertex(v2!(0., size), colors::WHITE, Vec2f::default()),
+                new_vertex(v2!(size, size), colors::WHITE, Vec2f::default()),
+            ],
+        );
+        render_vbuf(&mut window, &vbuf, &Transform2D::default());
+
+        assert!(window
+            .framebuffer
+            .pixels
+            .iter()
+            .all(|&px| px == colors::BLACK));
+    }
+
+    #[test]
+    fn textured_quad_samples_texture() {
+        let mut window = create_test_window();
+
+        #[rustfmt::skip]
+        let bytes = vec![
+            255, 0, 0, 255,   0, 255, 0, 255,
+            0, 0, 255, 255,   255, 255, 255, 255,
+        ];
+        let image = new_image_with_data(2, 2, Color_Type::RGBA, 8, bytes);
+        let mut texture = new_texture_from_image(&image, None);
+        set_texture_smooth(&mut texture, false);
+
+        let vbuf = window_quad(&mut window, colors::rgb(255, 255, 128));
+        render_vbuf_ws_with_texture(
+            &mut window,
+            &vbuf,
+            &Transform2D::default(),
+            &screen_camera(),
+            &texture,
+        );
+
+        let half = WIN_SIZE / 2;
+        assert_eq!(pixel(&window, 0, 0), colors::RED);
+        assert_eq!(pixel(&window, half, 0), colors::GREEN);
+        assert_eq!(pixel(&window, 0, half), colors::rgb(0, 0, 128));
+        assert_eq!(
+            pixel(&window, WIN_SIZE - 1, WIN_SIZE - 1),
+            colors::rgb(255, 255, 128)
+        );
+    }
+
+    #[test]
+    fn lit_shader_applies_ambient_and_point_lights() {
+        let mut window = create_test_window();
+
+        let texture = new_white_texture();
+        let vp = get_vp_matrix(&window, &screen_camera());
+        let mut shader = new_shader(b"", b"uniform LightsBlock {};", None);
+        assert_eq!(shader.model, Shader_Model::Lit);
+        set_uniform(&mut shader, c_str!("tex"), &texture);
+        set_uniform(&mut shader, c_str!("vp"), &vp);
+
+        let ambient = [1.0f32, 1.0, 1.0, 0.5];
+        // r, g, b, intensity, pos.x, pos.y, radius, attenuation
+        let point_lights = [
+            [1.0f32, 0.0, 0.0, 1.0, 2.0, 2.0, 4.0, 0.0],
+            [0.0f32, 1.0, 0.0, 1.0, 2.0, 2.0, 4.0, 0.0],
+        ];
+        let ubo = create_or_get_uniform_buffer(&mut window, &shader, c_str!("LightsBlock"));
+        unsafe {
+            let offset = write_into_uniform_buffer(ubo, 0, 16, 16, ambient.as_ptr() as *const u8);
+            write_into_uniform_buffer(ubo, offset, 16, 64, point_lights.as_ptr() as *const u8);
+        }
+
+        // A single tile covering everything, reached only by the first point light.
+        let grid_header = [0.0f32; 4];
+        let mut tiles = [[0u32; 2]; LIGHT_GRID_WIDTH * LIGHT_GRID_HEIGHT];
+        tiles[0] = [0, 1];
+        let light_indices = [0u32; 4];
+        let ubo = create_or_get_uniform_buffer(&mut window, &shader, c_str!("LightGridBlock"));
+        unsafe {
+            let offset =
+                write_into_uniform_buffer(ubo, 0, 16, 16, grid_header.as_ptr() as *const u8);
+            let offset = write_into_uniform_buffer(
+                ubo,
+                offset,
+                16,
+                mem::size_of_val(&tiles),
+                tiles.as_ptr() as *const u8,
+            );
+            write_into_uniform_buffer(ubo, offset, 16, 16, light_indices.as_ptr() as *const u8);
+        }
+
+        let vbuf = window_quad(&mut window, colors::WHITE);
+        render_vbuf_with_shader(&mut window, &vbuf, &shader);
+
+        // Near the light
+        assert_eq!(pixel(&window, 2, 2), colors::rgb(255, 128, 128));
+        // Out of the light's radius
+        assert_eq!(
+            pixel(&window, WIN_SIZE - 1, WIN_SIZE - 1),
+            colors::rgb(128, 128, 128)
+        );
+
+        // Shaders that don't use the lights just output the texture.
+        let mut shader = new_shader(b"", b"uniform sampler2D tex;", None);
+        assert_eq!(shader.model, Shader_Model::Unlit);
+        set_uniform(&mut shader, c_str!("tex"), &texture);
+        set_uniform(&mut shader, c_str!("vp"), &vp);
+        render_vbuf_with_shader(&mut window, &vbuf, &shader);
+
+        assert_eq!(pixel(&window, 2, 2), colors::WHITE);
+    }
+
+    #[test]
+    fn lit_shader_applies_spot_lights_only_inside_their_cone() {
+        let mut window = create_test_window();
+
+        let texture = new_white_texture();
+        let vp = get_vp_matrix(&window, &screen_camera());
+        let mut shader = new_shader(b"", b"uniform LightsBlock {};", None);
+        set_uniform(&mut shader, c_str!("tex"), &texture);
+        set_uniform(&mut shader, c_str!("vp"), &vp);
+
+        // A red light on the left edge, pointing right with a 90 degrees cone.
+        let half_cone = std::f32::consts::FRAC_PI_4;
+        // r, g, b, intensity, pos.x, pos.y, radius, attenuation, dir.x, dir.y, cos_outer, cos_inner
+        let spot_light = [
+            1.0f32,
+            0.0,
+            0.0,
+            1.0,
+            0.0,
+            4.0,
+            100.0,
+            0.0,
+            1.0,
+            0.0,
+            half_cone.cos(),
+            (half_cone * 0.5).cos(),
+        ];
+        let ubo = create_or_get_uniform_buffer(&mut window, &shader, c_str!("LightsBlock"));
+        unsafe {
+            write_into_uniform_buffer(
+                ubo,
+                SPOT_LIGHTS_START,
+                16,
+                SPOT_LIGHT_SIZE,
+                spot_light.as_ptr() as *const u8,
+            );
+        }
+
+        let grid_header = [0.0f32; 4];
+        let mut tiles = [[0u32; 2]; LIGHT_GRID_WIDTH * LIGHT_GRID_HEIGHT];
+        tiles[0] = [0, 1 << 16];
+        let light_indices = [0u32; 4];
+        let ubo = create_or_get_uniform_buffer(&mut window, &shader, c_str!("LightGridBlock"));
+        unsafe {
+            let offset =
+                write_into_uniform_buffer(ubo, 0, 16, 16, grid_header.as_ptr() as *const u8);
+            let offset = write_into_uniform_buffer(
+                ubo,
+                offset,
+                16,
+                mem::size_of_val(&tiles),
+                tiles.as_ptr() as *const u8,
+            );
+            write_into_uniform_buffer(ubo, offset, 16, 16, light_indices.as_ptr() as *const u8);
+        }
+
+        let vbuf = window_quad(&mut window, colors::WHITE);
+        render_vbuf_with_shader(&mut window, &vbuf, &shader);
+
+        // In front of the light
+        assert!(pixel(&window, WIN_SIZE - 2, 4).r > 200);
+        // Outside of the cone
+        assert_eq!(pixel(&window, 1, WIN_SIZE - 1), colors::BLACK);
+    }
+
+    #[test]
+    fn lit_shader_applies_point_light_shadows() {
+        let mut window = create_test_window();
+
+        let texture = new_white_texture();
+        let vp = get_vp_matrix(&window, &screen_camera());
+        let mut shader = new_shader(b"", b"uniform LightsBlock {};", None);
+        set_uniform(&mut shader, c_str!("tex"), &texture);
+        set_uniform(&mut shader, c_str!("vp"), &vp);
+
+        // A red light on the left edge.
+        // r, g, b, intensity, pos.x, pos.y, radius, attenuation
+        let point_light = [1.0f32, 0.0, 0.0, 1.0, 0.0, 4.0, 100.0, 0.0];
+        let ubo = create_or_get_uniform_buffer(&mut window, &shader, c_str!("LightsBlock"));
+        unsafe {
+            write_into_uniform_buffer(
+                ubo,
+                AMBIENT_LIGHT_SIZE,
+                16,
+                POINT_LIGHT_SIZE,
+                point_light.as_ptr() as *const u8,
+            );
+        }
+
+        let grid_header = [0.0f32; 4];
+        let mut tiles = [[0u32; 2]; LIGHT_GRID_WIDTH * LIGHT_GRID_HEIGHT];
+        tiles[0] = [0, 1];
+        let light_indices = [0u32; 4];
+        let ubo = create_or_get_uniform_buffer(&mut window, &shader, c_str!("LightGridBlock"));
+        unsafe {
+            let offset =
+                write_into_uniform_buffer(ubo, 0, 16, 16, grid_header.as_ptr() as *const u8);
+            let offset = write_into_uniform_buffer(
+                ubo,
+                offset,
+                16,
+                mem::size_of_val(&tiles),
+                tiles.as_ptr() as *const u8,
+            );
+            write_into_uniform_buffer(ubo, offset, 16, 16, light_indices.as_ptr() as *const u8);
+        }
+
+        // The light's shadow map has an occluder 3 units to its right.
+        let mut shadow_map_indices = [0u8; MAX_POINT_LIGHTS];
+        shadow_map_indices[0] = 1;
+        let mut shadow_map = [1.0f32; SHADOW_MAP_RESOLUTION];
+        let right = SHADOW_MAP_RESOLUTION / 2;
+        for depth in &mut shadow_map[right - 8..right + 8] {
+            *depth = 0.03;
+        }
+        let ubo = create_or_get_uniform_buffer(&mut window, &shader, c_str!("ShadowsBlock"));
+        unsafe {
+            let offset = write_into_uniform_buffer(
+                ubo,
+                0,
+                16,
+                MAX_POINT_LIGHTS,
+                shadow_map_indices.as_ptr(),
+            );
+            write_into_uniform_buffer(
+                ubo,
+                offset,
+                16,
+                mem::size_of_val(&shadow_map),
+                shadow_map.as_ptr() as *const u8,
+            );
+        }
+
+        let vbuf = window_quad(&mut window, colors::WHITE);
+        render_vbuf_with_shader(&mut window, &vbuf, &shader);
+
+        // Before the occluder
+        assert!(pixel(&window, 1, 4).r > 200);
+        // Behind the occluder
+        assert_eq!(pixel(&window, WIN_SIZE - 1, 4), colors::BLACK);
+        // Not in the occluder's direction
+        assert!(pixel(&window, 1, 0).r > 200);
+
+        // Occluders aren't shadowed by their own edges...
+        set_uniform(&mut shader, c_str!("occluder_extent"), 5.0);
+        render_vbuf_with_shader(&mut window, &vbuf, &shader);
+        assert!(pixel(&window, WIN_SIZE - 1, 4).r > 200);
+
+        // ...but they are by the occluders farther than their extent from them.
+        set_uniform(&mut shader, c_str!("occluder_extent"), 2.0);
+        render_vbuf_with_shader(&mut window, &vbuf, &shader);
+        assert_eq!(pixel(&window, WIN_SIZE - 1, 4), colors::BLACK);
+    }
+}
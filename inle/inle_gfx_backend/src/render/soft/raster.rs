@@ -0,0 +1,234 @@
+use super::Vertex;
+use crate::backend_common::types::Glsl_Vec4;
+use crate::render::Primitive_Type;
+use inle_common::colors::Color;
+use inle_math::matrix::Matrix3;
+use inle_math::rect::Recti;
+use inle_math::vector::Vec2f;
+
+/// The render target of the soft backend. Pixels are stored row by row, starting from the top-left.
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Color>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color::default(); (width * height) as usize],
+        }
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if self.width != width || self.height != height {
+            *self = Self::new(width, height);
+        }
+    }
+
+    pub fn clear(&mut self, color: Color) {
+        self.pixels.iter_mut().for_each(|px| *px = color);
+    }
+
+    /// Blends `src` over the pixel like gl does with (SRC_ALPHA, ONE_MINUS_SRC_ALPHA).
+    fn blend(&mut self, x: u32, y: u32, src: Glsl_Vec4) {
+        let dst = &mut self.pixels[(y * self.width + x) as usize];
+        let a = src.w.clamp(0., 1.);
+        let mix = |s: f32, d: u8| to_u8(s * a + (d as f32 / 255.) * (1. - a));
+        *dst = Color {
+            r: mix(src.x, dst.r),
+            g: mix(src.y, dst.g),
+            b: mix(src.z, dst.b),
+            a: mix(src.w, dst.a),
+        };
+    }
+}
+
+#[inline]
+fn to_u8(x: f32) -> u8 {
+    (x.clamp(0., 1.) * 255. + 0.5) as u8
+}
+
+/// Computes the color of a fragment from its interpolated vertex, or discards it returning None.
+/// Note that the interpolated position is the one *before* the mvp transform.
+pub trait Fragment_Shader: Fn(&Vertex) -> Option<Glsl_Vec4> {}
+
+impl<F: Fn(&Vertex) -> Option<Glsl_Vec4>> Fragment_Shader for F {}
+
+pub struct Rasterizer<'a> {
+    pub framebuffer: &'a mut Framebuffer,
+    /// Uses the gl convention, i.e. its origin is the bottom-left of the framebuffer.
+    pub viewport: Recti,
+    pub mvp: &'a Matrix3<f32>,
+}
+
+impl Rasterizer<'_> {
+    pub fn draw_primitives(
+        &mut self,
+        primitive: Primitive_Type,
+        vertices: &[Vertex],
+        shader: &impl Fragment_Shader,
+    ) {
+        match primitive {
+            Primitive_Type::Points => {
+                for v in vertices {
+                    self.draw_point(v, shader);
+                }
+            }
+            Primitive_Type::Lines => {
+                for l in vertices.chunks_exact(2) {
+                    self.draw_line(&l[0], &l[1], shader);
+                }
+            }
+            Primitive_Type::Line_Strip => {
+                for l in vertices.windows(2) {
+                    self.draw_line(&l[0], &l[1], shader);
+                }
+            }
+            Primitive_Type::Triangles => {
+                for t in vertices.chunks_exact(3) {
+                    self.draw_triangle([&t[0], &t[1], &t[2]], shader);
+                }
+            }
+            Primitive_Type::Triangle_Strip => {
+                for (i, t) in vertices.windows(3).enumerate() {
+                    // Keep the same winding for all triangles, as gl does.
+                    if i % 2 == 0 {
+                        self.draw_triangle([&t[0], &t[1], &t[2]], shader);
+                    } else {
+                        self.draw_triangle([&t[1], &t[0], &t[2]], shader);
+                    }
+                }
+            }
+            Primitive_Type::Triangle_Fan => {
+                if let Some((first, rest)) = vertices.split_first() {
+                    for t in rest.windows(2) {
+                        self.draw_triangle([first, &t[0], &t[1]], shader);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Transforms `pos` into framebuffer coordinates.
+    fn to_pixel(&self, pos: Vec2f) -> Vec2f {
+        let clip = self.mvp * v3!(pos.x, pos.y, 1.0);
+        let ndc = v2!(clip.x / clip.z, clip.y / clip.z);
+        let vp = &self.viewport;
+        v2!(
+            vp.x as f32 + (ndc.x + 1.) * 0.5 * vp.width as f32,
+            self.framebuffer.height as f32 - (vp.y as f32 + (ndc.y + 1.) * 0.5 * vp.height as f32),
+        )
+    }
+
+    /// Returns the range of pixels we're allowed to draw into as (min_x, min_y, max_x, max_y) (max excluded).
+    fn clip_rect(&self) -> (i32, i32, i32, i32) {
+        let vp = &self.viewport;
+        let fb_h = self.framebuffer.height as i32;
+        (
+            vp.x.max(0),
+            (fb_h - vp.y - vp.height).max(0),
+            (vp.x + vp.width).min(self.framebuffer.width as i32),
+            (fb_h - vp.y).min(fb_h),
+        )
+    }
+
+    fn shade_pixel(&mut self, x: i32, y: i32, v: &Vertex, shader: &impl Fragment_Shader) {
+        let (min_x, min_y, max_x, max_y) = self.clip_rect();
+        if x < min_x || x >= max_x || y < min_y || y >= max_y {
+            return;
+        }
+        if let Some(color) = shader(v) {
+            self.framebuffer.blend(x as u32, y as u32, color);
+        }
+    }
+
+    fn draw_point(&mut self, v: &Vertex, shader: &impl Fragment_Shader) {
+        let p = self.to_pixel(v.position);
+        self.shade_pixel(p.x.floor() as i32, p.y.floor() as i32, v, shader);
+    }
+
+    fn draw_line(&mut self, a: &Vertex, b: &Vertex, shader: &impl Fragment_Shader) {
+        let pa = self.to_pixel(a.position);
+        let pb = self.to_pixel(b.position);
+        let steps = (pb.x - pa.x).abs().max((pb.y - pa.y).abs()).ceil().max(1.);
+        // Like gl, we don't draw the last pixel, so line strips don't touch the same pixel twice.
+        for i in 0..steps as i32 {
+            let t = (i as f32 + 0.5) / steps;
+            let p = pa + (pb - pa) * t;
+            let v = interpolate(&[a, b], [1. - t, t]);
+            self.shade_pixel(p.x.floor() as i32, p.y.floor() as i32, &v, shader);
+        }
+    }
+
+    fn draw_triangle(&mut self, v: [&Vertex; 3], shader: &impl Fragment_Shader) {
+        let p = [
+            self.to_pixel(v[0].position),
+            self.to_pixel(v[1].position),
+            self.to_pixel(v[2].position),
+        ];
+
+        // The gl backend culls the triangles that are counter-clockwise on screen, and so do we.
+        let area = edge(p[0], p[1], p[2]);
+        if area <= 0. {
+            return;
+        }
+
+        let (clip_min_x, clip_min_y, clip_max_x, clip_max_y) = self.clip_rect();
+        let min_x = (p[0].x.min(p[1].x).min(p[2].x).floor() as i32).max(clip_min_x);
+        let min_y = (p[0].y.min(p[1].y).min(p[2].y).floor() as i32).max(clip_min_y);
+        let max_x = (p[0].x.max(p[1].x).max(p[2].x).ceil() as i32).min(clip_max_x);
+        let max_y = (p[0].y.max(p[1].y).max(p[2].y).ceil() as i32).min(clip_max_y);
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let px = v2!(x as f32 + 0.5, y as f32 + 0.5);
+                let w = [
+                    edge(p[1], p[2], px),
+                    edge(p[2], p[0], px),
+                    edge(p[0], p[1], px),
+                ];
+                let covered = covers(w[0], p[1], p[2])
+                    && covers(w[1], p[2], p[0])
+                    && covers(w[2], p[0], p[1]);
+                if !covered {
+                    continue;
+                }
+
+                let frag = interpolate(&v, [w[0] / area, w[1] / area, w[2] / area]);
+                if let Some(color) = shader(&frag) {
+                    self.framebuffer.blend(x as u32, y as u32, color);
+                }
+            }
+        }
+    }
+}
+
+/// Twice the signed area of the triangle `abc`: it's positive if `abc` is clockwise on screen.
+#[inline]
+fn edge(a: Vec2f, b: Vec2f, c: Vec2f) -> f32 {
+    (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)
+}
+
+/// Uses the top-left rule so pixels on the edge shared by two triangles are only drawn once.
+#[inline]
+fn covers(w: f32, a: Vec2f, b: Vec2f) -> bool {
+    let is_top = a.y == b.y && b.x > a.x;
+    let is_left = b.y < a.y;
+    w > 0. || (w == 0. && (is_top || is_left))
+}
+
+fn interpolate<const N: usize>(v: &[&Vertex; N], weights: [f32; N]) -> Vertex {
+    let mut res = Vertex::default();
+    for (v, &w) in v.iter().zip(weights.iter()) {
+        res.color.x += v.color.x * w;
+        res.color.y += v.color.y * w;
+        res.color.z += v.color.z * w;
+        res.color.w += v.color.w * w;
+        res.position += v.position * w;
+        res.tex_coords += v.tex_coords * w;
+    }
+    res
+}
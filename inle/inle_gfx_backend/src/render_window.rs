@@ -4,16 +4,29 @@ pub mod sfml;
 #[cfg(feature = "gfx-null")]
 pub mod null;
 
-#[cfg(feature = "gfx-gl")]
+#[cfg(all(feature = "gfx-soft", not(feature = "gfx-null")))]
+pub mod soft;
+
+#[cfg(all(
+    feature = "gfx-gl",
+    not(any(feature = "gfx-null", feature = "gfx-soft"))
+))]
 pub mod gl;
 
 #[cfg(feature = "gfx-sfml")]
 pub use self::sfml as backend;
 
+// Note: the null and soft backends take precedence over gl, since the latter is enabled by default.
 #[cfg(feature = "gfx-null")]
 pub use self::null as backend;
 
-#[cfg(feature = "gfx-gl")]
+#[cfg(all(feature = "gfx-soft", not(feature = "gfx-null")))]
+pub use self::soft as backend;
+
+#[cfg(all(
+    feature = "gfx-gl",
+    not(any(feature = "gfx-null", feature = "gfx-soft"))
+))]
 pub use self::gl as backend;
 
 pub type Render_Window_Handle = backend::Render_Window_Handle;
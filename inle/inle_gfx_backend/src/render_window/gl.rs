@@ -1,7 +1,7 @@
 use crate::backend_common::alloc::Buffer_Allocators;
 use crate::backend_common::misc::*;
 use crate::render::get_vp_matrix;
-use crate::render::gl::Uniform_Buffer;
+use crate::render::gl::{self as render, Color_Type, Image, Uniform_Buffer};
 use gl::types::*;
 use inle_alloc::temp;
 use inle_common::colors::Color;
@@ -310,6 +310,33 @@ pub fn start_new_frame(window: &mut Render_Window_Handle) {
     }
 }
 
+/// Reads back the pixels currently in the window's framebuffer, top row first.
+pub fn read_framebuffer(window: &Render_Window_Handle) -> Image {
+    let (width, height) = inle_win::window::get_window_real_size(window);
+    let row_size = 4 * width as usize;
+    let mut bytes = vec![0u8; row_size * height as usize];
+    unsafe {
+        glcheck!(gl::PixelStorei(gl::PACK_ALIGNMENT, 1));
+        glcheck!(gl::ReadPixels(
+            0,
+            0,
+            width as _,
+            height as _,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            bytes.as_mut_ptr() as _,
+        ));
+    }
+
+    // gl's rows start from the bottom of the window, while images start from the top.
+    let mut flipped = Vec::with_capacity(bytes.len());
+    for row in bytes.chunks_exact(row_size).rev() {
+        flipped.extend_from_slice(row);
+    }
+
+    render::new_image_with_data(width, height, Color_Type::RGBA, 8, flipped)
+}
+
 #[inline(always)]
 pub fn n_draw_calls_prev_frame(window: &Render_Window_Handle) -> u32 {
     #[cfg(debug_assertions)]
@@ -322,6 +349,24 @@ pub fn n_draw_calls_prev_frame(window: &Render_Window_Handle) -> u32 {
     }
 }
 
+#[inline(always)]
+pub fn n_draw_calls_this_frame(window: &Render_Window_Handle) -> u32 {
+    #[cfg(debug_assertions)]
+    {
+        window.gl.n_draw_calls_this_frame
+    }
+    #[cfg(not(debug_assertions))]
+    {
+        0
+    }
+}
+
+#[cfg(debug_assertions)]
+#[inline(always)]
+pub fn buffer_allocators(window: &Render_Window_Handle) -> Option<&Buffer_Allocators> {
+    Some(&window.gl.buffer_allocators)
+}
+
 #[cfg(debug_assertions)]
 extern "system" fn gl_msg_callback(
     _source: GLenum,
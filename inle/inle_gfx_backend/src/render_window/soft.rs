@@ -0,0 +1,151 @@
+#[cfg(debug_assertions)]
+use crate::backend_common::alloc::Buffer_Allocators;
+use crate::render::get_vp_matrix;
+use crate::render::soft::raster::Framebuffer;
+use crate::render::soft::{self as render, Color_Type, Image, Uniform_Buffer};
+use inle_alloc::temp;
+use inle_common::colors::{self, Color};
+use inle_math::rect::{Rect, Rectf, Recti};
+use inle_math::transform::Transform2D;
+use inle_math::vector::{Vec2f, Vec2i};
+use inle_win::window::Window_Handle;
+use std::collections::HashMap;
+
+pub struct Render_Window_Handle {
+    window: Window_Handle,
+    pub(crate) viewport: Recti,
+    pub(crate) framebuffer: Framebuffer,
+    clear_color: Color,
+    pub(crate) uniform_buffers: HashMap<&'static std::ffi::CStr, Uniform_Buffer>,
+    pub temp_allocator: temp::Temp_Allocator,
+
+    n_draw_calls_this_frame: u32,
+    n_draw_calls_prev_frame: u32,
+}
+
+impl Render_Window_Handle {
+    pub(crate) fn count_draw_call(&mut self) {
+        self.n_draw_calls_this_frame += 1;
+    }
+}
+
+impl AsRef<Window_Handle> for Render_Window_Handle {
+    fn as_ref(&self) -> &Window_Handle {
+        &self.window
+    }
+}
+
+impl AsMut<Window_Handle> for Render_Window_Handle {
+    fn as_mut(&mut self) -> &mut Window_Handle {
+        &mut self.window
+    }
+}
+
+pub fn create_render_window(window: Window_Handle) -> Render_Window_Handle {
+    let win_size = inle_win::window::get_window_real_size(&window);
+    Render_Window_Handle {
+        window,
+        viewport: Recti::new(0, 0, win_size.0 as _, win_size.1 as _),
+        framebuffer: Framebuffer::new(win_size.0, win_size.1),
+        clear_color: colors::BLACK,
+        uniform_buffers: HashMap::default(),
+        temp_allocator: temp::Temp_Allocator::with_capacity(inle_common::units::megabytes(10)),
+        n_draw_calls_this_frame: 0,
+        n_draw_calls_prev_frame: 0,
+    }
+}
+
+pub fn shutdown(_window: &mut Render_Window_Handle) {}
+
+pub fn recreate_render_window(_window: &mut Render_Window_Handle) {}
+
+pub fn set_clear_color(window: &mut Render_Window_Handle, color: Color) {
+    window.clear_color = color;
+}
+
+/// Note: this is also where the framebuffer follows the window's size.
+pub fn clear(window: &mut Render_Window_Handle) {
+    let (width, height) = inle_win::window::get_window_real_size(window);
+    window.framebuffer.resize(width, height);
+    window.framebuffer.clear(window.clear_color);
+}
+
+pub fn set_viewport(window: &mut Render_Window_Handle, viewport: &Rectf, _view_rect: &Rectf) {
+    let win_size = inle_win::window::get_window_real_size(window);
+    let width = win_size.0 as f32;
+    let height = win_size.1 as f32;
+
+    window.viewport = Rect::new(
+        (0.5 + width * viewport.x) as i32,
+        (0.5 + height * viewport.y) as i32,
+        (0.5 + width * viewport.width) as i32,
+        (0.5 + height * viewport.height) as i32,
+    );
+}
+
+pub fn unproject_screen_pos(
+    screen_pos: Vec2i,
+    window: &Render_Window_Handle,
+    camera: &Transform2D,
+) -> Vec2f {
+    let vp = get_vp_matrix(window, camera);
+    let ndc = v2!(
+        2. * (screen_pos.x as f32 - window.viewport.x as f32) / window.viewport.width as f32 - 1.,
+        1. - 2. * (screen_pos.y as f32 - window.viewport.y as f32) / window.viewport.height as f32,
+    );
+
+    (&vp.inverse() * v3!(ndc.x, ndc.y, 1.0)).into()
+}
+
+pub fn project_world_pos(
+    world_pos: Vec2f,
+    window: &Render_Window_Handle,
+    camera: &Transform2D,
+) -> Vec2i {
+    let vp = get_vp_matrix(window, camera);
+    let clip = &vp * v3!(world_pos.x, world_pos.y, 1.0);
+    let ndc = v2!(clip.x / clip.z, -clip.y / clip.z);
+    let (win_w, win_h) = inle_win::window::get_window_target_size(window);
+    v2!(
+        (ndc.x + 1.) * 0.5 * win_w as f32,
+        (ndc.y + 1.) * 0.5 * win_h as f32,
+    )
+    .into()
+}
+
+#[inline(always)]
+pub fn start_new_frame(window: &mut Render_Window_Handle) {
+    unsafe {
+        window.temp_allocator.dealloc_all();
+    }
+
+    window.n_draw_calls_prev_frame = window.n_draw_calls_this_frame;
+    window.n_draw_calls_this_frame = 0;
+}
+
+/// Reads back the pixels currently in the window's framebuffer, top row first.
+pub fn read_framebuffer(window: &Render_Window_Handle) -> Image {
+    let fb = &window.framebuffer;
+    let mut bytes = Vec::with_capacity(4 * fb.pixels.len());
+    for px in &fb.pixels {
+        bytes.extend_from_slice(&[px.r, px.g, px.b, px.a]);
+    }
+    render::new_image_with_data(fb.width, fb.height, Color_Type::RGBA, 8, bytes)
+}
+
+#[inline(always)]
+pub fn n_draw_calls_prev_frame(window: &Render_Window_Handle) -> u32 {
+    window.n_draw_calls_prev_frame
+}
+
+#[inline(always)]
+pub fn n_draw_calls_this_frame(window: &Render_Window_Handle) -> u32 {
+    window.n_draw_calls_this_frame
+}
+
+/// The soft backend does not allocate any GPU buffer.
+#[cfg(debug_assertions)]
+#[inline(always)]
+pub fn buffer_allocators(_window: &Render_Window_Handle) -> Option<&Buffer_Allocators> {
+    None
+}
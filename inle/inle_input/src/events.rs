@@ -4,12 +4,18 @@ use crate::mouse::Mouse_Button;
 use inle_win::window::Event as Win_Event;
 use std::convert::TryFrom;
 
-#[cfg(feature = "win-glfw")]
+#[cfg(all(feature = "win-glfw", not(feature = "win-null")))]
 mod glfw;
 
-#[cfg(feature = "win-glfw")]
+#[cfg(all(feature = "win-glfw", not(feature = "win-null")))]
 use self::glfw as backend;
 
+#[cfg(feature = "win-null")]
+mod null;
+
+#[cfg(feature = "win-null")]
+use self::null as backend;
+
 #[cfg(feature = "win-sfml")]
 mod sfml;
 
@@ -0,0 +1,6 @@
+use super::Input_Raw_Event;
+use inle_win::window::Event as Win_Event;
+
+pub(super) fn framework_to_engine_event(event: Win_Event) -> Option<Input_Raw_Event> {
+    match event {}
+}
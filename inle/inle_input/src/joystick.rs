@@ -7,15 +7,21 @@ use std::fmt;
 #[cfg(feature = "win-sfml")]
 mod sfml;
 
-#[cfg(feature = "win-glfw")]
+#[cfg(all(feature = "win-glfw", not(feature = "win-null")))]
 mod glfw;
 
 #[cfg(feature = "win-sfml")]
 use self::sfml as backend;
 
-#[cfg(feature = "win-glfw")]
+#[cfg(all(feature = "win-glfw", not(feature = "win-null")))]
 use self::glfw as backend;
 
+#[cfg(feature = "win-null")]
+mod null;
+
+#[cfg(feature = "win-null")]
+use self::null as backend;
+
 pub const JOY_COUNT: u8 = 8;
 
 pub type Joystick_Mask = u8;
@@ -0,0 +1,30 @@
+use super::{Joystick_Axis, Joystick_Button, Joystick_Id};
+use inle_core::env::Env_Info;
+use inle_win::window::Window_Handle;
+
+pub(super) fn get_joy_axis_value_xbox360(
+    _window: &Window_Handle,
+    _joystick_id: Joystick_Id,
+    _axis: Joystick_Axis,
+) -> f32 {
+    0.
+}
+
+pub(super) fn is_joy_btn_pressed_internal_xbox360(
+    _window: &Window_Handle,
+    _joystick_id: Joystick_Id,
+    _button: Joystick_Button,
+) -> bool {
+    false
+}
+
+pub(super) fn is_joy_connected_internal(
+    _window: &Window_Handle,
+    _id: Joystick_Id,
+) -> (bool, Option<Box<str>>) {
+    (false, None)
+}
+
+pub(super) fn update_joysticks() {}
+
+pub(super) fn init_joysticks(_window: &Window_Handle, _env: &Env_Info) {}
@@ -5,15 +5,21 @@ use std::collections::HashSet;
 #[cfg(feature = "win-sfml")]
 pub mod sfml;
 
-#[cfg(feature = "win-glfw")]
+#[cfg(all(feature = "win-glfw", not(feature = "win-null")))]
 pub mod glfw;
 
 #[cfg(feature = "win-sfml")]
 use self::sfml as backend;
 
-#[cfg(feature = "win-glfw")]
+#[cfg(all(feature = "win-glfw", not(feature = "win-null")))]
 use self::glfw as backend;
 
+#[cfg(feature = "win-null")]
+pub mod null;
+
+#[cfg(feature = "win-null")]
+use self::null as backend;
+
 #[derive(Debug, Default, Clone)]
 pub struct Keyboard_State {
     keys_pressed: HashSet<Key>,
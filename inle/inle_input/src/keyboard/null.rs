@@ -0,0 +1,8 @@
+use super::Key;
+
+/// The null backend has no keys of its own.
+pub enum Framework_Key {}
+
+pub(super) fn framework_to_engine_key(key: Framework_Key) -> Option<Key> {
+    match key {}
+}
@@ -6,15 +6,21 @@ use std::convert::TryFrom;
 #[cfg(feature = "win-sfml")]
 pub mod sfml;
 
-#[cfg(feature = "win-glfw")]
+#[cfg(all(feature = "win-glfw", not(feature = "win-null")))]
 pub mod glfw;
 
 #[cfg(feature = "win-sfml")]
 use self::sfml as backend;
 
-#[cfg(feature = "win-glfw")]
+#[cfg(all(feature = "win-glfw", not(feature = "win-null")))]
 use self::glfw as backend;
 
+#[cfg(feature = "win-null")]
+pub mod null;
+
+#[cfg(feature = "win-null")]
+use self::null as backend;
+
 pub type Button = backend::Button;
 
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Eq, Ord, Hash)]
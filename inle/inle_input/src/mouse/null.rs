@@ -0,0 +1,17 @@
+use super::Mouse_Button;
+
+/// The null backend has no buttons of its own.
+pub enum Button {}
+
+pub(super) fn get_mouse_btn(btn: Button) -> Option<Mouse_Button> {
+    match btn {}
+}
+
+pub(super) fn num_to_mouse_btn(num: usize) -> Option<Mouse_Button> {
+    match num {
+        0 => Some(Mouse_Button::Left),
+        1 => Some(Mouse_Button::Right),
+        2 => Some(Mouse_Button::Middle),
+        _ => None,
+    }
+}
@@ -18,6 +18,7 @@ pub mod angle;
 pub mod math;
 pub mod matrix;
 pub mod rect;
+mod serialize;
 pub mod shapes;
 pub mod transform;
 pub mod vector;
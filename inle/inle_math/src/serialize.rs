@@ -0,0 +1,80 @@
+use crate::angle::{rad, Angle};
+use crate::rect::Rect;
+use crate::transform::Transform2D;
+use crate::vector::Vector2;
+use inle_serialize::{Binary_Serializable, Byte_Stream};
+use std::io;
+
+impl<T: Binary_Serializable> Binary_Serializable for Vector2<T> {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.x.serialize(output)?;
+        self.y.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        let x = T::deserialize(input)?;
+        let y = T::deserialize(input)?;
+        Ok(Vector2 { x, y })
+    }
+}
+
+impl<T: Binary_Serializable> Binary_Serializable for Rect<T> {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.x.serialize(output)?;
+        self.y.serialize(output)?;
+        self.width.serialize(output)?;
+        self.height.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        let x = T::deserialize(input)?;
+        let y = T::deserialize(input)?;
+        let width = T::deserialize(input)?;
+        let height = T::deserialize(input)?;
+        Ok(Rect {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+}
+
+impl Binary_Serializable for Angle {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        output.write_f32(self.as_rad())
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(rad(input.read_f32()?))
+    }
+}
+
+impl Binary_Serializable for Transform2D {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.position().serialize(output)?;
+        self.rotation().serialize(output)?;
+        self.scale().serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        let pos = Vector2::<f32>::deserialize(input)?;
+        let rot = Angle::deserialize(input)?;
+        let scale = Vector2::<f32>::deserialize(input)?;
+        Ok(Transform2D::from_pos_rot_scale(pos, rot, scale))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialize_transform() {
+        let t = Transform2D::from_pos_rot_scale(v2!(1., -2.), rad(0.5), v2!(3., 4.));
+        let mut bs = Byte_Stream::new();
+        t.serialize(&mut bs).unwrap();
+        bs.seek(0);
+        assert_eq!(Transform2D::deserialize(&mut bs).unwrap(), t);
+    }
+}
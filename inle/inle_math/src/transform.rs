@@ -1,6 +1,6 @@
 use super::angle::{rad, Angle};
 use super::matrix::Matrix3;
-use super::vector::Vec2f;
+use super::vector::{lerp_v, Vec2f};
 
 #[cfg(feature = "gfx-sfml")]
 pub mod sfml;
@@ -193,6 +193,16 @@ impl inle_test::approx_eq_testable::Approx_Eq_Testable for Transform2D {
     }
 }
 
+/// Interpolates position, rotation and scale separately. Rotation goes along the shortest arc.
+pub fn lerp_transform(from: &Transform2D, to: &Transform2D, t: f32) -> Transform2D {
+    let rot_delta = (to.rotation - from.rotation).as_rad_negpipi();
+    Transform2D {
+        position: lerp_v(from.position, to.position, t),
+        rotation: from.rotation + rad(rot_delta * t),
+        scale: lerp_v(from.scale, to.scale, t),
+    }
+}
+
 // Note: Matrix3 is column-major
 pub fn matrix_pretty_print(m: &Matrix3<f32>) {
     println!(
@@ -315,4 +325,23 @@ mod tests {
         t5.translate(20., -100.);
         assert_approx_eq!(t5.position(), v2!(20., -70.));
     }
+
+    #[test]
+    fn lerp() {
+        let t1 = Transform2D::from_pos_rot_scale(v2!(0., 10.), rad(0.2), v2!(1., 1.));
+        let t2 = Transform2D::from_pos_rot_scale(v2!(10., -10.), rad(0.6), v2!(2., 3.));
+        assert_approx_eq!(lerp_transform(&t1, &t2, 0.), t1);
+        assert_approx_eq!(lerp_transform(&t1, &t2, 1.), t2);
+
+        let mid = lerp_transform(&t1, &t2, 0.5);
+        assert_approx_eq!(mid.position(), v2!(5., 0.));
+        assert_approx_eq!(mid.rotation(), rad(0.4));
+        assert_approx_eq!(mid.scale(), v2!(1.5, 2.));
+
+        // Rotation goes the short way around
+        let t3 = Transform2D::from_pos_rot_scale(v2!(0., 0.), rad(-3.), v2!(1., 1.));
+        let t4 = Transform2D::from_pos_rot_scale(v2!(0., 0.), rad(3.), v2!(1., 1.));
+        let mid = lerp_transform(&t3, &t4, 0.5);
+        assert_approx_eq!(mid.rotation().as_rad_0tau(), std::f32::consts::PI);
+    }
 }
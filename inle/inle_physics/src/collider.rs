@@ -1,20 +1,112 @@
 use super::layers::Collision_Layer;
 use super::phys_world::{Collider_Handle, Physics_Body_Handle};
 use inle_ecs::ecs_world::Entity;
+use inle_math::angle::{rad, Angle};
 use inle_math::vector::Vec2f;
+use inle_serialize::{Binary_Serializable, Byte_Stream};
+use smallvec::SmallVec;
+use std::io;
+use std::sync::Arc;
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-#[non_exhaustive]
+const SHAPE_RECT: u8 = 0x0;
+const SHAPE_CIRCLE: u8 = 0x1;
+const SHAPE_ORIENTED_RECT: u8 = 0x2;
+const SHAPE_POLYGON: u8 = 0x3;
+const SHAPE_CAPSULE: u8 = 0x4;
+
+pub const MAX_POLYGON_VERTICES: usize = 8;
+
+pub type Polygon_Vertices = SmallVec<[Vec2f; MAX_POLYGON_VERTICES]>;
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Collision_Shape {
-    Rect { width: f32, height: f32 },
-    Circle { radius: f32 },
+    /// Always axis-aligned, regardless of the collider's rotation.
+    Rect {
+        width: f32,
+        height: f32,
+    },
+    Circle {
+        radius: f32,
+    },
+    /// Like Rect, but rotates along with the collider.
+    Oriented_Rect {
+        width: f32,
+        height: f32,
+    },
+    /// A convex polygon whose vertices are relative to the collider's position.
+    /// Use Collision_Shape::new_polygon to create it.
+    /// The vertices are shared, so cloning the shape is cheap.
+    Polygon {
+        vertices: Arc<[Vec2f]>,
+        /// The distance of the farthest vertex from the collider's position.
+        bounding_radius: f32,
+    },
+    /// A segment going from -half_length to +half_length along the collider's local Y axis,
+    /// inflated by `radius`.
+    Capsule {
+        radius: f32,
+        half_length: f32,
+    },
+}
+
+/// Used to index the collision detection table.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Collision_Shape_Kind {
+    Circle,
+    Rect,
+    Oriented_Rect,
+    Polygon,
+    Capsule,
+    _Count,
 }
 
 impl Collision_Shape {
-    pub fn extent(self) -> Vec2f {
+    /// `vertices` must describe a convex polygon (in either winding order) with at least 3
+    /// and at most MAX_POLYGON_VERTICES vertices.
+    pub fn new_polygon(vertices: &[Vec2f]) -> Self {
+        assert!(
+            vertices.len() >= 3 && vertices.len() <= MAX_POLYGON_VERTICES,
+            "Polygon must have between 3 and {} vertices (has {})",
+            MAX_POLYGON_VERTICES,
+            vertices.len()
+        );
+        Collision_Shape::Polygon {
+            vertices: vertices.into(),
+            bounding_radius: vertices.iter().map(|v| v.magnitude()).fold(0., f32::max),
+        }
+    }
+
+    pub fn kind(&self) -> Collision_Shape_Kind {
         match self {
+            Collision_Shape::Circle { .. } => Collision_Shape_Kind::Circle,
+            Collision_Shape::Rect { .. } => Collision_Shape_Kind::Rect,
+            Collision_Shape::Oriented_Rect { .. } => Collision_Shape_Kind::Oriented_Rect,
+            Collision_Shape::Polygon { .. } => Collision_Shape_Kind::Polygon,
+            Collision_Shape::Capsule { .. } => Collision_Shape_Kind::Capsule,
+        }
+    }
+
+    /// Returns the size of an axis-aligned box containing the shape. For the shapes that rotate
+    /// with their collider, the box contains the shape at any rotation.
+    pub fn extent(&self) -> Vec2f {
+        match *self {
             Collision_Shape::Circle { radius } => v2!(radius, radius) * 2.,
             Collision_Shape::Rect { width, height } => v2!(width, height),
+            Collision_Shape::Oriented_Rect { width, height } => {
+                let diag = v2!(width, height).magnitude();
+                v2!(diag, diag)
+            }
+            Collision_Shape::Polygon {
+                bounding_radius, ..
+            } => v2!(bounding_radius, bounding_radius) * 2.,
+            Collision_Shape::Capsule {
+                radius,
+                half_length,
+            } => {
+                let size = 2. * (radius + half_length);
+                v2!(size, size)
+            }
         }
     }
 }
@@ -28,6 +120,7 @@ impl Default for Collision_Shape {
 #[derive(Clone, Debug, Default)]
 pub struct Collider {
     pub shape: Collision_Shape,
+    /// Relative to the entity's position, in its local space (so it rotates along with it).
     pub offset: Vec2f,
     pub is_static: bool,
     pub layer: Collision_Layer,
@@ -38,10 +131,207 @@ pub struct Collider {
 
     // These should not be written except by the physics system.
     pub position: Vec2f,
+    pub rotation: Angle,
     pub velocity: Vec2f,
 }
 
+impl Collider {
+    /// Returns the world-space vertices of the shape, if it's a polygonal one
+    /// (Rect, Oriented_Rect or Polygon).
+    pub fn world_polygon(&self) -> Option<Polygon_Vertices> {
+        match self.shape {
+            Collision_Shape::Rect { width, height } => {
+                Some(rect_vertices(self.position, width, height, rad(0.)))
+            }
+            Collision_Shape::Oriented_Rect { width, height } => {
+                Some(rect_vertices(self.position, width, height, self.rotation))
+            }
+            Collision_Shape::Polygon { ref vertices, .. } => Some(
+                vertices
+                    .iter()
+                    .map(|v| self.position + v.rotated(self.rotation))
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Returns the world-space segment and radius of the shape, if it's a rounded one
+    /// (Circle or Capsule). A Circle has a zero-length segment.
+    pub fn world_rounded(&self) -> Option<(Vec2f, Vec2f, f32)> {
+        match self.shape {
+            Collision_Shape::Circle { radius } => Some((self.position, self.position, radius)),
+            Collision_Shape::Capsule {
+                radius,
+                half_length,
+            } => {
+                let axis = v2!(0., half_length).rotated(self.rotation);
+                Some((self.position - axis, self.position + axis, radius))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn rect_vertices(center: Vec2f, width: f32, height: f32, rotation: Angle) -> Polygon_Vertices {
+    let hw = width * 0.5;
+    let hh = height * 0.5;
+    [v2!(-hw, -hh), v2!(hw, -hh), v2!(hw, hh), v2!(-hw, hh)]
+        .iter()
+        .map(|&v| center + v.rotated(rotation))
+        .collect()
+}
+
 #[derive(Copy, Clone, Debug, Default)]
 pub struct C_Collider {
     pub phys_body_handle: Physics_Body_Handle,
 }
+
+impl Binary_Serializable for Collision_Shape {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        match *self {
+            Collision_Shape::Rect { width, height } => {
+                output.write_u8(SHAPE_RECT)?;
+                output.write_f32(width)?;
+                output.write_f32(height)
+            }
+            Collision_Shape::Circle { radius } => {
+                output.write_u8(SHAPE_CIRCLE)?;
+                output.write_f32(radius)
+            }
+            Collision_Shape::Oriented_Rect { width, height } => {
+                output.write_u8(SHAPE_ORIENTED_RECT)?;
+                output.write_f32(width)?;
+                output.write_f32(height)
+            }
+            Collision_Shape::Polygon { ref vertices, .. } => {
+                output.write_u8(SHAPE_POLYGON)?;
+                output.write_u8(vertices.len() as u8)?;
+                for v in vertices.iter() {
+                    v.serialize(output)?;
+                }
+                Ok(())
+            }
+            Collision_Shape::Capsule {
+                radius,
+                half_length,
+            } => {
+                output.write_u8(SHAPE_CAPSULE)?;
+                output.write_f32(radius)?;
+                output.write_f32(half_length)
+            }
+        }
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        match input.read_u8()? {
+            SHAPE_RECT => {
+                let width = input.read_f32()?;
+                let height = input.read_f32()?;
+                Ok(Collision_Shape::Rect { width, height })
+            }
+            SHAPE_CIRCLE => Ok(Collision_Shape::Circle {
+                radius: input.read_f32()?,
+            }),
+            SHAPE_ORIENTED_RECT => {
+                let width = input.read_f32()?;
+                let height = input.read_f32()?;
+                Ok(Collision_Shape::Oriented_Rect { width, height })
+            }
+            SHAPE_POLYGON => {
+                let n_vertices = input.read_u8()?;
+                if n_vertices < 3 || n_vertices as usize > MAX_POLYGON_VERTICES {
+                    return Err(io::ErrorKind::InvalidData.into());
+                }
+                let mut vertices = Polygon_Vertices::new();
+                for _ in 0..n_vertices {
+                    vertices.push(Vec2f::deserialize(input)?);
+                }
+                Ok(Collision_Shape::new_polygon(&vertices))
+            }
+            SHAPE_CAPSULE => {
+                let radius = input.read_f32()?;
+                let half_length = input.read_f32()?;
+                Ok(Collision_Shape::Capsule {
+                    radius,
+                    half_length,
+                })
+            }
+            _ => Err(io::ErrorKind::InvalidData.into()),
+        }
+    }
+}
+
+impl Binary_Serializable for Collider {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.shape.serialize(output)?;
+        self.offset.serialize(output)?;
+        self.is_static.serialize(output)?;
+        self.layer.serialize(output)?;
+        self.entity.serialize(output)?;
+        self.handle.serialize(output)?;
+        self.position.serialize(output)?;
+        self.rotation.serialize(output)?;
+        self.velocity.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Collider {
+            shape: Collision_Shape::deserialize(input)?,
+            offset: Vec2f::deserialize(input)?,
+            is_static: bool::deserialize(input)?,
+            layer: Collision_Layer::deserialize(input)?,
+            entity: Entity::deserialize(input)?,
+            handle: Collider_Handle::deserialize(input)?,
+            position: Vec2f::deserialize(input)?,
+            rotation: Angle::deserialize(input)?,
+            velocity: Vec2f::deserialize(input)?,
+        })
+    }
+}
+
+impl Binary_Serializable for C_Collider {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.phys_body_handle.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(C_Collider {
+            phys_body_handle: Physics_Body_Handle::deserialize(input)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialize_deserialize_shapes() {
+        let shapes = [
+            Collision_Shape::Rect {
+                width: 2.,
+                height: 3.,
+            },
+            Collision_Shape::Circle { radius: 4. },
+            Collision_Shape::Oriented_Rect {
+                width: 5.,
+                height: 6.,
+            },
+            Collision_Shape::new_polygon(&[v2!(0., 0.), v2!(1., 0.), v2!(0., 1.)]),
+            Collision_Shape::Capsule {
+                radius: 7.,
+                half_length: 8.,
+            },
+        ];
+
+        let mut stream = Byte_Stream::new();
+        for shape in &shapes {
+            shape.serialize(&mut stream).unwrap();
+        }
+        stream.seek(0);
+        for shape in &shapes {
+            assert_eq!(Collision_Shape::deserialize(&mut stream).unwrap(), *shape);
+        }
+    }
+}
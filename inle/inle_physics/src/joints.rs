@@ -0,0 +1,119 @@
+use super::phys_world::Physics_Body_Handle;
+use inle_alloc::gen_alloc::Generational_Index;
+use inle_math::vector::Vec2f;
+use inle_serialize::{Binary_Serializable, Byte_Stream};
+use std::io;
+
+const JOINT_DISTANCE: u8 = 0x0;
+const JOINT_REVOLUTE: u8 = 0x1;
+const JOINT_SPRING: u8 = 0x2;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct Joint_Handle(pub(super) Generational_Index);
+
+impl std::ops::Deref for Joint_Handle {
+    type Target = Generational_Index;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Joint_Kind {
+    /// Keeps the anchors at `length` from each other, like a rigid rod.
+    Distance { length: f32 },
+    /// Keeps the anchors in the same place, like a pin.
+    Revolute,
+    /// Pulls the anchors towards `rest_length` from each other, like a damped spring.
+    Spring {
+        rest_length: f32,
+        stiffness: f32,
+        damping: f32,
+    },
+}
+
+/// Constrains the relative motion of two Physics_Bodies.
+/// Anchors are offsets from the position of their body's entity, in its local space (so they
+/// rotate along with it).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Joint {
+    pub kind: Joint_Kind,
+    pub body_a: Physics_Body_Handle,
+    pub anchor_a: Vec2f,
+    /// If None, `anchor_b` is a fixed point in world space (e.g. where a torch is hanging from).
+    pub body_b: Option<Physics_Body_Handle>,
+    pub anchor_b: Vec2f,
+}
+
+impl Binary_Serializable for Joint_Handle {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.0.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Joint_Handle(Generational_Index::deserialize(input)?))
+    }
+}
+
+impl Binary_Serializable for Joint_Kind {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        match *self {
+            Joint_Kind::Distance { length } => {
+                output.write_u8(JOINT_DISTANCE)?;
+                output.write_f32(length)
+            }
+            Joint_Kind::Revolute => output.write_u8(JOINT_REVOLUTE),
+            Joint_Kind::Spring {
+                rest_length,
+                stiffness,
+                damping,
+            } => {
+                output.write_u8(JOINT_SPRING)?;
+                output.write_f32(rest_length)?;
+                output.write_f32(stiffness)?;
+                output.write_f32(damping)
+            }
+        }
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        match input.read_u8()? {
+            JOINT_DISTANCE => Ok(Joint_Kind::Distance {
+                length: input.read_f32()?,
+            }),
+            JOINT_REVOLUTE => Ok(Joint_Kind::Revolute),
+            JOINT_SPRING => {
+                let rest_length = input.read_f32()?;
+                let stiffness = input.read_f32()?;
+                let damping = input.read_f32()?;
+                Ok(Joint_Kind::Spring {
+                    rest_length,
+                    stiffness,
+                    damping,
+                })
+            }
+            _ => Err(io::ErrorKind::InvalidData.into()),
+        }
+    }
+}
+
+impl Binary_Serializable for Joint {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.kind.serialize(output)?;
+        self.body_a.serialize(output)?;
+        self.anchor_a.serialize(output)?;
+        self.body_b.serialize(output)?;
+        self.anchor_b.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Joint {
+            kind: Joint_Kind::deserialize(input)?,
+            body_a: Physics_Body_Handle::deserialize(input)?,
+            anchor_a: Vec2f::deserialize(input)?,
+            body_b: Option::deserialize(input)?,
+            anchor_b: Vec2f::deserialize(input)?,
+        })
+    }
+}
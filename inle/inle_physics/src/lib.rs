@@ -11,7 +11,9 @@ extern crate inle_diagnostics;
 extern crate inle_math;
 
 pub mod collider;
+pub mod joints;
 pub mod layers;
 pub mod phys_world;
 pub mod physics;
+pub mod queries;
 pub mod spatial;
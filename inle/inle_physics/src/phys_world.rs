@@ -1,9 +1,13 @@
 use super::collider::Collider;
+use super::joints::{Joint, Joint_Handle};
 use inle_alloc::gen_alloc::{Generational_Allocator, Generational_Index};
 use inle_ecs::ecs_world::Entity;
 use inle_math::vector::Vec2f;
+use inle_serialize::{Binary_Serializable, Byte_Stream};
 use smallvec::SmallVec;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::time::Duration;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
 pub struct Collider_Handle(Generational_Index);
@@ -27,6 +31,26 @@ impl std::ops::Deref for Physics_Body_Handle {
     }
 }
 
+impl Binary_Serializable for Collider_Handle {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.0.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Collider_Handle(Generational_Index::deserialize(input)?))
+    }
+}
+
+impl Binary_Serializable for Physics_Body_Handle {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.0.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Physics_Body_Handle(Generational_Index::deserialize(input)?))
+    }
+}
+
 const INITIAL_SIZE: usize = 64;
 
 #[derive(Copy, Clone, Debug, Default)]
@@ -35,6 +59,9 @@ pub struct Phys_Data {
     pub restitution: f32,
     pub static_friction: f32,
     pub dyn_friction: f32,
+    /// If true, the motion of this collider is swept against the other rigidbodies, so it
+    /// can't tunnel through them when moving fast. Only Circle and Rect colliders support this.
+    pub continuous_collision: bool,
 }
 
 /// A Physics_Body can contain any number of Colliders, and it's what is associated
@@ -46,6 +73,51 @@ pub struct Physics_Body {
     pub trigger_colliders: Vec<Collider_Handle>,
 }
 
+impl Binary_Serializable for Phys_Data {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        output.write_f32(self.inv_mass)?;
+        output.write_f32(self.restitution)?;
+        output.write_f32(self.static_friction)?;
+        output.write_f32(self.dyn_friction)?;
+        self.continuous_collision.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Phys_Data {
+            inv_mass: input.read_f32()?,
+            restitution: input.read_f32()?,
+            static_friction: input.read_f32()?,
+            dyn_friction: input.read_f32()?,
+            continuous_collision: bool::deserialize(input)?,
+        })
+    }
+}
+
+impl Binary_Serializable for Physics_Body {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        output.write_u32(self.rigidbody_colliders.len() as u32)?;
+        for (handle, phys_data) in &self.rigidbody_colliders {
+            handle.serialize(output)?;
+            phys_data.serialize(output)?;
+        }
+        self.trigger_colliders.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        let n_rigidbodies = input.read_u32()?;
+        let mut rigidbody_colliders = SmallVec::new();
+        for _ in 0..n_rigidbodies {
+            let handle = Collider_Handle::deserialize(input)?;
+            let phys_data = Phys_Data::deserialize(input)?;
+            rigidbody_colliders.push((handle, phys_data));
+        }
+        Ok(Physics_Body {
+            rigidbody_colliders,
+            trigger_colliders: Vec::deserialize(input)?,
+        })
+    }
+}
+
 impl Physics_Body {
     pub fn all_colliders(&self) -> impl Iterator<Item = Collider_Handle> + '_ {
         Physics_Body_Cld_Iter { body: self, i: 0 }
@@ -78,6 +150,25 @@ impl Iterator for Physics_Body_Cld_Iter<'_> {
 pub struct Collision_Data {
     pub other_collider: Collider_Handle,
     pub info: Collision_Info,
+    /// True if the two colliders weren't touching when the latest collision events were raised
+    /// (i.e. at the end of the previous tick).
+    pub is_new: bool,
+}
+
+/// The arguments of the collision begin/persist/end events.
+/// Colliders A and B are always ordered in the same way for the same pair, so the
+/// events of a pair can be matched across frames.
+#[derive(Debug, Clone)]
+pub struct Collision_Event_Data {
+    pub cld_a: Collider_Handle,
+    pub entity_a: Entity,
+    pub cld_b: Collider_Handle,
+    pub entity_b: Entity,
+    /// True if at least one of the colliders is a trigger collider.
+    pub is_trigger: bool,
+    /// The normal goes from A to B. For an end event, this is the latest info the
+    /// pair had while touching.
+    pub info: Collision_Info,
 }
 
 #[derive(Debug, Clone)]
@@ -86,6 +177,68 @@ pub struct Collision_Info {
     pub normal: Vec2f,
 }
 
+impl Binary_Serializable for Collision_Info {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        output.write_f32(self.penetration)?;
+        self.normal.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Collision_Info {
+            penetration: input.read_f32()?,
+            normal: Vec2f::deserialize(input)?,
+        })
+    }
+}
+
+impl Binary_Serializable for Collision_Event_Data {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.cld_a.serialize(output)?;
+        self.entity_a.serialize(output)?;
+        self.cld_b.serialize(output)?;
+        self.entity_b.serialize(output)?;
+        self.is_trigger.serialize(output)?;
+        self.info.serialize(output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Collision_Event_Data {
+            cld_a: Collider_Handle::deserialize(input)?,
+            entity_a: Entity::deserialize(input)?,
+            cld_b: Collider_Handle::deserialize(input)?,
+            entity_b: Entity::deserialize(input)?,
+            is_trigger: bool::deserialize(input)?,
+            info: Collision_Info::deserialize(input)?,
+        })
+    }
+}
+
+type Contacts = HashMap<(Collider_Handle, Collider_Handle), Collision_Event_Data>;
+
+/// Saves the contacts sorted by key, so the same contacts always produce the same bytes.
+fn serialize_contacts(contacts: &Contacts, output: &mut Byte_Stream) -> io::Result<()> {
+    let mut sorted: Vec<_> = contacts.iter().collect();
+    sorted.sort_unstable_by_key(|((a, b), _)| (a.index, a.gen, b.index, b.gen));
+    output.write_u32(sorted.len() as u32)?;
+    for ((a, b), data) in sorted {
+        a.serialize(output)?;
+        b.serialize(output)?;
+        data.serialize(output)?;
+    }
+    Ok(())
+}
+
+fn deserialize_contacts(input: &mut Byte_Stream) -> io::Result<Contacts> {
+    let n_contacts = input.read_u32()?;
+    let mut contacts = Contacts::default();
+    for _ in 0..n_contacts {
+        let a = Collider_Handle::deserialize(input)?;
+        let b = Collider_Handle::deserialize(input)?;
+        contacts.insert((a, b), Collision_Event_Data::deserialize(input)?);
+    }
+    Ok(contacts)
+}
+
 pub struct Physics_World {
     cld_alloc: Generational_Allocator,
     /// Indexed by a Collider_Handle's index. Contains the index into `colliders`.
@@ -98,8 +251,121 @@ pub struct Physics_World {
     /// Indexed by a Physics_Body_Handle's index.
     pub(super) bodies: Vec<Physics_Body>,
 
+    joints_alloc: Generational_Allocator,
+    /// Indexed by a Joint_Handle's index.
+    pub(super) joints: Vec<Option<Joint>>,
+
     /// Contains all collisions for this frame.
     pub(super) collisions: HashMap<Collider_Handle, SmallVec<[Collision_Data; 4]>>,
+
+    /// Contains all the pairs of colliders that were touching during the latest physics update,
+    /// keyed by contact_key(). Used to tell apart the begin, persist and end of a contact.
+    pub(super) contacts: HashMap<(Collider_Handle, Collider_Handle), Collision_Event_Data>,
+    /// All the contacts found by the physics updates since the latest collision events were
+    /// raised, with their latest info. A tick may run several physics updates (substeps), but
+    /// it only raises the events once.
+    pub(super) contacts_this_tick:
+        HashMap<(Collider_Handle, Collider_Handle), Collision_Event_Data>,
+    /// The contacts as of the latest collision events.
+    pub(super) reported_contacts: HashMap<(Collider_Handle, Collider_Handle), Collision_Event_Data>,
+
+    /// The entities whose rigidbodies are asleep: they're not tested against other sleeping
+    /// or static colliders and they're not solved until something wakes them up.
+    pub(super) sleeping: HashSet<Entity>,
+    /// How long each awake rigidbody has been (almost) still for.
+    pub(super) still_time: HashMap<Entity, Duration>,
+}
+
+/// Returns the key of the (unordered) pair of colliders in Physics_World::contacts.
+pub(super) fn contact_key(
+    cld_a: Collider_Handle,
+    cld_b: Collider_Handle,
+) -> (Collider_Handle, Collider_Handle) {
+    if (cld_a.index, cld_a.gen) <= (cld_b.index, cld_b.gen) {
+        (cld_a, cld_b)
+    } else {
+        (cld_b, cld_a)
+    }
+}
+
+/// Note that this doesn't save the collisions of the current frame: they're recomputed
+/// on the next physics update. The contacts are saved instead, so the contacts that were
+/// already ongoing keep raising persist events rather than begin ones.
+impl Binary_Serializable for Physics_World {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        self.cld_alloc.serialize(output)?;
+        let cld_index_table: Vec<u32> = self.cld_index_table.iter().map(|&i| i as u32).collect();
+        cld_index_table.serialize(output)?;
+        self.colliders.serialize(output)?;
+        self.bodies_alloc.serialize(output)?;
+        self.bodies.serialize(output)?;
+        self.joints_alloc.serialize(output)?;
+        self.joints.serialize(output)?;
+
+        // Sort the sleep state, since it comes from hash containers and we want the same
+        // world to always produce the same bytes.
+        let mut sleeping: Vec<Entity> = self.sleeping.iter().copied().collect();
+        sleeping.sort_unstable_by_key(|e| (e.index, e.gen));
+        sleeping.serialize(output)?;
+
+        let mut still_time: Vec<(&Entity, &Duration)> = self.still_time.iter().collect();
+        still_time.sort_unstable_by_key(|(e, _)| (e.index, e.gen));
+        output.write_u32(still_time.len() as u32)?;
+        for (entity, time) in still_time {
+            entity.serialize(output)?;
+            time.serialize(output)?;
+        }
+
+        serialize_contacts(&self.contacts, output)?;
+        serialize_contacts(&self.contacts_this_tick, output)?;
+        serialize_contacts(&self.reported_contacts, output)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        let cld_alloc = Generational_Allocator::deserialize(input)?;
+        let cld_index_table: Vec<usize> = Vec::<u32>::deserialize(input)?
+            .into_iter()
+            .map(|i| i as usize)
+            .collect();
+        let colliders = Vec::<Collider>::deserialize(input)?;
+        let bodies_alloc = Generational_Allocator::deserialize(input)?;
+        let bodies = Vec::<Physics_Body>::deserialize(input)?;
+        let joints_alloc = Generational_Allocator::deserialize(input)?;
+        let joints = Vec::<Option<Joint>>::deserialize(input)?;
+        let sleeping = Vec::<Entity>::deserialize(input)?.into_iter().collect();
+        let n_still = input.read_u32()?;
+        let mut still_time = HashMap::default();
+        for _ in 0..n_still {
+            let entity = Entity::deserialize(input)?;
+            still_time.insert(entity, Duration::deserialize(input)?);
+        }
+        let contacts = deserialize_contacts(input)?;
+        let contacts_this_tick = deserialize_contacts(input)?;
+        let reported_contacts = deserialize_contacts(input)?;
+
+        if colliders
+            .iter()
+            .any(|cld| cld_index_table.get(cld.handle.index as usize).is_none())
+        {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+
+        Ok(Self {
+            cld_alloc,
+            cld_index_table,
+            colliders,
+            bodies_alloc,
+            bodies,
+            joints_alloc,
+            joints,
+            collisions: HashMap::default(),
+            contacts,
+            contacts_this_tick,
+            reported_contacts,
+            sleeping,
+            still_time,
+        })
+    }
 }
 
 impl Physics_World {
@@ -110,7 +376,14 @@ impl Physics_World {
             colliders: vec![],
             bodies_alloc: Generational_Allocator::new(INITIAL_SIZE),
             bodies: vec![],
+            joints_alloc: Generational_Allocator::new(INITIAL_SIZE),
+            joints: vec![],
             collisions: HashMap::default(),
+            contacts: HashMap::default(),
+            contacts_this_tick: HashMap::default(),
+            reported_contacts: HashMap::default(),
+            sleeping: HashSet::default(),
+            still_time: HashMap::default(),
         }
     }
 
@@ -125,12 +398,43 @@ impl Physics_World {
             self.bodies
                 .resize(handle.index as usize + 1, Physics_Body::default());
         } else {
-            self.bodies
-                .insert(handle.index as usize, Physics_Body::default());
+            self.bodies[handle.index as usize] = Physics_Body::default();
         }
         Physics_Body_Handle(handle)
     }
 
+    /// Removes the body along with all its colliders.
+    pub fn remove_physics_body(&mut self, handle: Physics_Body_Handle) {
+        let body = match self.get_physics_body_mut(handle) {
+            Some(body) => std::mem::take(body),
+            None => {
+                lwarn!("Tried to remove invalid physics body {:?}", handle);
+                return;
+            }
+        };
+
+        for cld in body.all_colliders() {
+            self.remove_collider(cld);
+        }
+
+        // Joints can't outlive their bodies, or they'd get attached to whatever body reuses the
+        // handle.
+        for (index, slot) in self.joints.iter_mut().enumerate() {
+            let is_attached =
+                slot.is_some_and(|joint| joint.body_a == handle || joint.body_b == Some(handle));
+            if is_attached {
+                *slot = None;
+                let index = index as _;
+                self.joints_alloc.deallocate(Generational_Index {
+                    index,
+                    gen: self.joints_alloc.cur_gen(index),
+                });
+            }
+        }
+
+        self.bodies_alloc.deallocate(*handle);
+    }
+
     pub fn new_physics_body_with_rigidbody(
         &mut self,
         cld: Collider,
@@ -188,11 +492,13 @@ impl Physics_World {
         cld.handle = handle;
         cld.entity = entity;
         self.colliders.push(cld);
-        self.cld_index_table.push(index);
+        if self.cld_index_table.len() <= handle.index as usize {
+            self.cld_index_table.resize(handle.index as usize + 1, 0);
+        }
+        self.cld_index_table[handle.index as usize] = index;
         handle
     }
 
-    /// Note: this is a O(n) operation, so use sparingly.
     pub fn remove_collider(&mut self, handle: Collider_Handle) {
         if !self.cld_alloc.is_valid(*handle) {
             lwarn!("Tried to remove invalid collider {:?}", handle);
@@ -207,15 +513,12 @@ impl Physics_World {
         );
 
         let index = self.cld_index_table[handle.index as usize];
-        let swapped_index = self.colliders.len() - 1;
         self.colliders.swap_remove(index);
 
         // Patch swapped index into index table
-        *self
-            .cld_index_table
-            .iter_mut()
-            .find(|idx| **idx == swapped_index)
-            .unwrap() = index;
+        if let Some(swapped) = self.colliders.get(index) {
+            self.cld_index_table[swapped.handle.index as usize] = index;
+        }
 
         self.cld_alloc.deallocate(*handle);
     }
@@ -330,6 +633,81 @@ impl Physics_World {
         })
     }
 
+    pub fn add_joint(&mut self, joint: Joint) -> Joint_Handle {
+        let handle = self.joints_alloc.allocate();
+        let index = handle.index as usize;
+        if self.joints.len() <= index {
+            self.joints.resize(index + 1, None);
+        }
+        self.joints[index] = Some(joint);
+        Joint_Handle(handle)
+    }
+
+    pub fn remove_joint(&mut self, handle: Joint_Handle) {
+        if !self.joints_alloc.is_valid(*handle) {
+            lwarn!("Tried to remove invalid joint {:?}", handle);
+            return;
+        }
+
+        self.joints[handle.index as usize] = None;
+        self.joints_alloc.deallocate(*handle);
+    }
+
+    #[inline]
+    pub fn get_joint(&self, handle: Joint_Handle) -> Option<&Joint> {
+        if !self.joints_alloc.is_valid(*handle) {
+            return None;
+        }
+        self.joints[handle.index as usize].as_ref()
+    }
+
+    #[inline]
+    pub fn get_joint_mut(&mut self, handle: Joint_Handle) -> Option<&mut Joint> {
+        if !self.joints_alloc.is_valid(*handle) {
+            return None;
+        }
+        self.joints[handle.index as usize].as_mut()
+    }
+
+    pub fn get_all_joints(&self) -> impl Iterator<Item = &Joint> {
+        self.joints.iter().flatten()
+    }
+
+    /// Returns the position of the body's entity, as of the latest physics update.
+    pub fn get_physics_body_position(&self, handle: Physics_Body_Handle) -> Option<Vec2f> {
+        self.get_first_rigidbody_collider(handle)
+            .map(|cld| cld.position - cld.offset.rotated(cld.rotation))
+    }
+
+    /// Returns the world-space positions of the joint's anchors, or None if any of its bodies
+    /// doesn't exist (or has no rigidbody colliders).
+    pub fn get_joint_anchors(&self, joint: &Joint) -> Option<(Vec2f, Vec2f)> {
+        let world_anchor = |body, anchor: Vec2f| {
+            self.get_first_rigidbody_collider(body).map(|cld| {
+                cld.position - cld.offset.rotated(cld.rotation) + anchor.rotated(cld.rotation)
+            })
+        };
+        let anchor_a = world_anchor(joint.body_a, joint.anchor_a)?;
+        let anchor_b = if let Some(body_b) = joint.body_b {
+            world_anchor(body_b, joint.anchor_b)?
+        } else {
+            joint.anchor_b
+        };
+        Some((anchor_a, anchor_b))
+    }
+
+    #[inline]
+    pub fn is_sleeping(&self, entity: Entity) -> bool {
+        self.sleeping.contains(&entity)
+    }
+
+    /// Wakes up the entity's rigidbody. Note that there's usually no need to call this,
+    /// as changing the entity's C_Spatial2D already wakes it up.
+    pub fn wake_up(&mut self, entity: Entity) {
+        self.sleeping.remove(&entity);
+        self.still_time.remove(&entity);
+    }
+
     pub(super) fn clear_collisions(&mut self) {
         self.collisions.clear();
     }
@@ -340,6 +718,9 @@ impl Physics_World {
         cld_b: Collider_Handle,
         info: &Collision_Info,
     ) {
+        let is_new = !self
+            .reported_contacts
+            .contains_key(&contact_key(cld_a, cld_b));
         self.collisions
             .entry(cld_a)
             .or_insert_with(SmallVec::default)
@@ -349,6 +730,7 @@ impl Physics_World {
                     normal: -info.normal,
                     ..*info
                 },
+                is_new,
             });
         self.collisions
             .entry(cld_b)
@@ -356,6 +738,7 @@ impl Physics_World {
             .push(Collision_Data {
                 other_collider: cld_a,
                 info: info.clone(),
+                is_new,
             });
     }
 
@@ -373,6 +756,7 @@ impl Physics_World {
 #[cfg(test)]
 mod tests {
     use super::super::collider::Collision_Shape;
+    use super::super::joints::Joint_Kind;
     use super::*;
 
     #[test]
@@ -438,4 +822,192 @@ mod tests {
         assert!(phys_world.get_collider(h1).is_none());
         assert!(phys_world.get_collider(h3).is_some());
     }
+
+    #[test]
+    fn remove_physics_body() {
+        let mut phys_world = Physics_World::new();
+        let e = Entity::INVALID;
+        let c = Collider {
+            shape: Collision_Shape::Circle { radius: 2. },
+            ..Default::default()
+        };
+        let other = phys_world.add_collider(c.clone(), e);
+        let body = phys_world.new_physics_body_with_rigidbody(c.clone(), e, Phys_Data::default());
+        let trigger = phys_world.add_collider(c.clone(), e);
+        phys_world
+            .get_physics_body_mut(body)
+            .unwrap()
+            .trigger_colliders
+            .push(trigger);
+        let body_cld = phys_world
+            .get_physics_body(body)
+            .unwrap()
+            .rigidbody_colliders[0]
+            .0;
+
+        phys_world.remove_physics_body(body);
+
+        assert!(phys_world.get_physics_body(body).is_none());
+        assert!(phys_world.get_collider(body_cld).is_none());
+        assert!(phys_world.get_collider(trigger).is_none());
+        assert_eq!(phys_world.get_collider(other).unwrap().handle, other);
+
+        // The freed slots are reused without disturbing the other bodies.
+        let kept = phys_world.new_physics_body_with_rigidbody(c.clone(), e, Phys_Data::default());
+        let new_body = phys_world.new_physics_body_with_rigidbody(c, e, Phys_Data::default());
+        for &handle in &[kept, new_body] {
+            let (cld, _) = phys_world
+                .get_physics_body(handle)
+                .unwrap()
+                .rigidbody_colliders[0];
+            assert_eq!(phys_world.get_collider(cld).unwrap().handle, cld);
+        }
+        assert_eq!(phys_world.get_collider(other).unwrap().handle, other);
+    }
+
+    #[test]
+    fn remove_physics_body_removes_its_joints() {
+        let mut phys_world = Physics_World::new();
+        let e = Entity::INVALID;
+        let c = Collider {
+            shape: Collision_Shape::Circle { radius: 2. },
+            ..Default::default()
+        };
+        let body_a = phys_world.new_physics_body_with_rigidbody(c.clone(), e, Phys_Data::default());
+        let body_b = phys_world.new_physics_body_with_rigidbody(c.clone(), e, Phys_Data::default());
+        let joint = |body_a, body_b| Joint {
+            kind: Joint_Kind::Revolute,
+            body_a,
+            anchor_a: Vec2f::default(),
+            body_b,
+            anchor_b: Vec2f::default(),
+        };
+        let to_world = phys_world.add_joint(joint(body_a, None));
+        let a_to_b = phys_world.add_joint(joint(body_a, Some(body_b)));
+        let b_to_a = phys_world.add_joint(joint(body_b, Some(body_a)));
+        let b_to_world = phys_world.add_joint(joint(body_b, None));
+
+        phys_world.remove_physics_body(body_a);
+
+        assert!(phys_world.get_joint(to_world).is_none());
+        assert!(phys_world.get_joint(a_to_b).is_none());
+        assert!(phys_world.get_joint(b_to_a).is_none());
+        assert!(phys_world.get_joint(b_to_world).is_some());
+        assert_eq!(phys_world.get_all_joints().count(), 1);
+
+        // A new body reusing the removed one's handle doesn't inherit its joints.
+        let new_body = phys_world.new_physics_body_with_rigidbody(c, e, Phys_Data::default());
+        assert_eq!(new_body.index, body_a.index);
+        assert!(phys_world
+            .get_all_joints()
+            .all(|joint| joint.body_a != new_body && joint.body_b != Some(new_body)));
+    }
+
+    #[test]
+    fn joint_anchors_rotate_with_their_body() {
+        let mut phys_world = Physics_World::new();
+        let c = Collider {
+            shape: Collision_Shape::Circle { radius: 2. },
+            position: v2!(10., 0.),
+            rotation: inle_math::angle::deg(90.),
+            ..Default::default()
+        };
+        let body =
+            phys_world.new_physics_body_with_rigidbody(c, Entity::INVALID, Phys_Data::default());
+        let joint = Joint {
+            kind: Joint_Kind::Revolute,
+            body_a: body,
+            anchor_a: v2!(1., 0.),
+            body_b: None,
+            anchor_b: v2!(5., 5.),
+        };
+
+        let (anchor_a, anchor_b) = phys_world.get_joint_anchors(&joint).unwrap();
+        assert!(
+            (anchor_a - v2!(10., 1.)).magnitude() < 1e-5,
+            "{:?}",
+            anchor_a
+        );
+        assert_eq!(anchor_b, v2!(5., 5.));
+    }
+
+    #[test]
+    fn serialize_phys_world() {
+        let mut phys_world = Physics_World::new();
+        let e = Entity::INVALID;
+        let c = Collider {
+            shape: Collision_Shape::Rect {
+                width: 2.,
+                height: 3.,
+            },
+            offset: v2!(1., 1.),
+            ..Default::default()
+        };
+        let body = phys_world.new_physics_body_with_rigidbody(
+            c,
+            e,
+            Phys_Data {
+                inv_mass: 0.5,
+                ..Default::default()
+            },
+        );
+        let trigger = phys_world.add_collider(
+            Collider {
+                shape: Collision_Shape::Circle { radius: 4. },
+                ..Default::default()
+            },
+            e,
+        );
+        phys_world
+            .get_physics_body_mut(body)
+            .unwrap()
+            .trigger_colliders
+            .push(trigger);
+
+        let mut bs = Byte_Stream::new();
+        phys_world.serialize(&mut bs).unwrap();
+        bs.seek(0);
+        let restored = Physics_World::deserialize(&mut bs).unwrap();
+
+        let body = restored.get_physics_body(body).unwrap();
+        assert_eq!(body.rigidbody_colliders.len(), 1);
+        assert_eq!(body.rigidbody_colliders[0].1.inv_mass, 0.5);
+        assert_eq!(body.trigger_colliders, vec![trigger]);
+
+        let rb = restored
+            .get_collider(body.rigidbody_colliders[0].0)
+            .unwrap();
+        assert_eq!(rb.offset, v2!(1., 1.));
+        assert_eq!(
+            restored.get_collider(trigger).unwrap().shape,
+            Collision_Shape::Circle { radius: 4. }
+        );
+    }
+
+    #[test]
+    fn serialize_phys_world_sleep_state() {
+        let mut phys_world = Physics_World::new();
+        let entities: Vec<Entity> = (0..10).map(|i| Entity { index: i, gen: 1 }).collect();
+        for (i, &e) in entities.iter().enumerate() {
+            if i % 2 == 0 {
+                phys_world.sleeping.insert(e);
+            } else {
+                phys_world
+                    .still_time
+                    .insert(e, Duration::from_nanos(1_000_000_007 * i as u64));
+            }
+        }
+
+        let mut bs = Byte_Stream::new();
+        phys_world.serialize(&mut bs).unwrap();
+        bs.seek(0);
+        let restored = Physics_World::deserialize(&mut bs).unwrap();
+
+        assert_eq!(restored.sleeping, phys_world.sleeping);
+        assert_eq!(restored.still_time, phys_world.still_time);
+
+        let mut bs2 = Byte_Stream::new();
+        restored.serialize(&mut bs2).unwrap();
+        assert_eq!(bs.as_ref(), bs2.as_ref());
+    }
 }
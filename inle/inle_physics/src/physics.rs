@@ -2,10 +2,11 @@
 
 use super::layers::Collision_Matrix;
 use super::phys_world::{
-    Collider_Handle, Collision_Data, Collision_Info, Phys_Data, Physics_World,
+    contact_key, Collider_Handle, Collision_Event_Data, Collision_Info, Phys_Data, Physics_World,
 };
 use super::spatial::Spatial_Accelerator;
-use crate::collider::{Collider, Collision_Shape};
+use crate::collider::{Collider, Collision_Shape, Collision_Shape_Kind};
+use crate::joints::Joint_Kind;
 use inle_alloc::temp::{excl_temp_array, Temp_Allocator};
 use inle_ecs::components::base::C_Spatial2D;
 use inle_ecs::ecs_world::{Ecs_World, Entity};
@@ -14,28 +15,61 @@ use inle_math::math::clamp;
 use inle_math::vector::{sanity_check_v, Vec2f};
 use rayon::prelude::*;
 use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 
-type Rigidbodies = HashMap<Collider_Handle, Rigidbody>;
+/// Raised when two colliders start touching.
+pub struct Evt_Collision_Begin;
 
-pub struct Evt_Collision_Happened;
+impl Event for Evt_Collision_Begin {
+    type Args = Collision_Event_Data;
+}
+
+/// Raised every physics update for each pair of colliders that were already touching
+/// during the previous one and still are.
+pub struct Evt_Collision_Persist;
 
-impl Event for Evt_Collision_Happened {
-    type Args = Collision_Data;
+impl Event for Evt_Collision_Persist {
+    type Args = Collision_Event_Data;
+}
+
+/// Raised when two colliders stop touching (including when one of them is removed).
+pub struct Evt_Collision_End;
+
+impl Event for Evt_Collision_End {
+    type Args = Collision_Event_Data;
 }
 
-#[derive(Default)]
 pub struct Physics_Settings {
     pub collision_matrix: Collision_Matrix,
+    /// How many times the physics are stepped for every gameplay tick (each step simulating
+    /// an equal fraction of it). More substeps make fast or stacked bodies more stable.
+    pub substeps: u32,
 }
 
+impl Default for Physics_Settings {
+    fn default() -> Self {
+        Self {
+            collision_matrix: Collision_Matrix::default(),
+            substeps: 1,
+        }
+    }
+}
+
+/// All the rigidbody colliders of a Physics_Body are solved as a single compound body,
+/// with a shared mass and velocity. Each collider keeps its own material, though.
 #[derive(Debug, Clone)]
 struct Rigidbody {
-    pub shape: Collision_Shape,
-    pub entity: Entity,
+    /// This is the entity's position, not its colliders'.
     pub position: Vec2f,
-    pub offset: Vec2f,
     pub velocity: Vec2f,
-    pub phys_data: Phys_Data,
+    pub inv_mass: f32,
+}
+
+#[derive(Default)]
+struct Rigidbodies {
+    bodies: HashMap<Entity, Rigidbody>,
+    /// { rigidbody collider => (the entity owning its body, its material) }
+    colliders: HashMap<Collider_Handle, (Entity, Phys_Data)>,
 }
 
 #[derive(Debug, Clone)]
@@ -50,6 +84,12 @@ struct Collision_Info_Internal {
 pub struct Collision_System_Debug_Data {
     // How many intersections were tested during this frame
     pub n_intersection_tests: usize,
+    // How many swept tests were done during this frame
+    pub n_sweep_tests: usize,
+    // How many islands were awake during this frame
+    pub n_islands: usize,
+    // How many rigidbodies were asleep at the end of this frame
+    pub n_sleeping_bodies: usize,
 }
 
 fn detect_circle_circle(a: &Collider, b: &Collider) -> Option<Collision_Info_Internal> {
@@ -100,8 +140,6 @@ fn detect_circle_circle(a: &Collider, b: &Collider) -> Option<Collision_Info_Int
 fn detect_rect_rect(a: &Collider, b: &Collider) -> Option<Collision_Info_Internal> {
     trace!("physics::detect_rect_rect");
 
-    const OVERLAP_EPSILON: f32 = f32::EPSILON;
-
     let (a_width, a_height) = if let Collision_Shape::Rect { width, height } = a.shape {
         (width, height)
     } else {
@@ -234,20 +272,282 @@ fn detect_rect_circle(rect: &Collider, circle: &Collider) -> Option<Collision_In
     detect_circle_rect(circle, rect)
 }
 
-fn collision_shape_type_index(shape: &Collision_Shape) -> usize {
-    match shape {
-        Collision_Shape::Circle { .. } => 0,
-        Collision_Shape::Rect { .. } => 1,
+const OVERLAP_EPSILON: f32 = f32::EPSILON;
+
+/// Returns the interval covered by `vertices` when projected on `axis`.
+fn project_polygon(vertices: &[Vec2f], axis: Vec2f) -> (f32, f32) {
+    vertices.iter().fold((f32::MAX, f32::MIN), |(min, max), v| {
+        let p = v.dot(axis);
+        (min.min(p), max.max(p))
+    })
+}
+
+/// Returns the interval covered by the segment `s1`-`s2` inflated by `radius`
+/// when projected on `axis`.
+fn project_rounded(s1: Vec2f, s2: Vec2f, radius: f32, axis: Vec2f) -> (f32, f32) {
+    let p1 = s1.dot(axis);
+    let p2 = s2.dot(axis);
+    (p1.min(p2) - radius, p1.max(p2) + radius)
+}
+
+fn edge_normals(vertices: &[Vec2f]) -> impl Iterator<Item = Vec2f> + '_ {
+    (0..vertices.len()).filter_map(move |i| {
+        let edge = vertices[(i + 1) % vertices.len()] - vertices[i];
+        let normal = v2!(-edge.y, edge.x).normalized_or_zero();
+        if normal.magnitude2() > 0. {
+            Some(normal)
+        } else {
+            None
+        }
+    })
+}
+
+/// Given the projections of A and B on a normalized `axis`, returns the direction (from A to B)
+/// and the distance along which B must be moved to stop overlapping with A, or None if the
+/// projections don't overlap.
+fn axis_overlap(axis: Vec2f, a: (f32, f32), b: (f32, f32)) -> Option<(Vec2f, f32)> {
+    let (a_min, a_max) = a;
+    let (b_min, b_max) = b;
+    let push_forward = a_max - b_min;
+    let push_backward = b_max - a_min;
+    let overlap = push_forward.min(push_backward);
+    if overlap <= OVERLAP_EPSILON {
+        None
+    } else if push_forward < push_backward {
+        Some((axis, overlap))
+    } else {
+        Some((-axis, overlap))
+    }
+}
+
+/// Applies SAT on all the given `axes`, returning the least penetration axis (oriented from A to B)
+/// and the penetration along it, or None if any of the axes separates the shapes.
+fn sat<P, Q>(axes: impl Iterator<Item = Vec2f>, project_a: P, project_b: Q) -> Option<(Vec2f, f32)>
+where
+    P: Fn(Vec2f) -> (f32, f32),
+    Q: Fn(Vec2f) -> (f32, f32),
+{
+    let mut best: Option<(Vec2f, f32)> = None;
+    for axis in axes {
+        let (normal, overlap) = axis_overlap(axis, project_a(axis), project_b(axis))?;
+        match best {
+            Some((_, best_overlap)) if best_overlap <= overlap => {}
+            _ => best = Some((normal, overlap)),
+        }
+    }
+    best
+}
+
+fn sat_polygon_polygon(a: &[Vec2f], b: &[Vec2f]) -> Option<(Vec2f, f32)> {
+    sat(
+        edge_normals(a).chain(edge_normals(b)),
+        |axis| project_polygon(a, axis),
+        |axis| project_polygon(b, axis),
+    )
+}
+
+/// `b` is the segment `s1`-`s2` inflated by `radius` (i.e. a capsule, or a circle if s1 == s2).
+fn sat_polygon_rounded(a: &[Vec2f], (s1, s2, radius): (Vec2f, Vec2f, f32)) -> Option<(Vec2f, f32)> {
+    // Besides the edge normals, the least penetration axis may pass through any polygon vertex
+    // and either end of the segment.
+    let seg_normal = {
+        let seg = s2 - s1;
+        v2!(-seg.y, seg.x).normalized_or_zero()
+    };
+    let vertex_axes = a
+        .iter()
+        .flat_map(|&v| {
+            let a1 = (v - s1).normalized_or_zero();
+            let a2 = (v - s2).normalized_or_zero();
+            std::iter::once(a1).chain(std::iter::once(a2))
+        })
+        .chain(std::iter::once(seg_normal))
+        .filter(|axis| axis.magnitude2() > 0.);
+
+    sat(
+        edge_normals(a).chain(vertex_axes),
+        |axis| project_polygon(a, axis),
+        |axis| project_rounded(s1, s2, radius, axis),
+    )
+}
+
+/// Returns the closest points between the segments `p1`-`q1` and `p2`-`q2`.
+// reference: Ericson, Real-Time Collision Detection, 5.1.9
+fn closest_points_segment_segment(p1: Vec2f, q1: Vec2f, p2: Vec2f, q2: Vec2f) -> (Vec2f, Vec2f) {
+    const EPS: f32 = 1e-6;
+
+    let d1 = q1 - p1;
+    let d2 = q2 - p2;
+    let r = p1 - p2;
+    let a = d1.magnitude2();
+    let e = d2.magnitude2();
+    let f = d2.dot(r);
+
+    let (s, t) = if a <= EPS && e <= EPS {
+        (0., 0.)
+    } else if a <= EPS {
+        (0., clamp(f / e, 0., 1.))
+    } else {
+        let c = d1.dot(r);
+        if e <= EPS {
+            (clamp(-c / a, 0., 1.), 0.)
+        } else {
+            let b = d1.dot(d2);
+            let denom = a * e - b * b;
+            let s = if denom > EPS {
+                clamp((b * f - c * e) / denom, 0., 1.)
+            } else {
+                0.
+            };
+            let t = (b * s + f) / e;
+            if t < 0. {
+                (clamp(-c / a, 0., 1.), 0.)
+            } else if t > 1. {
+                (clamp((b - c) / a, 0., 1.), 1.)
+            } else {
+                (s, t)
+            }
+        }
+    };
+
+    (p1 + d1 * s, p2 + d2 * t)
+}
+
+fn make_collision_info(
+    a: &Collider,
+    b: &Collider,
+    (normal, penetration): (Vec2f, f32),
+) -> Collision_Info_Internal {
+    debug_assert!(normal.is_normalized(), "{}", normal.magnitude());
+    Collision_Info_Internal {
+        cld1: a.handle,
+        cld2: b.handle,
+        info: Collision_Info {
+            normal,
+            penetration,
+        },
+    }
+}
+
+fn detect_polygon_polygon(a: &Collider, b: &Collider) -> Option<Collision_Info_Internal> {
+    trace!("physics::detect_polygon_polygon");
+
+    let a_verts = a.world_polygon().expect("Failed to unwrap polygon!");
+    let b_verts = b.world_polygon().expect("Failed to unwrap polygon!");
+
+    sat_polygon_polygon(&a_verts, &b_verts).map(|res| make_collision_info(a, b, res))
+}
+
+fn detect_polygon_rounded(a: &Collider, b: &Collider) -> Option<Collision_Info_Internal> {
+    trace!("physics::detect_polygon_rounded");
+
+    let a_verts = a.world_polygon().expect("Failed to unwrap polygon!");
+    let b_rounded = b.world_rounded().expect("Failed to unwrap rounded shape!");
+
+    sat_polygon_rounded(&a_verts, b_rounded).map(|res| make_collision_info(a, b, res))
+}
+
+fn detect_rounded_polygon(a: &Collider, b: &Collider) -> Option<Collision_Info_Internal> {
+    trace!("physics::detect_rounded_polygon");
+
+    let a_rounded = a.world_rounded().expect("Failed to unwrap rounded shape!");
+    let b_verts = b.world_polygon().expect("Failed to unwrap polygon!");
+
+    sat_polygon_rounded(&b_verts, a_rounded)
+        .map(|(normal, penetration)| make_collision_info(a, b, (-normal, penetration)))
+}
+
+fn detect_rounded_rounded(a: &Collider, b: &Collider) -> Option<Collision_Info_Internal> {
+    trace!("physics::detect_rounded_rounded");
+
+    let (a1, a2, a_radius) = a.world_rounded().expect("Failed to unwrap rounded shape!");
+    let (b1, b2, b_radius) = b.world_rounded().expect("Failed to unwrap rounded shape!");
+
+    let (pa, pb) = closest_points_segment_segment(a1, a2, b1, b2);
+    let diff = pb - pa;
+    let r = a_radius + b_radius;
+
+    if diff.magnitude2() > r * r {
+        return None;
+    }
+
+    let dist = diff.magnitude();
+    if dist > f32::EPSILON {
+        Some(make_collision_info(a, b, (diff / dist, r - dist)))
+    } else {
+        // The segments intersect: separate the shapes along the one that's best
+        // for getting B out of A.
+        let a_seg = a2 - a1;
+        let b_seg = b2 - b1;
+        let axes = [v2!(-a_seg.y, a_seg.x), v2!(-b_seg.y, b_seg.x)];
+        let axes = axes
+            .iter()
+            .map(|axis| axis.normalized_or_zero())
+            .filter(|axis| axis.magnitude2() > 0.);
+        let res = sat(
+            axes,
+            |axis| project_rounded(a1, a2, a_radius, axis),
+            |axis| project_rounded(b1, b2, b_radius, axis),
+        );
+        // If both shapes are circles in the same position there is no meaningful axis.
+        let res = res.unwrap_or((v2!(1., 0.), r)); // Arbitrary
+        Some(make_collision_info(a, b, res))
     }
 }
 
 type Collision_Cb = fn(&Collider, &Collider) -> Option<Collision_Info_Internal>;
 
-const COLLISION_CB_TABLE: [[Collision_Cb; 2]; 2] = [
-    [detect_circle_circle, detect_circle_rect],
-    [detect_rect_circle, detect_rect_rect],
+const N_SHAPE_KINDS: usize = Collision_Shape_Kind::_Count as usize;
+
+/// Indexed by [Collision_Shape_Kind of A][Collision_Shape_Kind of B].
+const COLLISION_CB_TABLE: [[Collision_Cb; N_SHAPE_KINDS]; N_SHAPE_KINDS] = [
+    // Circle
+    [
+        detect_circle_circle,
+        detect_circle_rect,
+        detect_rounded_polygon,
+        detect_rounded_polygon,
+        detect_rounded_rounded,
+    ],
+    // Rect
+    [
+        detect_rect_circle,
+        detect_rect_rect,
+        detect_polygon_polygon,
+        detect_polygon_polygon,
+        detect_polygon_rounded,
+    ],
+    // Oriented_Rect
+    [
+        detect_polygon_rounded,
+        detect_polygon_polygon,
+        detect_polygon_polygon,
+        detect_polygon_polygon,
+        detect_polygon_rounded,
+    ],
+    // Polygon
+    [
+        detect_polygon_rounded,
+        detect_polygon_polygon,
+        detect_polygon_polygon,
+        detect_polygon_polygon,
+        detect_polygon_rounded,
+    ],
+    // Capsule
+    [
+        detect_rounded_rounded,
+        detect_rounded_polygon,
+        detect_rounded_polygon,
+        detect_rounded_polygon,
+        detect_rounded_rounded,
+    ],
 ];
 
+/// Runs the narrow phase between `a` and `b`. The resulting normal goes from A to B.
+pub(crate) fn detect_collision(a: &Collider, b: &Collider) -> Option<Collision_Info> {
+    COLLISION_CB_TABLE[a.shape.kind() as usize][b.shape.kind() as usize](a, b).map(|info| info.info)
+}
+
 fn detect_collisions<T_Spatial_Accelerator>(
     phys_world: &Physics_World,
     accelerator: &T_Spatial_Accelerator,
@@ -269,12 +569,16 @@ where
     let mut storage: HashSet<(*const Collider, *const Collider)> = HashSet::default();
 
     // @Speed: maybe we should iterate on the chunks? Can we do that in parallel?
-    for a in phys_world.colliders.iter().filter(|cld| !cld.is_static) {
+    // Note: sleeping colliders only get tested against awake ones (as `b`).
+    for a in phys_world
+        .colliders
+        .iter()
+        .filter(|cld| !cld.is_static && !phys_world.is_sleeping(cld.entity))
+    {
         trace!("iterate_colliders");
 
         let a_extent = a.shape.extent();
-        let a_shape = collision_shape_type_index(&a.shape);
-        let a_partial_cb = COLLISION_CB_TABLE[a_shape];
+        let a_partial_cb = COLLISION_CB_TABLE[a.shape.kind() as usize];
         let ent_a = a.entity;
 
         let mut neighbours = excl_temp_array(temp_alloc);
@@ -286,7 +590,7 @@ where
             if ent_a == ent_b {
                 continue;
             }
-            let b_shape = collision_shape_type_index(&b.shape);
+            let b_shape = b.shape.kind() as usize;
 
             let pa: *const Collider = a as *const _;
             let pb: *const Collider = b as *const _;
@@ -312,18 +616,305 @@ where
     collision_infos
 }
 
+/// Returns the time of impact (in [0, 1]) of a point moving from `start` by `motion` against
+/// the box centered in `center`, along with the box's surface normal at the impact point.
+/// Returns None if the point doesn't hit the box or if it starts inside it.
+fn sweep_point_aabb(
+    start: Vec2f,
+    motion: Vec2f,
+    center: Vec2f,
+    half_ext: Vec2f,
+) -> Option<(f32, Vec2f)> {
+    let origin = start - center;
+    let mut t_enter = 0.;
+    let mut t_exit = 1.;
+    let mut normal = Vec2f::default();
+
+    for &(o, d, h, axis) in &[
+        (origin.x, motion.x, half_ext.x, v2!(1., 0.)),
+        (origin.y, motion.y, half_ext.y, v2!(0., 1.)),
+    ] {
+        if d.abs() <= f32::EPSILON {
+            if o < -h || o > h {
+                return None;
+            }
+            continue;
+        }
+
+        let t1 = (-h - o) / d;
+        let t2 = (h - o) / d;
+        let (t_near, t_far, near_normal) = if t1 < t2 {
+            (t1, t2, -axis)
+        } else {
+            (t2, t1, axis)
+        };
+        if t_near > t_enter {
+            t_enter = t_near;
+            normal = near_normal;
+        }
+        t_exit = t_far.min(t_exit);
+        if t_enter > t_exit {
+            return None;
+        }
+    }
+
+    if normal.magnitude2() > 0. {
+        Some((t_enter, normal))
+    } else {
+        // Already inside the box
+        None
+    }
+}
+
+/// Like sweep_point_aabb, but against a circle.
+fn sweep_point_circle(
+    start: Vec2f,
+    motion: Vec2f,
+    center: Vec2f,
+    radius: f32,
+) -> Option<(f32, Vec2f)> {
+    let origin = start - center;
+    let c = origin.magnitude2() - radius * radius;
+    let b = origin.dot(motion);
+    if c <= 0. || b >= 0. {
+        // Already inside the circle or moving away from it
+        return None;
+    }
+
+    let a = motion.magnitude2();
+    let discr = b * b - a * c;
+    if discr < 0. {
+        return None;
+    }
+
+    let t = (-b - discr.sqrt()) / a;
+    if t > 1. {
+        return None;
+    }
+
+    Some((t, (origin + motion * t) / radius))
+}
+
+/// Like sweep_point_aabb, but against the box inflated by `radius` (i.e. a rect with rounded
+/// corners, which is what a circle sees when sweeping against a box).
+fn sweep_point_rounded_aabb(
+    start: Vec2f,
+    motion: Vec2f,
+    center: Vec2f,
+    half_ext: Vec2f,
+    radius: f32,
+) -> Option<(f32, Vec2f)> {
+    let (t, normal) = sweep_point_aabb(start, motion, center, half_ext + v2!(radius, radius))?;
+
+    // If we hit the inflated box in one of its corner regions, we must check against the
+    // rounded corner instead.
+    let hit = start + motion * t - center;
+    if hit.x.abs() > half_ext.x && hit.y.abs() > half_ext.y {
+        let corner = v2!(half_ext.x.copysign(hit.x), half_ext.y.copysign(hit.y));
+        sweep_point_circle(start, motion, center + corner, radius)
+    } else {
+        Some((t, normal))
+    }
+}
+
+/// Returns the time of impact of `a` moving from `a_start` by `motion` against the still
+/// collider `b`, along with the collision normal (going from A to B).
+fn sweep_collider(
+    a_shape: &Collision_Shape,
+    a_start: Vec2f,
+    motion: Vec2f,
+    b: &Collider,
+) -> Option<(f32, Vec2f)> {
+    match (a_shape, &b.shape) {
+        (&Collision_Shape::Circle { radius: a_radius }, &Collision_Shape::Circle { radius }) => {
+            sweep_point_circle(a_start, motion, b.position, a_radius + radius)
+                .map(|(t, normal)| (t, -normal))
+        }
+        (&Collision_Shape::Circle { radius }, &Collision_Shape::Rect { width, height }) => {
+            sweep_point_rounded_aabb(
+                a_start,
+                motion,
+                b.position,
+                v2!(width, height) * 0.5,
+                radius,
+            )
+            .map(|(t, normal)| (t, -normal))
+        }
+        // Sweep B against A, in A's reference frame.
+        (&Collision_Shape::Rect { width, height }, &Collision_Shape::Circle { radius }) => {
+            sweep_point_rounded_aabb(
+                b.position,
+                -motion,
+                a_start,
+                v2!(width, height) * 0.5,
+                radius,
+            )
+        }
+        (
+            &Collision_Shape::Rect {
+                width: a_width,
+                height: a_height,
+            },
+            &Collision_Shape::Rect { width, height },
+        ) => sweep_point_aabb(
+            a_start,
+            motion,
+            b.position,
+            v2!(a_width + width, a_height + height) * 0.5,
+        )
+        .map(|(t, normal)| (t, -normal)),
+        _ => None,
+    }
+}
+
+/// Returns the colliders that opted in to continuous collision detection, along with the
+/// position they had at the start of the last movement step (i.e. their frame_starting_pos).
+/// This must be called before update_colliders_spatial overwrites it.
+fn gather_ccd_sweep_starts(
+    ecs_world: &Ecs_World,
+    phys_world: &Physics_World,
+) -> Vec<(Collider_Handle, Vec2f)> {
+    trace!("physics::gather_ccd_sweep_starts");
+
+    phys_world
+        .bodies
+        .iter()
+        .flat_map(|body| body.rigidbody_colliders.iter())
+        .filter(|(_, phys_data)| phys_data.continuous_collision)
+        .filter_map(|&(cld_handle, _)| {
+            let cld = phys_world.get_collider(cld_handle)?;
+            if cld.is_static {
+                return None;
+            }
+            let spatial = ecs_world.get_component::<C_Spatial2D>(cld.entity)?;
+            Some((
+                cld_handle,
+                spatial.frame_starting_pos + cld.offset.rotated(cld.rotation),
+            ))
+        })
+        .collect()
+}
+
+/// Sweeps the continuous colliders from their starting position to their current one against
+/// all the other rigidbody colliders (which are considered still), and clamps their position to
+/// the earliest time of impact. Returns the collisions found this way, which have zero penetration.
+fn sweep_ccd_colliders<T_Spatial_Accelerator>(
+    phys_world: &mut Physics_World,
+    accelerator: &T_Spatial_Accelerator,
+    collision_matrix: &Collision_Matrix,
+    sweep_starts: &[(Collider_Handle, Vec2f)],
+    temp_alloc: &mut Temp_Allocator,
+    #[cfg(debug_assertions)] debug_data: &mut Collision_System_Debug_Data,
+) -> Vec<Collision_Info_Internal>
+where
+    T_Spatial_Accelerator: Spatial_Accelerator<Collider_Handle>,
+{
+    trace!("physics::sweep_ccd_colliders");
+
+    #[cfg(debug_assertions)]
+    {
+        debug_data.n_sweep_tests = 0;
+    }
+
+    if sweep_starts.is_empty() {
+        return vec![];
+    }
+
+    let rigidbody_colliders = phys_world
+        .bodies
+        .iter()
+        .flat_map(|body| body.rigidbody_colliders.iter().map(|(handle, _)| *handle))
+        .collect::<HashSet<_>>();
+
+    // (entity, time of impact, motion, collision info)
+    let mut impacts: Vec<(Entity, f32, Vec2f, Collision_Info_Internal)> = vec![];
+
+    for &(a_handle, start) in sweep_starts {
+        let a = phys_world.get_collider(a_handle).unwrap();
+        let motion = a.position - start;
+        if motion.magnitude2() <= f32::EPSILON {
+            continue;
+        }
+
+        let swept_center = start + motion * 0.5;
+        let swept_extent = a.shape.extent() + v2!(motion.x.abs(), motion.y.abs());
+
+        let mut neighbours = excl_temp_array(temp_alloc);
+        accelerator.get_neighbours(swept_center, swept_extent, &mut neighbours);
+
+        let mut earliest: Option<(f32, Vec2f, Collider_Handle)> = None;
+        for &b_handle in &neighbours {
+            let b = phys_world.get_collider(b_handle).unwrap();
+            if a.entity == b.entity
+                || !rigidbody_colliders.contains(&b_handle)
+                || !collision_matrix.layers_collide(a.layer, b.layer)
+            {
+                continue;
+            }
+
+            #[cfg(debug_assertions)]
+            {
+                debug_data.n_sweep_tests += 1;
+            }
+
+            if let Some((t, normal)) = sweep_collider(&a.shape, start, motion, b) {
+                match earliest {
+                    Some((earliest_t, ..)) if earliest_t <= t => {}
+                    _ => earliest = Some((t, normal, b_handle)),
+                }
+            }
+        }
+
+        if let Some((t, normal, b_handle)) = earliest {
+            let info = Collision_Info_Internal {
+                cld1: a_handle,
+                cld2: b_handle,
+                info: Collision_Info {
+                    normal,
+                    penetration: 0.,
+                },
+            };
+            // A compound body may have several continuous colliders: only its earliest impact counts.
+            match impacts.iter_mut().find(|(entity, ..)| *entity == a.entity) {
+                Some(impact) if impact.1 <= t => {}
+                Some(impact) => *impact = (a.entity, t, motion, info),
+                None => impacts.push((a.entity, t, motion, info)),
+            }
+        }
+    }
+
+    // Move all the colliders of the body back to the impact point.
+    for (entity, t, motion, _) in &impacts {
+        for cld in phys_world
+            .colliders
+            .iter_mut()
+            .filter(|cld| cld.entity == *entity)
+        {
+            cld.position -= *motion * (1. - t);
+        }
+    }
+
+    impacts.into_iter().map(|(.., info)| info).collect()
+}
+
 fn solve_collision_velocities(
     objects: &mut Rigidbodies,
-    a_idx: Collider_Handle,
-    b_idx: Collider_Handle,
+    a_cld: Collider_Handle,
+    b_cld: Collider_Handle,
     normal: Vec2f,
 ) {
     trace!("physics::solve_collisions_velocities");
 
-    let a = objects[&a_idx].clone();
-    let b = objects[&b_idx].clone();
+    let (a_idx, a_mat) = objects.colliders[&a_cld];
+    let (b_idx, b_mat) = objects.colliders[&b_cld];
+    if a_idx == b_idx {
+        return;
+    }
+
+    let a = objects.bodies[&a_idx].clone();
+    let b = objects.bodies[&b_idx].clone();
 
-    if a.phys_data.inv_mass + b.phys_data.inv_mass == 0. {
+    if a.inv_mass + b.inv_mass == 0. {
         // Both infinite-mass objects
         return;
     }
@@ -340,19 +931,19 @@ fn solve_collision_velocities(
     sanity_check_v(rel_vel);
     debug_assert!(!vel_along_normal.is_nan());
 
-    let e = a.phys_data.restitution.min(b.phys_data.restitution);
+    let e = a_mat.restitution.min(b_mat.restitution);
 
     // Impulse scalar
-    let j = -(1. + e) * vel_along_normal / (a.phys_data.inv_mass + b.phys_data.inv_mass);
+    let j = -(1. + e) * vel_along_normal / (a.inv_mass + b.inv_mass);
     debug_assert!(!j.is_nan());
 
     let impulse = j * normal;
-    objects.get_mut(&a_idx).unwrap().velocity -= 1. * a.phys_data.inv_mass * impulse;
-    objects.get_mut(&b_idx).unwrap().velocity += 1. * b.phys_data.inv_mass * impulse;
+    objects.bodies.get_mut(&a_idx).unwrap().velocity -= 1. * a.inv_mass * impulse;
+    objects.bodies.get_mut(&b_idx).unwrap().velocity += 1. * b.inv_mass * impulse;
 
     // @Speed: cloning
-    let a = objects[&a_idx].clone();
-    let b = objects[&b_idx].clone();
+    let a = objects.bodies[&a_idx].clone();
+    let b = objects.bodies[&b_idx].clone();
 
     // apply friction
     let new_rel_vel = b.velocity - a.velocity;
@@ -362,32 +953,38 @@ fn solve_collision_velocities(
 
     let tangent = (new_rel_vel - new_rel_vel.dot(normal) * normal).normalized_or_zero();
 
-    let jt = -new_rel_vel.dot(tangent) / (a.phys_data.inv_mass * b.phys_data.inv_mass);
+    let jt = -new_rel_vel.dot(tangent) / (a.inv_mass * b.inv_mass);
 
-    let mu = (a.phys_data.static_friction + b.phys_data.static_friction) * 0.5;
+    let mu = (a_mat.static_friction + b_mat.static_friction) * 0.5;
 
     let friction_impulse = if jt.abs() < j * mu {
         jt * tangent
     } else {
-        let dyn_friction = (a.phys_data.dyn_friction + b.phys_data.dyn_friction) * 0.5;
+        let dyn_friction = (a_mat.dyn_friction + b_mat.dyn_friction) * 0.5;
         -j * tangent * dyn_friction
     };
 
-    objects.get_mut(&a_idx).unwrap().velocity -= 1. * a.phys_data.inv_mass * friction_impulse;
-    objects.get_mut(&b_idx).unwrap().velocity += 1. * b.phys_data.inv_mass * friction_impulse;
+    objects.bodies.get_mut(&a_idx).unwrap().velocity -= 1. * a.inv_mass * friction_impulse;
+    objects.bodies.get_mut(&b_idx).unwrap().velocity += 1. * b.inv_mass * friction_impulse;
 }
 
 fn positional_correction(
     objects: &mut Rigidbodies,
-    a_idx: Collider_Handle,
-    b_idx: Collider_Handle,
+    a_cld: Collider_Handle,
+    b_cld: Collider_Handle,
     normal: Vec2f,
     penetration: f32,
 ) {
     trace!("physics::positional_correction");
 
-    let a_inv_mass = objects[&a_idx].phys_data.inv_mass;
-    let b_inv_mass = objects[&b_idx].phys_data.inv_mass;
+    let (a_idx, _) = objects.colliders[&a_cld];
+    let (b_idx, _) = objects.colliders[&b_cld];
+    if a_idx == b_idx {
+        return;
+    }
+
+    let a_inv_mass = objects.bodies[&a_idx].inv_mass;
+    let b_inv_mass = objects.bodies[&b_idx].inv_mass;
 
     if a_inv_mass + b_inv_mass == 0. {
         return;
@@ -399,8 +996,8 @@ fn positional_correction(
     let correction =
         (penetration - slop).max(0.0) / (a_inv_mass + b_inv_mass) * correction_perc * normal;
 
-    objects.get_mut(&a_idx).unwrap().position -= a_inv_mass * correction;
-    objects.get_mut(&b_idx).unwrap().position += b_inv_mass * correction;
+    objects.bodies.get_mut(&a_idx).unwrap().position -= a_inv_mass * correction;
+    objects.bodies.get_mut(&b_idx).unwrap().position += b_inv_mass * correction;
 }
 
 fn solve_collisions(objects: &mut Rigidbodies, infos: &[&Collision_Info_Internal]) {
@@ -423,12 +1020,400 @@ fn solve_collisions(objects: &mut Rigidbodies, infos: &[&Collision_Info_Internal
     }
 }
 
+/// How many times the joint solver iterates over all joints. More iterations make long
+/// chains of joints stiffer.
+const JOINT_SOLVER_ITERATIONS: usize = 8;
+
+/// A joint whose bodies have been resolved to their rigidbodies.
+#[derive(Debug, Clone)]
+struct Joint_Internal {
+    kind: Joint_Kind,
+    body_a: Entity,
+    anchor_a: Vec2f,
+    /// If None, anchor_b is in world space.
+    body_b: Option<Entity>,
+    anchor_b: Vec2f,
+}
+
+fn gather_joints(phys_world: &Physics_World, objects: &Rigidbodies) -> Vec<Joint_Internal> {
+    trace!("physics::gather_joints");
+
+    // Bodies don't rotate while we solve, so we can rotate the anchors once here.
+    let get_entity_and_anchor = |body, anchor: Vec2f| {
+        phys_world
+            .get_first_rigidbody_collider(body)
+            .filter(|cld| objects.bodies.contains_key(&cld.entity))
+            .map(|cld| (cld.entity, anchor.rotated(cld.rotation)))
+    };
+
+    phys_world
+        .get_all_joints()
+        .filter_map(|joint| {
+            let (body_a, anchor_a) = get_entity_and_anchor(joint.body_a, joint.anchor_a)?;
+            let (body_b, anchor_b) = match joint.body_b {
+                Some(body_b) => {
+                    let (body_b, anchor_b) = get_entity_and_anchor(body_b, joint.anchor_b)?;
+                    (Some(body_b), anchor_b)
+                }
+                None => (None, joint.anchor_b),
+            };
+            if Some(body_a) == body_b {
+                return None;
+            }
+            Some(Joint_Internal {
+                kind: joint.kind,
+                body_a,
+                anchor_a,
+                body_b,
+                anchor_b,
+            })
+        })
+        .collect()
+}
+
+/// Returns (world anchor, velocity, inverse mass) of the joint's A and B sides.
+fn joint_state(objects: &Rigidbodies, joint: &Joint_Internal) -> [(Vec2f, Vec2f, f32); 2] {
+    let a = &objects.bodies[&joint.body_a];
+    let b = match joint.body_b {
+        Some(body_b) => {
+            let b = &objects.bodies[&body_b];
+            (b.position + joint.anchor_b, b.velocity, b.inv_mass)
+        }
+        // Anchored to the world, which is immovable.
+        None => (joint.anchor_b, Vec2f::default(), 0.),
+    };
+    [(a.position + joint.anchor_a, a.velocity, a.inv_mass), b]
+}
+
+fn apply_joint_impulse(objects: &mut Rigidbodies, joint: &Joint_Internal, impulse: Vec2f) {
+    let a = objects.bodies.get_mut(&joint.body_a).unwrap();
+    a.velocity -= a.inv_mass * impulse;
+    if let Some(body_b) = joint.body_b {
+        let b = objects.bodies.get_mut(&body_b).unwrap();
+        b.velocity += b.inv_mass * impulse;
+    }
+}
+
+fn apply_joint_correction(objects: &mut Rigidbodies, joint: &Joint_Internal, correction: Vec2f) {
+    let a = objects.bodies.get_mut(&joint.body_a).unwrap();
+    a.position += a.inv_mass * correction;
+    if let Some(body_b) = joint.body_b {
+        let b = objects.bodies.get_mut(&body_b).unwrap();
+        b.position -= b.inv_mass * correction;
+    }
+}
+
+/// Springs are soft, so they're applied once per step as a force rather than iteratively.
+fn apply_spring_forces(objects: &mut Rigidbodies, joints: &[Joint_Internal], dt: &Duration) {
+    trace!("physics::apply_spring_forces");
+
+    let dt_secs = dt.as_secs_f32();
+    for joint in joints {
+        if let Joint_Kind::Spring {
+            rest_length,
+            stiffness,
+            damping,
+        } = joint.kind
+        {
+            let [(pa, va, _), (pb, vb, _)] = joint_state(objects, joint);
+            let diff = pb - pa;
+            let length = diff.magnitude();
+            if length <= f32::EPSILON {
+                continue;
+            }
+            let normal = diff / length;
+            let force = -stiffness * (length - rest_length) - damping * normal.dot(vb - va);
+            apply_joint_impulse(objects, joint, force * dt_secs * normal);
+        }
+    }
+}
+
+fn solve_joint_velocities(objects: &mut Rigidbodies, joint: &Joint_Internal) {
+    let [(pa, va, a_inv_mass), (pb, vb, b_inv_mass)] = joint_state(objects, joint);
+    if a_inv_mass + b_inv_mass == 0. {
+        return;
+    }
+
+    let rel_vel = vb - va;
+    let impulse = match joint.kind {
+        Joint_Kind::Distance { .. } => {
+            let normal = (pb - pa).normalized_or_zero();
+            -normal.dot(rel_vel) / (a_inv_mass + b_inv_mass) * normal
+        }
+        Joint_Kind::Revolute => -rel_vel / (a_inv_mass + b_inv_mass),
+        Joint_Kind::Spring { .. } => return,
+    };
+    sanity_check_v(impulse);
+    apply_joint_impulse(objects, joint, impulse);
+}
+
+fn joint_positional_correction(objects: &mut Rigidbodies, joint: &Joint_Internal) {
+    let [(pa, _, a_inv_mass), (pb, _, b_inv_mass)] = joint_state(objects, joint);
+    if a_inv_mass + b_inv_mass == 0. {
+        return;
+    }
+
+    let correction_perc = 0.2;
+    let slop = 0.01;
+
+    let diff = pb - pa;
+    let error = match joint.kind {
+        Joint_Kind::Distance { length } => {
+            let dist = diff.magnitude();
+            if dist <= f32::EPSILON {
+                return;
+            }
+            let stretch = dist - length;
+            if stretch.abs() <= slop {
+                return;
+            }
+            diff / dist * stretch
+        }
+        Joint_Kind::Revolute => {
+            if diff.magnitude2() <= slop * slop {
+                return;
+            }
+            diff
+        }
+        Joint_Kind::Spring { .. } => return,
+    };
+
+    let correction = error / (a_inv_mass + b_inv_mass) * correction_perc;
+    apply_joint_correction(objects, joint, correction);
+}
+
+fn solve_joints(objects: &mut Rigidbodies, joints: &[Joint_Internal], dt: &Duration) {
+    trace!("physics::solve_joints");
+
+    apply_spring_forces(objects, joints, dt);
+
+    for _ in 0..JOINT_SOLVER_ITERATIONS {
+        for joint in joints {
+            solve_joint_velocities(objects, joint);
+        }
+    }
+
+    for _ in 0..JOINT_SOLVER_ITERATIONS {
+        for joint in joints {
+            joint_positional_correction(objects, joint);
+        }
+    }
+}
+
+/// Rigidbodies slower than this are considered still.
+const SLEEP_SPEED_THRESHOLD: f32 = 3.0;
+/// How long all the bodies of an island must be still before the island falls asleep.
+const SLEEP_DELAY: Duration = Duration::from_millis(500);
+
+/// A group of dynamic rigidbodies connected by contacts or joints. Since no constraint
+/// links two different islands, each island can be solved independently from the others.
+#[derive(Default)]
+struct Island<'a> {
+    /// The island's dynamic bodies.
+    entities: Vec<Entity>,
+    /// Contains the island's bodies plus any infinite-mass body they touch.
+    objects: Rigidbodies,
+    infos: Vec<&'a Collision_Info_Internal>,
+    joints: Vec<Joint_Internal>,
+}
+
+impl Island<'_> {
+    fn add_body(&mut self, objects: &Rigidbodies, entity: Entity) {
+        self.objects
+            .bodies
+            .entry(entity)
+            .or_insert_with(|| objects.bodies[&entity].clone());
+    }
+}
+
+fn find_island_root(parent: &mut [usize], mut node: usize) -> usize {
+    while parent[node] != node {
+        parent[node] = parent[parent[node]];
+        node = parent[node];
+    }
+    node
+}
+
+/// Splits all dynamic rigidbodies into islands. Every dynamic body belongs to exactly one island
+/// (possibly on its own).
+fn build_islands<'a>(
+    objects: &Rigidbodies,
+    rb_infos: &[&'a Collision_Info_Internal],
+    joints: &[Joint_Internal],
+) -> Vec<Island<'a>> {
+    trace!("physics::build_islands");
+
+    // Only dynamic bodies link islands together: an infinite-mass body (like a wall) may be
+    // touched by any number of islands, since the solver never moves it.
+    let mut entities: Vec<Entity> = objects
+        .bodies
+        .iter()
+        .filter(|(_, body)| body.inv_mass > 0.)
+        .map(|(&entity, _)| entity)
+        .collect();
+    // Keep the islands deterministic, as the bodies come from a HashMap.
+    entities.sort_unstable_by_key(|entity| (entity.index, entity.gen));
+    let node_of: HashMap<Entity, usize> = entities
+        .iter()
+        .enumerate()
+        .map(|(node, &entity)| (entity, node))
+        .collect();
+
+    // Union-find over the bodies
+    let mut parent: Vec<usize> = (0..entities.len()).collect();
+    let mut link = |a: Entity, b: Entity| {
+        if let (Some(&node_a), Some(&node_b)) = (node_of.get(&a), node_of.get(&b)) {
+            let root_a = find_island_root(&mut parent, node_a);
+            let root_b = find_island_root(&mut parent, node_b);
+            parent[root_a] = root_b;
+        }
+    };
+    for info in rb_infos {
+        link(
+            objects.colliders[&info.cld1].0,
+            objects.colliders[&info.cld2].0,
+        );
+    }
+    for joint in joints {
+        if let Some(body_b) = joint.body_b {
+            link(joint.body_a, body_b);
+        }
+    }
+
+    let mut islands: Vec<Island> = vec![];
+    let mut island_of_root = HashMap::new();
+    for (node, &entity) in entities.iter().enumerate() {
+        let root = find_island_root(&mut parent, node);
+        let island_idx = *island_of_root.entry(root).or_insert_with(|| {
+            islands.push(Island::default());
+            islands.len() - 1
+        });
+        let island = &mut islands[island_idx];
+        island.entities.push(entity);
+        island.add_body(objects, entity);
+    }
+
+    let mut island_of = |entity: Entity| {
+        let node = *node_of.get(&entity)?;
+        Some(island_of_root[&find_island_root(&mut parent, node)])
+    };
+
+    for &info in rb_infos {
+        let (entity1, mat1) = objects.colliders[&info.cld1];
+        let (entity2, mat2) = objects.colliders[&info.cld2];
+        // Contacts between two infinite-mass bodies don't need solving.
+        if let Some(island_idx) = island_of(entity1).or_else(|| island_of(entity2)) {
+            let island = &mut islands[island_idx];
+            island.objects.colliders.insert(info.cld1, (entity1, mat1));
+            island.objects.colliders.insert(info.cld2, (entity2, mat2));
+            island.add_body(objects, entity1);
+            island.add_body(objects, entity2);
+            island.infos.push(info);
+        }
+    }
+
+    for joint in joints {
+        let island_idx = island_of(joint.body_a).or_else(|| joint.body_b.and_then(&mut island_of));
+        if let Some(island_idx) = island_idx {
+            let island = &mut islands[island_idx];
+            island.add_body(objects, joint.body_a);
+            if let Some(body_b) = joint.body_b {
+                island.add_body(objects, body_b);
+            }
+            island.joints.push(joint.clone());
+        }
+    }
+
+    islands
+}
+
+/// Wakes up all the islands containing at least one awake body (e.g. because an awake body
+/// touched a sleeping one) and removes the islands that are still asleep.
+fn wake_islands(phys_world: &mut Physics_World, islands: &mut Vec<Island>) {
+    trace!("physics::wake_islands");
+
+    islands.retain(|island| {
+        let n_sleeping = island
+            .entities
+            .iter()
+            .filter(|&&entity| phys_world.is_sleeping(entity))
+            .count();
+        if n_sleeping == island.entities.len() {
+            return false;
+        }
+        if n_sleeping > 0 {
+            for &entity in &island.entities {
+                phys_world.wake_up(entity);
+            }
+        }
+        true
+    });
+}
+
+/// Updates how long the island's bodies have been still for, and puts the island to sleep
+/// if all of them have been still for long enough. Returns true if the island fell asleep.
+fn update_island_sleep(phys_world: &mut Physics_World, island: &Island, dt: &Duration) -> bool {
+    let mut can_sleep = true;
+    for entity in &island.entities {
+        let still_time = phys_world.still_time.entry(*entity).or_default();
+        let velocity = island.objects.bodies[entity].velocity;
+        if velocity.magnitude2() < SLEEP_SPEED_THRESHOLD * SLEEP_SPEED_THRESHOLD {
+            *still_time += *dt;
+        } else {
+            *still_time = Duration::default();
+        }
+        can_sleep &= *still_time >= SLEEP_DELAY;
+    }
+
+    if can_sleep {
+        for &entity in &island.entities {
+            phys_world.still_time.remove(&entity);
+            phys_world.sleeping.insert(entity);
+        }
+    }
+
+    can_sleep
+}
+
+/// Returns the contacts of the previous physics update between colliders that couldn't be
+/// tested this time, because both of them are either sleeping or static.
+fn carry_sleeping_contacts(
+    phys_world: &Physics_World,
+    infos: &[Collision_Info_Internal],
+) -> Vec<Collision_Info_Internal> {
+    trace!("physics::carry_sleeping_contacts");
+
+    let is_inactive = |handle: &Collider_Handle| match phys_world.get_collider(*handle) {
+        Some(cld) => cld.is_static || phys_world.is_sleeping(cld.entity),
+        None => false,
+    };
+    let detected: HashSet<_> = infos
+        .iter()
+        .map(|info| contact_key(info.cld1, info.cld2))
+        .collect();
+
+    phys_world
+        .contacts
+        .iter()
+        .filter(|(key @ (cld_a, cld_b), _)| {
+            is_inactive(cld_a) && is_inactive(cld_b) && !detected.contains(key)
+        })
+        .map(|(&(cld_a, cld_b), data)| Collision_Info_Internal {
+            cld1: cld_a,
+            cld2: cld_b,
+            info: data.info.clone(),
+        })
+        .collect()
+}
+
+/// Steps the physics by `dt`. This doesn't raise the collision events: call
+/// raise_collision_events once all the physics updates of this tick are done.
 pub fn update_collisions<T_Spatial_Accelerator>(
+    dt: &Duration,
     ecs_world: &mut Ecs_World,
     accelerator: &T_Spatial_Accelerator,
     phys_world: &mut Physics_World,
     settings: &Physics_Settings,
-    evt_register: &mut Event_Register,
     temp_alloc: &mut Temp_Allocator,
     #[cfg(debug_assertions)] debug_data: &mut Collision_System_Debug_Data,
 ) where
@@ -438,17 +1423,42 @@ pub fn update_collisions<T_Spatial_Accelerator>(
 
     phys_world.clear_collisions();
 
+    let ccd_sweep_starts = gather_ccd_sweep_starts(ecs_world, phys_world);
+
     update_colliders_spatial(ecs_world, phys_world);
 
-    let infos = detect_collisions(
+    let ccd_infos = sweep_ccd_colliders(
         phys_world,
         accelerator,
         &settings.collision_matrix,
+        &ccd_sweep_starts,
         temp_alloc,
         #[cfg(debug_assertions)]
         debug_data,
     );
 
+    let mut infos = detect_collisions(
+        phys_world,
+        accelerator,
+        &settings.collision_matrix,
+        temp_alloc,
+        #[cfg(debug_assertions)]
+        debug_data,
+    );
+
+    for ccd_info in ccd_infos {
+        let already_colliding = infos.iter().any(|info| {
+            (info.cld1 == ccd_info.cld1 && info.cld2 == ccd_info.cld2)
+                || (info.cld1 == ccd_info.cld2 && info.cld2 == ccd_info.cld1)
+        });
+        if !already_colliding {
+            infos.push(ccd_info);
+        }
+    }
+
+    let sleeping_infos = carry_sleeping_contacts(phys_world, &infos);
+    infos.extend(sleeping_infos);
+
     {
         trace!("add_collisions_to_phys_world");
         infos.iter().for_each(|info| {
@@ -456,48 +1466,154 @@ pub fn update_collisions<T_Spatial_Accelerator>(
         });
     }
 
-    let mut objects = gather_rigidbodies(phys_world);
+    let objects = gather_rigidbodies(phys_world);
+
+    update_contacts(phys_world, &objects, &infos);
 
     let rb_infos = infos
         .par_iter()
-        .filter(|info| objects.contains_key(&info.cld1) && objects.contains_key(&info.cld2))
+        .filter(|info| {
+            objects.colliders.contains_key(&info.cld1) && objects.colliders.contains_key(&info.cld2)
+        })
         .collect::<Vec<_>>();
 
-    solve_collisions(&mut objects, &rb_infos);
+    let joints = gather_joints(phys_world, &objects);
+
+    let mut islands = build_islands(&objects, &rb_infos, &joints);
+    wake_islands(phys_world, &mut islands);
+
+    islands.par_iter_mut().for_each(|island| {
+        solve_collisions(&mut island.objects, &island.infos);
+        solve_joints(&mut island.objects, &island.joints, dt);
+    });
 
     // Copy back positions and velocities
-    let mut processed = std::collections::HashSet::new();
-    for info in &rb_infos {
-        let Collision_Info_Internal { cld1, cld2, .. } = info;
-
-        for cld in &[*cld1, *cld2] {
-            if !processed.contains(cld) {
-                processed.insert(*cld);
-
-                let Rigidbody {
-                    position,
-                    velocity,
-                    entity,
-                    offset,
-                    ..
-                } = objects[cld];
+    for island in &islands {
+        let solved = !island.infos.is_empty() || !island.joints.is_empty();
+        let fell_asleep = update_island_sleep(phys_world, island, dt);
+        if !solved && !fell_asleep {
+            continue;
+        }
 
-                let mut spatial = ecs_world.get_component_mut::<C_Spatial2D>(entity).unwrap();
-                spatial.transform.set_position_v(position - offset);
-                spatial.velocity = velocity;
-            }
+        for entity in &island.entities {
+            let Rigidbody {
+                position, velocity, ..
+            } = island.objects.bodies[entity];
+
+            let mut spatial = ecs_world.get_component_mut::<C_Spatial2D>(*entity).unwrap();
+            spatial.transform.set_position_v(position);
+            spatial.velocity = if fell_asleep {
+                Vec2f::default()
+            } else {
+                velocity
+            };
         }
     }
 
-    // Note: we do this last to avoid polluting the cache (we don't know how many observers are
-    // subscribed to this event).
-    let data: Vec<&Collision_Data> = phys_world
-        .collisions
-        .values()
-        .map(|v| v.as_slice())
-        .flatten()
+    // Forget about the bodies that were removed
+    phys_world
+        .sleeping
+        .retain(|entity| objects.bodies.contains_key(entity));
+    phys_world
+        .still_time
+        .retain(|entity, _| objects.bodies.contains_key(entity));
+
+    #[cfg(debug_assertions)]
+    {
+        debug_data.n_islands = islands.len();
+        debug_data.n_sleeping_bodies = phys_world.sleeping.len();
+    }
+}
+
+/// Replaces the contacts of the previous physics update with this update's ones, returning
+/// the old ones.
+fn update_contacts(
+    phys_world: &mut Physics_World,
+    objects: &Rigidbodies,
+    infos: &[Collision_Info_Internal],
+) {
+    trace!("physics::update_contacts");
+
+    let mut contacts = HashMap::with_capacity(infos.len());
+    for Collision_Info_Internal { cld1, cld2, info } in infos {
+        let (cld_a, cld_b) = contact_key(*cld1, *cld2);
+        let info = if cld_a == *cld1 {
+            info.clone()
+        } else {
+            Collision_Info {
+                normal: -info.normal,
+                ..*info
+            }
+        };
+        let data = Collision_Event_Data {
+            cld_a,
+            entity_a: phys_world.get_collider(cld_a).unwrap().entity,
+            cld_b,
+            entity_b: phys_world.get_collider(cld_b).unwrap().entity,
+            is_trigger: !objects.colliders.contains_key(&cld_a)
+                || !objects.colliders.contains_key(&cld_b),
+            info,
+        };
+        phys_world
+            .contacts_this_tick
+            .insert((cld_a, cld_b), data.clone());
+        contacts.insert((cld_a, cld_b), data);
+    }
+
+    phys_world.contacts = contacts;
+}
+
+/// Raises the begin, persist and end events of all the contacts found since the previous call.
+/// This should be called once per tick, after its last physics update: a contact that began and
+/// ended during the tick gets both its begin and end events.
+pub fn raise_collision_events(phys_world: &mut Physics_World, evt_register: &mut Event_Register) {
+    trace!("physics::raise_collision_events");
+
+    let reported = &phys_world.reported_contacts;
+    let this_tick = &phys_world.contacts_this_tick;
+    let cur = &phys_world.contacts;
+
+    let mut began: Vec<_> = this_tick
+        .iter()
+        .filter(|(key, _)| !reported.contains_key(key))
+        .map(|(_, data)| data)
         .collect();
-    evt_register.raise_batch::<Evt_Collision_Happened>(&data);
+    let mut persisted: Vec<_> = cur
+        .iter()
+        .filter(|(key, _)| reported.contains_key(key))
+        .map(|(_, data)| data)
+        .collect();
+    // Prefer the info from this tick, as it's the latest.
+    let mut ended: Vec<_> = this_tick
+        .iter()
+        .chain(
+            reported
+                .iter()
+                .filter(|(key, _)| !this_tick.contains_key(key)),
+        )
+        .filter(|(key, _)| !cur.contains_key(key))
+        .map(|(_, data)| data)
+        .collect();
+
+    // Keep the events order deterministic, as the contacts come from HashMaps.
+    let sort_key = |data: &&Collision_Event_Data| {
+        (
+            data.cld_a.index,
+            data.cld_a.gen,
+            data.cld_b.index,
+            data.cld_b.gen,
+        )
+    };
+    began.sort_unstable_by_key(sort_key);
+    persisted.sort_unstable_by_key(sort_key);
+    ended.sort_unstable_by_key(sort_key);
+
+    evt_register.raise_batch::<Evt_Collision_End>(&ended);
+    evt_register.raise_batch::<Evt_Collision_Begin>(&began);
+    evt_register.raise_batch::<Evt_Collision_Persist>(&persisted);
+
+    phys_world.reported_contacts = phys_world.contacts.clone();
+    phys_world.contacts_this_tick.clear();
 }
 
 fn update_colliders_spatial(ecs_world: &mut Ecs_World, phys_world: &mut Physics_World) {
@@ -505,43 +1621,884 @@ fn update_colliders_spatial(ecs_world: &mut Ecs_World, phys_world: &mut Physics_
 
     if let Some(mut spatials) = ecs_world.write_component_storage::<C_Spatial2D>() {
         for collider in &mut phys_world.colliders {
-            let mut spatial = spatials.must_get_mut(collider.entity);
+            let spatial = spatials.must_get_mut(collider.entity);
             let pos = spatial.transform.position();
+            let rotation = spatial.transform.rotation();
             spatial.frame_starting_pos = pos;
+            // The offset is in the entity's local space, so it rotates along with it.
+            let cld_pos = pos + collider.offset.rotated(rotation);
 
-            collider.position = pos + collider.offset;
+            // Wake up sleeping bodies that were moved (or given a velocity) from outside
+            if phys_world.sleeping.contains(&collider.entity)
+                && ((cld_pos - collider.position).magnitude2() > f32::EPSILON
+                    || spatial.velocity != Vec2f::default())
+            {
+                phys_world.sleeping.remove(&collider.entity);
+                phys_world.still_time.remove(&collider.entity);
+            }
+
+            collider.position = cld_pos;
+            collider.rotation = rotation;
             collider.velocity = spatial.velocity;
         }
     }
 }
 
-/// Returns { collider => rigidbody }
-/// Note that some entities may have non-physical colliders (i.e. trigger colliders),
-/// but each entity must have at most 1 physical collider. // :MultipleRigidbodies: lift this restriction!
+/// Returns all the rigidbodies, each made of all the rigidbody colliders of a Physics_Body.
+/// Note that some entities may have non-physical colliders (i.e. trigger colliders).
 fn gather_rigidbodies(phys_world: &mut Physics_World) -> Rigidbodies {
     trace!("gather_rigidbodies");
 
     // @Speed: try to use an array rather than a HashMap
-    let mut objects = HashMap::new();
+    let mut objects = Rigidbodies::default();
 
     for body in &phys_world.bodies {
-        // @Incomplete :MultipleRigidbodies: handle multiple rigidbody colliders
-        if let Some(&(cld_handle, phys_data)) = body.rigidbody_colliders.get(0) {
+        let mut rigidbody: Option<(Entity, Rigidbody)> = None;
+        let mut mass = 0.;
+
+        for &(cld_handle, phys_data) in &body.rigidbody_colliders {
             if let Some(rb_cld) = phys_world.get_collider(cld_handle) {
-                objects.insert(
-                    cld_handle,
-                    Rigidbody {
-                        entity: rb_cld.entity,
-                        position: rb_cld.position,
-                        offset: rb_cld.offset,
-                        velocity: rb_cld.velocity,
-                        shape: rb_cld.shape,
-                        phys_data,
-                    },
-                );
+                // Note: an infinite-mass collider (inv_mass = 0) makes the whole body immovable,
+                // since its mass is infinite and so is the sum.
+                mass += 1. / phys_data.inv_mass;
+                objects
+                    .colliders
+                    .insert(cld_handle, (rb_cld.entity, phys_data));
+                rigidbody.get_or_insert_with(|| {
+                    (
+                        rb_cld.entity,
+                        Rigidbody {
+                            position: rb_cld.position - rb_cld.offset.rotated(rb_cld.rotation),
+                            velocity: rb_cld.velocity,
+                            inv_mass: 0.,
+                        },
+                    )
+                });
             }
         }
+
+        if let Some((entity, mut rigidbody)) = rigidbody {
+            rigidbody.inv_mass = 1. / mass;
+            objects.bodies.insert(entity, rigidbody);
+        }
     }
 
     objects
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use inle_math::angle::{deg, Angle};
+
+    const EPS: f32 = 0.001;
+
+    fn collider(shape: Collision_Shape, position: Vec2f, rotation: Angle) -> Collider {
+        Collider {
+            shape,
+            position,
+            rotation,
+            ..Default::default()
+        }
+    }
+
+    fn assert_collision(info: Option<Collision_Info>, normal: Vec2f, penetration: f32) {
+        let info = info.expect("shapes should collide");
+        assert!(
+            (info.normal - normal).magnitude() < EPS,
+            "normal is {:?}, expected {:?}",
+            info.normal,
+            normal
+        );
+        assert!(
+            (info.penetration - penetration).abs() < EPS,
+            "penetration is {}, expected {}",
+            info.penetration,
+            penetration
+        );
+    }
+
+    #[test]
+    fn oriented_rect_vs_rect() {
+        let obb = collider(
+            Collision_Shape::Oriented_Rect {
+                width: 2.,
+                height: 2.,
+            },
+            v2!(0., 0.),
+            deg(45.),
+        );
+        let half_diag = 2f32.sqrt();
+        let rect = |x| {
+            collider(
+                Collision_Shape::Rect {
+                    width: 2.,
+                    height: 2.,
+                },
+                v2!(x, 0.),
+                deg(0.),
+            )
+        };
+
+        // Would overlap if the obb were axis-aligned
+        assert!(detect_collision(&obb, &rect(2.5)).is_none());
+
+        assert_collision(
+            detect_collision(&obb, &rect(2.)),
+            v2!(1., 0.),
+            half_diag - 1.,
+        );
+        assert_collision(
+            detect_collision(&rect(2.), &obb),
+            v2!(-1., 0.),
+            half_diag - 1.,
+        );
+    }
+
+    #[test]
+    fn polygon_vs_polygon() {
+        let triangle = Collision_Shape::new_polygon(&[v2!(-1., 0.), v2!(1., 0.), v2!(0., -1.)]);
+        let floor = collider(
+            Collision_Shape::Rect {
+                width: 10.,
+                height: 2.,
+            },
+            v2!(0., 1.),
+            deg(0.),
+        );
+
+        // The triangle's base lies on top of the floor
+        assert!(
+            detect_collision(&collider(triangle.clone(), v2!(0., -0.1), deg(0.)), &floor).is_none()
+        );
+        assert_collision(
+            detect_collision(&collider(triangle.clone(), v2!(0., 0.25), deg(0.)), &floor),
+            v2!(0., 1.),
+            0.25,
+        );
+        // Upside down, the tip is sinking into the floor
+        assert_collision(
+            detect_collision(&collider(triangle, v2!(0., -0.75), deg(180.)), &floor),
+            v2!(0., 1.),
+            0.25,
+        );
+    }
+
+    #[test]
+    fn capsule_vs_polygon() {
+        let capsule = |pos, rot| {
+            collider(
+                Collision_Shape::Capsule {
+                    radius: 0.5,
+                    half_length: 1.,
+                },
+                pos,
+                rot,
+            )
+        };
+        let box_ = collider(
+            Collision_Shape::Oriented_Rect {
+                width: 2.,
+                height: 2.,
+            },
+            v2!(0., 0.),
+            deg(0.),
+        );
+
+        // Vertical capsule on top of the box
+        assert_collision(
+            detect_collision(&capsule(v2!(0., -2.25), deg(0.)), &box_),
+            v2!(0., 1.),
+            0.25,
+        );
+        // Horizontal capsule to the right of the box
+        assert_collision(
+            detect_collision(&box_, &capsule(v2!(2.25, 0.), deg(90.))),
+            v2!(1., 0.),
+            0.25,
+        );
+        assert!(detect_collision(&box_, &capsule(v2!(2.75, 0.), deg(90.))).is_none());
+        // Capsule end near the box corner
+        let dir = v2!(1., 1.).normalized();
+        let end = v2!(1., 1.) + dir * 0.25;
+        assert_collision(
+            detect_collision(&box_, &capsule(end + v2!(0., 1.), deg(0.))),
+            dir,
+            0.25,
+        );
+    }
+
+    #[test]
+    fn capsule_vs_capsule_and_circle() {
+        let capsule = collider(
+            Collision_Shape::Capsule {
+                radius: 1.,
+                half_length: 2.,
+            },
+            v2!(0., 0.),
+            deg(90.),
+        );
+        let circle = |pos| collider(Collision_Shape::Circle { radius: 1. }, pos, deg(0.));
+
+        assert_collision(
+            detect_collision(&capsule, &circle(v2!(1., 1.5))),
+            v2!(0., 1.),
+            0.5,
+        );
+        assert_collision(
+            detect_collision(&circle(v2!(1., 1.5)), &capsule),
+            v2!(0., -1.),
+            0.5,
+        );
+        assert_collision(
+            detect_collision(&capsule, &circle(v2!(3.5, 0.))),
+            v2!(1., 0.),
+            0.5,
+        );
+        assert!(detect_collision(&capsule, &circle(v2!(0., 2.5))).is_none());
+
+        let other = collider(
+            Collision_Shape::Capsule {
+                radius: 0.5,
+                half_length: 1.,
+            },
+            v2!(0., 2.),
+            deg(0.),
+        );
+        assert_collision(detect_collision(&capsule, &other), v2!(0., 1.), 0.5);
+    }
+
+    #[test]
+    fn sweep_shapes() {
+        let wall = collider(
+            Collision_Shape::Rect {
+                width: 2.,
+                height: 20.,
+            },
+            v2!(0., 0.),
+            deg(0.),
+        );
+        let circle = Collision_Shape::Circle { radius: 1. };
+        let rect = Collision_Shape::Rect {
+            width: 2.,
+            height: 2.,
+        };
+
+        // Tunneling through the wall in a single step
+        let (t, normal) = sweep_collider(&rect, v2!(-10., 0.), v2!(20., 0.), &wall).unwrap();
+        assert!((t - 0.4).abs() < EPS, "{}", t);
+        assert_eq!(normal, v2!(1., 0.));
+
+        let (t, normal) = sweep_collider(&circle, v2!(10., 0.), v2!(-20., 0.), &wall).unwrap();
+        assert!((t - 0.4).abs() < EPS, "{}", t);
+        assert_eq!(normal, v2!(-1., 0.));
+
+        // Passing near the wall's corner, but missing it...
+        let dir = v2!(1., -1.).normalized();
+        let near_corner = v2!(-1., -10.) + v2!(-1., -1.).normalized() * 1.2;
+        assert!(sweep_collider(&circle, near_corner - dir * 5., dir * 10., &wall).is_none());
+        // ...while a rect would hit it
+        assert!(sweep_collider(&rect, near_corner - dir * 5., dir * 10., &wall).is_some());
+
+        // Hitting the rounded corner diagonally
+        let dir = v2!(1., 1.).normalized();
+        let corner = v2!(-1., -10.);
+        let (t, normal) = sweep_collider(&circle, corner - dir * 3., dir * 4., &wall).unwrap();
+        assert!((t - 0.5).abs() < EPS, "{}", t);
+        assert!((normal - dir).magnitude() < EPS, "{:?}", normal);
+
+        // Too short a motion
+        assert!(sweep_collider(&rect, v2!(-10., 0.), v2!(5., 0.), &wall).is_none());
+
+        // Circle vs circle, in both directions
+        let ball = collider(circle.clone(), v2!(0., 0.), deg(0.));
+        let (t, normal) = sweep_collider(&circle, v2!(0., -10.), v2!(0., 20.), &ball).unwrap();
+        assert!((t - 0.4).abs() < EPS, "{}", t);
+        assert_eq!(normal, v2!(0., 1.));
+
+        let (t, normal) = sweep_collider(&rect, v2!(0., 10.), v2!(0., -20.), &ball).unwrap();
+        assert!((t - 0.4).abs() < EPS, "{}", t);
+        assert_eq!(normal, v2!(0., -1.));
+    }
+
+    struct All_Colliders(Vec<Collider_Handle>);
+
+    impl Spatial_Accelerator<Collider_Handle> for All_Colliders {
+        fn get_neighbours<R>(&self, _pos: Vec2f, _extent: Vec2f, result: &mut R)
+        where
+            R: Extend<Collider_Handle>,
+        {
+            result.extend(self.0.iter().copied());
+        }
+    }
+
+    #[test]
+    fn continuous_body_does_not_tunnel() {
+        use crate::phys_world::Phys_Data;
+        use inle_math::transform::Transform2D;
+
+        let mut ecs_world = Ecs_World::new();
+        let mut phys_world = Physics_World::new();
+        let mut settings = Physics_Settings::default();
+        settings.collision_matrix.set_layers_collide(0, 0);
+
+        let wall = ecs_world.new_entity();
+        ecs_world.add_component(wall, C_Spatial2D::default());
+        let wall_cld = Collider {
+            shape: Collision_Shape::Rect {
+                width: 2.,
+                height: 20.,
+            },
+            is_static: true,
+            ..Default::default()
+        };
+        phys_world.new_physics_body_with_rigidbody(wall_cld, wall, Phys_Data::default());
+
+        let mut bodies = vec![];
+        for &continuous_collision in &[true, false] {
+            let body = ecs_world.new_entity();
+            // This body moved from x = -10 to x = 10 during the last step.
+            ecs_world.add_component(
+                body,
+                C_Spatial2D {
+                    transform: Transform2D::from_pos(v2!(10., 0.)),
+                    frame_starting_pos: v2!(-10., 0.),
+                    velocity: v2!(1200., 0.),
+                    ..Default::default()
+                },
+            );
+            let cld = Collider {
+                shape: Collision_Shape::Circle { radius: 1. },
+                ..Default::default()
+            };
+            let phys_data = Phys_Data {
+                inv_mass: 1.,
+                continuous_collision,
+                ..Default::default()
+            };
+            phys_world.new_physics_body_with_rigidbody(cld, body, phys_data);
+            bodies.push(body);
+        }
+
+        let accelerator =
+            All_Colliders(phys_world.colliders.iter().map(|cld| cld.handle).collect());
+        update_collisions(
+            &Duration::from_millis(16),
+            &mut ecs_world,
+            &accelerator,
+            &mut phys_world,
+            &settings,
+            &mut Temp_Allocator::with_capacity(1024),
+            #[cfg(debug_assertions)]
+            &mut Collision_System_Debug_Data::default(),
+        );
+
+        let spatial = ecs_world.get_component::<C_Spatial2D>(bodies[0]).unwrap();
+        assert!(
+            (spatial.transform.position() - v2!(-2., 0.)).magnitude() < EPS,
+            "{:?}",
+            spatial.transform.position()
+        );
+        assert!(spatial.velocity.x <= 0., "{:?}", spatial.velocity);
+
+        // Without continuous collision detection, the body went through the wall.
+        let spatial = ecs_world.get_component::<C_Spatial2D>(bodies[1]).unwrap();
+        assert_eq!(spatial.transform.position(), v2!(10., 0.));
+        assert_eq!(spatial.velocity, v2!(1200., 0.));
+    }
+
+    #[test]
+    fn compound_body_shares_mass_and_velocity() {
+        use crate::phys_world::Phys_Data;
+        use inle_math::transform::Transform2D;
+
+        let mut ecs_world = Ecs_World::new();
+        let mut phys_world = Physics_World::new();
+        let mut settings = Physics_Settings::default();
+        settings.collision_matrix.set_layers_collide(0, 0);
+
+        let phys_data = |inv_mass| Phys_Data {
+            inv_mass,
+            restitution: 1.,
+            ..Default::default()
+        };
+
+        // A body made of two circles, with a total mass of 2
+        let compound = ecs_world.new_entity();
+        ecs_world.add_component(compound, C_Spatial2D::default());
+        let body = phys_world.new_physics_body();
+        for &offset in &[v2!(0., -2.), v2!(0., 2.)] {
+            let cld = Collider {
+                shape: Collision_Shape::Circle { radius: 1. },
+                offset,
+                ..Default::default()
+            };
+            let cld = phys_world.add_collider(cld, compound);
+            phys_world
+                .get_physics_body_mut(body)
+                .unwrap()
+                .rigidbody_colliders
+                .push((cld, phys_data(1.)));
+        }
+
+        // A ball with mass 2 hitting the compound's second collider
+        let ball = ecs_world.new_entity();
+        let ball_pos = v2!(0., 3.9);
+        ecs_world.add_component(
+            ball,
+            C_Spatial2D {
+                transform: Transform2D::from_pos(ball_pos),
+                frame_starting_pos: ball_pos,
+                velocity: v2!(0., -10.),
+                ..Default::default()
+            },
+        );
+        let cld = Collider {
+            shape: Collision_Shape::Circle { radius: 1. },
+            ..Default::default()
+        };
+        phys_world.new_physics_body_with_rigidbody(cld, ball, phys_data(0.5));
+
+        let accelerator =
+            All_Colliders(phys_world.colliders.iter().map(|cld| cld.handle).collect());
+        update_collisions(
+            &Duration::from_millis(16),
+            &mut ecs_world,
+            &accelerator,
+            &mut phys_world,
+            &settings,
+            &mut Temp_Allocator::with_capacity(1024),
+            #[cfg(debug_assertions)]
+            &mut Collision_System_Debug_Data::default(),
+        );
+
+        // Elastic collision between equal masses: the velocities are swapped.
+        let compound_vel = ecs_world
+            .get_component::<C_Spatial2D>(compound)
+            .unwrap()
+            .velocity;
+        let ball_vel = ecs_world
+            .get_component::<C_Spatial2D>(ball)
+            .unwrap()
+            .velocity;
+        assert!(
+            (compound_vel - v2!(0., -10.)).magnitude() < EPS,
+            "{:?}",
+            compound_vel
+        );
+        assert!(ball_vel.magnitude() < EPS, "{:?}", ball_vel);
+
+        // The positional correction moved the whole body
+        let compound_pos = ecs_world
+            .get_component::<C_Spatial2D>(compound)
+            .unwrap()
+            .transform
+            .position();
+        assert!(compound_pos.y < 0., "{:?}", compound_pos);
+        assert_eq!(compound_pos.x, 0.);
+    }
+
+    fn joint_test_body(
+        ecs_world: &mut Ecs_World,
+        phys_world: &mut Physics_World,
+        position: Vec2f,
+        velocity: Vec2f,
+    ) -> (Entity, crate::phys_world::Physics_Body_Handle) {
+        use crate::phys_world::Phys_Data;
+        use inle_math::transform::Transform2D;
+
+        let entity = ecs_world.new_entity();
+        ecs_world.add_component(
+            entity,
+            C_Spatial2D {
+                transform: Transform2D::from_pos(position),
+                frame_starting_pos: position,
+                velocity,
+                ..Default::default()
+            },
+        );
+        let cld = Collider {
+            shape: Collision_Shape::Circle { radius: 0.5 },
+            ..Default::default()
+        };
+        let phys_data = Phys_Data {
+            inv_mass: 1.,
+            ..Default::default()
+        };
+        let body = phys_world.new_physics_body_with_rigidbody(cld, entity, phys_data);
+        (entity, body)
+    }
+
+    fn step_joints(ecs_world: &mut Ecs_World, phys_world: &mut Physics_World) {
+        let accelerator =
+            All_Colliders(phys_world.colliders.iter().map(|cld| cld.handle).collect());
+        update_collisions(
+            &Duration::from_millis(16),
+            ecs_world,
+            &accelerator,
+            phys_world,
+            &Physics_Settings::default(),
+            &mut Temp_Allocator::with_capacity(1024),
+            #[cfg(debug_assertions)]
+            &mut Collision_System_Debug_Data::default(),
+        );
+    }
+
+    fn spatial(ecs_world: &Ecs_World, entity: Entity) -> (Vec2f, Vec2f) {
+        let spatial = ecs_world.get_component::<C_Spatial2D>(entity).unwrap();
+        (spatial.transform.position(), spatial.velocity)
+    }
+
+    #[test]
+    fn distance_joint_keeps_length() {
+        use crate::joints::Joint;
+
+        let mut ecs_world = Ecs_World::new();
+        let mut phys_world = Physics_World::new();
+
+        // A pendulum hanging from the origin, stretched and moving away from its pivot
+        let (bob, body) =
+            joint_test_body(&mut ecs_world, &mut phys_world, v2!(0., 11.), v2!(3., 5.));
+        phys_world.add_joint(Joint {
+            kind: Joint_Kind::Distance { length: 10. },
+            body_a: body,
+            anchor_a: v2!(0., 0.),
+            body_b: None,
+            anchor_b: v2!(0., 0.),
+        });
+
+        step_joints(&mut ecs_world, &mut phys_world);
+
+        // Only the tangential velocity survives
+        let (pos, vel) = spatial(&ecs_world, bob);
+        assert!((vel - v2!(3., 0.)).magnitude() < EPS, "{:?}", vel);
+        assert!(pos.magnitude() < 11., "{:?}", pos);
+        assert!(pos.magnitude() > 10. - EPS, "{:?}", pos);
+    }
+
+    #[test]
+    fn revolute_joint_pins_anchors() {
+        use crate::joints::Joint;
+
+        let mut ecs_world = Ecs_World::new();
+        let mut phys_world = Physics_World::new();
+
+        let (a, body_a) =
+            joint_test_body(&mut ecs_world, &mut phys_world, v2!(0., 0.), v2!(0., 0.));
+        let (b, body_b) =
+            joint_test_body(&mut ecs_world, &mut phys_world, v2!(3., 0.), v2!(4., 2.));
+        phys_world.add_joint(Joint {
+            kind: Joint_Kind::Revolute,
+            body_a,
+            anchor_a: v2!(1., 0.),
+            body_b: Some(body_b),
+            anchor_b: v2!(-1., 0.),
+        });
+
+        let anchor_dist = |ecs_world: &Ecs_World| {
+            let (pa, _) = spatial(ecs_world, a);
+            let (pb, _) = spatial(ecs_world, b);
+            ((pb + v2!(-1., 0.)) - (pa + v2!(1., 0.))).magnitude()
+        };
+        let dist_before = anchor_dist(&ecs_world);
+
+        step_joints(&mut ecs_world, &mut phys_world);
+
+        // Pinned bodies of equal mass move together with their average velocity.
+        let (_, va) = spatial(&ecs_world, a);
+        let (_, vb) = spatial(&ecs_world, b);
+        assert!((va - v2!(2., 1.)).magnitude() < EPS, "{:?}", va);
+        assert!((vb - v2!(2., 1.)).magnitude() < EPS, "{:?}", vb);
+        assert!(anchor_dist(&ecs_world) < dist_before);
+    }
+
+    #[test]
+    fn spring_joint_pulls_anchors() {
+        use crate::joints::Joint;
+
+        let mut ecs_world = Ecs_World::new();
+        let mut phys_world = Physics_World::new();
+
+        let (a, body_a) =
+            joint_test_body(&mut ecs_world, &mut phys_world, v2!(0., 0.), v2!(0., 0.));
+        let (b, body_b) =
+            joint_test_body(&mut ecs_world, &mut phys_world, v2!(10., 0.), v2!(0., 0.));
+        phys_world.add_joint(Joint {
+            kind: Joint_Kind::Spring {
+                rest_length: 5.,
+                stiffness: 100.,
+                damping: 1.,
+            },
+            body_a,
+            anchor_a: v2!(0., 0.),
+            body_b: Some(body_b),
+            anchor_b: v2!(0., 0.),
+        });
+
+        step_joints(&mut ecs_world, &mut phys_world);
+
+        let (_, va) = spatial(&ecs_world, a);
+        let (_, vb) = spatial(&ecs_world, b);
+        assert!(va.x > 0. && va.y.abs() < EPS, "{:?}", va);
+        assert!((va + vb).magnitude() < EPS, "{:?} {:?}", va, vb);
+    }
+
+    #[test]
+    fn serialize_deserialize_joints() {
+        use crate::joints::Joint;
+        use inle_serialize::{Binary_Serializable, Byte_Stream};
+
+        let joints = [
+            Joint {
+                kind: Joint_Kind::Distance { length: 4. },
+                body_a: Default::default(),
+                anchor_a: v2!(1., 2.),
+                body_b: None,
+                anchor_b: v2!(-3., 4.),
+            },
+            Joint {
+                kind: Joint_Kind::Revolute,
+                body_a: Default::default(),
+                anchor_a: v2!(0., 0.),
+                body_b: Some(Default::default()),
+                anchor_b: v2!(5., 0.),
+            },
+            Joint {
+                kind: Joint_Kind::Spring {
+                    rest_length: 2.,
+                    stiffness: 30.,
+                    damping: 0.5,
+                },
+                body_a: Default::default(),
+                anchor_a: v2!(0., 1.),
+                body_b: None,
+                anchor_b: v2!(0., 0.),
+            },
+        ];
+
+        let mut stream = Byte_Stream::new();
+        for joint in &joints {
+            joint.serialize(&mut stream).unwrap();
+        }
+        stream.seek(0);
+        for joint in &joints {
+            assert_eq!(Joint::deserialize(&mut stream).unwrap(), *joint);
+        }
+    }
+
+    #[test]
+    fn collision_begin_persist_end_events() {
+        use crate::phys_world::Collision_Event_Data;
+        use inle_events::evt_register::{with_cb_data, wrap_cb_data, Event_Callback};
+        use inle_math::transform::Transform2D;
+        use inle_serialize::{Binary_Serializable, Byte_Stream};
+
+        let mut ecs_world = Ecs_World::new();
+        let mut phys_world = Physics_World::new();
+        let mut settings = Physics_Settings::default();
+        settings.collision_matrix.set_layers_collide(0, 0);
+
+        let (a, _) = joint_test_body(&mut ecs_world, &mut phys_world, v2!(0., 0.), v2!(0., 0.));
+        let (b, _) = joint_test_body(&mut ecs_world, &mut phys_world, v2!(0.5, 0.), v2!(0., 0.));
+        // A trigger on the same entity as `b`
+        let trigger_cld = Collider {
+            shape: Collision_Shape::Circle { radius: 0.5 },
+            ..Default::default()
+        };
+        let trigger = phys_world.add_collider(trigger_cld, b);
+
+        // [(event kind, data)]
+        let events = wrap_cb_data(Vec::<(&'static str, Collision_Event_Data)>::new());
+        let mut evt_register = Event_Register::new();
+        // Note: all collision events share the same callback type.
+        fn record(kind: &'static str) -> Event_Callback<Evt_Collision_Begin> {
+            Box::new(move |data, events| {
+                with_cb_data(events.unwrap(), |events: &mut Vec<_>| {
+                    events.push((kind, data.clone()))
+                });
+            })
+        }
+        evt_register.subscribe::<Evt_Collision_Begin>(record("begin"), Some(events.clone()));
+        evt_register.subscribe::<Evt_Collision_Persist>(record("persist"), Some(events.clone()));
+        evt_register.subscribe::<Evt_Collision_End>(record("end"), Some(events.clone()));
+
+        let substep = |ecs_world: &mut Ecs_World, phys_world: &mut Physics_World| {
+            let accelerator =
+                All_Colliders(phys_world.colliders.iter().map(|cld| cld.handle).collect());
+            update_collisions(
+                &Duration::from_millis(16),
+                ecs_world,
+                &accelerator,
+                phys_world,
+                &settings,
+                &mut Temp_Allocator::with_capacity(1024),
+                #[cfg(debug_assertions)]
+                &mut Collision_System_Debug_Data::default(),
+            );
+        };
+        let mut step = |ecs_world: &mut Ecs_World, phys_world: &mut Physics_World| {
+            substep(ecs_world, phys_world);
+            raise_collision_events(phys_world, &mut evt_register);
+        };
+        let take_events = || {
+            let mut events = events.clone();
+            with_cb_data(
+                &mut events,
+                |events: &mut Vec<(&'static str, Collision_Event_Data)>| {
+                    let mut taken = std::mem::take(events)
+                        .into_iter()
+                        .map(|(kind, data)| (kind, data.entity_a, data.entity_b, data.is_trigger))
+                        .collect::<Vec<_>>();
+                    taken.sort_by_key(|&(kind, _, _, is_trigger)| (kind, is_trigger));
+                    taken
+                },
+            )
+        };
+        let freeze = |ecs_world: &mut Ecs_World, entity, pos| {
+            let mut spatial = ecs_world.get_component_mut::<C_Spatial2D>(entity).unwrap();
+            spatial.transform = Transform2D::from_pos(pos);
+            spatial.velocity = Vec2f::default();
+        };
+
+        step(&mut ecs_world, &mut phys_world);
+        assert_eq!(
+            take_events(),
+            vec![("begin", a, b, false), ("begin", a, b, true)]
+        );
+        assert!(phys_world.get_collisions(trigger)[0].is_new);
+
+        freeze(&mut ecs_world, a, v2!(0., 0.));
+        freeze(&mut ecs_world, b, v2!(0.5, 0.));
+        step(&mut ecs_world, &mut phys_world);
+        assert_eq!(
+            take_events(),
+            vec![("persist", a, b, false), ("persist", a, b, true)]
+        );
+        assert!(!phys_world.get_collisions(trigger)[0].is_new);
+
+        // Restoring a snapshot keeps the ongoing contacts, so they don't begin again.
+        let mut snapshot = Byte_Stream::new();
+        phys_world.serialize(&mut snapshot).unwrap();
+        snapshot.seek(0);
+        phys_world = Physics_World::deserialize(&mut snapshot).unwrap();
+        freeze(&mut ecs_world, a, v2!(0., 0.));
+        freeze(&mut ecs_world, b, v2!(0.5, 0.));
+        step(&mut ecs_world, &mut phys_world);
+        assert_eq!(
+            take_events(),
+            vec![("persist", a, b, false), ("persist", a, b, true)]
+        );
+        assert!(!phys_world.get_collisions(trigger)[0].is_new);
+
+        freeze(&mut ecs_world, b, v2!(10., 0.));
+        step(&mut ecs_world, &mut phys_world);
+        assert_eq!(
+            take_events(),
+            vec![("end", a, b, false), ("end", a, b, true)]
+        );
+
+        step(&mut ecs_world, &mut phys_world);
+        assert!(take_events().is_empty());
+
+        // Substeps of the same tick only raise the events once...
+        freeze(&mut ecs_world, b, v2!(0.5, 0.));
+        substep(&mut ecs_world, &mut phys_world);
+        freeze(&mut ecs_world, a, v2!(0., 0.));
+        freeze(&mut ecs_world, b, v2!(0.5, 0.));
+        step(&mut ecs_world, &mut phys_world);
+        assert_eq!(
+            take_events(),
+            vec![("begin", a, b, false), ("begin", a, b, true)]
+        );
+        assert!(phys_world.get_collisions(trigger)[0].is_new);
+
+        // ...and a contact that begins and ends within a tick gets both events.
+        freeze(&mut ecs_world, b, v2!(10., 0.));
+        step(&mut ecs_world, &mut phys_world);
+        take_events();
+        freeze(&mut ecs_world, b, v2!(0.5, 0.));
+        substep(&mut ecs_world, &mut phys_world);
+        freeze(&mut ecs_world, b, v2!(10., 0.));
+        step(&mut ecs_world, &mut phys_world);
+        assert_eq!(
+            take_events(),
+            vec![
+                ("begin", a, b, false),
+                ("begin", a, b, true),
+                ("end", a, b, false),
+                ("end", a, b, true)
+            ]
+        );
+    }
+
+    #[test]
+    fn still_bodies_fall_asleep_and_wake_up() {
+        use inle_math::transform::Transform2D;
+
+        let mut ecs_world = Ecs_World::new();
+        let mut phys_world = Physics_World::new();
+
+        // Two resting bodies touching each other, forming an island
+        let (a, body_a) =
+            joint_test_body(&mut ecs_world, &mut phys_world, v2!(0., 0.), v2!(0., 0.));
+        let (b, _) = joint_test_body(&mut ecs_world, &mut phys_world, v2!(0.995, 0.), v2!(0., 0.));
+        // A body far away from them
+        let (c, _) = joint_test_body(&mut ecs_world, &mut phys_world, v2!(-10., 0.), v2!(0., 0.));
+
+        let mut settings = Physics_Settings::default();
+        settings.collision_matrix.set_layers_collide(0, 0);
+        #[cfg(debug_assertions)]
+        let mut debug_data = Collision_System_Debug_Data::default();
+        let mut step = |ecs_world: &mut Ecs_World, phys_world: &mut Physics_World| {
+            let accelerator =
+                All_Colliders(phys_world.colliders.iter().map(|cld| cld.handle).collect());
+            update_collisions(
+                &Duration::from_millis(100),
+                ecs_world,
+                &accelerator,
+                phys_world,
+                &settings,
+                &mut Temp_Allocator::with_capacity(1024),
+                #[cfg(debug_assertions)]
+                &mut debug_data,
+            );
+        };
+
+        for _ in 0..(SLEEP_DELAY.as_millis() / 100) {
+            step(&mut ecs_world, &mut phys_world);
+        }
+        assert!(phys_world.is_sleeping(a));
+        assert!(phys_world.is_sleeping(b));
+        assert!(phys_world.is_sleeping(c));
+
+        // Sleeping bodies keep their contacts
+        step(&mut ecs_world, &mut phys_world);
+        let a_cld = phys_world
+            .get_first_rigidbody_collider(body_a)
+            .unwrap()
+            .handle;
+        assert_eq!(phys_world.get_collisions(a_cld).len(), 1);
+
+        // Moving a body from outside wakes up only that body...
+        ecs_world
+            .get_component_mut::<C_Spatial2D>(c)
+            .unwrap()
+            .transform = Transform2D::from_pos(v2!(-5., 0.));
+        step(&mut ecs_world, &mut phys_world);
+        assert!(!phys_world.is_sleeping(c));
+        assert!(phys_world.is_sleeping(a));
+        assert!(phys_world.is_sleeping(b));
+
+        // ...until it touches the island, which wakes up as a whole.
+        {
+            let mut spatial = ecs_world.get_component_mut::<C_Spatial2D>(c).unwrap();
+            spatial.transform = Transform2D::from_pos(v2!(-0.95, 0.));
+            spatial.velocity = v2!(10., 0.);
+        }
+        step(&mut ecs_world, &mut phys_world);
+        assert!(!phys_world.is_sleeping(a));
+        assert!(!phys_world.is_sleeping(b));
+        #[cfg(debug_assertions)]
+        assert_eq!(debug_data.n_islands, 1);
+    }
+}
@@ -0,0 +1,783 @@
+use super::collider::{Collider, Collision_Shape};
+use super::layers::{Collision_Layer, Collision_Matrix};
+use super::phys_world::{Collider_Handle, Physics_World};
+use super::physics::detect_collision;
+use super::spatial::Spatial_Accelerator;
+use inle_ecs::ecs_world::Entity;
+use inle_math::angle::{rad, Angle};
+use inle_math::math::clamp;
+use inle_math::vector::Vec2f;
+use smallvec::SmallVec;
+
+/// Describes which colliders a query can hit.
+#[derive(Copy, Clone)]
+pub struct Query_Filter<'a> {
+    /// The query only hits colliders whose layer collides with this one.
+    pub layer: Collision_Layer,
+    pub collision_matrix: &'a Collision_Matrix,
+    /// The colliders of this entity are never hit (typically it's the entity doing the query).
+    pub ignored_entity: Option<Entity>,
+}
+
+/// A shape placed in the world, used by shapecast and overlap_shape.
+#[derive(Clone, Debug)]
+pub struct Query_Shape {
+    pub shape: Collision_Shape,
+    pub position: Vec2f,
+    pub rotation: Angle,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Query_Hit {
+    pub collider: Collider_Handle,
+    pub entity: Entity,
+    /// For casts, this is the point of contact. For overlaps, it's the point of the query shape
+    /// that's deepest inside the collider.
+    pub point: Vec2f,
+    /// The normal of the collider's surface, pointing out of it.
+    pub normal: Vec2f,
+    /// For casts, this is the distance travelled before the hit. For overlaps, it's the
+    /// penetration depth along `normal`.
+    pub distance: f32,
+}
+
+impl Query_Filter<'_> {
+    fn accepts(&self, cld: &Collider) -> bool {
+        Some(cld.entity) != self.ignored_entity
+            && self.collision_matrix.layers_collide(self.layer, cld.layer)
+    }
+}
+
+impl Query_Shape {
+    fn to_collider(&self) -> Collider {
+        Collider {
+            shape: self.shape.clone(),
+            position: self.position,
+            rotation: self.rotation,
+            ..Default::default()
+        }
+    }
+}
+
+/// Returns the distinct neighbours of the box centered in `pos`.
+fn get_candidates<T_Spatial_Accelerator>(
+    accelerator: &T_Spatial_Accelerator,
+    pos: Vec2f,
+    extent: Vec2f,
+) -> Vec<Collider_Handle>
+where
+    T_Spatial_Accelerator: Spatial_Accelerator<Collider_Handle>,
+{
+    let mut candidates = vec![];
+    accelerator.get_neighbours(pos, extent, &mut candidates);
+    // A collider may be in more than one chunk.
+    candidates.sort_unstable_by_key(|handle| (handle.index, handle.gen));
+    candidates.dedup();
+    candidates
+}
+
+impl Physics_World {
+    /// Returns the first collider hit by the ray starting at `origin` and going along `dir`
+    /// (which must be normalized) for at most `max_dist`.
+    /// Colliders that contain `origin` are not hit.
+    pub fn raycast<T_Spatial_Accelerator>(
+        &self,
+        accelerator: &T_Spatial_Accelerator,
+        origin: Vec2f,
+        dir: Vec2f,
+        max_dist: f32,
+        filter: &Query_Filter,
+    ) -> Option<Query_Hit>
+    where
+        T_Spatial_Accelerator: Spatial_Accelerator<Collider_Handle>,
+    {
+        trace!("physics::raycast");
+
+        let query = Query_Shape {
+            shape: Collision_Shape::Circle { radius: 0. },
+            position: origin,
+            rotation: rad(0.),
+        };
+        self.shapecast(accelerator, &query, dir, max_dist, filter)
+    }
+
+    /// Like raycast, but moves the whole `query` shape along `dir`.
+    /// Colliders that already overlap the shape at its starting position are not hit.
+    pub fn shapecast<T_Spatial_Accelerator>(
+        &self,
+        accelerator: &T_Spatial_Accelerator,
+        query: &Query_Shape,
+        dir: Vec2f,
+        max_dist: f32,
+        filter: &Query_Filter,
+    ) -> Option<Query_Hit>
+    where
+        T_Spatial_Accelerator: Spatial_Accelerator<Collider_Handle>,
+    {
+        trace!("physics::shapecast");
+
+        debug_assert!(dir.is_normalized(), "{}", dir.magnitude());
+
+        let motion = dir * max_dist;
+        let (query_points, query_radius) = hull_points(&query.to_collider());
+        let candidates = get_candidates(
+            accelerator,
+            query.position + motion * 0.5,
+            query.shape.extent() + v2!(motion.x.abs(), motion.y.abs()),
+        );
+
+        let mut scratch = Cast_Scratch::default();
+        let mut closest: Option<(f32, Vec2f, &Collider)> = None;
+        for handle in candidates {
+            let cld = self.get_collider(handle).unwrap();
+            if !filter.accepts(cld) {
+                continue;
+            }
+
+            if let Some((t, normal)) = cast(&query_points, query_radius, motion, cld, &mut scratch)
+            {
+                match closest {
+                    Some((closest_t, ..)) if closest_t <= t => {}
+                    _ => closest = Some((t, normal, cld)),
+                }
+            }
+        }
+
+        closest.map(|(t, normal, cld)| {
+            let moved_points = query_points
+                .iter()
+                .map(|&p| p + motion * t)
+                .collect::<SmallVec<[Vec2f; 8]>>();
+            Query_Hit {
+                collider: cld.handle,
+                entity: cld.entity,
+                point: support_point(&moved_points, query_radius, -normal),
+                normal,
+                distance: t * max_dist,
+            }
+        })
+    }
+
+    /// Adds to `result` all the colliders containing `point`.
+    pub fn overlap_point<T_Spatial_Accelerator, R>(
+        &self,
+        accelerator: &T_Spatial_Accelerator,
+        point: Vec2f,
+        filter: &Query_Filter,
+        result: &mut R,
+    ) where
+        T_Spatial_Accelerator: Spatial_Accelerator<Collider_Handle>,
+        R: Extend<Query_Hit>,
+    {
+        trace!("physics::overlap_point");
+
+        let query = Query_Shape {
+            shape: Collision_Shape::Circle { radius: 0. },
+            position: point,
+            rotation: rad(0.),
+        };
+        self.overlap_shape(accelerator, &query, filter, result);
+    }
+
+    /// Adds to `result` all the colliders overlapping `query`.
+    pub fn overlap_shape<T_Spatial_Accelerator, R>(
+        &self,
+        accelerator: &T_Spatial_Accelerator,
+        query: &Query_Shape,
+        filter: &Query_Filter,
+        result: &mut R,
+    ) where
+        T_Spatial_Accelerator: Spatial_Accelerator<Collider_Handle>,
+        R: Extend<Query_Hit>,
+    {
+        trace!("physics::overlap_shape");
+
+        let query_cld = query.to_collider();
+        let (query_points, query_radius) = hull_points(&query_cld);
+        let candidates = get_candidates(accelerator, query.position, query.shape.extent());
+
+        result.extend(candidates.into_iter().filter_map(|handle| {
+            let cld = self.get_collider(handle).unwrap();
+            if !filter.accepts(cld) {
+                return None;
+            }
+
+            detect_collision(&query_cld, cld).map(|info| Query_Hit {
+                collider: cld.handle,
+                entity: cld.entity,
+                point: support_point(&query_points, query_radius, info.normal),
+                normal: -info.normal,
+                distance: info.penetration,
+            })
+        }));
+    }
+}
+
+/// Every shape can be seen as the convex hull of some points, inflated by a radius.
+fn hull_points(cld: &Collider) -> (SmallVec<[Vec2f; 8]>, f32) {
+    if let Some(vertices) = cld.world_polygon() {
+        (vertices, 0.)
+    } else if let Some((s1, s2, radius)) = cld.world_rounded() {
+        let mut points = SmallVec::new();
+        points.push(s1);
+        if s2 != s1 {
+            points.push(s2);
+        }
+        (points, radius)
+    } else {
+        fatal!("Unsupported shape {:?}", cld.shape)
+    }
+}
+
+/// Returns the point of the shape furthest along `dir`.
+fn support_point(points: &[Vec2f], radius: f32, dir: Vec2f) -> Vec2f {
+    let furthest = points
+        .iter()
+        .copied()
+        .fold(None, |best: Option<Vec2f>, p| match best {
+            Some(b) if b.dot(dir) >= p.dot(dir) => Some(b),
+            _ => Some(p),
+        })
+        .unwrap();
+    furthest + dir * radius
+}
+
+/// The buffers used by `cast`, so we don't allocate them again for each candidate.
+#[derive(Default)]
+struct Cast_Scratch {
+    diff: Vec<Vec2f>,
+    hull: Vec<Vec2f>,
+}
+
+/// Returns the time of impact (in [0, 1]) of the shape described by `points` and `radius`
+/// moving by `motion` against `cld`, along with the normal of `cld` at the impact point.
+fn cast(
+    points: &[Vec2f],
+    radius: f32,
+    motion: Vec2f,
+    cld: &Collider,
+    scratch: &mut Cast_Scratch,
+) -> Option<(f32, Vec2f)> {
+    let (cld_points, cld_radius) = hull_points(cld);
+
+    // The shapes touch when `motion * t` reaches their Minkowski difference.
+    scratch.diff.clear();
+    scratch.diff.extend(
+        cld_points
+            .iter()
+            .flat_map(|&b| points.iter().map(move |&a| b - a)),
+    );
+    convex_hull(&mut scratch.diff, &mut scratch.hull);
+
+    raycast_rounded_hull(v2!(0., 0.), motion, &scratch.hull, radius + cld_radius)
+}
+
+fn cross(o: Vec2f, a: Vec2f, b: Vec2f) -> f32 {
+    (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+}
+
+/// Writes into `hull` the convex hull of `points` in counter-clockwise order (with y pointing up).
+/// `points` gets sorted in the process.
+// reference: Andrew's monotone chain algorithm
+fn convex_hull(points: &mut Vec<Vec2f>, hull: &mut Vec<Vec2f>) {
+    points.sort_unstable_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    points.dedup();
+    hull.clear();
+    if points.len() < 3 {
+        hull.extend_from_slice(points);
+        return;
+    }
+
+    // Lower half
+    for &p in points.iter() {
+        while hull.len() >= 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0. {
+            hull.pop();
+        }
+        hull.push(p);
+    }
+    // Upper half: its first point is the last of the lower one, which we must not pop.
+    let lower_len = hull.len() + 1;
+    for &p in points.iter().rev().skip(1) {
+        while hull.len() >= lower_len && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.
+        {
+            hull.pop();
+        }
+        hull.push(p);
+    }
+    // The last point of the upper half is the first of the lower one.
+    hull.pop();
+}
+
+/// Returns the segments from each vertex of `hull` to the next one, along with their outward normal.
+fn hull_edges(hull: &[Vec2f]) -> impl Iterator<Item = (Vec2f, Vec2f, Vec2f)> + '_ {
+    (0..hull.len()).filter_map(move |i| {
+        let from = hull[i];
+        let to = hull[(i + 1) % hull.len()];
+        let edge = to - from;
+        let normal = v2!(edge.y, -edge.x).normalized_or_zero();
+        if normal.magnitude2() > 0. {
+            Some((from, to, normal))
+        } else {
+            None
+        }
+    })
+}
+
+/// Returns the entry time (in [0, 1]) and normal of the ray `origin + motion * t` against the
+/// convex polygon `hull` (in counter-clockwise order), or None if the ray misses it or starts inside it.
+// reference: Cyrus-Beck clipping
+fn raycast_polygon(origin: Vec2f, motion: Vec2f, hull: &[Vec2f]) -> Option<(f32, Vec2f)> {
+    let mut t_enter = 0.;
+    let mut t_exit = 1.;
+    let mut enter_normal = None;
+
+    for (from, _, normal) in hull_edges(hull) {
+        let dist = normal.dot(from - origin);
+        let speed = normal.dot(motion);
+        if speed.abs() <= f32::EPSILON {
+            if dist < 0. {
+                // Parallel to this edge and outside of it
+                return None;
+            }
+            continue;
+        }
+
+        let t = dist / speed;
+        if speed < 0. {
+            if t > t_enter {
+                t_enter = t;
+                enter_normal = Some(normal);
+            }
+        } else {
+            t_exit = t.min(t_exit);
+        }
+        if t_enter > t_exit {
+            return None;
+        }
+    }
+
+    // If we never crossed an edge inwards, we started inside the polygon.
+    enter_normal.map(|normal| (t_enter, normal))
+}
+
+fn raycast_circle(
+    origin: Vec2f,
+    motion: Vec2f,
+    center: Vec2f,
+    radius: f32,
+) -> Option<(f32, Vec2f)> {
+    let rel = origin - center;
+    let c = rel.magnitude2() - radius * radius;
+    let b = rel.dot(motion);
+    if c <= 0. || b >= 0. {
+        return None;
+    }
+
+    let a = motion.magnitude2();
+    let discr = b * b - a * c;
+    if discr < 0. {
+        return None;
+    }
+
+    let t = (-b - discr.sqrt()) / a;
+    if t > 1. {
+        return None;
+    }
+
+    Some((t, (rel + motion * t) / radius))
+}
+
+fn distance2_point_segment(p: Vec2f, from: Vec2f, to: Vec2f) -> f32 {
+    let seg = to - from;
+    let len2 = seg.magnitude2();
+    let t = if len2 > 0. {
+        clamp((p - from).dot(seg) / len2, 0., 1.)
+    } else {
+        0.
+    };
+    (p - (from + seg * t)).magnitude2()
+}
+
+fn rounded_hull_contains(hull: &[Vec2f], radius: f32, p: Vec2f) -> bool {
+    if hull.len() >= 3 && hull_edges(hull).all(|(from, _, normal)| normal.dot(p - from) <= 0.) {
+        return true;
+    }
+    let r2 = radius * radius;
+    match hull.len() {
+        0 => false,
+        1 => (p - hull[0]).magnitude2() < r2,
+        _ => (0..hull.len())
+            .any(|i| distance2_point_segment(p, hull[i], hull[(i + 1) % hull.len()]) < r2),
+    }
+}
+
+/// Like raycast_polygon, but against `hull` inflated by `radius`.
+fn raycast_rounded_hull(
+    origin: Vec2f,
+    motion: Vec2f,
+    hull: &[Vec2f],
+    radius: f32,
+) -> Option<(f32, Vec2f)> {
+    if motion.magnitude2() <= f32::EPSILON || rounded_hull_contains(hull, radius, origin) {
+        return None;
+    }
+
+    // The inflated hull is the union of the hull itself, a circle on each vertex and a rect on
+    // each edge: the first of them we enter is where we enter the inflated hull.
+    let mut hits: SmallVec<[(f32, Vec2f); 16]> = SmallVec::new();
+    if hull.len() >= 3 {
+        hits.extend(raycast_polygon(origin, motion, hull));
+    }
+    if radius > 0. {
+        hits.extend(
+            hull.iter()
+                .filter_map(|&v| raycast_circle(origin, motion, v, radius)),
+        );
+        if hull.len() >= 2 {
+            let edges = if hull.len() == 2 {
+                // A segment: hull_edges would give us both of its sides.
+                hull_edges(hull).take(1).collect::<SmallVec<[_; 1]>>()
+            } else {
+                hull_edges(hull).collect()
+            };
+            for (from, to, normal) in edges {
+                let offset = normal * radius;
+                let mut rect = [from - offset, to - offset, to + offset, from + offset];
+                // raycast_polygon wants it counter-clockwise.
+                if cross(rect[0], rect[1], rect[2]) < 0. {
+                    rect.reverse();
+                }
+                hits.extend(raycast_polygon(origin, motion, &rect));
+            }
+        }
+    }
+
+    hits.into_iter()
+        .fold(None, |best: Option<(f32, Vec2f)>, hit| match best {
+            Some(b) if b.0 <= hit.0 => Some(b),
+            _ => Some(hit),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::phys_world::Phys_Data;
+    use inle_math::angle::deg;
+
+    const EPS: f32 = 0.001;
+
+    struct All_Colliders(Vec<Collider_Handle>);
+
+    impl Spatial_Accelerator<Collider_Handle> for All_Colliders {
+        fn get_neighbours<R>(&self, _pos: Vec2f, _extent: Vec2f, result: &mut R)
+        where
+            R: Extend<Collider_Handle>,
+        {
+            // Return everything twice, like a collider spanning multiple chunks would.
+            result.extend(self.0.iter().copied());
+            result.extend(self.0.iter().copied());
+        }
+    }
+
+    fn assert_near(a: Vec2f, b: Vec2f) {
+        assert!((a - b).magnitude() < EPS, "{:?} != {:?}", a, b);
+    }
+
+    struct Test_World {
+        phys_world: Physics_World,
+        accelerator: All_Colliders,
+        collision_matrix: Collision_Matrix,
+        ground: Entity,
+        ball: Entity,
+        ghost: Entity,
+        player: Entity,
+    }
+
+    const LAYER_SOLID: Collision_Layer = 0;
+    const LAYER_GHOST: Collision_Layer = 1;
+
+    // A flat ground at y = 0, a ball with radius 1 at (5, -1) and a rotated box (on a
+    // layer that doesn't collide with solid things) at (-5, -1).
+    fn test_world() -> Test_World {
+        let mut phys_world = Physics_World::new();
+        let mut collision_matrix = Collision_Matrix::default();
+        collision_matrix.set_layers_collide(LAYER_SOLID, LAYER_SOLID);
+
+        let mut add = |shape, position, rotation, layer, index| {
+            let entity = Entity {
+                index,
+                ..Default::default()
+            };
+            let cld = Collider {
+                shape,
+                position,
+                rotation,
+                layer,
+                ..Default::default()
+            };
+            phys_world.new_physics_body_with_rigidbody(cld, entity, Phys_Data::default());
+            entity
+        };
+
+        let ground = add(
+            Collision_Shape::Rect {
+                width: 100.,
+                height: 2.,
+            },
+            v2!(0., 1.),
+            deg(0.),
+            LAYER_SOLID,
+            1,
+        );
+        let ball = add(
+            Collision_Shape::Circle { radius: 1. },
+            v2!(5., -1.),
+            deg(0.),
+            LAYER_SOLID,
+            2,
+        );
+        let ghost = add(
+            Collision_Shape::Oriented_Rect {
+                width: 1.,
+                height: 1.,
+            },
+            v2!(-5., -1.),
+            deg(45.),
+            LAYER_GHOST,
+            3,
+        );
+        let player = add(
+            Collision_Shape::Capsule {
+                radius: 0.5,
+                half_length: 0.5,
+            },
+            v2!(0., -10.),
+            deg(0.),
+            LAYER_SOLID,
+            4,
+        );
+
+        let accelerator =
+            All_Colliders(phys_world.colliders.iter().map(|cld| cld.handle).collect());
+
+        Test_World {
+            phys_world,
+            accelerator,
+            collision_matrix,
+            ground,
+            ball,
+            ghost,
+            player,
+        }
+    }
+
+    #[test]
+    fn raycast() {
+        let world = test_world();
+        let filter = Query_Filter {
+            layer: LAYER_SOLID,
+            collision_matrix: &world.collision_matrix,
+            ignored_entity: Some(world.player),
+        };
+
+        // Straight down from the player
+        let hit = world
+            .phys_world
+            .raycast(&world.accelerator, v2!(0., -10.), v2!(0., 1.), 20., &filter)
+            .unwrap();
+        assert_eq!(hit.entity, world.ground);
+        assert_near(hit.point, v2!(0., 0.));
+        assert_near(hit.normal, v2!(0., -1.));
+        assert!((hit.distance - 10.).abs() < EPS, "{}", hit.distance);
+
+        // Too short
+        assert!(world
+            .phys_world
+            .raycast(&world.accelerator, v2!(0., -10.), v2!(0., 1.), 5., &filter)
+            .is_none());
+
+        // Hitting the ball from the left
+        let hit = world
+            .phys_world
+            .raycast(&world.accelerator, v2!(0., -1.), v2!(1., 0.), 20., &filter)
+            .unwrap();
+        assert_eq!(hit.entity, world.ball);
+        assert_near(hit.point, v2!(4., -1.));
+        assert_near(hit.normal, v2!(-1., 0.));
+        assert!((hit.distance - 4.).abs() < EPS, "{}", hit.distance);
+
+        // The ghost is on a layer that doesn't collide with ours.
+        assert!(world
+            .phys_world
+            .raycast(&world.accelerator, v2!(0., -1.), v2!(-1., 0.), 20., &filter)
+            .is_none());
+
+        // Without ignoring the player, we hit it (but not if we start inside it).
+        let filter = Query_Filter {
+            ignored_entity: None,
+            ..filter
+        };
+        let hit = world
+            .phys_world
+            .raycast(&world.accelerator, v2!(0., -20.), v2!(0., 1.), 20., &filter)
+            .unwrap();
+        assert_eq!(hit.entity, world.player);
+        assert!((hit.distance - 9.).abs() < EPS, "{}", hit.distance);
+        let hit = world
+            .phys_world
+            .raycast(&world.accelerator, v2!(0., -10.), v2!(0., 1.), 20., &filter)
+            .unwrap();
+        assert_eq!(hit.entity, world.ground);
+    }
+
+    #[test]
+    fn shapecast() {
+        let world = test_world();
+        let filter = Query_Filter {
+            layer: LAYER_SOLID,
+            collision_matrix: &world.collision_matrix,
+            ignored_entity: Some(world.player),
+        };
+
+        // A box falling on the ball
+        let query = Query_Shape {
+            shape: Collision_Shape::Rect {
+                width: 2.,
+                height: 2.,
+            },
+            position: v2!(5.5, -10.),
+            rotation: deg(0.),
+        };
+        let hit = world
+            .phys_world
+            .shapecast(&world.accelerator, &query, v2!(0., 1.), 20., &filter)
+            .unwrap();
+        assert_eq!(hit.entity, world.ball);
+        assert_near(hit.normal, v2!(0., -1.));
+        // The box's bottom side touches the top of the ball.
+        assert!((hit.distance - 7.).abs() < EPS, "{}", hit.distance);
+        assert!((hit.point.y + 2.).abs() < EPS, "{:?}", hit.point);
+
+        // A circle falling past the ball, onto the ground
+        let query = Query_Shape {
+            shape: Collision_Shape::Circle { radius: 0.5 },
+            position: v2!(7., -10.),
+            rotation: deg(0.),
+        };
+        let hit = world
+            .phys_world
+            .shapecast(&world.accelerator, &query, v2!(0., 1.), 20., &filter)
+            .unwrap();
+        assert_eq!(hit.entity, world.ground);
+        assert!((hit.distance - 9.5).abs() < EPS, "{}", hit.distance);
+        assert_near(hit.point, v2!(7., 0.));
+
+        // A tilted capsule sliding along the ground towards the ball
+        let query = Query_Shape {
+            shape: Collision_Shape::Capsule {
+                radius: 0.5,
+                half_length: 1.,
+            },
+            position: v2!(0., -1.5),
+            rotation: deg(90.),
+        };
+        let hit = world
+            .phys_world
+            .shapecast(&world.accelerator, &query, v2!(1., 0.), 20., &filter)
+            .unwrap();
+        assert_eq!(hit.entity, world.ball);
+        // Its right end touches the ball
+        assert_near(hit.normal, v2!(-(2f32.sqrt()), -0.5).normalized());
+        assert!(
+            (hit.distance - (4. - 2f32.sqrt())).abs() < EPS,
+            "{}",
+            hit.distance
+        );
+    }
+
+    #[test]
+    fn overlaps() {
+        let world = test_world();
+        let filter = Query_Filter {
+            layer: LAYER_SOLID,
+            collision_matrix: &world.collision_matrix,
+            ignored_entity: None,
+        };
+
+        let mut hits = vec![];
+        world
+            .phys_world
+            .overlap_point(&world.accelerator, v2!(5., -0.5), &filter, &mut hits);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].entity, world.ball);
+
+        hits.clear();
+        world
+            .phys_world
+            .overlap_point(&world.accelerator, v2!(-5., -1.), &filter, &mut hits);
+        assert!(hits.is_empty());
+
+        let ghost_filter = Query_Filter {
+            layer: LAYER_GHOST,
+            ..filter
+        };
+        let mut collision_matrix = Collision_Matrix::default();
+        collision_matrix.set_layers_collide(LAYER_GHOST, LAYER_GHOST);
+        let ghost_filter = Query_Filter {
+            collision_matrix: &collision_matrix,
+            ..ghost_filter
+        };
+        world
+            .phys_world
+            .overlap_point(&world.accelerator, v2!(-5., -1.), &ghost_filter, &mut hits);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].entity, world.ghost);
+
+        // A box sinking into the ground, next to the ball
+        hits.clear();
+        let query = Query_Shape {
+            shape: Collision_Shape::Oriented_Rect {
+                width: 2.,
+                height: 2.,
+            },
+            position: v2!(4., -0.75),
+            rotation: deg(0.),
+        };
+        world
+            .phys_world
+            .overlap_shape(&world.accelerator, &query, &filter, &mut hits);
+        hits.sort_by_key(|hit| hit.entity.index);
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].entity, world.ground);
+        assert_near(hits[0].normal, v2!(0., -1.));
+        assert!(
+            (hits[0].distance - 0.25).abs() < EPS,
+            "{}",
+            hits[0].distance
+        );
+        assert!((hits[0].point.y - 0.25).abs() < EPS, "{:?}", hits[0].point);
+        assert_eq!(hits[1].entity, world.ball);
+        assert_near(hits[1].normal, v2!(-1., 0.));
+        assert!((hits[1].distance - 1.).abs() < EPS, "{}", hits[1].distance);
+    }
+
+    #[test]
+    fn convex_hull_skips_inner_duplicate_and_collinear_points() {
+        let mut points = vec![
+            v2!(1., 1.),
+            v2!(0., 0.),
+            v2!(2., 0.),
+            v2!(1., 0.),
+            v2!(2., 2.),
+            v2!(0., 2.),
+            v2!(2., 0.),
+        ];
+        let mut hull = vec![];
+        convex_hull(&mut points, &mut hull);
+        assert_eq!(
+            hull,
+            vec![v2!(0., 0.), v2!(2., 0.), v2!(2., 2.), v2!(0., 2.)]
+        );
+    }
+}
@@ -1,6 +1,9 @@
-use super::recording_thread;
-use super::replay_data::{Replay_Data_Point, Replay_Joystick_Data};
+use super::recording_thread::{self, Recording_Thread_Msg};
+use super::replay_data::{
+    Replay_Data_Point, Replay_Header, Replay_Joystick_Data, Replay_Record, Replay_State_Checksum,
+};
 use inle_cfg::{self, Cfg_Var};
+use inle_common::stringid::String_Id;
 use inle_common::Maybe_Error;
 use inle_core::env::Env_Info;
 use inle_core::rand::Default_Rng_Seed;
@@ -14,14 +17,18 @@ use std::thread::JoinHandle;
 pub struct Replay_Recording_System_Config {
     pub ms_per_frame: f32,
     pub rng_seed: Default_Rng_Seed,
+    /// A checksum of the game state is recorded every this many frames. 0 disables checksums.
+    pub state_checksum_interval: u64,
 }
 
 pub struct Replay_Recording_System {
     config: Replay_Recording_System_Config,
-    data_tx: Sender<Replay_Data_Point>,
-    data_rx: Option<Receiver<Replay_Data_Point>>,
+    data_tx: Option<Sender<Recording_Thread_Msg>>,
+    data_rx: Option<Receiver<Recording_Thread_Msg>>,
     recording_thread_handle: Option<JoinHandle<()>>,
     prev_axes_values: [Real_Axes_Values; joystick::JOY_COUNT as usize],
+    /// How many frames we recorded so far, including those without any input.
+    n_frames: u64,
 }
 
 impl Replay_Recording_System {
@@ -30,16 +37,19 @@ impl Replay_Recording_System {
         Replay_Recording_System {
             config: cfg,
             data_rx: Some(data_rx),
-            data_tx,
+            data_tx: Some(data_tx),
             recording_thread_handle: None,
             prev_axes_values: std::default::Default::default(),
+            n_frames: 0,
         }
     }
 
+    /// `level_id` is the level being played, which gets saved in the replay header.
     pub fn start_recording_thread(
         &mut self,
         env: &Env_Info,
         cfg: &inle_cfg::Config,
+        level_id: String_Id,
     ) -> Maybe_Error {
         let data_rx = self
             .data_rx
@@ -56,7 +66,13 @@ impl Replay_Recording_System {
         linfo!("Recording input to {:?}", output_file_path);
 
         let rec_cfg = recording_thread::Recording_Thread_Config {
-            recording_cfg: self.config,
+            header: Replay_Header {
+                ms_per_frame: self.config.ms_per_frame,
+                seed: self.config.rng_seed,
+                level_id: Some(level_id),
+                config_hash: Some(cfg.content_hash()),
+                ..Default::default()
+            },
             output_file: output_file_path.into_boxed_path(),
             file_write_interval: std::time::Duration::from_millis(
                 (file_write_interval_secs.read(cfg) * 1000.0) as u64,
@@ -72,11 +88,51 @@ impl Replay_Recording_System {
     }
 
     pub fn is_recording(&self) -> bool {
-        self.data_rx.is_none()
+        self.data_rx.is_none() && self.data_tx.is_some()
+    }
+
+    /// Flushes the recorded data and finalizes the replay file. Must be called before quitting,
+    /// or the replay will lack its trailer.
+    pub fn stop_recording(&mut self) {
+        if let Some(data_tx) = self.data_tx.take() {
+            // The recording thread can't know about the trailing frames without input otherwise.
+            let _ = data_tx.send(Recording_Thread_Msg::Stop {
+                n_frames: self.n_frames,
+            });
+        }
+        if let Some(handle) = self.recording_thread_handle.take() {
+            if handle.join().is_err() {
+                lerr!("Recording thread panicked: the replay file may be incomplete.");
+            }
+        }
+    }
+
+    pub fn should_record_state_checksum(&self, cur_frame: u64) -> bool {
+        let interval = self.config.state_checksum_interval;
+        self.is_recording() && interval > 0 && cur_frame.is_multiple_of(interval)
+    }
+
+    pub fn record_state_checksum(&mut self, checksum: Replay_State_Checksum) {
+        if let Some(data_tx) = &self.data_tx {
+            data_tx
+                .send(Recording_Thread_Msg::Record(Replay_Record::State_Checksum(
+                    checksum,
+                )))
+                .unwrap_or_else(|err| {
+                    panic!("Failed to send state checksum to replay thread: {}", err)
+                });
+        }
     }
 
     /// Note: joy_mask tells which values of `axes` must be considered.
     pub fn update(&mut self, input_raw_state: &Input_Raw_State, cur_frame: u64) {
+        if self.data_tx.is_none() {
+            // Recording was stopped.
+            return;
+        }
+
+        self.n_frames = self.n_frames.max(cur_frame + 1);
+
         let mut should_send = !input_raw_state.events.is_empty();
         let mut joy_data: [Replay_Joystick_Data; joystick::JOY_COUNT as usize] =
             std::default::Default::default();
@@ -97,12 +153,11 @@ impl Replay_Recording_System {
 
         if should_send {
             self.data_tx
-                .send(Replay_Data_Point::new(
-                    cur_frame,
-                    &input_raw_state.events,
-                    &joy_data,
-                    joy_mask,
-                ))
+                .as_ref()
+                .unwrap()
+                .send(Recording_Thread_Msg::Record(Replay_Record::Input(
+                    Replay_Data_Point::new(cur_frame, &input_raw_state.events, &joy_data, joy_mask),
+                )))
                 .unwrap_or_else(|err| {
                     panic!("Failed to send game actions to replay thread: {}", err)
                 });
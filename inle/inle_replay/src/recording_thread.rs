@@ -1,22 +1,34 @@
-use super::recording_system::Replay_Recording_System_Config;
-use super::replay_data::Replay_Data_Point;
+use super::replay_data::{Replay_Header, Replay_Record, N_FRAMES_OFFSET};
+use inle_common::hash::{fnv1a_64_update, FNV1A_64_OFFSET_BASIS};
 use inle_common::Maybe_Error;
 use inle_serialize::{Binary_Serializable, Byte_Stream};
 use std::fs::File;
-use std::io::prelude::*;
+use std::io::{prelude::*, SeekFrom};
 use std::path::Path;
 use std::sync::mpsc::{Receiver, RecvTimeoutError};
 use std::thread;
 use std::time::Duration;
 
+/// What the recording thread receives from the recording system.
+// Stop is only sent once, so it's not worth boxing the records to shrink it.
+#[allow(clippy::large_enum_variant)]
+pub enum Recording_Thread_Msg {
+    Record(Replay_Record),
+    /// Sent when the recording is stopped. `n_frames` counts all the recorded frames,
+    /// including the trailing ones that didn't produce any record.
+    Stop {
+        n_frames: u64,
+    },
+}
+
 pub struct Recording_Thread_Config {
     pub output_file: Box<Path>,
     pub file_write_interval: Duration,
-    pub recording_cfg: Replay_Recording_System_Config,
+    pub header: Replay_Header,
 }
 
 pub fn start_recording_thread(
-    recv: Receiver<Replay_Data_Point>,
+    recv: Receiver<Recording_Thread_Msg>,
     cfg: Recording_Thread_Config,
 ) -> std::io::Result<thread::JoinHandle<()>> {
     thread::Builder::new()
@@ -24,11 +36,16 @@ pub fn start_recording_thread(
         .spawn(move || recording_loop(recv, cfg).unwrap())
 }
 
-fn recording_loop(recv: Receiver<Replay_Data_Point>, cfg: Recording_Thread_Config) -> Maybe_Error {
+fn recording_loop(
+    recv: Receiver<Recording_Thread_Msg>,
+    cfg: Recording_Thread_Config,
+) -> Maybe_Error {
     let mut file = File::create(&cfg.output_file)?;
-    write_prelude(&mut file, cfg.recording_cfg)?;
+    write_prelude(&mut file, &cfg.header)?;
 
     let mut replay_data_buffer = vec![];
+    let mut checksum = FNV1A_64_OFFSET_BASIS;
+    let mut n_frames = 0;
     let mut timeout = cfg.file_write_interval;
     loop {
         let start_t = std::time::Instant::now();
@@ -36,34 +53,166 @@ fn recording_loop(recv: Receiver<Replay_Data_Point>, cfg: Recording_Thread_Confi
         // Blocking call with variable timeout so we don't stress the CPU but we still
         // get to write to file regularly with our chosen time interval.
         match recv.recv_timeout(timeout) {
-            Ok(point) => {
-                replay_data_buffer.push(point);
+            Ok(Recording_Thread_Msg::Record(record)) => {
+                n_frames = n_frames.max(record.frame_number() + 1);
+                replay_data_buffer.push(record);
                 timeout = timeout.checked_sub(start_t.elapsed()).unwrap_or_default();
             }
+            Ok(Recording_Thread_Msg::Stop {
+                n_frames: n_recorded_frames,
+            }) => {
+                checksum = write_record_data(&mut file, &replay_data_buffer, checksum)?;
+                break write_epilogue(&mut file, n_frames.max(n_recorded_frames), checksum);
+            }
             Err(RecvTimeoutError::Timeout) => {
-                write_record_data(&mut file, &replay_data_buffer)?;
+                checksum = write_record_data(&mut file, &replay_data_buffer, checksum)?;
                 replay_data_buffer.clear();
                 timeout = cfg.file_write_interval;
             }
-            Err(RecvTimeoutError::Disconnected) => break Ok(()),
+            Err(RecvTimeoutError::Disconnected) => {
+                // We weren't told how many frames were recorded, so we only know about
+                // those up to the last record.
+                checksum = write_record_data(&mut file, &replay_data_buffer, checksum)?;
+                break write_epilogue(&mut file, n_frames, checksum);
+            }
         }
     }
 }
 
-fn write_prelude(
-    file: &mut File,
-    recording_cfg: Replay_Recording_System_Config,
-) -> std::io::Result<()> {
+fn write_prelude(file: &mut File, header: &Replay_Header) -> std::io::Result<()> {
     let mut byte_stream = Byte_Stream::new();
-    byte_stream.write_f32(recording_cfg.ms_per_frame)?;
-    recording_cfg.rng_seed.serialize(&mut byte_stream)?;
+    header.serialize(&mut byte_stream)?;
     file.write_all(byte_stream.as_ref())
 }
 
-fn write_record_data(file: &mut File, data: &[Replay_Data_Point]) -> std::io::Result<()> {
+/// Returns the checksum updated with the written data.
+fn write_record_data(
+    file: &mut File,
+    data: &[Replay_Record],
+    checksum: u64,
+) -> std::io::Result<u64> {
     let mut byte_stream = Byte_Stream::new();
-    for point in data.iter() {
-        point.serialize(&mut byte_stream)?;
+    for record in data.iter() {
+        record.serialize(&mut byte_stream)?;
+    }
+    file.write_all(byte_stream.as_ref())?;
+    Ok(fnv1a_64_update(checksum, byte_stream.as_ref()))
+}
+
+/// Writes the trailer and patches the frame count in the header, marking the replay as finalized.
+fn write_epilogue(file: &mut File, n_frames: u64, checksum: u64) -> Maybe_Error {
+    let mut byte_stream = Byte_Stream::new();
+    byte_stream.write_u64(checksum)?;
+    file.write_all(byte_stream.as_ref())?;
+
+    let mut byte_stream = Byte_Stream::new();
+    byte_stream.write_u64(n_frames)?;
+    file.seek(SeekFrom::Start(N_FRAMES_OFFSET))?;
+    file.write_all(byte_stream.as_ref())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay_data::{Replay_Data, Replay_Data_Point, Replay_State_Checksum};
+    use inle_common::stringid::String_Id;
+    use std::sync::mpsc;
+
+    #[test]
+    fn recorded_replay_is_finalized() {
+        let output_file =
+            std::env::temp_dir().join(format!("inle_replay_test_{}.replay", std::process::id()));
+        let header = Replay_Header {
+            ms_per_frame: 16.67,
+            ..Default::default()
+        };
+
+        let (data_tx, data_rx) = mpsc::channel();
+        let handle = start_recording_thread(
+            data_rx,
+            Recording_Thread_Config {
+                output_file: output_file.clone().into_boxed_path(),
+                file_write_interval: Duration::from_millis(1),
+                header: header.clone(),
+            },
+        )
+        .unwrap();
+
+        let data_points = (0..10)
+            .map(|i| Replay_Data_Point {
+                frame_number: i * 3,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let checksum = Replay_State_Checksum {
+            frame_number: 30,
+            hashes: vec![(String_Id::from_u32(1), 42)],
+        };
+        for point in &data_points {
+            data_tx
+                .send(Recording_Thread_Msg::Record(Replay_Record::Input(
+                    point.clone(),
+                )))
+                .unwrap();
+        }
+        data_tx
+            .send(Recording_Thread_Msg::Record(Replay_Record::State_Checksum(
+                checksum.clone(),
+            )))
+            .unwrap();
+        drop(data_tx);
+        handle.join().unwrap();
+
+        let replay = Replay_Data::from_file(&output_file);
+        let _ = std::fs::remove_file(&output_file);
+        let replay = replay.unwrap();
+
+        assert_eq!(replay.header.n_frames, Some(31));
+        assert_eq!(replay.header.ms_per_frame, header.ms_per_frame);
+        assert_eq!(replay.data, data_points);
+        assert_eq!(replay.checksums, vec![checksum]);
+    }
+
+    #[test]
+    fn recorded_replay_includes_idle_trailing_frames() {
+        let output_file = std::env::temp_dir().join(format!(
+            "inle_replay_test_idle_{}.replay",
+            std::process::id()
+        ));
+
+        let (data_tx, data_rx) = mpsc::channel();
+        let handle = start_recording_thread(
+            data_rx,
+            Recording_Thread_Config {
+                output_file: output_file.clone().into_boxed_path(),
+                file_write_interval: Duration::from_millis(1),
+                header: Replay_Header::default(),
+            },
+        )
+        .unwrap();
+
+        let point = Replay_Data_Point {
+            frame_number: 2,
+            ..Default::default()
+        };
+        data_tx
+            .send(Recording_Thread_Msg::Record(Replay_Record::Input(
+                point.clone(),
+            )))
+            .unwrap();
+        // Frames 3 to 9 had no input.
+        data_tx
+            .send(Recording_Thread_Msg::Stop { n_frames: 10 })
+            .unwrap();
+        handle.join().unwrap();
+
+        let replay = Replay_Data::from_file(&output_file);
+        let _ = std::fs::remove_file(&output_file);
+        let replay = replay.unwrap();
+
+        assert_eq!(replay.header.n_frames, Some(10));
+        assert_eq!(replay.data, vec![point]);
     }
-    file.write_all(byte_stream.as_ref())
 }
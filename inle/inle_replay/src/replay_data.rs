@@ -1,5 +1,5 @@
-// @Incomplete! We should save the rng seed somewhere, or the replay won't be deterministic.
-
+use inle_common::hash::fnv1a_64;
+use inle_common::stringid::String_Id;
 use inle_core::rand::Default_Rng_Seed;
 use inle_input::events::Input_Raw_Event;
 use inle_input::input_state::Input_Raw_State;
@@ -8,13 +8,146 @@ use inle_input::mouse::Mouse_State;
 use inle_input::serialize;
 use inle_serialize::{Binary_Serializable, Byte_Stream};
 use std::fs::File;
-use std::io::prelude::*;
+use std::io::{self, prelude::*};
 use std::path::Path;
 use std::time::Duration;
 
 const AXES_COUNT: usize = joystick::Joystick_Axis::_Count as usize;
 const JOY_COUNT: usize = joystick::JOY_COUNT as usize;
 
+// A replay file is laid out as:
+//   header (see Replay_Header)
+//   records (see Replay_Record) until the trailer
+//   trailer: FNV-1a checksum (u64) of the records bytes
+// Replays written before the format was versioned (version 0) only contain ms_per_frame and
+// the rng seed, followed by data points until EOF.
+// Version 1 replays contain untagged data points in place of the records.
+
+pub const REPLAY_MAGIC: [u8; 4] = *b"IRPL";
+
+/// Bumped whenever the replay layout changes. Older versions must keep being readable
+/// by migrating them in Replay_Header::deserialize.
+pub const REPLAY_FORMAT_VERSION: u16 = 2;
+
+/// Written in place of the frame count while the replay is still being recorded.
+/// If it's still there when loading, the recording was interrupted (e.g. the game crashed)
+/// and the file has no trailer.
+pub(crate) const N_FRAMES_UNFINISHED: u64 = u64::MAX;
+
+/// Position of the frame count inside the header, so the recording thread can patch it
+/// once the recording ends.
+pub(crate) const N_FRAMES_OFFSET: u64 = (REPLAY_MAGIC.len() + std::mem::size_of::<u16>()) as u64;
+
+const TRAILER_SIZE: usize = std::mem::size_of::<u64>();
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay_Header {
+    /// The version the replay was recorded with (it may have been migrated since).
+    pub version: u16,
+    /// The number of frames covered by the data points.
+    /// None if the recording was interrupted before it could be finalized.
+    pub n_frames: Option<u64>,
+    pub ms_per_frame: f32,
+    pub seed: Default_Rng_Seed,
+    /// The level that was loaded when the recording started. None for version 0 replays.
+    pub level_id: Option<String_Id>,
+    /// The Config::content_hash() at the time the recording started. None for version 0 replays.
+    pub config_hash: Option<u64>,
+}
+
+impl Default for Replay_Header {
+    fn default() -> Self {
+        Self {
+            version: REPLAY_FORMAT_VERSION,
+            n_frames: None,
+            ms_per_frame: 0.,
+            seed: Default_Rng_Seed::default(),
+            level_id: None,
+            config_hash: None,
+        }
+    }
+}
+
+impl Binary_Serializable for Replay_Header {
+    /// Always writes the header in the latest version.
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        output.write_bytes(&REPLAY_MAGIC)?;
+        output.write_u16(REPLAY_FORMAT_VERSION)?;
+        output.write_u64(self.n_frames.unwrap_or(N_FRAMES_UNFINISHED))?;
+        output.write_f32(self.ms_per_frame)?;
+        self.seed.serialize(output)?;
+        self.level_id
+            .unwrap_or_else(|| String_Id::from_u32(0))
+            .serialize(output)?;
+        output.write_u64(self.config_hash.unwrap_or(0))
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        let start = input.pos();
+        let mut magic = [0u8; 4];
+        if input.read_bytes(&mut magic).is_err() || magic != REPLAY_MAGIC {
+            input.seek(start);
+            return Self::deserialize_v0(input);
+        }
+
+        let version = input.read_u16()?;
+        match version {
+            1 | 2 => Self::deserialize_v1(input, version),
+            _ if version > REPLAY_FORMAT_VERSION => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "replay format version is {}, but this build only supports up to version {}.",
+                    version, REPLAY_FORMAT_VERSION
+                ),
+            )),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown replay format version {}.", version),
+            )),
+        }
+    }
+}
+
+impl Replay_Header {
+    fn deserialize_v0(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(Self {
+            version: 0,
+            n_frames: None,
+            ms_per_frame: input.read_f32()?,
+            seed: Default_Rng_Seed::deserialize(input)?,
+            level_id: None,
+            config_hash: None,
+        })
+    }
+
+    /// The header didn't change in version 2.
+    fn deserialize_v1(input: &mut Byte_Stream, version: u16) -> io::Result<Self> {
+        let n_frames = input.read_u64()?;
+        Ok(Self {
+            version,
+            n_frames: if n_frames == N_FRAMES_UNFINISHED {
+                None
+            } else {
+                Some(n_frames)
+            },
+            ms_per_frame: input.read_f32()?,
+            seed: Default_Rng_Seed::deserialize(input)?,
+            level_id: Some(String_Id::deserialize(input)?),
+            config_hash: Some(input.read_u64()?),
+        })
+    }
+
+    /// Whether the data points are followed by the checksum trailer.
+    fn has_trailer(&self) -> bool {
+        self.version >= 1 && self.n_frames.is_some()
+    }
+
+    /// Whether the data section contains tagged records rather than just data points.
+    fn has_tagged_records(&self) -> bool {
+        self.version >= 2
+    }
+}
+
 /// Contains the replay data for a single frame. It consists in time information (a frame number)
 /// plus the diff from the previous saved point.
 /// Note that raw events and real axes, rather than processed game actions or virtual axes, are saved.
@@ -165,37 +298,118 @@ impl Binary_Serializable for Replay_Data_Point {
     }
 }
 
+/// Hashes of parts of the game state at the end of a frame, recorded periodically so that the
+/// playback can detect when it diverges from the recording.
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct Replay_State_Checksum {
+    pub frame_number: u64,
+    /// Each hash is identified by the name of the state it was computed from
+    /// (e.g. a component storage).
+    pub hashes: Vec<(String_Id, u64)>,
+}
+
+impl Binary_Serializable for Replay_State_Checksum {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        output.write_u32(self.frame_number as u32)?;
+        output.write_u16(self.hashes.len() as u16)?;
+        for (name, hash) in &self.hashes {
+            name.serialize(output)?;
+            output.write_u64(*hash)?;
+        }
+        Ok(())
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        let frame_number = u64::from(input.read_u32()?);
+        let n_hashes = input.read_u16()?;
+        let mut hashes = Vec::with_capacity(n_hashes as usize);
+        for _ in 0..n_hashes {
+            hashes.push((String_Id::deserialize(input)?, input.read_u64()?));
+        }
+        Ok(Self {
+            frame_number,
+            hashes,
+        })
+    }
+}
+
+/// An entry of the replay data, as sent to the recording thread and saved to file.
+// Inputs are by far the most common records, so there's no point in boxing them.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Replay_Record {
+    Input(Replay_Data_Point),
+    State_Checksum(Replay_State_Checksum),
+}
+
+const RECORD_TAG_INPUT: u8 = 0;
+const RECORD_TAG_STATE_CHECKSUM: u8 = 1;
+
+impl Replay_Record {
+    pub fn frame_number(&self) -> u64 {
+        match self {
+            Replay_Record::Input(point) => point.frame_number,
+            Replay_Record::State_Checksum(checksum) => checksum.frame_number,
+        }
+    }
+}
+
+impl Binary_Serializable for Replay_Record {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        match self {
+            Replay_Record::Input(point) => {
+                output.write_u8(RECORD_TAG_INPUT)?;
+                point.serialize(output)
+            }
+            Replay_Record::State_Checksum(checksum) => {
+                output.write_u8(RECORD_TAG_STATE_CHECKSUM)?;
+                checksum.serialize(output)
+            }
+        }
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        match input.read_u8()? {
+            RECORD_TAG_INPUT => Ok(Replay_Record::Input(Replay_Data_Point::deserialize(input)?)),
+            RECORD_TAG_STATE_CHECKSUM => Ok(Replay_Record::State_Checksum(
+                Replay_State_Checksum::deserialize(input)?,
+            )),
+            tag => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid replay record tag {}.", tag),
+            )),
+        }
+    }
+}
+
 /// Replay_Data is used only for the playback. It loads serialized replay data from a file
 /// and provides an iterator to access all the recorded events.
 #[derive(Debug, Default)]
 pub struct Replay_Data {
+    pub header: Replay_Header,
     pub data: Vec<Replay_Data_Point>,
-    pub ms_per_frame: f32,
-    pub seed: Default_Rng_Seed,
+    /// Empty for replays recorded before version 2.
+    pub checksums: Vec<Replay_State_Checksum>,
     pub duration: Duration,
 }
 
 impl Replay_Data {
-    pub fn new(ms_per_frame: f32, seed: Default_Rng_Seed) -> Replay_Data {
+    pub fn new(header: Replay_Header) -> Replay_Data {
         Replay_Data {
+            header,
             data: vec![],
-            ms_per_frame,
-            seed,
+            checksums: vec![],
             duration: Duration::new(0, 0),
         }
     }
 
     #[cfg(test)]
-    pub fn new_from_data(
-        ms_per_frame: f32,
-        data: &[Replay_Data_Point],
-        seed: Default_Rng_Seed,
-    ) -> Replay_Data {
+    pub fn new_from_data(header: Replay_Header, data: &[Replay_Data_Point]) -> Replay_Data {
         let mut replay = Replay_Data {
+            header,
             data: data.to_vec(),
-            ms_per_frame,
+            checksums: vec![],
             duration: Duration::new(0, 0),
-            seed,
         };
         replay.duration = Self::calc_duration(&replay);
         replay
@@ -211,6 +425,20 @@ impl Replay_Data {
         let mut byte_stream = Byte_Stream::new_from_vec(buf);
         let replay = Self::deserialize(&mut byte_stream)?;
 
+        if replay.header.version < REPLAY_FORMAT_VERSION {
+            lwarn!(
+                "Replay {:?} was recorded with format version {} and was migrated to version {}.",
+                path,
+                replay.header.version,
+                REPLAY_FORMAT_VERSION
+            );
+        } else if replay.header.n_frames.is_none() {
+            lwarn!(
+                "Replay {:?} was not finalized (the recording was interrupted?): its checksum cannot be verified.",
+                path
+            );
+        }
+
         lok!(
             "Loaded replay data from {:?} in {} ms. Replay duration = {} s.",
             path,
@@ -227,21 +455,77 @@ impl Replay_Data {
             Duration::new(0, 0)
         } else {
             let last_frame_number = replay.data[replay.data.len() - 1].frame_number;
-            Duration::from_secs_f32(last_frame_number as f32 * replay.ms_per_frame * 0.001)
+            Duration::from_secs_f32(last_frame_number as f32 * replay.header.ms_per_frame * 0.001)
         }
     }
 }
 
 impl Binary_Serializable for Replay_Data {
     fn deserialize(input: &mut Byte_Stream) -> std::io::Result<Replay_Data> {
-        let mut replay = Replay_Data {
-            ms_per_frame: input.read_f32()?,
-            seed: Default_Rng_Seed::deserialize(input)?,
-            ..Default::default()
+        let header = Replay_Header::deserialize(input)?;
+
+        let data_start = input.pos() as usize;
+        let data_end = if header.has_trailer() {
+            input
+                .len()
+                .checked_sub(TRAILER_SIZE)
+                .filter(|&end| end >= data_start)
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::UnexpectedEof, "replay trailer is missing.")
+                })?
+        } else {
+            input.len()
         };
 
-        while (input.pos() as usize) < input.len() {
-            replay.data.push(Replay_Data_Point::deserialize(input)?);
+        let mut replay = Replay_Data::new(header);
+        while (input.pos() as usize) < data_end {
+            if replay.header.has_tagged_records() {
+                match Replay_Record::deserialize(input)? {
+                    Replay_Record::Input(point) => replay.data.push(point),
+                    Replay_Record::State_Checksum(checksum) => replay.checksums.push(checksum),
+                }
+            } else {
+                replay.data.push(Replay_Data_Point::deserialize(input)?);
+            }
+        }
+
+        if input.pos() as usize != data_end {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "replay data overlaps its trailer.",
+            ));
+        }
+
+        if replay.header.has_trailer() {
+            let expected = input.read_u64()?;
+            let actual = fnv1a_64(&input.as_ref()[data_start..data_end]);
+            if actual != expected {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "replay checksum mismatch (expected {:016x}, got {:016x}): the file is corrupted.",
+                        expected, actual
+                    ),
+                ));
+            }
+        }
+
+        let last_frame = replay.data.last().map(|point| point.frame_number).max(
+            replay
+                .checksums
+                .last()
+                .map(|checksum| checksum.frame_number),
+        );
+        if let (Some(n_frames), Some(last_frame)) = (replay.header.n_frames, last_frame) {
+            if last_frame >= n_frames {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "replay has data for frame {} but its header says it has only {} frames.",
+                        last_frame, n_frames
+                    ),
+                ));
+            }
         }
 
         replay.duration = Self::calc_duration(&replay);
@@ -327,11 +611,10 @@ mod tests {
         }
     }
 
-    #[test]
-    fn serialize_deserialize_replay_data() {
+    fn test_data_points() -> Vec<Replay_Data_Point> {
         // @Incomplete :replay_actions:
         let joy_data: [Replay_Joystick_Data; JOY_COUNT] = Default::default();
-        let data_points = vec![
+        vec![
             Replay_Data_Point::new(0, &[], &joy_data, 0x0),
             Replay_Data_Point::new(1, &[], &joy_data, 0x0),
             Replay_Data_Point::new(10, &[], &joy_data, 0x0),
@@ -340,11 +623,144 @@ mod tests {
             Replay_Data_Point::new(1111, &[], &joy_data, 0x0),
             Replay_Data_Point::new(6531, &[], &joy_data, 0x0),
             Replay_Data_Point::new(424_242, &[], &joy_data, 0x0),
-        ];
+        ]
+    }
+
+    fn test_header() -> Replay_Header {
+        Replay_Header {
+            n_frames: Some(424_243),
+            ms_per_frame: 16.67,
+            seed: Default_Rng_Seed([42; 32]),
+            level_id: Some(String_Id::from_u32(0x1234)),
+            config_hash: Some(0xdead_beef),
+            ..Default::default()
+        }
+    }
+
+    fn test_checksums() -> Vec<Replay_State_Checksum> {
+        (0..3)
+            .map(|i| Replay_State_Checksum {
+                frame_number: i * 500,
+                hashes: vec![
+                    (String_Id::from_u32(1), 0x1111 * i),
+                    (String_Id::from_u32(2), 0x2222 * i),
+                ],
+            })
+            .collect()
+    }
+
+    // Simulate the serialization done by the recording thread
+    fn write_replay(
+        header: &Replay_Header,
+        data_points: &[Replay_Data_Point],
+        checksums: &[Replay_State_Checksum],
+    ) -> Byte_Stream {
+        let mut records = data_points
+            .iter()
+            .cloned()
+            .map(Replay_Record::Input)
+            .chain(checksums.iter().cloned().map(Replay_Record::State_Checksum))
+            .collect::<Vec<_>>();
+        records.sort_by_key(Replay_Record::frame_number);
+
+        write_replay_raw(header, |byte_stream| {
+            for record in records.iter() {
+                record
+                    .serialize(byte_stream)
+                    .unwrap_or_else(|err| panic!("Failed to serialize replay record: {}", err));
+            }
+        })
+    }
+
+    fn write_replay_raw(
+        header: &Replay_Header,
+        write_data: impl FnOnce(&mut Byte_Stream),
+    ) -> Byte_Stream {
+        let mut byte_stream = Byte_Stream::new();
+        header.serialize(&mut byte_stream).unwrap();
+
+        let data_start = byte_stream.len();
+        write_data(&mut byte_stream);
+
+        if header.n_frames.is_some() {
+            let checksum = fnv1a_64(&byte_stream.as_ref()[data_start..]);
+            byte_stream.write_u64(checksum).unwrap();
+        }
+
+        byte_stream.seek(0);
+        byte_stream
+    }
+
+    #[test]
+    fn serialize_deserialize_replay_data() {
+        let data_points = test_data_points();
+        let checksums = test_checksums();
+        let header = test_header();
+        let mut byte_stream = write_replay(&header, &data_points, &checksums);
+
+        let deserialized = Replay_Data::deserialize(&mut byte_stream)
+            .unwrap_or_else(|err| panic!("Failed to deserialize replay data: {}", err));
+
+        assert_eq!(deserialized.header, header);
+        assert_eq!(
+            deserialized.duration,
+            Duration::from_secs_f32(424_242f32 * header.ms_per_frame * 0.001)
+        );
+        assert_eq!(deserialized.data, data_points);
+        assert_eq!(deserialized.checksums, checksums);
+    }
+
+    #[test]
+    fn deserialize_unfinalized_replay_data() {
+        let data_points = test_data_points();
+        let header = Replay_Header {
+            n_frames: None,
+            ..test_header()
+        };
+        let mut byte_stream = write_replay(&header, &data_points, &[]);
+
+        let deserialized = Replay_Data::deserialize(&mut byte_stream)
+            .unwrap_or_else(|err| panic!("Failed to deserialize replay data: {}", err));
+
+        assert_eq!(deserialized.header, header);
+        assert_eq!(deserialized.data, data_points);
+    }
+
+    #[test]
+    fn migrate_v1_replay_data() {
+        let data_points = test_data_points();
+        let header = test_header();
+
+        // Version 1 had the same header, but untagged data points instead of records.
+        let mut byte_stream = write_replay_raw(&header, |byte_stream| {
+            for point in data_points.iter() {
+                point.serialize(byte_stream).unwrap();
+            }
+        });
+        byte_stream.seek(REPLAY_MAGIC.len() as u64);
+        byte_stream.write_u16(1).unwrap();
+        byte_stream.seek(0);
+
+        let deserialized = Replay_Data::deserialize(&mut byte_stream)
+            .unwrap_or_else(|err| panic!("Failed to deserialize replay data: {}", err));
+
+        assert_eq!(
+            deserialized.header,
+            Replay_Header {
+                version: 1,
+                ..header
+            }
+        );
+        assert_eq!(deserialized.data, data_points);
+        assert!(deserialized.checksums.is_empty());
+    }
 
+    #[test]
+    fn migrate_v0_replay_data() {
+        let data_points = test_data_points();
         let mut byte_stream = Byte_Stream::new();
 
-        // Simulate the serialization done by the recording thread
+        // Version 0 had no header besides ms_per_frame and the seed, nor trailer.
         let ms_per_frame = 16.67;
         byte_stream.write_f32(ms_per_frame).unwrap();
 
@@ -352,9 +768,7 @@ mod tests {
         seed.serialize(&mut byte_stream).unwrap();
 
         for point in data_points.iter() {
-            point
-                .serialize(&mut byte_stream)
-                .unwrap_or_else(|err| panic!("Failed to serialize replay data point: {}", err));
+            point.serialize(&mut byte_stream).unwrap();
         }
 
         byte_stream.seek(0);
@@ -362,16 +776,54 @@ mod tests {
         let deserialized = Replay_Data::deserialize(&mut byte_stream)
             .unwrap_or_else(|err| panic!("Failed to deserialize replay data: {}", err));
 
-        assert_eq!(deserialized.ms_per_frame, ms_per_frame);
-        assert_eq!(deserialized.seed, seed);
         assert_eq!(
-            deserialized.duration,
-            Duration::from_secs_f32(424_242f32 * ms_per_frame * 0.001)
+            deserialized.header,
+            Replay_Header {
+                version: 0,
+                n_frames: None,
+                ms_per_frame,
+                seed,
+                level_id: None,
+                config_hash: None,
+            }
         );
-        assert_eq!(deserialized.data.len(), data_points.len());
-        for i in 0..data_points.len() {
-            assert_eq!(deserialized.data[i], data_points[i]);
-        }
+        assert_eq!(deserialized.data, data_points);
+    }
+
+    #[test]
+    fn replay_data_newer_version_is_rejected() {
+        let mut byte_stream = write_replay(&test_header(), &test_data_points(), &[]);
+        byte_stream.seek(REPLAY_MAGIC.len() as u64);
+        byte_stream.write_u16(REPLAY_FORMAT_VERSION + 1).unwrap();
+        byte_stream.seek(0);
+
+        let err = Replay_Data::deserialize(&mut byte_stream).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn replay_data_checksum_mismatch_is_rejected() {
+        let byte_stream = write_replay(&test_header(), &test_data_points(), &[]);
+        let mut bytes = byte_stream.as_ref().to_vec();
+        // Corrupt the frame number of the last data point.
+        let idx = bytes.len() - std::mem::size_of::<u64>() - 5;
+        bytes[idx] ^= 0x1;
+        let mut byte_stream = Byte_Stream::new_from_vec(bytes);
+
+        let err = Replay_Data::deserialize(&mut byte_stream).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn replay_data_wrong_frame_count_is_rejected() {
+        let header = Replay_Header {
+            n_frames: Some(10),
+            ..test_header()
+        };
+        let mut byte_stream = write_replay(&header, &test_data_points(), &test_checksums());
+
+        let err = Replay_Data::deserialize(&mut byte_stream).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
     }
 
     #[test]
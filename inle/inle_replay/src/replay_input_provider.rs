@@ -1,15 +1,48 @@
-use super::replay_data::{Replay_Data, Replay_Data_Iter};
+use super::replay_data::{Replay_Data, Replay_Data_Iter, Replay_State_Checksum};
+use inle_common::stringid::String_Id;
 use inle_input::input_state::Input_Raw_State;
+use std::fmt;
 use std::iter::Peekable;
 
 pub struct Replay_Input_Provider {
     replay_data_iter: Peekable<Replay_Data_Iter>,
+    checksums_iter: Peekable<std::vec::IntoIter<Replay_State_Checksum>>,
+    last_verified_frame: Option<u64>,
+    desync: Option<Replay_Desync>,
+}
+
+/// Describes the first recorded state checksum that didn't match the playback.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay_Desync {
+    pub frame_number: u64,
+    /// The last frame whose checksum matched, if any: the divergence happened after it.
+    pub last_verified_frame: Option<u64>,
+    /// The names of the hashes that differ (or are missing on either side).
+    pub mismatches: Vec<String_Id>,
+}
+
+impl fmt::Display for Replay_Desync {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "replay desynced at frame {}", self.frame_number)?;
+        if let Some(frame) = self.last_verified_frame {
+            write!(f, " (last matching frame was {})", frame)?;
+        }
+        write!(f, ". Mismatching state:")?;
+        for name in &self.mismatches {
+            write!(f, " {}", name)?;
+        }
+        Ok(())
+    }
 }
 
 impl Replay_Input_Provider {
-    pub fn new(replay_data: Replay_Data) -> Self {
+    pub fn new(mut replay_data: Replay_Data) -> Self {
+        let checksums = std::mem::take(&mut replay_data.checksums);
         Self {
             replay_data_iter: replay_data.into_iter().peekable(),
+            checksums_iter: checksums.into_iter().peekable(),
+            last_verified_frame: None,
+            desync: None,
         }
     }
 
@@ -31,4 +64,116 @@ impl Replay_Input_Provider {
             None
         }
     }
+
+    /// If the replay has a state checksum for `cur_frame`, compares it with the hashes returned
+    /// by `calc_hashes` (which is only called in that case).
+    /// Only the first desync is reported: after that, the states are expected to differ.
+    pub fn check_state_checksum<F>(&mut self, cur_frame: u64, calc_hashes: F)
+    where
+        F: FnOnce() -> Vec<(String_Id, u64)>,
+    {
+        if self.desync.is_some() {
+            return;
+        }
+
+        while let Some(checksum) = self.checksums_iter.peek() {
+            if checksum.frame_number >= cur_frame {
+                break;
+            }
+            // We didn't get to check this one (e.g. the frame was skipped).
+            self.checksums_iter.next();
+        }
+
+        let expected = match self.checksums_iter.peek() {
+            Some(checksum) if checksum.frame_number == cur_frame => {
+                self.checksums_iter.next().unwrap()
+            }
+            _ => return,
+        };
+
+        let actual = calc_hashes();
+        let mismatches = find_mismatching_hashes(&expected.hashes, &actual);
+        if mismatches.is_empty() {
+            self.last_verified_frame = Some(cur_frame);
+        } else {
+            let desync = Replay_Desync {
+                frame_number: cur_frame,
+                last_verified_frame: self.last_verified_frame,
+                mismatches,
+            };
+            lerr!("{}", desync);
+            self.desync = Some(desync);
+        }
+    }
+
+    pub fn desync(&self) -> Option<&Replay_Desync> {
+        self.desync.as_ref()
+    }
+}
+
+fn find_mismatching_hashes(
+    expected: &[(String_Id, u64)],
+    actual: &[(String_Id, u64)],
+) -> Vec<String_Id> {
+    let mut mismatches = expected
+        .iter()
+        .filter(|(name, hash)| !actual.iter().any(|(n, h)| n == name && h == hash))
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>();
+    mismatches.extend(
+        actual
+            .iter()
+            .filter(|(name, _)| !expected.iter().any(|(n, _)| n == name))
+            .map(|(name, _)| *name),
+    );
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay_data::Replay_Header;
+
+    fn hashes(values: &[(u32, u64)]) -> Vec<(String_Id, u64)> {
+        values
+            .iter()
+            .map(|&(name, hash)| (String_Id::from_u32(name), hash))
+            .collect()
+    }
+
+    #[test]
+    fn detect_first_desync() {
+        let mut replay = Replay_Data::new(Replay_Header::default());
+        replay.checksums = vec![
+            Replay_State_Checksum {
+                frame_number: 10,
+                hashes: hashes(&[(1, 100), (2, 200)]),
+            },
+            Replay_State_Checksum {
+                frame_number: 20,
+                hashes: hashes(&[(1, 101), (2, 200)]),
+            },
+            Replay_State_Checksum {
+                frame_number: 30,
+                hashes: hashes(&[(1, 102), (2, 201)]),
+            },
+        ];
+        let mut provider = Replay_Input_Provider::new(replay);
+
+        provider.check_state_checksum(5, || panic!("no checksum should be computed here"));
+        provider.check_state_checksum(10, || hashes(&[(1, 100), (2, 200)]));
+        assert!(provider.desync().is_none());
+
+        provider.check_state_checksum(20, || hashes(&[(1, 101), (2, 999), (3, 300)]));
+        let expected = Replay_Desync {
+            frame_number: 20,
+            last_verified_frame: Some(10),
+            mismatches: vec![String_Id::from_u32(2), String_Id::from_u32(3)],
+        };
+        assert_eq!(provider.desync(), Some(&expected));
+
+        // Later desyncs are not reported.
+        provider.check_state_checksum(30, || hashes(&[(1, 0), (2, 0)]));
+        assert_eq!(provider.desync(), Some(&expected));
+    }
 }
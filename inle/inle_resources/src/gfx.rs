@@ -3,6 +3,8 @@ mod font;
 mod image;
 mod shader;
 
+pub use self::image::{load_image_from_file, save_image_to_file};
+
 use super::loaders;
 use inle_common::colors;
 use inle_common::stringid::{const_sid_from_str, String_Id};
@@ -1,6 +1,7 @@
 use inle_gfx_backend::render::{self, Color_Type, Image, Texture};
 use std::error::Error;
 use std::fs::File;
+use std::io::BufWriter;
 use std::path::Path;
 
 const fn png_to_engine_color_type(c: png::ColorType) -> Color_Type {
@@ -13,6 +14,16 @@ const fn png_to_engine_color_type(c: png::ColorType) -> Color_Type {
     }
 }
 
+const fn engine_to_png_color_type(c: Color_Type) -> png::ColorType {
+    match c {
+        Color_Type::Grayscale => png::ColorType::Grayscale,
+        Color_Type::RGB => png::ColorType::RGB,
+        Color_Type::Indexed => png::ColorType::Indexed,
+        Color_Type::Grayscale_Alpha => png::ColorType::GrayscaleAlpha,
+        Color_Type::RGBA => png::ColorType::RGBA,
+    }
+}
+
 pub fn load_image_from_file(fname: &Path) -> Result<Image, Box<dyn Error>> {
     // @Incomplete: we may want to choose different decoders. For now, png is the only option.
     let decoder = png::Decoder::new(File::open(fname)?);
@@ -36,3 +47,29 @@ pub fn load_texture_from_file<'a>(fname: &Path) -> Result<Texture<'a>, Box<dyn E
     let image = load_image_from_file(fname)?;
     Ok(render::new_texture_from_image(&image, None))
 }
+
+pub fn save_image_to_file(image: &Image, fname: &Path) -> Result<(), Box<dyn Error>> {
+    let (width, height) = render::get_image_size(image);
+    let color_type = render::get_image_color_type(image);
+    if let Color_Type::Indexed = color_type {
+        return Err("cannot save indexed images, since we don't keep their palette".into());
+    }
+    let png_color_type = engine_to_png_color_type(color_type);
+    let bit_depth = png::BitDepth::from_u8(render::get_image_bit_depth(image))
+        .ok_or("unsupported image bit depth")?;
+
+    // Note: the image buffer may be larger than the actual image data.
+    let row_size = (width as usize * png_color_type.samples() * bit_depth as usize).div_ceil(8);
+    let bytes = render::get_image_bytes(image)
+        .get(..row_size * height as usize)
+        .ok_or("image data is smaller than its size")?;
+
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(fname)?), width, height);
+    encoder.set_color(png_color_type);
+    encoder.set_depth(bit_depth);
+    encoder.write_header()?.write_image_data(bytes)?;
+
+    lverbose!("saved image {:?} ({}x{})", fname, width, height);
+
+    Ok(())
+}
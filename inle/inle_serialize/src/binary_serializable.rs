@@ -1,4 +1,7 @@
 use super::byte_stream::Byte_Stream;
+use std::convert::TryFrom;
+use std::io;
+use std::time::Duration;
 
 pub trait Binary_Serializable: Sized {
     fn serialize(&self, _output: &mut Byte_Stream) -> std::io::Result<()> {
@@ -9,3 +12,171 @@ pub trait Binary_Serializable: Sized {
         unimplemented!();
     }
 }
+
+macro_rules! impl_serializable_primitive {
+    ($type: ty, $write: ident, $read: ident) => {
+        impl Binary_Serializable for $type {
+            fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+                output.$write(*self)
+            }
+
+            fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+                input.$read()
+            }
+        }
+    };
+}
+
+impl_serializable_primitive!(u8, write_u8, read_u8);
+impl_serializable_primitive!(u16, write_u16, read_u16);
+impl_serializable_primitive!(u32, write_u32, read_u32);
+impl_serializable_primitive!(u64, write_u64, read_u64);
+impl_serializable_primitive!(f32, write_f32, read_f32);
+
+impl Binary_Serializable for i8 {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        output.write_u8(*self as u8)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(input.read_u8()? as i8)
+    }
+}
+
+impl Binary_Serializable for i32 {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        output.write_u32(*self as u32)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        Ok(input.read_u32()? as i32)
+    }
+}
+
+impl Binary_Serializable for bool {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        output.write_u8(*self as u8)
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        match input.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(io::ErrorKind::InvalidData.into()),
+        }
+    }
+}
+
+/// Durations are serialized as their whole seconds (as u64) followed by their nanoseconds (as u32).
+impl Binary_Serializable for Duration {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        output.write_u64(self.as_secs())?;
+        output.write_u32(self.subsec_nanos())
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        let secs = input.read_u64()?;
+        let nanos = input.read_u32()?;
+        if nanos >= 1_000_000_000 {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        Ok(Duration::new(secs, nanos))
+    }
+}
+
+impl<T: Binary_Serializable> Binary_Serializable for Option<T> {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        match self {
+            Some(x) => {
+                output.write_u8(1)?;
+                x.serialize(output)
+            }
+            None => output.write_u8(0),
+        }
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        match input.read_u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(T::deserialize(input)?)),
+            _ => Err(io::ErrorKind::InvalidData.into()),
+        }
+    }
+}
+
+/// Vecs are serialized as their length (as u32) followed by their elements.
+impl<T: Binary_Serializable> Binary_Serializable for Vec<T> {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        let len = u32::try_from(self.len()).map_err(|_| io::ErrorKind::InvalidInput)?;
+        output.write_u32(len)?;
+        for elem in self {
+            elem.serialize(output)?;
+        }
+        Ok(())
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        let len = input.read_u32()? as usize;
+        // Don't trust `len` blindly for the allocation, as the data may be corrupted.
+        let mut res = Vec::with_capacity(len.min(input.len()));
+        for _ in 0..len {
+            res.push(T::deserialize(input)?);
+        }
+        Ok(res)
+    }
+}
+
+/// Strings are serialized as their length in bytes (as u32) followed by their UTF-8 bytes.
+impl Binary_Serializable for String {
+    fn serialize(&self, output: &mut Byte_Stream) -> io::Result<()> {
+        let len = u32::try_from(self.len()).map_err(|_| io::ErrorKind::InvalidInput)?;
+        output.write_u32(len)?;
+        output.write_bytes(self.as_bytes())
+    }
+
+    fn deserialize(input: &mut Byte_Stream) -> io::Result<Self> {
+        let len = input.read_u32()? as usize;
+        if len > input.len() {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        let mut bytes = vec![0; len];
+        input.read_bytes(&mut bytes)?;
+        String::from_utf8(bytes).map_err(|_| io::ErrorKind::InvalidData.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialize_vec() {
+        let v: Vec<u32> = vec![1, 2, 3, 0xdead_beef];
+        let mut bs = Byte_Stream::new();
+        v.serialize(&mut bs).unwrap();
+        assert_eq!(bs.len(), 4 + 4 * v.len());
+
+        bs.seek(0);
+        assert_eq!(Vec::<u32>::deserialize(&mut bs).unwrap(), v);
+    }
+
+    #[test]
+    fn serialize_string() {
+        let s = String::from("music1.ogg àè");
+        let mut bs = Byte_Stream::new();
+        s.serialize(&mut bs).unwrap();
+        assert_eq!(bs.len(), 4 + s.len());
+
+        bs.seek(0);
+        assert_eq!(String::deserialize(&mut bs).unwrap(), s);
+
+        let mut bs = Byte_Stream::new_from_vec(vec![2, 0, 0, 0, 0xff, 0xfe]);
+        assert!(String::deserialize(&mut bs).is_err());
+    }
+
+    #[test]
+    fn deserialize_invalid_bool() {
+        let mut bs = Byte_Stream::new_from_vec(vec![2]);
+        assert!(bool::deserialize(&mut bs).is_err());
+    }
+}
@@ -1,5 +1,5 @@
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use std::io::Cursor;
+use std::io::{Cursor, Read, Write};
 
 #[derive(Default)]
 pub struct Byte_Stream {
@@ -65,6 +65,10 @@ impl Byte_Stream {
         Ok(())
     }
 
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.cursor.write_all(bytes)
+    }
+
     pub fn read_u8(&mut self) -> std::io::Result<u8> {
         self.cursor.read_u8()
     }
@@ -88,4 +92,9 @@ impl Byte_Stream {
         }
         Ok(f32::from_le_bytes(x_as_le))
     }
+
+    /// Fills `buf` with the next `buf.len()` bytes of the stream.
+    pub fn read_bytes(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+        self.cursor.read_exact(buf)
+    }
 }
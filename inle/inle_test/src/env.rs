@@ -7,3 +7,10 @@ pub fn get_test_cfg_root(env: &Env_Info) -> Box<Path> {
     tests_root_buf.push("cfg");
     tests_root_buf.into_boxed_path()
 }
+
+pub fn get_test_golden_root(env: &Env_Info) -> Box<Path> {
+    let mut golden_root_buf = PathBuf::from(env.working_dir.clone());
+    golden_root_buf.push("test_resources");
+    golden_root_buf.push("golden");
+    golden_root_buf.into_boxed_path()
+}
@@ -0,0 +1,280 @@
+use crate::env::get_test_golden_root;
+use inle_common::colors::{self, Color};
+use inle_core::env::Env_Info;
+use inle_gfx_backend::render::{self, Color_Type, Image};
+use inle_gfx_backend::render_window::{backend as render_window, Render_Window_Handle};
+use inle_resources::gfx::{load_image_from_file, save_image_to_file};
+use std::fmt;
+use std::path::Path;
+
+// Golden image tests render a scene and compare it against a reference png stored in
+// test_resources/golden. They need a backend that can render without a GPU, so they should be
+// run with the gfx-soft feature.
+
+/// If this env var is set, the golden images are overwritten with the rendered ones rather than
+/// being compared with them.
+pub const UPDATE_GOLDEN_IMAGES_VAR: &str = "INLE_UPDATE_GOLDEN_IMAGES";
+
+pub struct Golden_Scene<'a> {
+    /// The golden image is `test_resources/golden/<name>.png`
+    pub name: &'a str,
+    pub window_size: (u32, u32),
+    pub clear_color: Color,
+    /// Max difference allowed between each channel of two corresponding pixels.
+    pub tolerance: u8,
+}
+
+impl Default for Golden_Scene<'_> {
+    fn default() -> Self {
+        Self {
+            name: "",
+            window_size: (64, 64),
+            clear_color: colors::BLACK,
+            tolerance: 2,
+        }
+    }
+}
+
+pub enum Image_Mismatch {
+    Size {
+        actual: (u32, u32),
+        expected: (u32, u32),
+    },
+    Pixels {
+        n_different: usize,
+        /// Different pixels are red, the others are a dimmed grayscale version of the actual image.
+        diff: Image,
+    },
+}
+
+impl fmt::Display for Image_Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Size { actual, expected } => write!(
+                f,
+                "image size is {}x{} but {}x{} was expected",
+                actual.0, actual.1, expected.0, expected.1
+            ),
+            Self::Pixels { n_different, .. } => write!(f, "{} pixels differ", n_different),
+        }
+    }
+}
+
+impl fmt::Debug for Image_Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Renders `scene` by calling `render_scene` and panics if the result doesn't match its golden image.
+/// On failure, the rendered image (and the diff image, if any) are written to a `golden`
+/// directory next to the test executable.
+pub fn assert_matches_golden_image(
+    scene: &Golden_Scene,
+    render_scene: impl FnOnce(&mut Render_Window_Handle),
+) {
+    let env = Env_Info::gather().expect("Failed to gather env info!");
+
+    let window =
+        inle_win::window::create_window(&Default::default(), scene.window_size, scene.name);
+    let mut window = render_window::create_render_window(window);
+    render_window::set_clear_color(&mut window, scene.clear_color);
+    render_window::clear(&mut window);
+    render_scene(&mut window);
+    let actual = render_window::read_framebuffer(&window);
+
+    let golden_path = get_test_golden_root(&env).join(format!("{}.png", scene.name));
+    if std::env::var_os(UPDATE_GOLDEN_IMAGES_VAR).is_some() {
+        save_image(&actual, &golden_path);
+        return;
+    }
+
+    let out_dir = env.full_exe_path.parent().unwrap().join("golden");
+    let actual_path = out_dir.join(format!("{}.png", scene.name));
+    let expected = match load_image_from_file(&golden_path) {
+        Ok(image) => image,
+        Err(err) => {
+            save_image(&actual, &actual_path);
+            panic!(
+                "Failed to load golden image {:?}: {}. The rendered image was saved to {:?}: set {} to use it as the golden image.",
+                golden_path, err, actual_path, UPDATE_GOLDEN_IMAGES_VAR
+            );
+        }
+    };
+
+    if let Err(mismatch) = compare_images(&actual, &expected, scene.tolerance) {
+        save_image(&actual, &actual_path);
+        let diff_path = out_dir.join(format!("{}.diff.png", scene.name));
+        if let Image_Mismatch::Pixels { diff, .. } = &mismatch {
+            save_image(diff, &diff_path);
+        }
+        panic!(
+            "Rendered scene `{}` doesn't match its golden image: {}. See {:?} and {:?}",
+            scene.name, mismatch, actual_path, diff_path
+        );
+    }
+}
+
+/// Compares two images pixel by pixel. Two pixels are considered equal if none of their channels
+/// differ by more than `tolerance`.
+pub fn compare_images(
+    actual: &Image,
+    expected: &Image,
+    tolerance: u8,
+) -> Result<(), Image_Mismatch> {
+    let actual_size = render::get_image_size(actual);
+    let expected_size = render::get_image_size(expected);
+    if actual_size != expected_size {
+        return Err(Image_Mismatch::Size {
+            actual: actual_size,
+            expected: expected_size,
+        });
+    }
+
+    let actual_px = image_colors(actual);
+    let expected_px = image_colors(expected);
+
+    let mut diff_bytes = Vec::with_capacity(4 * actual_px.len());
+    let mut n_different = 0;
+    for (&a, &e) in actual_px.iter().zip(expected_px.iter()) {
+        let differs = [(a.r, e.r), (a.g, e.g), (a.b, e.b), (a.a, e.a)]
+            .iter()
+            .any(|&(a, e)| a.abs_diff(e) > tolerance);
+        let diff_color = if differs {
+            n_different += 1;
+            colors::RED
+        } else {
+            let gray = colors::to_gray_scale(a).r / 3;
+            colors::rgb(gray, gray, gray)
+        };
+        diff_bytes.extend_from_slice(&[diff_color.r, diff_color.g, diff_color.b, diff_color.a]);
+    }
+
+    if n_different > 0 {
+        let diff = render::new_image_with_data(
+            actual_size.0,
+            actual_size.1,
+            Color_Type::RGBA,
+            8,
+            diff_bytes,
+        );
+        Err(Image_Mismatch::Pixels { n_different, diff })
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns the pixels of a 8-bit image, converted to RGBA.
+fn image_colors(image: &Image) -> Vec<Color> {
+    assert_eq!(
+        render::get_image_bit_depth(image),
+        8,
+        "only 8-bit images can be compared"
+    );
+
+    let (width, height) = render::get_image_size(image);
+    let bytes = render::get_image_bytes(image);
+    let color_type = render::get_image_color_type(image);
+    let n_channels = match color_type {
+        Color_Type::Grayscale => 1,
+        Color_Type::Grayscale_Alpha => 2,
+        Color_Type::RGB => 3,
+        Color_Type::RGBA => 4,
+        Color_Type::Indexed => panic!("indexed images cannot be compared"),
+    };
+
+    bytes
+        .chunks_exact(n_channels)
+        .take((width * height) as usize)
+        .map(|px| match color_type {
+            Color_Type::Grayscale => colors::rgb(px[0], px[0], px[0]),
+            Color_Type::Grayscale_Alpha => colors::rgba(px[0], px[0], px[0], px[1]),
+            Color_Type::RGB => colors::rgb(px[0], px[1], px[2]),
+            _ => colors::rgba(px[0], px[1], px[2], px[3]),
+        })
+        .collect()
+}
+
+fn save_image(image: &Image, path: &Path) {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .unwrap_or_else(|err| panic!("Failed to create directory {:?}: {}", dir, err));
+    }
+    save_image_to_file(image, path)
+        .unwrap_or_else(|err| panic!("Failed to save image {:?}: {}", path, err));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_solid_image(width: u32, height: u32, color: Color) -> Image {
+        let bytes = [color.r, color.g, color.b, color.a].repeat((width * height) as usize);
+        render::new_image_with_data(width, height, Color_Type::RGBA, 8, bytes)
+    }
+
+    #[test]
+    fn compare_images_respects_tolerance() {
+        let a = new_solid_image(4, 4, colors::rgb(100, 100, 100));
+        let b = new_solid_image(4, 4, colors::rgb(102, 100, 99));
+
+        assert!(compare_images(&a, &b, 2).is_ok());
+        assert!(compare_images(&a, &b, 1).is_err());
+    }
+
+    #[test]
+    fn compare_images_reports_mismatches() {
+        let a = new_solid_image(2, 2, colors::WHITE);
+        let b = new_solid_image(2, 3, colors::WHITE);
+        assert!(matches!(
+            compare_images(&a, &b, 0),
+            Err(Image_Mismatch::Size {
+                actual: (2, 2),
+                expected: (2, 3)
+            })
+        ));
+
+        let mut bytes = [255; 16].to_vec();
+        // Make the bottom-right pixel black
+        bytes[12..15].copy_from_slice(&[0, 0, 0]);
+        let b = render::new_image_with_data(2, 2, Color_Type::RGBA, 8, bytes);
+        match compare_images(&a, &b, 0) {
+            Err(Image_Mismatch::Pixels { n_different, diff }) => {
+                assert_eq!(n_different, 1);
+                assert_eq!(&render::get_image_bytes(&diff)[12..16], &[255, 0, 0, 255]);
+                assert_ne!(&render::get_image_bytes(&diff)[..4], &[255, 0, 0, 255]);
+            }
+            _ => panic!("images should have different pixels"),
+        }
+    }
+
+    #[cfg(feature = "gfx-soft")]
+    #[test]
+    fn basic_shapes_match_golden_image() {
+        use inle_gfx_backend::render::backend;
+        use inle_math::rect::Rect;
+        use inle_math::shapes::Circle;
+        use inle_math::vector::Vec2f;
+
+        let scene = Golden_Scene {
+            name: "basic_shapes",
+            ..Default::default()
+        };
+        assert_matches_golden_image(&scene, |window| {
+            backend::fill_color_rect(window, &colors::RED.into(), Rect::new(4., 4., 32., 24.));
+            backend::fill_color_circle(
+                window,
+                &colors::rgba(0, 255, 0, 160).into(),
+                Circle {
+                    center: Vec2f::new(40., 40.),
+                    radius: 16.,
+                },
+            );
+            backend::render_line(
+                window,
+                &backend::new_vertex(Vec2f::new(0., 63.), colors::WHITE, Vec2f::default()),
+                &backend::new_vertex(Vec2f::new(63., 0.), colors::YELLOW, Vec2f::default()),
+            );
+        });
+    }
+}
@@ -11,6 +11,7 @@ pub mod prelude;
 
 pub mod approx_eq_testable;
 pub mod env;
+pub mod golden;
 pub mod test_common;
 
 pub use prelude::*;
@@ -5,22 +5,29 @@ use inle_math::vector::{Vec2f, Vec2i, Vec2u};
 #[cfg(feature = "win-sfml")]
 mod sfml;
 
-#[cfg(feature = "win-glfw")]
+#[cfg(all(feature = "win-glfw", not(feature = "win-null")))]
 mod glfw;
 
 #[cfg(feature = "win-winit")]
 mod winit;
 
+#[cfg(feature = "win-null")]
+mod null;
+
 #[cfg(feature = "win-sfml")]
 use self::sfml as backend;
 
-#[cfg(feature = "win-glfw")]
+#[cfg(all(feature = "win-glfw", not(feature = "win-null")))]
 use self::glfw as backend;
 
 #[cfg(feature = "win-winit")]
 use self::winit as backend;
 
-#[cfg(all(feature = "win-glfw", feature = "gfx-gl"))]
+// Note: the null backend takes precedence over glfw, since the latter is enabled by default.
+#[cfg(feature = "win-null")]
+use self::null as backend;
+
+#[cfg(all(feature = "win-glfw", feature = "gfx-gl", not(feature = "win-null")))]
 pub use backend::get_gl_handle;
 
 pub type Window_Handle = backend::Window_Handle;
@@ -0,0 +1,54 @@
+/// The null backend never produces any event.
+pub enum Event {}
+
+#[derive(Default)]
+pub struct Create_Window_Args {
+    pub vsync: bool,
+}
+
+pub struct Window_Handle {
+    target_size: (u32, u32),
+    real_size: (u32, u32),
+    vsync: bool,
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+pub fn create_window(
+    args: &Create_Window_Args,
+    target_size: (u32, u32),
+    _title: &str,
+) -> Window_Handle {
+    Window_Handle {
+        target_size,
+        real_size: target_size,
+        vsync: args.vsync,
+    }
+}
+
+pub fn recreate_window(_window: &mut Window_Handle) {}
+
+pub fn has_vsync(window: &Window_Handle) -> bool {
+    window.vsync
+}
+
+pub fn set_vsync(window: &mut Window_Handle, vsync: bool) {
+    window.vsync = vsync;
+}
+
+pub fn display(_window: &mut Window_Handle) {}
+
+pub fn get_window_target_size(window: &Window_Handle) -> (u32, u32) {
+    window.target_size
+}
+
+pub fn get_window_real_size(window: &Window_Handle) -> (u32, u32) {
+    window.real_size
+}
+
+pub fn prepare_poll_events(_window: &mut Window_Handle) {}
+
+pub fn poll_event(_window: &mut Window_Handle) -> Option<Event> {
+    None
+}
+
+pub fn set_key_repeat_enabled(_window: &mut Window_Handle, _enabled: bool) {}